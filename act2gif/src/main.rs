@@ -7,7 +7,7 @@ mod term_image;
 mod theme;
 use self::draw::GifEncoder;
 use clap::{self, ArgMatches};
-use rogue_gym_core::{error::*, input::InputCode, json_to_inputs, read_file, GameConfig};
+use rogue_gym_core::{error::*, input::InputCode, read_file, replay::ReplayFile, GameConfig};
 const UBUNTU_MONO: &[u8; 205748] = include_bytes!("../../data/fonts/UbuntuMono-R.ttf");
 use self::font::FontHandle;
 use self::theme::Theme;
@@ -99,10 +99,10 @@ fn get_config(args: &ArgMatches) -> GameResult<GameConfig> {
     GameConfig::from_json(&f)
 }
 
-fn get_replay(args: &ArgMatches) -> GameResult<Vec<InputCode>> {
+fn get_replay(args: &ArgMatches, config: &GameConfig) -> GameResult<ReplayFile> {
     let fname = args.value_of("actions").unwrap();
     let replay = read_file(fname).with_context(|| "Failed to read replay file!")?;
-    json_to_inputs(&replay)
+    ReplayFile::from_json_or_inputs(&replay, config)
 }
 
 fn get_arg<T: ::std::str::FromStr>(args: &ArgMatches, value: &str) -> Option<T> {
@@ -125,21 +125,23 @@ fn get_out_file(args: &ArgMatches) -> GameResult<String> {
 fn setup<'a>() -> GameResult<(GifEncoder<'a>, Vec<InputCode>, String)> {
     let args = parse_args();
     let mut config = get_config(&args)?;
-    let mut replay = get_replay(&args)?;
     let interval = get_arg(&args, "interval").unwrap();
     let scale = get_arg(&args, "fontsize").unwrap();
     let max = get_arg(&args, "max_actions").unwrap();
     if let Some(seed) = get_arg(&args, "seed") {
         config.seed = Some(seed);
     }
-    replay.truncate(max);
+    let ReplayFile {
+        config, mut inputs, ..
+    } = get_replay(&args, &config)?;
+    inputs.truncate(max);
     let theme = args.value_of("theme").unwrap_or("solarized-dark");
     let theme = Theme::from_str(theme).expect("Unknown theme was specified");
     let font = FontHandle::new(&UBUNTU_MONO[..], scale);
     let out_file = get_out_file(&args)?;
     Ok((
         GifEncoder::new(config, font, scale, theme, interval),
-        replay,
+        inputs,
         out_file,
     ))
 }
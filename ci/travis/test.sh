@@ -4,6 +4,7 @@ set -ex
 
 cargo build --verbose --all
 cargo test --manifest-path=core/Cargo.toml
+cargo test --manifest-path=core/Cargo.toml --features parallel
 cd python
 cargo test --no-default-features
 tox -e py
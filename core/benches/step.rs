@@ -0,0 +1,77 @@
+//! benchmarks of the core step loop, run by `cargo bench -p rogue-gym-core`
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rogue_gym_core::rng::RngHandle;
+use rogue_gym_core::step::ReactionBuffer;
+use rogue_gym_core::symbol::Layout;
+use rogue_gym_core::{GameConfig, RunTime};
+
+fn config(seed: u128) -> GameConfig {
+    let mut config = GameConfig::default();
+    config.seed = Some(seed);
+    config
+}
+
+fn dungeon_generation(c: &mut Criterion) {
+    let mut seed = 0;
+    c.bench_function("dungeon_generation", |b| {
+        b.iter(|| {
+            seed += 1;
+            black_box(config(seed).build().unwrap())
+        })
+    });
+}
+
+/// take `turns` random actions, starting over when the game ends
+fn random_walk(runtime: &mut RunTime, turns: usize, rng: &mut RngHandle) {
+    let actions = runtime.action_set();
+    let mut buf = ReactionBuffer::default();
+    for _ in 0..turns {
+        let action = actions[rng.range(0..actions.len())];
+        let (_, done) = runtime.step_into(action, &mut buf).unwrap();
+        if done {
+            *runtime = config(rng.range(0..u128::from(u64::MAX))).build().unwrap();
+        }
+    }
+}
+
+fn random_walk_episode(c: &mut Criterion) {
+    c.bench_function("random_walk_1000_turns", |b| {
+        let mut rng = RngHandle::from_seed(1);
+        b.iter_batched(
+            || config(1).build().unwrap(),
+            |mut runtime| random_walk(&mut runtime, 1000, &mut rng),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn observation(c: &mut Criterion) {
+    let mut runtime = config(1).build().unwrap();
+    random_walk(&mut runtime, 100, &mut RngHandle::from_seed(1));
+    let symbols = runtime.symbols().unwrap();
+    let [channels, h, w] = runtime.observation_shape().unwrap();
+    let mut out = vec![0.0f32; channels * h * w];
+    c.bench_function("observe", |b| {
+        b.iter(|| runtime.observe(symbols, Layout::Nchw, &mut out).unwrap())
+    });
+    c.bench_function("step_observation", |b| {
+        b.iter(|| black_box(runtime.observation().unwrap()))
+    });
+}
+
+fn clone_runtime(c: &mut Criterion) {
+    let mut runtime = config(1).build().unwrap();
+    random_walk(&mut runtime, 1000, &mut RngHandle::from_seed(1));
+    c.bench_function("clone_deterministic", |b| {
+        b.iter(|| black_box(runtime.clone_deterministic()))
+    });
+}
+
+criterion_group!(
+    benches,
+    dungeon_generation,
+    random_walk_episode,
+    observation,
+    clone_runtime
+);
+criterion_main!(benches);
@@ -2,17 +2,26 @@
 use log::warn;
 
 use crate::character::{
-    fight, player::PlayerEvent, Action, DamageReaction, Enemy, EnemyHandler, Player,
+    enemies::Attack,
+    fight::{self, CombatEvent, Combatant, Projectile},
+    player::PlayerEvent,
+    Action, Damage, DamageKind, DamageReaction, Dice, Effect, Enemy, EnemyHandler, HitPoint,
+    Player,
 };
-use crate::dungeon::{Direction, Dungeon, DungeonPath};
+use crate::dungeon::{Direction, Dungeon, DungeonPath, TrapKind};
 use crate::error::*;
-use crate::item::{itembox::Entry as ItemEntry, ItemHandler, ItemToken};
+use crate::item::{
+    itembox::Entry as ItemEntry, Identify, ItemAttr, ItemBox, ItemClass, ItemHandler, ItemKind,
+    ItemNum, ItemToken,
+};
+use crate::rng::{Parcent, RngHandle};
 use crate::ui::UiState;
 use crate::{GameInfo, GameMsg, Reaction};
 use anyhow::{bail, Context};
-use std::iter;
+use enum_iterator::IntoEnumIterator;
 use std::rc::Rc;
 
+/// process `action` and push reactions to `out`, which should be empty
 pub(crate) fn process_action(
     action: Action,
     info: &mut GameInfo,
@@ -20,9 +29,10 @@ pub(crate) fn process_action(
     item: &mut ItemHandler,
     player: &mut Player,
     enemies: &mut EnemyHandler,
-) -> GameResult<(Option<UiState>, Vec<Reaction>)> {
-    let mut out = Vec::new();
+    out: &mut Vec<Reaction>,
+) -> GameResult<Option<UiState>> {
     let mut ui = None;
+    let action = perturb_action(action, player, enemies.combat_rng());
     match action {
         Action::DownStair => {
             if dungeon.is_downstair(&player.pos) {
@@ -32,36 +42,189 @@ pub(crate) fn process_action(
             } else {
                 out.push(Reaction::Notify(GameMsg::NoDownStair));
             }
-            ui = after_turn(player, enemies, dungeon, &mut out)?;
+            ui = after_turn(player, enemies, dungeon, out)?;
         }
         Action::UpStair => {
-            bail!(ErrorKind::Unimplemented("UpStair Command"));
+            if upper_level(info, dungeon, item, player, enemies)
+                .context("action::process_action")?
+            {
+                out.extend_from_slice(&[Reaction::Redraw, Reaction::StatusUpdated]);
+            } else {
+                out.push(Reaction::Notify(GameMsg::NoUpStair));
+            }
+            ui = after_turn(player, enemies, dungeon, out)?;
         }
         Action::Move(d) => {
-            out.append(&mut move_player(d, dungeon, player, enemies)?.0);
-            ui = after_turn(player, enemies, dungeon, &mut out)?;
+            let (_, dead) = move_player(d, dungeon, item, player, enemies, out)?;
+            if dead.is_some() {
+                return Ok(dead);
+            }
+            ui = after_turn(player, enemies, dungeon, out)?;
         }
         Action::MoveUntil(d) => loop {
-            let res = move_player(d, dungeon, player, enemies)?;
+            let len = out.len();
+            let (done, dead) = move_player(d, dungeon, item, player, enemies, out)?;
+            if dead.is_some() {
+                return Ok(dead);
+            }
             let tile = dungeon
                 .tile(&player.pos)
                 .map(|t| t.to_char())
                 .unwrap_or(' ');
-            if res.1 || (tile != '.' && tile != '#') {
-                out.extend(res.0);
+            if done || (tile != '.' && tile != '#') {
+                break;
+            } else if len > 0 {
+                // only reactions to the first and last steps are kept
+                out.truncate(len);
+            }
+            // the player may die while walking
+            ui = after_turn(player, enemies, dungeon, out)?;
+            if ui.is_some() {
                 break;
-            } else if out.is_empty() {
-                out.extend(res.0);
             }
-            ui = after_turn(player, enemies, dungeon, &mut out)?;
         },
         Action::Search => {
-            out.append(&mut search(dungeon, player)?);
-            ui = after_turn(player, enemies, dungeon, &mut out)?;
+            search(dungeon, player, out)?;
+            ui = after_turn(player, enemies, dungeon, out)?;
+        }
+        Action::Throw(d) => {
+            let (mut res, threw) = throw(d, dungeon, item, player, enemies)?;
+            out.append(&mut res);
+            if threw {
+                info.stats.items_used += 1;
+                ui = after_turn(player, enemies, dungeon, out)?;
+            }
+        }
+        Action::Drop(letter) => {
+            let (mut res, dropped) = drop_item(letter, None, dungeon, item, player)?;
+            out.append(&mut res);
+            if dropped {
+                ui = after_turn(player, enemies, dungeon, out)?;
+            }
+        }
+        Action::DropSome(letter, num) => {
+            let (mut res, dropped) = drop_item(letter, Some(num), dungeon, item, player)?;
+            out.append(&mut res);
+            if dropped {
+                ui = after_turn(player, enemies, dungeon, out)?;
+            }
+        }
+        Action::PickUp => {
+            match get_item(dungeon, item, player, true).context("actions::process_action")? {
+                Some(msg) => {
+                    out.push(Reaction::Notify(msg));
+                    out.push(Reaction::StatusUpdated);
+                    ui = after_turn(player, enemies, dungeon, out)?;
+                }
+                None => out.push(Reaction::Notify(GameMsg::NothingToPickUp)),
+            }
+        }
+        Action::Read(letter) => {
+            let (mut res, read) = read_scroll(letter, item, player);
+            out.append(&mut res);
+            if read {
+                info.stats.items_used += 1;
+                ui = after_turn(player, enemies, dungeon, out)?;
+            }
+        }
+        Action::Open(d) => {
+            if dungeon.open_door(&player.pos, d) {
+                out.push(Reaction::Notify(GameMsg::DoorOpened));
+                out.push(Reaction::Redraw);
+                ui = after_turn(player, enemies, dungeon, out)?;
+            } else {
+                out.push(Reaction::Notify(GameMsg::NoDoor));
+            }
+        }
+        Action::Close(d) => {
+            if dungeon.close_door(&player.pos, d, enemies) {
+                out.push(Reaction::Notify(GameMsg::DoorClosed));
+                out.push(Reaction::Redraw);
+                ui = after_turn(player, enemies, dungeon, out)?;
+            } else {
+                out.push(Reaction::Notify(GameMsg::NoDoor));
+            }
+        }
+        Action::AutoExplore => {
+            ui = travel(
+                |dungeon, pos| dungeon.way_to_unexplored(pos),
+                dungeon,
+                item,
+                player,
+                enemies,
+                out,
+            )?;
         }
-        Action::NoOp => return Ok((None, out)),
+        Action::TravelTo(cd) => {
+            ui = travel(
+                |dungeon, pos| dungeon.way_to(pos, cd),
+                dungeon,
+                item,
+                player,
+                enemies,
+                out,
+            )?;
+        }
+        Action::Rest => {
+            ui = rest(dungeon, player, enemies, out)?;
+        }
+        Action::NoOp => return Ok(None),
+    }
+    Ok(ui)
+}
+
+/// read a scroll, and returns reactions and if the player read it or not.
+/// Scrolls of identify are identified by reading, and identify items of their classes in the pack.
+/// Other scrolls are only marked as tried.
+fn read_scroll(
+    letter: char,
+    item_handle: &mut ItemHandler,
+    player: &mut Player,
+) -> (Vec<Reaction>, bool) {
+    let kind = match player.itembox.get(letter) {
+        Some(token) => token.get().kind.clone(),
+        None => return (vec![Reaction::Notify(GameMsg::NoSuchItem(letter))], false),
+    };
+    let targets = match &kind {
+        ItemKind::Scroll(name) => ItemClass::identified_by(name.as_str()),
+        _ => return (vec![Reaction::Notify(GameMsg::CantRead(kind))], false),
+    };
+    player
+        .itembox
+        .take_one_by(|item| item.kind == kind, item_handle);
+    let mut res = vec![Reaction::Notify(GameMsg::Read(kind.clone()))];
+    if item_handle.used(&kind, targets.is_some()) {
+        res.push(Reaction::Notify(GameMsg::Identified(kind)));
+    }
+    let targets = targets.unwrap_or(&[]);
+    let kinds: Vec<_> = player
+        .itembox
+        .items()
+        .filter(|item| ItemClass::from_kind(&item.kind).is_some_and(|(c, _)| targets.contains(&c)))
+        .map(|item| item.kind.clone())
+        .collect();
+    for kind in kinds {
+        if item_handle.identify(&kind) {
+            res.push(Reaction::Notify(GameMsg::Identified(kind)));
+        }
+    }
+    res.push(Reaction::StatusUpdated);
+    (res, true)
+}
+
+/// confused player moves to a random direction sometimes
+fn perturb_action(action: Action, player: &Player, rng: &mut RngHandle) -> Action {
+    if !player.effects().is_active(Effect::Confusion) {
+        return action;
+    }
+    match action {
+        Action::Move(_) | Action::MoveUntil(_) if !rng.does_happen(5) => {
+            let idx = rng.range(0..8);
+            Action::Move(Direction::into_enum_iter().nth(idx).unwrap())
+        }
+        Action::MoveUntil(d) => Action::Move(d),
+        _ => action,
     }
-    Ok((ui, out))
 }
 
 fn after_turn(
@@ -70,12 +233,58 @@ fn after_turn(
     dungeon: &mut dyn Dungeon,
     res: &mut Vec<Reaction>,
 ) -> GameResult<Option<UiState>> {
-    for event in player.turn_passed(enemies.rng()) {
+    // a hasted player may have enough energy to act again in the same turn
+    if !player.effects().is_active(Effect::Sleep) && player.spend_energy() {
+        return Ok(None);
+    }
+    loop {
+        if let Some(ui) = pass_turn(player, enemies, dungeon, res)? {
+            return Ok(Some(ui));
+        }
+        // turns pass while the player is sleeping
+        if player.effects().is_active(Effect::Sleep) {
+            continue;
+        }
+        player.gain_energy();
+        if player.spend_energy() {
+            return Ok(None);
+        }
+    }
+}
+
+fn pass_turn(
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+    dungeon: &mut dyn Dungeon,
+    res: &mut Vec<Reaction>,
+) -> GameResult<Option<UiState>> {
+    for event in player.turn_passed(enemies.combat_rng()) {
         match event {
-            PlayerEvent::Dead => {}
+            PlayerEvent::Dead => {
+                let mordal = UiState::die("Starved to death".to_owned());
+                res.push(Reaction::UiTransition(mordal.clone()));
+                return Ok(Some(mordal));
+            }
             PlayerEvent::Healed | PlayerEvent::Hungry => res.push(Reaction::StatusUpdated),
         }
     }
+    let expired = player.effects_mut().tick();
+    if !expired.is_empty() {
+        res.extend(
+            expired
+                .into_iter()
+                .map(|effect| Reaction::Notify(GameMsg::EffectEnded(effect))),
+        );
+        res.push(Reaction::StatusUpdated);
+    }
+    if enemies.tick_wander() {
+        if let Some(path) = dungeon.spawn_wanderer(&player.pos, enemies) {
+            let name = enemies
+                .get_enemy(&path)
+                .map(|enemy| enemy.name().to_owned());
+            res.extend(name.map(|name| Reaction::Notify(GameMsg::EnemySpawned(name))));
+        }
+    }
     move_active_enemies(enemies, dungeon, player, res)
 }
 
@@ -85,31 +294,52 @@ fn move_active_enemies(
     player: &mut Player,
     res: &mut Vec<Reaction>,
 ) -> GameResult<Option<UiState>> {
-    let attacks = enemies.move_actives(&player.pos, None, dungeon);
+    let attacks = enemies.move_actives(&player.pos, dungeon);
     if !attacks.is_empty() {
         player.buttle();
     }
     let mut did_hit = false;
     for at in attacks {
-        match fight::enemy_attack(at.enemy(), player, enemies.rng()) {
-            Some(hp) => {
-                let name = at.enemy().name();
-                res.push(Reaction::Notify(GameMsg::HitFrom(name.to_owned())));
-                did_hit = true;
-                match player.get_damage(hp) {
-                    DamageReaction::Death => {
-                        let mordal = UiState::die(format!("Killed by {}", name));
-                        res.push(Reaction::UiTransition(mordal.clone()));
-                        return Ok(Some(mordal));
-                    }
-                    DamageReaction::None => {}
-                }
-            }
-            None => {
-                res.push(Reaction::Notify(GameMsg::MissFrom(
-                    at.enemy().name().to_owned(),
-                )));
+        let name = at.enemy().name();
+        let ranged = at.enemy().ranged().filter(|_| at.projectile().is_some());
+        let roll = match (ranged, at.projectile()) {
+            (Some(ranged), Some(projectile)) => {
+                let path = projectile.iter().map(|p| dungeon.path_to_cd(p)).collect();
+                res.push(Reaction::Notify(GameMsg::Projectile(Projectile {
+                    from: name.to_owned(),
+                    symbol: ranged.symbol,
+                    path,
+                })));
+                fight::ranged_attack(&ranged, player, enemies.combat_rng())
             }
+            _ => fight::enemy_attack(at.enemy(), player, enemies.combat_rng()),
+        }
+        .scaled(enemies.damage_percent());
+        let killed = roll.damage.is_some_and(|hp| match player.get_damage(hp) {
+            DamageReaction::Death => true,
+            DamageReaction::None => false,
+        });
+        res.push(Reaction::Notify(GameMsg::Combat(CombatEvent {
+            attacker: Combatant::Enemy(name.to_owned()),
+            defender: Combatant::Player,
+            roll,
+            killed,
+        })));
+        if !roll.did_hit() {
+            res.push(Reaction::Notify(GameMsg::MissFrom(name.to_owned())));
+            continue;
+        }
+        if ranged.is_none() {
+            res.push(Reaction::Notify(GameMsg::HitFrom(name.to_owned())));
+        }
+        did_hit = true;
+        if killed {
+            let mordal = UiState::die(format!("Killed by {}", name));
+            res.push(Reaction::UiTransition(mordal.clone()));
+            return Ok(Some(mordal));
+        }
+        if ranged.is_none() {
+            special_attack(&at, dungeon.level(), player, enemies, res);
         }
     }
     if did_hit {
@@ -118,6 +348,66 @@ fn move_active_enemies(
     Ok(None)
 }
 
+/// special attacks by enemies like aquator or leprechaun
+fn special_attack(
+    at: &Attack,
+    level: u32,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+    res: &mut Vec<Reaction>,
+) {
+    let enemy = at.enemy();
+    if enemy.rusts_armor() && player.rust_armor() {
+        res.push(Reaction::Notify(GameMsg::ArmorWeakened));
+    }
+    if enemy.reduces_str()
+        && player.resistances().get(DamageKind::Poison) < Parcent(100)
+        && !fight::save_throw(fight::VS_POISON, player, enemies.combat_rng())
+        && player.reduce_strength()
+    {
+        res.push(Reaction::Notify(GameMsg::StrengthDrained));
+    }
+    if enemy.drains_level() && enemies.combat_rng().parcent(Parcent(15)) {
+        player.drain_level(enemies.combat_rng());
+        res.push(Reaction::Notify(GameMsg::LevelDrained));
+    }
+    if enemy.freezes() && !fight::save_throw(fight::VS_MAGIC, player, enemies.combat_rng()) {
+        let turns = enemies.combat_rng().range(2..4);
+        player.effects_mut().add(Effect::Sleep, turns);
+        res.push(Reaction::Notify(GameMsg::EffectStarted(Effect::Sleep)));
+        res.push(Reaction::StatusUpdated);
+    }
+    // thieves vanish after stealing something
+    let mut vanished = false;
+    if enemy.steals_gold() {
+        let rng = enemies.combat_rng();
+        let gold_calc = |rng: &mut RngHandle| rng.range(0..50 + 10 * level) + 2;
+        let mut amount = gold_calc(rng);
+        if !fight::save_throw(fight::VS_MAGIC, player, rng) {
+            amount += (0..4).map(|_| gold_calc(rng)).sum::<u32>();
+        }
+        let stolen = player.steal_gold(amount);
+        if stolen > 0 {
+            res.push(Reaction::Notify(GameMsg::GoldStolen(stolen)));
+        }
+        vanished = true;
+    }
+    if enemy.steals_item() {
+        if let Some(item) = player.steal_item(enemies.combat_rng()) {
+            let item = item.get();
+            res.push(Reaction::Notify(GameMsg::ItemStolen {
+                kind: item.kind.clone(),
+                num: item.how_many.0,
+            }));
+            vanished = true;
+        }
+    }
+    if vanished {
+        enemies.remove(at.place().clone());
+        res.push(Reaction::Redraw);
+    }
+}
+
 pub(crate) fn new_level(
     info: &GameInfo,
     dungeon: &mut dyn Dungeon,
@@ -137,47 +427,254 @@ pub(crate) fn new_level(
     dungeon.enter_room(&player.pos, enemies)
 }
 
+/// returns if the player went up or not
+fn upper_level(
+    info: &GameInfo,
+    dungeon: &mut dyn Dungeon,
+    item: &mut ItemHandler,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+) -> GameResult<bool> {
+    let pos = match dungeon.upper_level(&player.pos, info, item, enemies)? {
+        Some(pos) => pos,
+        None => return Ok(false),
+    };
+    player.pos = pos;
+    dungeon.enter_room(&player.pos, enemies)?;
+    Ok(true)
+}
+
+/// returns reactions and if the player hit the enemy or not
 fn player_attack(
     player: &mut Player,
+    throw_weapon: Option<&ItemToken>,
     enemy: Rc<Enemy>,
     place: DungeonPath,
+    dungeon: &mut dyn Dungeon,
+    item: &mut ItemHandler,
     enemies: &mut EnemyHandler,
-) -> GameResult<Vec<Reaction>> {
+) -> GameResult<(Vec<Reaction>, bool)> {
     let mut res = Vec::new();
     player.buttle();
     enemies.activate(place.clone());
-    if let Some(hp) = fight::player_attack(player, None, &*enemy, enemies.rng()) {
-        res.push(Reaction::Notify(GameMsg::HitTo(enemy.name().to_owned())));
-        match enemy.get_damage(hp) {
-            DamageReaction::Death => {
-                enemies.remove(place);
-                if player.level_up(enemy.exp(), enemies.rng()) {
-                    res.push(Reaction::StatusUpdated);
+    let throw_weapon = throw_weapon.map(|token| token.get());
+    let roll = fight::player_attack(player, throw_weapon, &enemy, enemies.combat_rng());
+    let killed = roll.damage.is_some_and(|hp| match enemy.get_damage(hp) {
+        DamageReaction::Death => true,
+        DamageReaction::None => false,
+    });
+    res.push(Reaction::Notify(GameMsg::Combat(CombatEvent {
+        attacker: Combatant::Player,
+        defender: Combatant::Enemy(enemy.name().to_owned()),
+        roll,
+        killed,
+    })));
+    if !roll.did_hit() {
+        res.push(Reaction::Notify(GameMsg::MissTo(enemy.name().to_owned())));
+        return Ok((res, false));
+    }
+    res.push(Reaction::Notify(GameMsg::HitTo(enemy.name().to_owned())));
+    if killed {
+        enemies.remove(place.clone());
+        enemies.killed(&enemy);
+        if player.level_up(enemy.exp(), enemies.combat_rng()) {
+            res.push(Reaction::StatusUpdated);
+        }
+        res.push(Reaction::Notify(GameMsg::Killed(enemy.name().to_owned())));
+        let loot = enemies
+            .gen_loot(&enemy, dungeon.level(), item)
+            .context("actions::player_attack")?;
+        if let Some(loot) = loot {
+            let (kind, num) = (loot.kind.clone(), loot.how_many.0);
+            if dungeon.put_item(&place, loot).is_ok() {
+                res.push(Reaction::Notify(GameMsg::EnemyDropped {
+                    enemy: enemy.name().to_owned(),
+                    kind,
+                    num,
+                }));
+            }
+        }
+        res.push(Reaction::Redraw);
+    }
+    Ok((res, true))
+}
+
+/// throw a missile toward the direction, and returns reactions and
+/// if the player threw something or not
+fn throw(
+    direction: Direction,
+    dungeon: &mut dyn Dungeon,
+    item: &mut ItemHandler,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+) -> GameResult<(Vec<Reaction>, bool)> {
+    let missile = match player.take_missile(item) {
+        Some(missile) => missile,
+        None => return Ok((vec![Reaction::Notify(GameMsg::NothingToThrow)], false)),
+    };
+    let mut res = vec![Reaction::StatusUpdated];
+    let mut pos = player.pos.clone();
+    let mut did_hit = false;
+    for next in missile_path(direction, dungeon, &player.pos, enemies) {
+        if let Some(enemy) = enemies.get_cloned(&next) {
+            let (mut attack, hit) =
+                player_attack(player, Some(&missile), enemy, next, dungeon, item, enemies)?;
+            res.append(&mut attack);
+            did_hit = hit;
+            break;
+        }
+        pos = next;
+    }
+    // a returning weapon comes back to the pack if there's space
+    if missile.special().is_some_and(|s| s.returning) {
+        let returned = match player.itembox.entry(&missile) {
+            Some(ItemEntry::Insert(entry)) => Some(entry.exec(ItemToken::clone(&missile))),
+            Some(ItemEntry::Merge(entry)) => Some(entry.exec(missile.get_cloned())),
+            None => None,
+        };
+        if let Some(returned) = returned {
+            res.push(Reaction::Notify(GameMsg::Returned(returned.kind)));
+            return Ok((res, true));
+        }
+    }
+    // the missile is lost when it hits
+    if did_hit {
+        return Ok((res, true));
+    }
+    if dungeon.put_item(&pos, missile).is_ok() {
+        res.push(Reaction::Redraw);
+    }
+    Ok((res, true))
+}
+
+/// cells a missile thrown toward the direction passes,
+/// ending at the cell before a wall or at an enemy
+pub(crate) fn missile_path(
+    direction: Direction,
+    dungeon: &dyn Dungeon,
+    start: &DungeonPath,
+    enemies: &EnemyHandler,
+) -> Vec<DungeonPath> {
+    let mut path = vec![];
+    if direction == Direction::Stay {
+        return path;
+    }
+    let mut pos = start.clone();
+    while let Some(next) = dungeon.can_move_player(&pos, direction) {
+        path.push(next.clone());
+        if enemies.get_enemy(&next).is_some() {
+            break;
+        }
+        pos = next;
+    }
+    path
+}
+
+/// drop an item, and returns reactions and if the player dropped it or not.
+/// If `num` is specified, only `num` items are split off the stack and dropped.
+/// Items dropped in a shop are sold.
+fn drop_item(
+    letter: char,
+    num: Option<u32>,
+    dungeon: &mut dyn Dungeon,
+    item_handle: &mut ItemHandler,
+    player: &mut Player,
+) -> GameResult<(Vec<Reaction>, bool)> {
+    if dungeon.get_item(&player.pos).is_some() {
+        return Ok((vec![Reaction::Notify(GameMsg::CantDropHere)], false));
+    }
+    if num == Some(0) {
+        return Ok((vec![], false));
+    }
+    let slot = ItemBox::slot(letter);
+    // the stack is split only if some items are left
+    let split = match (slot, num) {
+        (Some(slot), Some(num))
+            if player
+                .itembox
+                .get(letter)
+                .is_some_and(|stack| stack.how_many > ItemNum(num)) =>
+        {
+            Some((slot, num))
+        }
+        _ => None,
+    };
+    let item = match split {
+        Some((slot, num)) => player.itembox.split(slot, ItemNum(num), item_handle),
+        None => player.remove_item(letter),
+    };
+    let item = match item {
+        Some(item) => item,
+        None => return Ok((vec![Reaction::Notify(GameMsg::NoSuchItem(letter))], false)),
+    };
+    let (kind, num) = (item.kind.clone(), item.how_many.0);
+    let price = item_handle.prices().sell_price(&item);
+    match dungeon.put_item(&player.pos, item) {
+        Ok(path) if dungeon.is_shop(&path) && kind != ItemKind::Gold => {
+            if let Some(item) = dungeon.get_item(&path) {
+                ItemToken::clone(item).get_mut().attr.or(ItemAttr::FOR_SALE);
+            }
+            let gold = item_handle.gen_gold(price);
+            match player.itembox.entry(&gold) {
+                Some(ItemEntry::Insert(entry)) => entry.exec(gold),
+                Some(ItemEntry::Merge(entry)) => entry.exec(gold.get_cloned()),
+                None => bail!(ErrorKind::MaybeBug(
+                    "[actions::drop_item] no space for gold"
+                )),
+            };
+            Ok((
+                vec![
+                    Reaction::Notify(GameMsg::Sold { kind, num, price }),
+                    Reaction::StatusUpdated,
+                    Reaction::Redraw,
+                ],
+                true,
+            ))
+        }
+        Ok(_) => Ok((
+            vec![
+                Reaction::Notify(GameMsg::Dropped { kind, num }),
+                Reaction::StatusUpdated,
+                Reaction::Redraw,
+            ],
+            true,
+        )),
+        Err(item) => {
+            match (player.itembox.get(letter), split) {
+                (Some(stack), Some(_)) => {
+                    ItemToken::clone(stack).get_mut().how_many += item.how_many;
+                }
+                _ => {
+                    if let Some(slot) = slot {
+                        player.itembox.put_back(slot, item);
+                    }
                 }
-                res.push(Reaction::Notify(GameMsg::Killed(enemy.name().to_owned())));
-                res.push(Reaction::Redraw);
             }
-            DamageReaction::None => {}
+            Ok((vec![Reaction::Notify(GameMsg::CantDropHere)], false))
         }
-    } else {
-        res.push(Reaction::Notify(GameMsg::MissTo(enemy.name().to_owned())));
     }
-    Ok(res)
 }
 
+/// move the player and push reactions to `res`.
+/// Returns if the player should stop and the UI state if the player died.
 fn move_player(
     direction: Direction,
     dungeon: &mut dyn Dungeon,
+    item: &mut ItemHandler,
     player: &mut Player,
     enemies: &mut EnemyHandler,
-) -> GameResult<(Vec<Reaction>, bool)> {
+    res: &mut Vec<Reaction>,
+) -> GameResult<(bool, Option<UiState>)> {
     let new_pos = if let Some(next) = dungeon.can_move_player(&player.pos, direction) {
         next
     } else {
-        return Ok((vec![Reaction::Notify(GameMsg::CantMove(direction))], true));
+        res.push(Reaction::Notify(GameMsg::CantMove(direction)));
+        return Ok((true, None));
     };
     if let Some(enemy) = enemies.get_cloned(&new_pos) {
-        return player_attack(player, enemy, new_pos, enemies).map(|r| (r, true));
+        let (mut attack, _) = player_attack(player, None, enemy, new_pos, dungeon, item, enemies)?;
+        res.append(&mut attack);
+        return Ok((true, None));
     }
     let new_pos = dungeon
         .move_player(&player.pos, direction, enemies)
@@ -185,25 +682,188 @@ fn move_player(
     player.pos = new_pos;
     player.run(true);
     let mut done = false;
-    let mut res = vec![Reaction::Redraw];
-    if let Some(msg) = get_item(dungeon, player).context("in actions::move_player")? {
+    res.push(Reaction::Redraw);
+    if let Some(msg) = get_item(dungeon, item, player, false).context("in actions::move_player")? {
         res.push(Reaction::Notify(msg));
         res.push(Reaction::StatusUpdated);
         done = true;
     }
-    Ok((res, done))
+    if let Some(kind) = dungeon.spring_trap(&player.pos) {
+        res.push(Reaction::Notify(GameMsg::Trapped(kind)));
+        let dead = spring_trap(kind, player, enemies.combat_rng(), res);
+        return Ok((true, dead));
+    }
+    Ok((done, None))
 }
 
-fn search(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Vec<Reaction>> {
-    dungeon.search(&player.pos).map(|v| {
-        v.into_iter()
-            .map(|msg| Reaction::Notify(msg))
-            .chain(iter::once(Reaction::Redraw))
-            .collect()
+/// applies the effect of the trap the player fell into
+fn spring_trap(
+    kind: TrapKind,
+    player: &mut Player,
+    rng: &mut RngHandle,
+    res: &mut Vec<Reaction>,
+) -> Option<UiState> {
+    let damage = match kind {
+        TrapKind::Sleep => {
+            let turns = rng.range(2..7);
+            player.effects_mut().add(Effect::Sleep, turns);
+            res.push(Reaction::Notify(GameMsg::EffectStarted(Effect::Sleep)));
+            None
+        }
+        TrapKind::Arrow => Some((Dice::new(1, HitPoint(6)).random(rng), "an arrow")),
+        TrapKind::Dart => {
+            let damage = Dice::new(1, HitPoint(4)).random(rng);
+            if player.resistances().get(DamageKind::Poison) < Parcent(100)
+                && !fight::save_throw(fight::VS_POISON, player, rng)
+                && player.reduce_strength()
+            {
+                res.push(Reaction::Notify(GameMsg::StrengthDrained));
+            }
+            Some((damage, "a poisoned dart"))
+        }
+        TrapKind::Rust => {
+            if player.rust_armor() {
+                res.push(Reaction::Notify(GameMsg::ArmorWeakened));
+            }
+            None
+        }
+    };
+    res.push(Reaction::StatusUpdated);
+    if let Some((damage, name)) = damage {
+        let damage = player.resistances().reduce(DamageKind::Physical, damage);
+        if let DamageReaction::Death = player.get_damage(damage) {
+            let mordal = UiState::die(format!("Killed by {}", name));
+            res.push(Reaction::UiTransition(mordal.clone()));
+            return Some(mordal);
+        }
+    }
+    None
+}
+
+/// moves the player step by step toward the direction `next_step` returns,
+/// until the player reaches the goal, an enemy or an item comes into sight,
+/// or something happens
+fn travel(
+    next_step: impl Fn(&dyn Dungeon, &DungeonPath) -> Option<Direction>,
+    dungeon: &mut dyn Dungeon,
+    item: &mut ItemHandler,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+    out: &mut Vec<Reaction>,
+) -> GameResult<Option<UiState>> {
+    let mut moved = false;
+    let mut sighted = count_sighted(dungeon, player, enemies);
+    while let Some(d) = next_step(dungeon, &player.pos) {
+        let len = out.len();
+        let (done, dead) = move_player(d, dungeon, item, player, enemies, out)?;
+        moved = true;
+        let mut i = 0;
+        out.retain(|r| {
+            i += 1;
+            i <= len || *r != Reaction::Redraw
+        });
+        if dead.is_some() {
+            out.push(Reaction::Redraw);
+            return Ok(dead);
+        }
+        if done {
+            break;
+        }
+        let len = out.len();
+        if let Some(ui) = after_turn(player, enemies, dungeon, out)? {
+            out.push(Reaction::Redraw);
+            return Ok(Some(ui));
+        }
+        let interrupted = interrupts(&out[len..]);
+        let now_sighted = count_sighted(dungeon, player, enemies);
+        if interrupted || now_sighted.0 > sighted.0 || now_sighted.1 > sighted.1 {
+            break;
+        }
+        sighted = now_sighted;
+    }
+    if moved {
+        out.push(Reaction::Redraw);
+    } else {
+        out.push(Reaction::Notify(GameMsg::NoWay));
+    }
+    Ok(None)
+}
+
+/// passes turns until the player's HP is full, an enemy comes into sight,
+/// or something happens
+fn rest(
+    dungeon: &mut dyn Dungeon,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+    out: &mut Vec<Reaction>,
+) -> GameResult<Option<UiState>> {
+    let sighted = count_sighted(dungeon, player, enemies).0;
+    let mut turns = 0;
+    while turns < player.max_rest_turns() && !player.hp_is_full() {
+        let len = out.len();
+        if let Some(ui) = after_turn(player, enemies, dungeon, out)? {
+            return Ok(Some(ui));
+        }
+        turns += 1;
+        let interrupted = interrupts(&out[len..]);
+        if interrupted || count_sighted(dungeon, player, enemies).0 > sighted {
+            out.push(Reaction::Notify(GameMsg::RestInterrupted));
+            break;
+        }
+    }
+    if turns > 0 {
+        out.push(Reaction::Redraw);
+    }
+    Ok(None)
+}
+
+/// returns if any of the reactions should stop a repeated action like travel or rest
+fn interrupts(reactions: &[Reaction]) -> bool {
+    reactions.iter().any(|r| match r {
+        // the player doesn't notice wandering enemies until they come into sight
+        Reaction::Notify(GameMsg::EnemySpawned(_)) => false,
+        Reaction::Notify(_) => true,
+        _ => false,
     })
 }
 
-fn get_item(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Option<GameMsg>> {
+/// returns the numbers of enemies and items the player can see
+fn count_sighted(dungeon: &dyn Dungeon, player: &Player, enemies: &EnemyHandler) -> (usize, usize) {
+    let is_blind = player.effects().is_active(Effect::Blindness);
+    dungeon
+        .draw_ranges()
+        .iter()
+        .fold((0, 0), |(num_enemies, num_items), path| {
+            let sees_enemy = enemies.get_enemy(path).is_some_and(|enemy| {
+                !enemy.is_invisible() && !is_blind && dungeon.draw_enemy(&player.pos, path)
+            });
+            let sees_item = dungeon.get_item(path).is_some();
+            (
+                num_enemies + sees_enemy as usize,
+                num_items + sees_item as usize,
+            )
+        })
+}
+
+fn search(
+    dungeon: &mut dyn Dungeon,
+    player: &mut Player,
+    out: &mut Vec<Reaction>,
+) -> GameResult<()> {
+    let found = dungeon.search(&player.pos)?;
+    out.extend(found.into_iter().map(Reaction::Notify));
+    out.push(Reaction::Redraw);
+    Ok(())
+}
+
+/// pick up the item under the player.
+/// An item sold in a shop is bought if `buy` is true, and otherwise only its price is told.
+fn get_item(
+    dungeon: &mut dyn Dungeon,
+    item_handle: &ItemHandler,
+    player: &mut Player,
+    buy: bool,
+) -> GameResult<Option<GameMsg>> {
     macro_rules! try_or_ok {
         ($res: expr) => {
             match $res {
@@ -212,20 +872,141 @@ fn get_item(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Option
             }
         };
     }
-    let got_item = {
+    let (id, got_item, price) = {
         let item_ref = try_or_ok!(dungeon.get_item(&player.pos));
-        let pack_entry = try_or_ok!(player.itembox.entry(item_ref));
-        match pack_entry {
-            ItemEntry::Insert(player_entry) => player_entry.exec(ItemToken::clone(item_ref)),
-            ItemEntry::Merge(player_entry) => player_entry.exec(item_ref.get().clone()),
+        let price = if item_ref.attr.contains(ItemAttr::FOR_SALE) {
+            let (kind, num) = (item_ref.kind.clone(), item_ref.how_many.0);
+            let price = item_handle.prices().price(item_ref);
+            if !buy {
+                return Ok(Some(GameMsg::ForSale { kind, num, price }));
+            }
+            if player.itembox.gold() < price {
+                return Ok(Some(GameMsg::CantAfford { kind, num, price }));
+            }
+            Some(price)
+        } else {
+            None
+        };
+        let pack_entry = match player.itembox.entry(item_ref) {
+            Some(entry) => entry,
+            None => {
+                return Ok(Some(GameMsg::PackIsFull {
+                    kind: item_ref.kind.clone(),
+                    num: item_ref.how_many.0,
+                }))
+            }
+        };
+        if price.is_some() {
+            ItemToken::clone(item_ref)
+                .get_mut()
+                .attr
+                .remove(ItemAttr::FOR_SALE);
         }
+        let (id, got_item) = match pack_entry {
+            ItemEntry::Insert(player_entry) => {
+                (item_ref.id(), player_entry.exec(ItemToken::clone(item_ref)))
+            }
+            ItemEntry::Merge(player_entry) => {
+                (player_entry.id(), player_entry.exec(item_ref.get().clone()))
+            }
+        };
+        (id, got_item, price)
     };
     if dungeon.remove_item(&player.pos).is_none() {
         warn!("[actions::get_item] couldn't remove object!!!")
     }
     //dungeon.remove_from_place(&player.pos);
+    if let Some(price) = price {
+        player.itembox.pay_gold(price);
+        return Ok(Some(GameMsg::Bought {
+            id,
+            kind: got_item.kind.clone(),
+            num: got_item.how_many.0,
+            price,
+        }));
+    }
     Ok(Some(GameMsg::GotItem {
+        id,
         kind: got_item.kind.clone(),
         num: got_item.how_many.0,
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::InputCode;
+    use crate::{GameConfig, RunTime};
+    /// two lit rooms connected by a passage, where no enemy appears unless placed
+    fn setup_runtime(max_rest_turns: u32) -> RunTime {
+        let config = format!(
+            r#"{{
+                "width": 32,
+                "height": 16,
+                "seed": 1,
+                "dungeon": {{"style": "rogue", "static_floors": [{{"map": [
+                    "--------       --------",
+                    "|......+#######+......|",
+                    "|......|       |......|",
+                    "--------       --------"
+                ]}}]}},
+                "enemies": {{"enemies": [7], "appear_rate_gold": 0, "appear_rate_nogold": 0, "wander_interval": 0}},
+                "player": {{"max_rest_turns": {}}}
+            }}"#,
+            max_rest_turns
+        );
+        let mut runtime = GameConfig::from_json(&config).unwrap().build().unwrap();
+        runtime.player.pos = [1, 1, 2].into();
+        runtime
+    }
+    fn rest(runtime: &mut RunTime) -> (Vec<Reaction>, u32) {
+        let turns = runtime.player_status().turns;
+        let res = runtime
+            .react_to_input(InputCode::Act(Action::Rest))
+            .unwrap();
+        (res, runtime.player_status().turns - turns)
+    }
+    fn interrupted(reactions: &[Reaction]) -> bool {
+        reactions
+            .iter()
+            .any(|r| matches!(r, Reaction::Notify(GameMsg::RestInterrupted)))
+    }
+    #[test]
+    fn rest_until_healed() {
+        let mut runtime = setup_runtime(200);
+        // nothing happens with full HP
+        assert_eq!(rest(&mut runtime).1, 0);
+        runtime.player.get_damage(HitPoint(1));
+        let (res, turns) = rest(&mut runtime);
+        assert!(turns > 0 && turns < 200);
+        assert!(runtime.player.hp_is_full());
+        assert!(!interrupted(&res));
+    }
+    #[test]
+    fn rest_is_capped() {
+        let mut runtime = setup_runtime(3);
+        runtime.player.get_damage(HitPoint(5));
+        let (res, turns) = rest(&mut runtime);
+        assert_eq!(turns, 3);
+        assert!(!runtime.player.hp_is_full());
+        assert!(!interrupted(&res));
+    }
+    #[test]
+    fn rest_is_interrupted_by_enemies() {
+        let mut runtime = setup_runtime(200);
+        runtime.player.get_damage(HitPoint(5));
+        // an enemy in the other room comes to the player
+        let enemy = runtime.enemies.gen_enemy_always(1, 0..1, 0).unwrap();
+        let path: DungeonPath = [1, 20, 2].into();
+        runtime.enemies.place(path.clone(), enemy);
+        runtime.enemies.activate(path);
+        let (res, turns) = rest(&mut runtime);
+        assert!(interrupted(&res));
+        assert!(turns > 0 && turns < 20);
+        assert!(!runtime.player.hp_is_full());
+        assert!(runtime.dungeon.draw_ranges().iter().any(|p| {
+            runtime.enemies.get_enemy(p).is_some()
+                && runtime.dungeon.draw_enemy(&runtime.player.pos, p)
+        }));
+    }
+}
@@ -0,0 +1,286 @@
+//! programmatic agents written in Rust, for baselines, tests and making datasets
+use crate::character::player::Status;
+use crate::character::Action;
+use crate::dungeon::{Coord, Direction};
+use crate::error::*;
+use crate::input::{InputCode, System};
+use crate::rng::RngHandle;
+use crate::summary::GameSummary;
+use crate::{GameConfig, RunTime};
+
+/// What an agent sees before each action
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameState {
+    /// symbol indices made by `RunTime::observe_symbols`, in row-major order
+    pub symbols: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub status: Status,
+    /// the position of the player on the screen
+    pub player: Coord,
+    /// same as `RunTime::action_set`
+    pub actions: Vec<InputCode>,
+    /// mask of `actions` made by `RunTime::valid_actions`
+    pub valid_actions: Vec<bool>,
+}
+
+impl GameState {
+    /// the symbol at (x, y), or None if it's out of the observation
+    pub fn symbol(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.symbols.get(y * self.width + x).copied()
+    }
+    /// actions which do something now
+    pub fn available_actions(&self) -> impl Iterator<Item = InputCode> + '_ {
+        self.actions
+            .iter()
+            .zip(&self.valid_actions)
+            .filter(|(_, &valid)| valid)
+            .map(|(&action, _)| action)
+    }
+    /// the first position of `symbol` in the observation
+    fn find(&self, symbol: u8) -> Option<(usize, usize)> {
+        let i = self.symbols.iter().position(|&s| s == symbol)?;
+        Some((i % self.width, i / self.width))
+    }
+}
+
+impl RunTime {
+    /// the current state for agents
+    pub fn game_state(&self) -> GameResult<GameState> {
+        let (width, height) = self.observation_size();
+        let mut symbols = vec![0; width * height];
+        self.observe_symbols(&mut symbols)?;
+        Ok(GameState {
+            symbols,
+            width,
+            height,
+            status: self.player_status(),
+            player: self.player_position(),
+            actions: self.action_set(),
+            valid_actions: self.valid_actions(),
+        })
+    }
+}
+
+/// An agent which chooses an input for each state
+pub trait Agent {
+    fn act(&mut self, state: &GameState) -> InputCode;
+    /// called before each episode
+    fn reset(&mut self) {}
+}
+
+impl<F: FnMut(&GameState) -> InputCode> Agent for F {
+    fn act(&mut self, state: &GameState) -> InputCode {
+        self(state)
+    }
+}
+
+/// A baseline which chooses one of the available actions uniformly at random
+#[derive(Clone)]
+pub struct RandomAgent {
+    rng: RngHandle,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u128) -> Self {
+        RandomAgent {
+            rng: RngHandle::from_seed(seed),
+        }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn act(&mut self, state: &GameState) -> InputCode {
+        let actions: Vec<_> = state.available_actions().collect();
+        if actions.is_empty() {
+            return InputCode::Sys(System::Cancel);
+        }
+        actions[self.rng.range(0..actions.len())]
+    }
+}
+
+const PLAYER_SYMBOL: u8 = 1;
+const STAIR_SYMBOL: u8 = 5;
+/// symbols of enemies, which are upper case letters
+const ENEMY_SYMBOLS: u8 = 17;
+
+/// A rule-based baseline which goes down the stairs as soon as possible.
+/// It attacks adjacent enemies, travels to the stairs if they are visible,
+/// and explores the floor otherwise.
+#[derive(Clone, Debug, Default)]
+pub struct GreedyAgent {
+    last_turns: Option<u32>,
+}
+
+impl GreedyAgent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Agent for GreedyAgent {
+    fn act(&mut self, state: &GameState) -> InputCode {
+        let stuck = self.last_turns == Some(state.status.turns);
+        self.last_turns = Some(state.status.turns);
+        let available: Vec<_> = state.available_actions().collect();
+        if available.is_empty() {
+            // in menus, where no action is available
+            return InputCode::Sys(System::Cancel);
+        }
+        let has = |act| available.contains(&InputCode::Act(act));
+        if has(Action::DownStair) {
+            return InputCode::Act(Action::DownStair);
+        }
+        let (px, py) = match state.find(PLAYER_SYMBOL) {
+            Some(pos) => pos,
+            None => return InputCode::Act(Action::Search),
+        };
+        for &d in Direction::NEIGHBORS.iter() {
+            let Coord { x, y } = d.to_cd();
+            let (x, y) = (px as i32 + x.0, py as i32 + y.0);
+            if x < 0 || y < 0 {
+                continue;
+            }
+            let enemy = state.symbol(x as usize, y as usize);
+            if enemy.is_some_and(|s| s >= ENEMY_SYMBOLS) {
+                return InputCode::Act(Action::Move(d));
+            }
+        }
+        // traveling or exploring does nothing if it's interrupted before the first step
+        if stuck {
+            return InputCode::Act(Action::Search);
+        }
+        if let Some((sx, sy)) = state.find(STAIR_SYMBOL) {
+            let diff = Coord::new(sx as i32 - px as i32, sy as i32 - py as i32);
+            return InputCode::Act(Action::TravelTo(state.player + diff));
+        }
+        if has(Action::AutoExplore) {
+            return InputCode::Act(Action::AutoExplore);
+        }
+        InputCode::Act(Action::Search)
+    }
+    fn reset(&mut self) {
+        self.last_turns = None;
+    }
+}
+
+/// A step of a trajectory
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    pub action: InputCode,
+    pub reward: f32,
+    pub done: bool,
+}
+
+/// What happened in an episode played by an agent
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trajectory {
+    pub steps: Vec<Transition>,
+    pub summary: GameSummary,
+}
+
+impl Trajectory {
+    pub fn total_reward(&self) -> f32 {
+        self.steps.iter().map(|t| t.reward).sum()
+    }
+}
+
+/// let `agent` play `episodes` games and collect their trajectories.
+/// Seeds are chosen by `config.seed_schedule`, or if it's not specified and `config` has a seed,
+/// the i-th episode uses `seed + i`.
+///
+/// Episodes end only when the player dies or a limit in `GameConfig::episode` is reached,
+/// so agents which may get stuck should be run with limits.
+pub fn run_agent(
+    config: &GameConfig,
+    agent: &mut dyn Agent,
+    episodes: usize,
+) -> GameResult<Vec<Trajectory>> {
+    run_agent_with(config, agent, episodes, |_, _| Ok(()))
+}
+
+/// `run_agent` calling `on_step` with the state before each action and the transition
+pub fn run_agent_with(
+    config: &GameConfig,
+    agent: &mut dyn Agent,
+    episodes: usize,
+    mut on_step: impl FnMut(&GameState, &Transition) -> GameResult<()>,
+) -> GameResult<Vec<Trajectory>> {
+    let mut res = Vec::with_capacity(episodes);
+    for i in 0..episodes {
+        let mut config = config.for_episode(i);
+        if config.seed_schedule.is_none() {
+            config.seed = config.seed.map(|s| s.wrapping_add(i as u128));
+        }
+        let mut runtime = config.build()?;
+        agent.reset();
+        let mut steps = vec![];
+        loop {
+            let state = runtime.game_state()?;
+            let action = agent.act(&state);
+            let (reward, info) = runtime.advance(action)?;
+            let transition = Transition {
+                action,
+                reward,
+                done: info.is_done(),
+            };
+            let done = transition.done;
+            on_step(&state, &transition)?;
+            steps.push(transition);
+            if done {
+                break;
+            }
+        }
+        res.push(Trajectory {
+            steps,
+            summary: runtime.summary(),
+        });
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::Action;
+    #[test]
+    fn run_agent() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.episode.max_turns = Some(3);
+        let mut agent = |state: &GameState| {
+            assert_eq!(state.symbols.len(), state.width * state.height);
+            assert_eq!(state.actions.len(), state.valid_actions.len());
+            InputCode::Act(Action::Search)
+        };
+        let trajectories = super::run_agent(&config, &mut agent, 2).unwrap();
+        assert_eq!(trajectories.len(), 2);
+        assert_eq!(trajectories[0].summary.seed, 1);
+        assert_eq!(trajectories[1].summary.seed, 2);
+        for trajectory in &trajectories {
+            let dones: Vec<_> = trajectory.steps.iter().map(|t| t.done).collect();
+            assert_eq!(dones, vec![false, false, true]);
+            assert_eq!(trajectory.summary.turns, 3);
+            assert_eq!(trajectory.total_reward(), 0.0);
+        }
+    }
+    #[test]
+    fn bots() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.episode.max_turns = Some(500);
+        let mut random = RandomAgent::new(1);
+        let trajectories = super::run_agent(&config, &mut random, 2).unwrap();
+        let again = super::run_agent(&config, &mut RandomAgent::new(1), 2).unwrap();
+        assert_eq!(trajectories, again);
+        let trajectories = super::run_agent(&config, &mut GreedyAgent::new(), 2).unwrap();
+        let again = super::run_agent(&config, &mut GreedyAgent::new(), 2).unwrap();
+        assert_eq!(trajectories, again);
+        for trajectory in &trajectories {
+            assert!(trajectory.steps.last().unwrap().done);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! timed status effects of characters
+use super::Speed;
+use enum_iterator::IntoEnumIterator;
+use std::fmt;
+
+/// A kind of status effect
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Ord,
+    PartialOrd,
+    IntoEnumIterator,
+)]
+pub enum Effect {
+    /// act twice per turn
+    Haste,
+    /// act once per 2 turns
+    Slow,
+    /// move to a random direction sometimes
+    Confusion,
+    /// can't see enemies
+    Blindness,
+    /// can't act at all
+    Sleep,
+}
+
+impl Effect {
+    pub const NUM: usize = 5;
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Effect::Haste => "hasted",
+            Effect::Slow => "slowed",
+            Effect::Confusion => "confused",
+            Effect::Blindness => "blind",
+            Effect::Sleep => "asleep",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Timers of status effects
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct StatusEffects {
+    /// turns left for each effect
+    timers: [u32; Effect::NUM],
+}
+
+impl StatusEffects {
+    /// adds `turns` to the timer of the effect
+    pub fn add(&mut self, effect: Effect, turns: u32) {
+        let timer = &mut self.timers[effect.index()];
+        *timer = timer.saturating_add(turns);
+    }
+    pub fn remove(&mut self, effect: Effect) {
+        self.timers[effect.index()] = 0;
+    }
+    pub fn is_active(&self, effect: Effect) -> bool {
+        self.turns_left(effect) > 0
+    }
+    pub fn turns_left(&self, effect: Effect) -> u32 {
+        self.timers[effect.index()]
+    }
+    /// returns all active effects
+    pub fn actives(&self) -> impl Iterator<Item = Effect> + '_ {
+        Effect::into_enum_iter().filter(move |e| self.is_active(*e))
+    }
+    /// tick down all timers and returns effects which have expired
+    pub fn tick(&mut self) -> Vec<Effect> {
+        let mut expired = vec![];
+        for effect in Effect::into_enum_iter() {
+            let timer = &mut self.timers[effect.index()];
+            if *timer == 0 {
+                continue;
+            }
+            *timer -= 1;
+            if *timer == 0 {
+                expired.push(effect);
+            }
+        }
+        expired
+    }
+    /// the speed of the character under the effects
+    pub fn speed(&self, base: Speed) -> Speed {
+        match (self.is_active(Effect::Haste), self.is_active(Effect::Slow)) {
+            (true, false) => base.double(),
+            (false, true) => base.half(),
+            _ => base,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn tick() {
+        let mut effects = StatusEffects::default();
+        effects.add(Effect::Confusion, 2);
+        effects.add(Effect::Sleep, 1);
+        assert_eq!(
+            effects.actives().collect::<Vec<_>>(),
+            vec![Effect::Confusion, Effect::Sleep]
+        );
+        assert_eq!(effects.tick(), vec![Effect::Sleep]);
+        assert_eq!(effects.tick(), vec![Effect::Confusion]);
+        assert_eq!(effects.actives().count(), 0);
+        assert!(effects.tick().is_empty());
+    }
+}
@@ -1,14 +1,26 @@
-use super::{DamageReaction, Defense, Dice, Exp, HitPoint, Level, Strength};
+use super::{
+    DamageKind, DamageReaction, Defense, Dice, Effect, Energy, Exp, HitPoint, Level, Resistances,
+    Speed, StatusEffects, Strength,
+};
 use crate::dungeon::{Dungeon, DungeonPath, MoveResult};
 use crate::{
-    item::ItemNum,
-    rng::{Parcent, RngHandle},
+    difficulty::{self, Multipliers},
+    error::*,
+    item::{InitItem, ItemHandler, ItemNum, ItemToken},
+    rng::{Probability, RngHandle, RngState, Stream},
+    snapshot::DeepCloner,
+    spawn::{self, DepthTable},
     tile::Tile,
+    validate::Validator,
     Drawable, SmallStr,
 };
+use anyhow::Context;
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
 use smallvec::SmallVec;
 use std::cell::Cell;
 use std::collections::BTreeMap;
+use std::fmt;
 use std::ops::Range;
 use std::rc::{Rc, Weak};
 
@@ -18,54 +30,201 @@ pub type DiceVec<T> = SmallVec<[Dice<T>; 4]>;
 pub struct Config {
     #[serde(default = "default_enemies")]
     pub enemies: Vec<Preset>,
+    /// enemies appended to `enemies`, to extend the builtin set without listing it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_enemies: Vec<Preset>,
     #[serde(default = "default_appear_rate_gold")]
     #[serde(skip_serializing_if = "is_default_appear_rate_gold")]
-    pub appear_rate_gold: Parcent,
+    pub appear_rate_gold: Probability,
     #[serde(default = "default_appear_rate_nogold")]
     #[serde(skip_serializing_if = "is_default_appear_rate_nogold")]
-    pub appear_rate_nogold: Parcent,
+    pub appear_rate_nogold: Probability,
+    #[serde(default = "default_pathfinding")]
+    #[serde(skip_serializing_if = "is_default_pathfinding")]
+    pub pathfinding: Pathfinding,
+    /// a wandering enemy appears once per `wander_interval` turns on average
+    /// (0 means that no enemy wanders in)
+    #[serde(default = "default_wander_interval")]
+    #[serde(skip_serializing_if = "is_default_wander_interval")]
+    pub wander_interval: u32,
+    /// spawn weights of enemies by their names, which replace the default selection
+    /// in the depths they cover (no enemy appears if all weights are 0)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spawn_table: Vec<DepthTable>,
+    /// unique enemies placed once per game at fixed depths
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bosses: Vec<Boss>,
+    /// enemies which appear in packs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<SpawnGroup>,
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        // enemies are told apart only by their symbols in observations
+        let mut symbols = BTreeMap::new();
+        let lists = [
+            ("enemies", &self.enemies),
+            ("extra_enemies", &self.extra_enemies),
+        ];
+        for (name, presets) in lists.iter() {
+            v.field(name, |v| {
+                for (i, preset) in presets.iter().enumerate() {
+                    v.element(i, |v| {
+                        preset.validate(v);
+                        let tile = match preset.checked_tile() {
+                            Some(tile) => tile.to_byte(),
+                            None => return,
+                        };
+                        if let Some(other) = symbols.insert(tile, format!("{}[{}]", name, i)) {
+                            v.error(format!(
+                                "symbol {} is already used by {}",
+                                tile as char, other
+                            ));
+                        }
+                    });
+                }
+            });
+        }
+        v.probability("appear_rate_gold", self.appear_rate_gold);
+        v.probability("appear_rate_nogold", self.appear_rate_nogold);
+        v.field("bosses", |v| {
+            for (i, boss) in self.bosses.iter().enumerate() {
+                v.element(i, |v| {
+                    v.check("depth", boss.depth > 0, "depth should be larger than 0");
+                    v.field("enemy", |v| boss.enemy.validate(v));
+                })
+            }
+        });
+        let names: Vec<_> = self.presets().filter_map(Preset::name).collect();
+        v.field("spawn_table", |v| {
+            spawn::validate(&self.spawn_table, v, &|name| names.contains(&name))
+        });
+        v.field("groups", |v| {
+            for (i, group) in self.groups.iter().enumerate() {
+                v.element(i, |v| {
+                    let name = group.name.as_str();
+                    v.check("name", names.contains(&name), "unknown enemy name");
+                    v.check("min", group.min > 0, "min should be larger than 0");
+                    v.check(
+                        "max",
+                        group.min <= group.max,
+                        "max should not be less than min",
+                    );
+                })
+            }
+        });
+    }
+    /// builtin enemies are expanded to their definitions, and extra enemies are merged
+    pub(crate) fn canonical(&self) -> Self {
+        let enemies = self.presets().map(|preset| match *preset {
+            Preset::Builtin(i) | Preset::Carrying { builtin: i, .. }
+                if i < BUILTIN_ENEMIES.len() =>
+            {
+                Preset::Custom(Box::new(preset.clone().build()))
+            }
+            ref preset => preset.clone(),
+        });
+        let bosses = self.bosses.iter().map(|boss| match boss.enemy {
+            Preset::Builtin(i) | Preset::Carrying { builtin: i, .. }
+                if i < BUILTIN_ENEMIES.len() =>
+            {
+                Boss {
+                    enemy: Preset::Custom(Box::new(boss.enemy.clone().build())),
+                    ..boss.clone()
+                }
+            }
+            _ => boss.clone(),
+        });
+        Config {
+            enemies: enemies.collect(),
+            extra_enemies: vec![],
+            bosses: bosses.collect(),
+            ..self.clone()
+        }
+    }
+    fn presets(&self) -> impl Iterator<Item = &Preset> {
+        self.enemies.iter().chain(self.extra_enemies.iter())
+    }
     pub fn tile_max(&self) -> Option<u8> {
-        self.enemies.iter().map(|p| p.tile().to_byte()).max()
+        let bosses = self.bosses.iter().map(|b| &b.enemy);
+        self.presets()
+            .chain(bosses)
+            .map(|p| p.tile().to_byte())
+            .max()
     }
     pub fn build(self, seed: u128) -> EnemyHandler {
-        let rng = RngHandle::from_seed(seed);
+        let rng = RngHandle::from_stream(seed, Stream::EnemyAi);
+        let combat_rng = RngHandle::from_stream(seed, Stream::Combat);
         let Config {
             appear_rate_gold,
             appear_rate_nogold,
-            enemies,
+            mut enemies,
+            extra_enemies,
+            pathfinding,
+            wander_interval,
+            spawn_table,
+            bosses,
+            groups,
         } = self;
+        enemies.extend(extra_enemies);
         let config_inner = ConfigInner {
             appear_rate_gold,
             appear_rate_nogold,
+            pathfinding,
+            wander_interval,
+            spawn_table,
+            groups,
+            difficulty: Multipliers::default(),
         };
         let stats = enemies.into_iter().map(Preset::build).collect();
-        EnemyHandler::new(stats, rng, config_inner)
+        let mut handler = EnemyHandler::new(stats, rng, combat_rng, config_inner);
+        handler.bosses = bosses.into_iter().map(Boss::build).collect();
+        handler
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 struct ConfigInner {
-    appear_rate_gold: Parcent,
-    appear_rate_nogold: Parcent,
+    appear_rate_gold: Probability,
+    appear_rate_nogold: Probability,
+    pathfinding: Pathfinding,
+    wander_interval: u32,
+    spawn_table: Vec<DepthTable>,
+    groups: Vec<SpawnGroup>,
+    difficulty: Multipliers,
+}
+
+const fn default_appear_rate_gold() -> Probability {
+    Probability::from_percent(80)
 }
 
-const fn default_appear_rate_gold() -> Parcent {
-    Parcent(80)
+const fn default_appear_rate_nogold() -> Probability {
+    Probability::from_percent(25)
 }
 
-const fn default_appear_rate_nogold() -> Parcent {
-    Parcent(25)
+fn is_default_appear_rate_gold(u: &Probability) -> bool {
+    crate::skip_defaults() && *u == default_appear_rate_gold()
 }
 
-fn is_default_appear_rate_gold(u: &Parcent) -> bool {
-    cfg!(not(test)) && *u == default_appear_rate_gold()
+fn is_default_appear_rate_nogold(u: &Probability) -> bool {
+    crate::skip_defaults() && *u == default_appear_rate_nogold()
 }
 
-fn is_default_appear_rate_nogold(u: &Parcent) -> bool {
-    cfg!(not(test)) && *u == default_appear_rate_nogold()
+const fn default_pathfinding() -> Pathfinding {
+    Pathfinding::DistMap
+}
+
+fn is_default_pathfinding(p: &Pathfinding) -> bool {
+    crate::skip_defaults() && *p == default_pathfinding()
+}
+
+const fn default_wander_interval() -> u32 {
+    0
+}
+
+fn is_default_wander_interval(u: &u32) -> bool {
+    crate::skip_defaults() && *u == default_wander_interval()
 }
 
 fn default_enemies() -> Vec<Preset> {
@@ -78,8 +237,96 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             enemies: default_enemies(),
+            extra_enemies: vec![],
             appear_rate_gold: default_appear_rate_gold(),
             appear_rate_nogold: default_appear_rate_nogold(),
+            pathfinding: default_pathfinding(),
+            wander_interval: default_wander_interval(),
+            spawn_table: vec![],
+            bosses: vec![],
+            groups: vec![],
+        }
+    }
+}
+
+/// Enemies of the same kind which appear together, like a flock of kestrels
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SpawnGroup {
+    /// the name of the enemy
+    pub name: String,
+    /// the smallest size of a group, including the first enemy
+    pub min: u32,
+    /// the largest size of a group, including the first enemy
+    pub max: u32,
+}
+
+/// A unique enemy, which is placed once per game at the configured depth
+/// and always drops an item when killed
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Boss {
+    /// the depth where the boss is placed
+    pub depth: u32,
+    pub enemy: Preset,
+    /// multiplier of the boss's hit points, in percent
+    #[serde(default = "default_boss_hp_percent")]
+    pub hp_percent: u32,
+    /// items the boss drops, one of which is chosen at random
+    /// If empty, the enemy's own loot is used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub loot: Vec<InitItem>,
+}
+
+impl Boss {
+    fn build(self) -> BossState {
+        let mut status = self.enemy.build();
+        status.carry = Some(Probability::ONE);
+        if !self.loot.is_empty() {
+            status.loot = self.loot;
+        }
+        BossState {
+            depth: self.depth,
+            status,
+            hp_percent: self.hp_percent,
+            spawned: false,
+            killed: false,
+        }
+    }
+}
+
+const fn default_boss_hp_percent() -> u32 {
+    200
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct BossState {
+    depth: u32,
+    status: Status,
+    hp_percent: u32,
+    spawned: bool,
+    killed: bool,
+}
+
+/// How active enemies find the way to the player
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Pathfinding {
+    /// follow the distance map of the whole floor
+    DistMap,
+    /// A* search, which also goes around other enemies
+    AStar,
+}
+
+impl Pathfinding {
+    fn move_enemy(
+        self,
+        dungeon: &mut dyn Dungeon,
+        path: &DungeonPath,
+        dist: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult {
+        match self {
+            Pathfinding::DistMap => dungeon.move_enemy(path, dist, skip),
+            Pathfinding::AStar => dungeon.move_enemy_astar(path, dist, skip),
         }
     }
 }
@@ -88,22 +335,80 @@ impl Default for Config {
 #[serde(rename_all = "lowercase", untagged)]
 pub enum Preset {
     Builtin(usize),
-    Custom(Status),
+    /// a builtin enemy which carries items
+    Carrying {
+        builtin: usize,
+        carry: Probability,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        loot: Vec<InitItem>,
+    },
+    Custom(Box<Status>),
 }
 
 impl Preset {
     fn build(self) -> Status {
         match self {
             Preset::Builtin(i) => BUILTIN_ENEMIES[i].to_status(),
-            Preset::Custom(s) => s,
+            Preset::Carrying {
+                builtin,
+                carry,
+                loot,
+            } => Status {
+                carry: Some(carry),
+                loot,
+                ..BUILTIN_ENEMIES[builtin].to_status()
+            },
+            Preset::Custom(s) => *s,
         }
     }
     fn tile(&self) -> Tile {
         match self {
-            Preset::Builtin(i) => BUILTIN_ENEMIES[*i].tile.into(),
+            Preset::Builtin(i) | Preset::Carrying { builtin: i, .. } => {
+                BUILTIN_ENEMIES[*i].tile.into()
+            }
             Preset::Custom(s) => s.tile,
         }
     }
+    /// the name of the enemy, or `None` for an invalid builtin index
+    fn name(&self) -> Option<&str> {
+        match self {
+            Preset::Builtin(i) | Preset::Carrying { builtin: i, .. } => {
+                BUILTIN_ENEMIES.get(*i).map(|s| s.name)
+            }
+            Preset::Custom(s) => Some(s.name.as_str()),
+        }
+    }
+    /// same as `tile`, but returns `None` for an invalid builtin index
+    fn checked_tile(&self) -> Option<Tile> {
+        match self {
+            Preset::Builtin(i) | Preset::Carrying { builtin: i, .. } => {
+                BUILTIN_ENEMIES.get(*i).map(|s| s.tile.into())
+            }
+            Preset::Custom(s) => Some(s.tile),
+        }
+    }
+    fn validate(&self, v: &mut Validator) {
+        match *self {
+            Preset::Builtin(b) => v.builtin(b, BUILTIN_ENEMIES.len()),
+            Preset::Carrying { builtin, carry, .. } => {
+                v.field("builtin", |v| v.builtin(builtin, BUILTIN_ENEMIES.len()));
+                v.probability("carry", carry);
+            }
+            Preset::Custom(ref s) => {
+                v.check(
+                    "tile",
+                    s.tile.to_byte().is_ascii_graphic(),
+                    "an enemy symbol should be a printable ASCII character",
+                );
+                if let Some(carry) = s.carry {
+                    v.probability("carry", carry);
+                }
+                if let Some(ranged) = s.ranged {
+                    v.field("ranged", |v| v.probability("rate", ranged.rate));
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -117,31 +422,159 @@ pub struct Status {
     name: SmallStr,
     tile: Tile,
     rarelity: u8,
+    /// dice of the enemy's hit points, which are `8` dice of `level` sides by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hp: Option<Dice<HitPoint>>,
+    /// the probability that the enemy drops an item when it dies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    carry: Option<Probability>,
+    /// items the enemy may drop, chosen at random
+    /// If empty, a random weapon or armor is dropped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    loot: Vec<InitItem>,
+    /// the kind of damage the enemy's attacks deal
+    #[serde(default, skip_serializing_if = "is_physical")]
+    damage_kind: DamageKind,
+    #[serde(default, skip_serializing_if = "Resistances::is_empty")]
+    resistances: Resistances,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ranged: Option<RangedAttack>,
+    /// how fast the enemy acts, where 12 means once per turn
+    #[serde(default, skip_serializing_if = "Speed::is_normal")]
+    speed: Speed,
+}
+
+/// An attack from a distance, like the dragon's flame.
+/// Enemies can use it only when the player is in a straight line (including diagonal ones).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RangedAttack {
+    pub damage: Dice<HitPoint>,
+    /// the maximum distance the projectile reaches
+    pub range: u32,
+    /// symbol of the projectile
+    pub symbol: Tile,
+    #[serde(default)]
+    pub kind: DamageKind,
+    /// the probability that the enemy uses this attack, if it can
+    pub rate: Probability,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize, BitOr)]
+fn is_physical(kind: &DamageKind) -> bool {
+    *kind == DamageKind::Physical
+}
+
+/// Behavior flags of an enemy.
+/// In configuration, it's written as a list of flag names like `["MEAN", "FLYING"]`,
+/// or as a raw integer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, BitOr)]
 pub struct EnemyAttr(u16);
 
 #[rustfmt::skip]
 impl EnemyAttr {
-    pub const MEAN: EnemyAttr        = EnemyAttr(0b000_000_000_001);
-    pub const FLYING: EnemyAttr      = EnemyAttr(0b000_000_000_010);
-    pub const REGENERATE: EnemyAttr  = EnemyAttr(0b000_000_000_100);
-    pub const GREEDY: EnemyAttr      = EnemyAttr(0b000_000_001_000);
-    pub const INVISIBLE: EnemyAttr   = EnemyAttr(0b000_000_010_000);
-    pub const RUSTS_ARMOR: EnemyAttr = EnemyAttr(0b000_000_100_000);
-    pub const STEAL_GOLD: EnemyAttr  = EnemyAttr(0b000_001_000_000);
-    pub const REDUCE_STR: EnemyAttr  = EnemyAttr(0b000_010_000_000);
-    pub const FREEZES: EnemyAttr     = EnemyAttr(0b000_100_000_000);
-    pub const RANDOM: EnemyAttr      = EnemyAttr(0b001_000_000_000);
-    pub const CONFUSED: EnemyAttr    = EnemyAttr(0b010_000_000_000);
-    pub const NONE: EnemyAttr        = EnemyAttr(0b000_000_000_000);
+    pub const MEAN: EnemyAttr        = EnemyAttr(0b000_000_000_000_001);
+    pub const FLYING: EnemyAttr      = EnemyAttr(0b000_000_000_000_010);
+    pub const REGENERATE: EnemyAttr  = EnemyAttr(0b000_000_000_000_100);
+    pub const GREEDY: EnemyAttr      = EnemyAttr(0b000_000_000_001_000);
+    pub const INVISIBLE: EnemyAttr   = EnemyAttr(0b000_000_000_010_000);
+    pub const RUSTS_ARMOR: EnemyAttr = EnemyAttr(0b000_000_000_100_000);
+    pub const STEAL_GOLD: EnemyAttr  = EnemyAttr(0b000_000_001_000_000);
+    pub const REDUCE_STR: EnemyAttr  = EnemyAttr(0b000_000_010_000_000);
+    pub const FREEZES: EnemyAttr     = EnemyAttr(0b000_000_100_000_000);
+    pub const RANDOM: EnemyAttr      = EnemyAttr(0b000_001_000_000_000);
+    pub const CONFUSED: EnemyAttr    = EnemyAttr(0b000_010_000_000_000);
+    pub const STEAL_ITEM: EnemyAttr  = EnemyAttr(0b000_100_000_000_000);
+    pub const DRAIN_LEVEL: EnemyAttr = EnemyAttr(0b001_000_000_000_000);
+    pub const NONE: EnemyAttr        = EnemyAttr(0b000_000_000_000_000);
 }
 
 impl EnemyAttr {
+    const NAMES: [(&'static str, EnemyAttr); 13] = [
+        ("MEAN", EnemyAttr::MEAN),
+        ("FLYING", EnemyAttr::FLYING),
+        ("REGENERATE", EnemyAttr::REGENERATE),
+        ("GREEDY", EnemyAttr::GREEDY),
+        ("INVISIBLE", EnemyAttr::INVISIBLE),
+        ("RUSTS_ARMOR", EnemyAttr::RUSTS_ARMOR),
+        ("STEAL_GOLD", EnemyAttr::STEAL_GOLD),
+        ("REDUCE_STR", EnemyAttr::REDUCE_STR),
+        ("FREEZES", EnemyAttr::FREEZES),
+        ("RANDOM", EnemyAttr::RANDOM),
+        ("CONFUSED", EnemyAttr::CONFUSED),
+        ("STEAL_ITEM", EnemyAttr::STEAL_ITEM),
+        ("DRAIN_LEVEL", EnemyAttr::DRAIN_LEVEL),
+    ];
     pub fn contains(self, r: Self) -> bool {
         (self.0 & r.0) != 0
     }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|t| t.1)
+    }
+}
+
+impl Serialize for EnemyAttr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let names: Vec<_> = Self::NAMES
+            .iter()
+            .filter(|(_, attr)| self.contains(*attr))
+            .map(|t| t.0)
+            .collect();
+        let mut seq = serializer.serialize_seq(Some(names.len()))?;
+        for name in names {
+            seq.serialize_element(name)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for EnemyAttr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(EnemyAttrVisitor)
+    }
+}
+
+struct EnemyAttrVisitor;
+
+impl<'de> Visitor<'de> for EnemyAttrVisitor {
+    type Value = EnemyAttr;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of enemy attribute names or an integer")
+    }
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        if v > u64::from(u16::MAX) {
+            return Err(E::custom(format!("invalid enemy attribute {}", v)));
+        }
+        Ok(EnemyAttr(v as u16))
+    }
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut res = EnemyAttr::NONE;
+        while let Some(name) = seq.next_element::<SmallStr>()? {
+            match EnemyAttr::from_name(name.as_str()) {
+                Some(attr) => res = res | attr,
+                None => {
+                    return Err(A::Error::custom(format!(
+                        "unknown enemy attribute {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(res)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
@@ -160,6 +593,8 @@ pub struct Enemy {
     attack: DiceVec<HitPoint>,
     attr: Cell<EnemyAttr>,
     defense: Defense,
+    effects: Cell<StatusEffects>,
+    energy: Cell<Energy>,
     exp: Exp,
     hp: Cell<HitPoint>,
     id: EnemyId,
@@ -167,6 +602,15 @@ pub struct Enemy {
     max_hp: HitPoint,
     name: SmallStr,
     running: Cell<bool>,
+    /// index of the status this enemy is generated from
+    status_id: usize,
+    /// index of the boss if this enemy is a boss
+    #[serde(default)]
+    boss: Option<usize>,
+    damage_kind: DamageKind,
+    resistances: Resistances,
+    ranged: Option<RangedAttack>,
+    speed: Speed,
     tile: Tile,
 }
 
@@ -175,6 +619,15 @@ impl Enemy {
     pub fn is_mean(&self) -> bool {
         self.attr.get().contains(EnemyAttr::MEAN)
     }
+    pub fn is_flying(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::FLYING)
+    }
+    pub fn is_invisible(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::INVISIBLE)
+    }
+    pub fn regenerates(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::REGENERATE)
+    }
     pub fn is_greedy(&self) -> bool {
         self.attr.get().contains(EnemyAttr::GREEDY)
     }
@@ -184,6 +637,35 @@ impl Enemy {
     pub fn is_confused(&self) -> bool {
         self.attr.get().contains(EnemyAttr::CONFUSED)
     }
+    pub fn rusts_armor(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::RUSTS_ARMOR)
+    }
+    pub fn steals_gold(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::STEAL_GOLD)
+    }
+    pub fn steals_item(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::STEAL_ITEM)
+    }
+    pub fn drains_level(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::DRAIN_LEVEL)
+    }
+    pub fn reduces_str(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::REDUCE_STR)
+    }
+    pub fn freezes(&self) -> bool {
+        self.attr.get().contains(EnemyAttr::FREEZES)
+    }
+    pub fn effects(&self) -> StatusEffects {
+        self.effects.get()
+    }
+    pub fn add_effect(&self, effect: Effect, turns: u32) {
+        let mut effects = self.effects.get();
+        effects.add(effect, turns);
+        self.effects.set(effects);
+    }
+    pub fn is_boss(&self) -> bool {
+        self.boss.is_some()
+    }
     pub fn is_running(&self) -> bool {
         self.running.get()
     }
@@ -202,18 +684,54 @@ impl Enemy {
     pub fn name(&self) -> &SmallStr {
         &self.name
     }
+    pub fn id(&self) -> EnemyId {
+        self.id
+    }
+    pub fn damage_kind(&self) -> DamageKind {
+        self.damage_kind
+    }
+    pub fn resistances(&self) -> &Resistances {
+        &self.resistances
+    }
+    pub fn ranged(&self) -> Option<RangedAttack> {
+        self.ranged
+    }
+    pub fn speed(&self) -> Speed {
+        self.effects.get().speed(self.speed)
+    }
     pub fn get_damage(&self, damage: HitPoint) -> DamageReaction {
         let cur = self.hp.get();
         if cur <= damage {
             DamageReaction::Death
         } else {
-            self.hp.replace(damage - cur);
+            self.hp.replace(cur - damage);
             DamageReaction::None
         }
     }
     fn run(&self) {
         self.running.replace(true);
     }
+    /// tick down status effects and returns how many times the enemy acts in this turn
+    fn tick_effects(&self) -> u32 {
+        let mut effects = self.effects.get();
+        let actions = if effects.is_active(Effect::Sleep) {
+            0
+        } else {
+            let mut energy = self.energy.get();
+            let actions = energy.actions_in_turn(effects.speed(self.speed));
+            self.energy.set(energy);
+            actions
+        };
+        effects.tick();
+        self.effects.set(effects);
+        actions
+    }
+    fn regenerate(&self) {
+        let cur = self.hp.get();
+        if self.regenerates() && cur < self.max_hp {
+            self.hp.replace(cur + HitPoint(1));
+        }
+    }
 }
 
 impl Drawable for Enemy {
@@ -228,26 +746,68 @@ impl Drawable for Enemy {
     }
 }
 
-pub(crate) struct Attack(Rc<Enemy>);
+/// an attack to the player, with the cells the projectile passes if it's a ranged one
+pub(crate) struct Attack(Rc<Enemy>, DungeonPath, Option<Vec<DungeonPath>>);
 
 impl Attack {
     pub fn enemy(&self) -> &Enemy {
         self.0.as_ref()
     }
+    pub fn place(&self) -> &DungeonPath {
+        &self.1
+    }
+    pub fn projectile(&self) -> Option<&[DungeonPath]> {
+        self.2.as_deref()
+    }
+}
+
+/// enemies taken out of `EnemyHandler` while the player is in other levels
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EnemyStash {
+    #[serde(with = "crate::snapshot::enemy_map")]
+    placed_enemies: BTreeMap<DungeonPath, Rc<Enemy>>,
+    #[serde(with = "crate::snapshot::enemy_map")]
+    active_enemies: BTreeMap<DungeonPath, Rc<Enemy>>,
+}
+
+impl EnemyStash {
+    pub(crate) fn deep_clone(&self, cloner: &mut DeepCloner) -> EnemyStash {
+        EnemyStash {
+            placed_enemies: cloner.enemy_map(&self.placed_enemies),
+            active_enemies: cloner.enemy_map(&self.active_enemies),
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct EnemyHandler {
     enemy_stats: Vec<Status>,
+    #[serde(skip)]
     enemies: Vec<Weak<Enemy>>,
+    #[serde(with = "crate::snapshot::enemy_map")]
     placed_enemies: BTreeMap<DungeonPath, Rc<Enemy>>,
+    #[serde(with = "crate::snapshot::enemy_map")]
     active_enemies: BTreeMap<DungeonPath, Rc<Enemy>>,
+    /// saved in `RngStates`, like `combat_rng`
+    #[serde(skip)]
     rng: RngHandle,
+    /// random number generator for fights, which is separated from enemies' movement
+    #[serde(skip)]
+    combat_rng: RngHandle,
     config: ConfigInner,
     next_id: EnemyId,
+    /// turns left until the next wandering enemy appears
+    wander_timer: u32,
+    bosses: Vec<BossState>,
 }
 
 impl EnemyHandler {
-    fn new(mut stats: Vec<Status>, rng: RngHandle, config: ConfigInner) -> Self {
+    fn new(
+        mut stats: Vec<Status>,
+        rng: RngHandle,
+        combat_rng: RngHandle,
+        config: ConfigInner,
+    ) -> Self {
         stats.sort_by_key(|stat| stat.rarelity);
         EnemyHandler {
             enemy_stats: stats,
@@ -255,21 +815,56 @@ impl EnemyHandler {
             placed_enemies: Default::default(),
             active_enemies: Default::default(),
             rng,
+            combat_rng,
             config,
             next_id: EnemyId(0),
+            wander_timer: 0,
+            bosses: Vec::new(),
+        }
+    }
+    /// the largest byte of enemy tiles which can appear
+    pub(crate) fn tile_max(&self) -> Option<u8> {
+        let bosses = self.bosses.iter().map(|b| &b.status);
+        self.enemy_stats
+            .iter()
+            .chain(bosses)
+            .map(|s| s.tile.to_byte())
+            .max()
+    }
+    /// scale enemies' hit points, damage and appearance rates
+    pub fn set_difficulty(&mut self, multipliers: &Multipliers) {
+        let config = &mut self.config;
+        let appear = multipliers.enemy_appear;
+        config.appear_rate_gold = difficulty::scale_probability(config.appear_rate_gold, appear);
+        config.appear_rate_nogold =
+            difficulty::scale_probability(config.appear_rate_nogold, appear);
+        if config.wander_interval > 0 {
+            config.wander_interval = difficulty::scale_rate_inv(config.wander_interval, appear);
         }
+        config.difficulty = *multipliers;
+    }
+    /// multiplier of the damage enemies deal, in percent
+    pub(crate) fn damage_percent(&self) -> u32 {
+        self.config.difficulty.enemy_damage
     }
     pub fn is_no_enemy(&self) -> bool {
         self.enemy_stats.is_empty()
     }
-    fn select(&mut self, range: Range<u32>) -> usize {
+    fn select(&mut self, depth: u32, range: Range<u32>) -> Option<usize> {
+        if let Some(table) = spawn::lookup(&self.config.spawn_table, depth) {
+            let name = table.choose(&mut self.rng)?;
+            return self
+                .enemy_stats
+                .iter()
+                .position(|s| s.name.as_str() == name);
+        }
         let id = self.rng.range(range) as usize;
         if id > self.enemy_stats.len() {
             let len = self.enemy_stats.len();
             let range = ::std::cmp::min(len, 5);
-            self.rng.range(len - range..len)
+            Some(self.rng.range(len - range..len))
         } else {
-            id
+            Some(id)
         }
     }
     fn exp_add(&self, level: Level, maxhp: HitPoint) -> Exp {
@@ -285,26 +880,63 @@ impl EnemyHandler {
     }
     pub fn gen_enemy(
         &mut self,
+        depth: u32,
         range: Range<u32>,
         lev_add: i64,
         has_gold: bool,
     ) -> Option<Rc<Enemy>> {
-        let appear_parcent = if has_gold {
+        let appear_rate = if has_gold {
             self.config.appear_rate_gold
         } else {
             self.config.appear_rate_nogold
         };
-        if !self.rng.parcent(appear_parcent) {
+        if !self.rng.chance(appear_rate) {
             return None;
         }
-        let idx = self.select(range);
-        let stat = self.enemy_stats.get(idx)?;
+        self.gen_enemy_always(depth, range, lev_add)
+    }
+    /// same as `gen_enemy`, but always generates an enemy (e.g., for guards of treasures)
+    /// unless the spawn table forbids it
+    pub fn gen_enemy_always(
+        &mut self,
+        depth: u32,
+        range: Range<u32>,
+        lev_add: i64,
+    ) -> Option<Rc<Enemy>> {
+        let idx = self.select(depth, range)?;
+        self.spawn(idx, None, lev_add)
+    }
+    /// generate the boss of `depth`, unless it has already appeared
+    pub(crate) fn gen_boss(&mut self, depth: u32, lev_add: i64) -> Option<Rc<Enemy>> {
+        let boss = self
+            .bosses
+            .iter()
+            .position(|b| b.depth == depth && !b.spawned)?;
+        self.bosses[boss].spawned = true;
+        self.spawn(0, Some(boss), lev_add)
+    }
+    /// generate an enemy from the `idx`-th status, or the status of `boss`
+    fn spawn(&mut self, idx: usize, boss: Option<usize>, lev_add: i64) -> Option<Rc<Enemy>> {
+        let (stat, hp_percent) = match boss {
+            Some(i) => (&self.bosses.get(i)?.status, self.bosses[i].hp_percent),
+            None => (self.enemy_stats.get(idx)?, 100),
+        };
         let level = stat.level + lev_add.into();
-        let hp = Dice::new(8, level).exec::<i64>(&mut self.rng).0.into();
+        let hp: HitPoint = match stat.hp {
+            Some(dice) => dice.exec::<i64>(&mut self.rng),
+            None => Dice::new(8, level).exec::<i64>(&mut self.rng).0.into(),
+        };
+        let hp = difficulty::scale(hp.0, hp_percent);
+        let hp = HitPoint(::std::cmp::max(
+            difficulty::scale(hp, self.config.difficulty.enemy_hp),
+            1,
+        ));
         let enem = Enemy {
             attr: Cell::new(stat.attr),
             attack: stat.attack.clone(),
             defense: stat.defense - (lev_add as i32).into(),
+            effects: Cell::default(),
+            energy: Cell::default(),
             exp: stat.exp + Exp::from((lev_add * 10) as u32) + self.exp_add(level, hp),
             hp: Cell::new(hp),
             id: self.next_id.increment(),
@@ -312,6 +944,12 @@ impl EnemyHandler {
             name: stat.name.clone(),
             max_hp: hp,
             running: Cell::default(),
+            status_id: idx,
+            boss,
+            damage_kind: stat.damage_kind,
+            resistances: stat.resistances.clone(),
+            ranged: stat.ranged,
+            speed: stat.speed,
             tile: stat.tile,
         };
         let enem = Rc::new(enem);
@@ -363,10 +1001,39 @@ impl EnemyHandler {
         self.active_enemies = BTreeMap::new();
         self.placed_enemies = BTreeMap::new();
     }
+    pub(crate) fn deep_clone(&self, cloner: &mut DeepCloner) -> EnemyHandler {
+        EnemyHandler {
+            enemy_stats: self.enemy_stats.clone(),
+            enemies: self
+                .enemies
+                .iter()
+                .filter_map(|enemy| Some(Rc::downgrade(&cloner.enemy(&enemy.upgrade()?))))
+                .collect(),
+            placed_enemies: cloner.enemy_map(&self.placed_enemies),
+            active_enemies: cloner.enemy_map(&self.active_enemies),
+            rng: self.rng.clone(),
+            combat_rng: self.combat_rng.clone(),
+            config: self.config.clone(),
+            next_id: self.next_id,
+            wander_timer: self.wander_timer,
+            bosses: self.bosses.clone(),
+        }
+    }
+    /// take out all enemies to keep them until the player comes back
+    pub(crate) fn stash(&mut self) -> EnemyStash {
+        EnemyStash {
+            placed_enemies: ::std::mem::take(&mut self.placed_enemies),
+            active_enemies: ::std::mem::take(&mut self.active_enemies),
+        }
+    }
+    /// put back enemies taken out by `stash`, removing the current ones
+    pub(crate) fn restore(&mut self, stash: EnemyStash) {
+        self.placed_enemies = stash.placed_enemies;
+        self.active_enemies = stash.active_enemies;
+    }
     pub(crate) fn move_actives(
         &mut self,
         player_pos: &DungeonPath,
-        gold_pos: Option<&DungeonPath>,
         dungeon: &mut dyn Dungeon,
     ) -> Vec<Attack> {
         let mut out = Vec::new();
@@ -380,40 +1047,27 @@ impl EnemyHandler {
             tmp
         };
         for (path, enemy) in active_enemies {
-            let next = (|| {
+            enemy.regenerate();
+            let actions = enemy.tick_effects();
+            let next = {
                 let EnemyHandler {
                     ref mut rng,
                     ref active_enemies,
                     ref placed_enemies,
+                    ref config,
                     ..
                 } = self;
                 let skip: &dyn Fn(&DungeonPath) -> bool =
                     &|p| active_enemies.contains_key(p) || placed_enemies.contains_key(p);
-                if let Some(gold_pos) = gold_pos {
-                    if enemy.is_greedy() {
-                        match dungeon.move_enemy(&path, gold_pos, skip) {
-                            MoveResult::Reach => return path,
-                            MoveResult::CanMove(path) => return path,
-                            MoveResult::CantMove => {}
-                        }
-                    }
-                }
-                let res = if (rng.does_happen(2) && enemy.is_random())
-                    || (!rng.does_happen(5) && enemy.is_confused())
-                {
-                    dungeon.move_enemy_randomly(&path, player_pos, skip)
-                } else {
-                    dungeon.move_enemy(&path, player_pos, skip)
+                let mut mover = Mover {
+                    pathfinding: config.pathfinding,
+                    rng,
+                    skip,
                 };
-                match res {
-                    MoveResult::Reach => {
-                        out.push(Attack(Rc::clone(&enemy)));
-                        path
-                    }
-                    MoveResult::CanMove(p) => p,
-                    MoveResult::CantMove => path,
-                }
-            })();
+                (0..actions).fold(path, |path, _| {
+                    mover.move_enemy(&enemy, path, player_pos, dungeon, &mut out)
+                })
+            };
             self.active_enemies.insert(next, enemy);
         }
         debug!(
@@ -422,9 +1076,181 @@ impl EnemyHandler {
         );
         out
     }
+    /// decide whether the dead enemy drops an item or not, and generates the item
+    pub(crate) fn gen_loot(
+        &mut self,
+        enemy: &Enemy,
+        depth: u32,
+        items: &mut ItemHandler,
+    ) -> GameResult<Option<ItemToken>> {
+        let stat = match enemy.boss {
+            Some(i) => self.bosses.get(i).map(|b| &b.status),
+            None => self.enemy_stats.get(enemy.status_id),
+        };
+        let stat = match stat {
+            Some(stat) => stat,
+            None => return Ok(None),
+        };
+        match stat.carry {
+            Some(carry) if carry > Probability::ZERO && self.rng.chance(carry) => {}
+            _ => return Ok(None),
+        }
+        if stat.loot.is_empty() {
+            return Ok(Some(items.gen_random_item(depth)));
+        }
+        let idx = self.rng.range(0..stat.loot.len());
+        let item = stat.loot[idx]
+            .clone()
+            .initialize(items)
+            .context("EnemyHandler::gen_loot")?;
+        Ok(Some(item))
+    }
+    /// how many enemies of the same kind should appear with `enemy`
+    pub(crate) fn group_size(&mut self, enemy: &Enemy) -> u32 {
+        if enemy.is_boss() {
+            return 0;
+        }
+        let group = self
+            .config
+            .groups
+            .iter()
+            .find(|g| g.name.as_str() == enemy.name.as_str());
+        match group {
+            Some(g) => self.rng.range(g.min..=g.max.max(g.min)).saturating_sub(1),
+            None => 0,
+        }
+    }
+    /// generate an enemy of the same kind as `leader`, to make a group
+    pub(crate) fn gen_follower(&mut self, leader: &Enemy, lev_add: i64) -> Option<Rc<Enemy>> {
+        self.spawn(leader.status_id, None, lev_add)
+    }
+    /// record that the player killed `enemy`
+    pub(crate) fn killed(&mut self, enemy: &Enemy) {
+        if let Some(boss) = enemy.boss.and_then(|i| self.bosses.get_mut(i)) {
+            boss.killed = true;
+        }
+    }
+    /// names of all bosses, and whether they have been killed or not
+    pub fn bosses(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.bosses
+            .iter()
+            .map(|b| (b.status.name.as_str(), b.killed))
+    }
+    /// count down the wander timer, and returns if a wandering enemy should appear now
+    pub(crate) fn tick_wander(&mut self) -> bool {
+        let interval = self.config.wander_interval;
+        if interval == 0 || self.is_no_enemy() {
+            return false;
+        }
+        if self.wander_timer == 0 {
+            self.wander_timer = self.rng.range(1..=interval * 2);
+        }
+        self.wander_timer -= 1;
+        self.wander_timer == 0
+    }
     pub(crate) fn rng(&mut self) -> &mut RngHandle {
         &mut self.rng
     }
+    pub(crate) fn combat_rng(&mut self) -> &mut RngHandle {
+        &mut self.combat_rng
+    }
+    /// returns the states of `rng` and `combat_rng`
+    pub(crate) fn rng_states(&self) -> (RngState, RngState) {
+        (self.rng.save_state(), self.combat_rng.save_state())
+    }
+}
+
+struct Mover<'a> {
+    pathfinding: Pathfinding,
+    rng: &'a mut RngHandle,
+    skip: &'a dyn Fn(&DungeonPath) -> bool,
+}
+
+impl<'a> Mover<'a> {
+    fn move_enemy(
+        &mut self,
+        enemy: &Rc<Enemy>,
+        path: DungeonPath,
+        player_pos: &DungeonPath,
+        dungeon: &mut dyn Dungeon,
+        out: &mut Vec<Attack>,
+    ) -> DungeonPath {
+        // greedy enemies go for the gold in the room first
+        if enemy.is_greedy() {
+            if let Some(gold) = dungeon.gold_in_room(&path) {
+                match self
+                    .pathfinding
+                    .move_enemy(dungeon, &path, &gold, self.skip)
+                {
+                    MoveResult::Reach => {
+                        if !(self.skip)(&gold) && gold != *player_pos {
+                            dungeon.remove_item(&gold);
+                            return gold;
+                        }
+                    }
+                    MoveResult::CanMove(path) => return path,
+                    MoveResult::CantMove => {}
+                }
+            }
+        }
+        if let Some(projectile) = self.aim(enemy, &path, player_pos, dungeon) {
+            out.push(Attack(Rc::clone(enemy), path.clone(), Some(projectile)));
+            return path;
+        }
+        match self.chase(enemy, &path, player_pos, dungeon) {
+            MoveResult::Reach => {
+                out.push(Attack(Rc::clone(enemy), path.clone(), None));
+                path
+            }
+            // flying enemies move twice if they don't reach the player
+            MoveResult::CanMove(p) if enemy.is_flying() => {
+                match self.chase(enemy, &p, player_pos, dungeon) {
+                    MoveResult::CanMove(next) => next,
+                    _ => p,
+                }
+            }
+            MoveResult::CanMove(p) => p,
+            MoveResult::CantMove => path,
+        }
+    }
+    /// decide whether the enemy uses its ranged attack or not, and returns the projectile's way
+    fn aim(
+        &mut self,
+        enemy: &Enemy,
+        path: &DungeonPath,
+        player_pos: &DungeonPath,
+        dungeon: &dyn Dungeon,
+    ) -> Option<Vec<DungeonPath>> {
+        let ranged = enemy.ranged()?;
+        let line = dungeon.line_of_fire(path, player_pos, ranged.range)?;
+        // the player is next to the enemy, or someone is in the way
+        if line.len() < 2 || line[..line.len() - 1].iter().any(|p| (self.skip)(p)) {
+            return None;
+        }
+        if !self.rng.chance(ranged.rate) {
+            return None;
+        }
+        Some(line)
+    }
+    fn chase(
+        &mut self,
+        enemy: &Enemy,
+        path: &DungeonPath,
+        player_pos: &DungeonPath,
+        dungeon: &mut dyn Dungeon,
+    ) -> MoveResult {
+        let rng = &mut *self.rng;
+        let effects = enemy.effects();
+        let is_confused = enemy.is_confused()
+            || effects.is_active(Effect::Confusion)
+            || effects.is_active(Effect::Blindness);
+        if (rng.does_happen(2) && enemy.is_random()) || (!rng.does_happen(5) && is_confused) {
+            dungeon.move_enemy_randomly(path, player_pos, self.skip)
+        } else {
+            self.pathfinding
+                .move_enemy(dungeon, path, player_pos, self.skip)
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -437,6 +1263,7 @@ pub struct StaticStatus {
     level: Level,
     rarelity: u8,
     name: &'static str,
+    ranged: Option<RangedAttack>,
     tile: u8,
 }
 
@@ -452,6 +1279,13 @@ impl StaticStatus {
             name: SmallStr::from_str(self.name),
             tile: Tile::from(self.tile),
             rarelity: self.rarelity,
+            hp: None,
+            carry: None,
+            loot: Vec::new(),
+            damage_kind: DamageKind::Physical,
+            resistances: Resistances::default(),
+            ranged: self.ranged,
+            speed: Speed::NORMAL,
         }
     }
 }
@@ -468,6 +1302,24 @@ macro_rules! enem_attr {
     };
 }
 
+/// the dragon's flame in the original rogue
+const DRAGON_FLAME: RangedAttack = RangedAttack {
+    damage: hp_dice!(6, 6),
+    range: 6,
+    symbol: Tile(b'*'),
+    kind: DamageKind::Fire,
+    rate: Probability::from_percent(20),
+};
+
+/// the icemonster's frost, which doesn't hurt much but keeps the player in danger
+const ICE_FROST: RangedAttack = RangedAttack {
+    damage: hp_dice!(1, 4),
+    range: 4,
+    symbol: Tile(b'*'),
+    kind: DamageKind::Cold,
+    rate: Probability::from_percent(25),
+};
+
 pub const ROGUE_ENEMY_START: usize = 0;
 pub const ROGUE_ENEMY_END: usize = 25;
 
@@ -481,6 +1333,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(5),
         name: "aquator",
         rarelity: 12,
+        ranged: None,
         tile: b'A',
     },
     StaticStatus {
@@ -492,6 +1345,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(1),
         name: "bat",
         rarelity: 2,
+        ranged: None,
         tile: b'B',
     },
     StaticStatus {
@@ -503,6 +1357,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(4),
         name: "centaur",
         rarelity: 10,
+        ranged: None,
         tile: b'C',
     },
     StaticStatus {
@@ -514,6 +1369,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(10),
         name: "dragon",
         rarelity: 25,
+        ranged: Some(DRAGON_FLAME),
         tile: b'D',
     },
     StaticStatus {
@@ -525,6 +1381,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(1),
         name: "emu",
         rarelity: 1,
+        ranged: None,
         tile: b'E',
     },
     StaticStatus {
@@ -536,6 +1393,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(8),
         name: "venus flytrap",
         rarelity: 15,
+        ranged: None,
         tile: b'F',
     },
     StaticStatus {
@@ -547,6 +1405,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(13),
         name: "griffin",
         rarelity: 23,
+        ranged: None,
         tile: b'G',
     },
     StaticStatus {
@@ -558,6 +1417,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(1),
         name: "hobgoblin",
         rarelity: 4,
+        ranged: None,
         tile: b'H',
     },
     StaticStatus {
@@ -569,6 +1429,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(1),
         name: "icemonster",
         rarelity: 5,
+        ranged: Some(ICE_FROST),
         tile: b'I',
     },
     StaticStatus {
@@ -580,6 +1441,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(15),
         name: "jabberwock",
         rarelity: 24,
+        ranged: None,
         tile: b'J',
     },
     StaticStatus {
@@ -591,6 +1453,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(1),
         name: "kestrel",
         rarelity: 0,
+        ranged: None,
         tile: b'K',
     },
     StaticStatus {
@@ -602,6 +1465,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(3),
         name: "leperachaun",
         rarelity: 9,
+        ranged: None,
         tile: b'L',
     },
     StaticStatus {
@@ -613,17 +1477,19 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(8),
         name: "medusa",
         rarelity: 21,
+        ranged: None,
         tile: b'M',
     },
     StaticStatus {
         attack: &[hp_dice!(0, 0)],
-        attr: enem_attr!(),
+        attr: enem_attr!(STEAL_ITEM,),
         defense: Defense(9),
         exp: Exp(37),
         gold: ItemNum(100),
         level: Level(3),
         name: "nymph",
         rarelity: 13,
+        ranged: None,
         tile: b'N',
     },
     StaticStatus {
@@ -635,6 +1501,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(1),
         name: "orc",
         rarelity: 7,
+        ranged: None,
         tile: b'O',
     },
     StaticStatus {
@@ -646,6 +1513,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(8),
         name: "phantom",
         rarelity: 18,
+        ranged: None,
         tile: b'P',
     },
     StaticStatus {
@@ -657,6 +1525,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(3),
         name: "quagga",
         rarelity: 11,
+        ranged: None,
         tile: b'Q',
     },
     StaticStatus {
@@ -668,6 +1537,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(2),
         name: "rattlesnake",
         rarelity: 6,
+        ranged: None,
         tile: b'R',
     },
     StaticStatus {
@@ -679,6 +1549,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(1),
         name: "snake",
         rarelity: 3,
+        ranged: None,
         tile: b'S',
     },
     StaticStatus {
@@ -690,6 +1561,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(6),
         name: "troll",
         rarelity: 16,
+        ranged: None,
         tile: b'T',
     },
     StaticStatus {
@@ -701,6 +1573,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(7),
         name: "urvile",
         rarelity: 20,
+        ranged: None,
         tile: b'U',
     },
     StaticStatus {
@@ -712,17 +1585,19 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(8),
         name: "vampire",
         rarelity: 22,
+        ranged: None,
         tile: b'V',
     },
     StaticStatus {
         attack: &[hp_dice!(1, 6)],
-        attr: enem_attr!(),
+        attr: enem_attr!(DRAIN_LEVEL,),
         defense: Defense(4),
         exp: Exp(55),
         gold: ItemNum(0),
         level: Level(5),
         name: "wraith",
         rarelity: 17,
+        ranged: None,
         tile: b'W',
     },
     StaticStatus {
@@ -734,6 +1609,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(7),
         name: "xeroc",
         rarelity: 19,
+        ranged: None,
         tile: b'X',
     },
     StaticStatus {
@@ -745,6 +1621,7 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(4),
         name: "yeti",
         rarelity: 14,
+        ranged: None,
         tile: b'Y',
     },
     StaticStatus {
@@ -756,6 +1633,242 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         level: Level(2),
         name: "zombie",
         rarelity: 8,
+        ranged: None,
         tile: b'Z',
     },
 ];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+    use std::cmp;
+    #[test]
+    fn attr_from_names() {
+        let attr: EnemyAttr = serde_json::from_str(r#"["MEAN", "flying"]"#).unwrap();
+        assert_eq!(attr, EnemyAttr::MEAN | EnemyAttr::FLYING);
+        let attr: EnemyAttr = serde_json::from_str("9").unwrap();
+        assert_eq!(attr, EnemyAttr::MEAN | EnemyAttr::GREEDY);
+        assert!(serde_json::from_str::<EnemyAttr>(r#"["FLY"]"#).is_err());
+        let json = serde_json::to_string(&(EnemyAttr::MEAN | EnemyAttr::REGENERATE)).unwrap();
+        assert_eq!(json, r#"["MEAN","REGENERATE"]"#);
+    }
+    #[test]
+    fn damage_reduces_hp() {
+        let mut config = Config::default();
+        config.appear_rate_gold = Probability::from_percent(100);
+        let mut handler = config.build(1);
+        let enemy = (0..)
+            .filter_map(|_| handler.gen_enemy(1, 0..26, 1, true))
+            .find(|e| e.hp.get() > HitPoint(3))
+            .unwrap();
+        let hp = enemy.hp.get();
+        assert!(matches!(
+            enemy.get_damage(HitPoint(2)),
+            DamageReaction::None
+        ));
+        assert_eq!(enemy.hp.get(), hp - HitPoint(2));
+        assert!(matches!(enemy.get_damage(hp), DamageReaction::Death));
+    }
+    #[test]
+    fn regenerating_enemies_recover() {
+        for &(attr, regenerates) in &[(r#"["MEAN"]"#, false), (r#"["REGENERATE"]"#, true)] {
+            let config = format!(
+                r#"{{"enemies": [{{
+                    "attack": [{{"times": 1, "max": 1}}],
+                    "attr": {},
+                    "defense": 10,
+                    "exp": 1,
+                    "gold": 0,
+                    "level": 3,
+                    "name": "dummy",
+                    "tile": 68,
+                    "rarelity": 1
+                }}]}}"#,
+                attr
+            );
+            let config: Config = serde_json::from_str(&config).unwrap();
+            let mut handler = config.build(1);
+            let enemy = (0..)
+                .find_map(|_| handler.gen_enemy(1, 0..1, 0, true))
+                .unwrap();
+            let max_hp = enemy.hp.get();
+            enemy.get_damage(HitPoint(3));
+            for turn in 1..=5 {
+                enemy.regenerate();
+                let expected = if regenerates {
+                    cmp::min(max_hp - HitPoint(3) + HitPoint(turn), max_hp)
+                } else {
+                    max_hp - HitPoint(3)
+                };
+                assert_eq!(enemy.hp.get(), expected);
+            }
+        }
+    }
+    #[test]
+    fn loot() {
+        use crate::item::{Config as ItemConfig, ItemKind};
+        let config: Config = serde_json::from_str(
+            r#"{"enemies": [{
+                "builtin": 7,
+                "carry": 100,
+                "loot": [{"Weapon": {"name": "dagger", "num_plus": 0, "hit_plus": 0, "dam_plus": 0}}]
+            }]}"#,
+        )
+        .unwrap();
+        let mut enemies = config.build(1);
+        let mut items = ItemHandler::new(ItemConfig::default(), 1);
+        let enemy = enemies.gen_enemy_always(1, 0..1, 0).unwrap();
+        assert_eq!(enemy.name().as_str(), BUILTIN_ENEMIES[7].name);
+        let loot = enemies.gen_loot(&enemy, 1, &mut items).unwrap().unwrap();
+        match &loot.kind {
+            ItemKind::Weapon(weapon) => assert_eq!(weapon.name(), "dagger"),
+            kind => panic!("unexpected loot {:?}", kind),
+        }
+        let mut enemies = Config::default().build(1);
+        let enemy = enemies.gen_enemy_always(1, 0..1, 0).unwrap();
+        assert!(enemies.gen_loot(&enemy, 1, &mut items).unwrap().is_none());
+    }
+    #[test]
+    fn ranged_enemies() {
+        let ranged: Vec<_> = BUILTIN_ENEMIES
+            .iter()
+            .filter(|s| s.ranged.is_some())
+            .map(|s| (s.name, s.ranged.unwrap().kind))
+            .collect();
+        assert_eq!(
+            ranged,
+            vec![
+                ("dragon", DamageKind::Fire),
+                ("icemonster", DamageKind::Cold)
+            ]
+        );
+    }
+    #[test]
+    fn custom_enemies() {
+        let config: Config = serde_json::from_str(
+            r#"{"enemies": [], "extra_enemies": [{
+                "attack": [{"times": 1, "max": 3}],
+                "attr": ["MEAN", "FLYING"],
+                "defense": 5,
+                "exp": 10,
+                "gold": 0,
+                "level": 3,
+                "name": "wyvern",
+                "tile": 87,
+                "rarelity": 5,
+                "hp": {"times": 2, "max": 1}
+            }]}"#,
+        )
+        .unwrap();
+        let mut v = Validator::new();
+        config.validate(&mut v);
+        assert!(v.finish().is_empty());
+        let canonical = config.canonical();
+        assert_eq!(canonical.enemies.len(), 1);
+        assert!(canonical.extra_enemies.is_empty());
+        let mut enemies = config.build(1);
+        let enemy = enemies.gen_enemy_always(1, 0..1, 0).unwrap();
+        assert_eq!(enemy.name().as_str(), "wyvern");
+        assert!(enemy.is_mean() && enemy.is_flying());
+        assert_eq!(enemy.max_hp, HitPoint(2));
+    }
+    #[test]
+    fn symbol_collision() {
+        let config: Config = serde_json::from_str(
+            r#"{"extra_enemies": [{
+                "attack": [], "attr": [], "defense": 5, "exp": 10, "gold": 0,
+                "level": 3, "name": "big bat", "tile": 66, "rarelity": 5
+            }]}"#,
+        )
+        .unwrap();
+        let mut v = Validator::new();
+        config.validate(&mut v);
+        let violations = v.finish();
+        assert_eq!(violations.0.len(), 1);
+        assert_eq!(violations.0[0].path, "extra_enemies[0]");
+        assert!(violations.0[0].message.contains("enemies[1]"));
+    }
+    #[test]
+    fn spawn_table() {
+        let config: Config = serde_json::from_str(
+            r#"{"spawn_table": [
+                {"min_depth": 1, "max_depth": 3, "weights": {"bat": 1}},
+                {"min_depth": 4, "max_depth": 4, "weights": {"bat": 0}}
+            ]}"#,
+        )
+        .unwrap();
+        let mut enemies = config.build(1);
+        for _ in 0..20 {
+            let enemy = enemies.gen_enemy_always(3, 0..9, 0).unwrap();
+            assert_eq!(enemy.name().as_str(), "bat");
+        }
+        assert!(enemies.gen_enemy_always(4, 0..10, 0).is_none());
+        assert!(enemies.gen_enemy_always(5, 1..11, 0).is_some());
+    }
+    #[test]
+    fn boss() {
+        use crate::item::{Config as ItemConfig, ItemKind};
+        let config: Config = serde_json::from_str(
+            r#"{"bosses": [{
+                "depth": 3,
+                "enemy": 3,
+                "loot": [{"Weapon": {"name": "long-sword", "num_plus": 0, "hit_plus": 2, "dam_plus": 2}}]
+            }]}"#,
+        )
+        .unwrap();
+        let mut enemies = config.build(1);
+        let mut items = ItemHandler::new(ItemConfig::default(), 1);
+        assert!(enemies.gen_boss(2, 0).is_none());
+        let boss = enemies.gen_boss(3, 0).unwrap();
+        assert!(boss.is_boss());
+        assert_eq!(boss.name().as_str(), "dragon");
+        assert!(enemies.gen_boss(3, 0).is_none());
+        assert_eq!(
+            enemies.bosses().collect::<Vec<_>>(),
+            vec![("dragon", false)]
+        );
+        let loot = enemies.gen_loot(&boss, 3, &mut items).unwrap().unwrap();
+        match &loot.kind {
+            ItemKind::Weapon(weapon) => assert_eq!(weapon.name(), "long-sword"),
+            kind => panic!("unexpected loot {:?}", kind),
+        }
+        enemies.killed(&boss);
+        assert_eq!(enemies.bosses().collect::<Vec<_>>(), vec![("dragon", true)]);
+    }
+    #[test]
+    fn groups() {
+        let config: Config = serde_json::from_str(
+            r#"{"groups": [{"name": "kestrel", "min": 3, "max": 6}, {"name": "rat", "min": 2, "max": 1}]}"#,
+        )
+        .unwrap();
+        let mut v = Validator::new();
+        config.validate(&mut v);
+        let paths: Vec<_> = v.finish().0.into_iter().map(|v| v.path).collect();
+        assert_eq!(paths, vec!["groups[1].name", "groups[1].max"]);
+        let mut enemies = config.build(1);
+        let kestrel = (0..)
+            .map(|_| enemies.gen_enemy_always(5, 0..26, 0).unwrap())
+            .find(|e| e.name().as_str() == "kestrel")
+            .unwrap();
+        for _ in 0..10 {
+            let n = enemies.group_size(&kestrel);
+            assert!((2..=5).contains(&n));
+        }
+        let follower = enemies.gen_follower(&kestrel, 0).unwrap();
+        assert_eq!(follower.name().as_str(), "kestrel");
+        assert_ne!(follower.id(), kestrel.id());
+    }
+    #[test]
+    fn difficulty() {
+        use crate::difficulty::Preset;
+        let mut original = Config::default().build(1);
+        let mut hard = Config::default().build(1);
+        hard.set_difficulty(&Preset::Hard.multipliers());
+        let weak = original.gen_enemy_always(1, 0..1, 0).unwrap();
+        let strong = hard.gen_enemy_always(1, 0..1, 0).unwrap();
+        assert_eq!(strong.max_hp.0, weak.max_hp.0 * 130 / 100);
+        assert_eq!(hard.damage_percent(), 130);
+        assert!(hard.config.appear_rate_gold > original.config.appear_rate_gold);
+    }
+}
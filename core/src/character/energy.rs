@@ -0,0 +1,100 @@
+//! energy based scheduling of characters with various speeds
+//!
+//! Every turn, each character gains energy by its speed, and spends
+//! `Energy::ACTION_COST` per action.
+//! So a character with `Speed::NORMAL` acts once per turn, a hasted one acts twice per turn
+//! and a slowed one acts once per 2 turns.
+
+/// How fast a character acts
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Speed(pub u32);
+
+impl Speed {
+    pub const NORMAL: Speed = Speed(Energy::ACTION_COST);
+    pub fn is_normal(&self) -> bool {
+        *self == Speed::NORMAL
+    }
+    pub fn double(self) -> Speed {
+        Speed(self.0.saturating_mul(2))
+    }
+    /// half of the speed, which is never 0
+    pub fn half(self) -> Speed {
+        Speed(::std::cmp::max(self.0 / 2, 1))
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::NORMAL
+    }
+}
+
+/// Energy stored by a character
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Energy(u32);
+
+impl Energy {
+    /// energy needed for one action
+    pub const ACTION_COST: u32 = 12;
+    /// gain energy for one turn
+    pub fn gain(&mut self, speed: Speed) {
+        self.0 = self.0.saturating_add(speed.0);
+    }
+    /// spend energy for one action, and returns false if there isn't enough energy
+    pub fn spend(&mut self) -> bool {
+        if self.0 < Self::ACTION_COST {
+            return false;
+        }
+        self.0 -= Self::ACTION_COST;
+        true
+    }
+    /// gain energy for one turn, and returns how many times the character acts in the turn
+    pub fn actions_in_turn(&mut self, speed: Speed) -> u32 {
+        self.gain(speed);
+        let mut actions = 0;
+        while self.spend() {
+            actions += 1;
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::{Effect, StatusEffects};
+    /// how many turns pass while the player acts `actions` times
+    fn turns_for(effects: &StatusEffects, actions: usize) -> u32 {
+        let mut energy = Energy::default();
+        let mut turns = 0;
+        for _ in 0..actions {
+            while !energy.spend() {
+                energy.gain(effects.speed(Speed::NORMAL));
+                turns += 1;
+            }
+        }
+        turns
+    }
+    #[test]
+    fn haste_and_slow() {
+        let mut effects = StatusEffects::default();
+        effects.add(Effect::Haste, 10);
+        assert_eq!(turns_for(&effects, 4), 2);
+        effects.remove(Effect::Haste);
+        effects.add(Effect::Slow, 10);
+        assert_eq!(turns_for(&effects, 4), 8);
+        effects.add(Effect::Haste, 10);
+        assert_eq!(turns_for(&effects, 1), 1);
+    }
+    #[test]
+    fn fast_enemy() {
+        let mut energy = Energy::default();
+        let speed = Speed(Energy::ACTION_COST * 3 / 2);
+        let actions: Vec<_> = (0..4).map(|_| energy.actions_in_turn(speed)).collect();
+        assert_eq!(actions, vec![1, 2, 1, 2]);
+        let actions: u32 = (0..4)
+            .map(|_| energy.actions_in_turn(Speed::NORMAL.half()))
+            .sum();
+        assert_eq!(actions, 2);
+    }
+}
@@ -1,73 +1,203 @@
-use super::{Damage, Defense, Dice, Enemy, HitPoint, Level, Player, Strength};
-use crate::item::ItemToken;
+use super::enemies::RangedAttack;
+use super::{
+    Damage, DamageKind, Defense, Dice, Enemy, HitPoint, Level, Player, Resistances, Strength,
+};
+use crate::difficulty;
+use crate::dungeon::Coord;
+use crate::item::Item;
 use crate::rng::{Parcent, RngHandle};
+use crate::tile::Tile;
+use crate::SmallStr;
 use std::iter;
 
+/// one side of a fight
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Combatant {
+    Player,
+    Enemy(SmallStr),
+}
+
+/// result of an attack roll
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Roll {
+    pub kind: DamageKind,
+    /// the probability that each attack hits
+    pub hit_rate: Parcent,
+    /// total damage, or None if all attacks missed
+    pub damage: Option<HitPoint>,
+    /// the largest damage the attack can deal
+    pub max_damage: HitPoint,
+}
+
+impl Roll {
+    pub fn did_hit(&self) -> bool {
+        self.damage.is_some()
+    }
+    /// multiply the damage by `percent`%
+    pub fn scaled(self, percent: u32) -> Self {
+        let scale = |dam: HitPoint| HitPoint(difficulty::scale(dam.0, percent));
+        Roll {
+            damage: self.damage.map(scale),
+            max_damage: scale(self.max_damage),
+            ..self
+        }
+    }
+    /// reduce the damage by the defender's resistance
+    pub fn resisted(self, resistances: &Resistances) -> Self {
+        Roll {
+            damage: self.damage.map(|dam| resistances.reduce(self.kind, dam)),
+            max_damage: resistances.reduce(self.kind, self.max_damage),
+            ..self
+        }
+    }
+    /// the attack is critical when every dice rolled its maximum
+    pub fn is_critical(&self) -> bool {
+        self.damage
+            .is_some_and(|dam| dam > HitPoint(0) && dam >= self.max_damage)
+    }
+}
+
+/// a projectile shot by an enemy, for UIs to animate it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Projectile {
+    /// name of the enemy who shot it
+    pub from: SmallStr,
+    pub symbol: Tile,
+    /// cells the projectile passes, ending at the target
+    pub path: Vec<Coord>,
+}
+
+/// structured data of an attack, so that UIs and gym wrappers don't have to parse messages
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CombatEvent {
+    pub attacker: Combatant,
+    pub defender: Combatant,
+    pub roll: Roll,
+    /// the defender died by this attack or not
+    pub killed: bool,
+}
+
 pub fn player_attack(
     player: &Player,
-    throw_weapon: Option<ItemToken>,
+    throw_weapon: Option<&Item>,
     enemy: &Enemy,
     rng: &mut RngHandle,
-) -> Option<HitPoint> {
-    let (attack_rate, dam_plus) = if let Some(ref item) = throw_weapon {
-        let (mut hit_plus, mut dam_plus) = (item.hit_plus(), item.dam_plus());
-        let name = player.weapon().and_then(|w| w.name());
-        if name.is_some() && name == item.launcher() {
-            hit_plus += player.weapon().map(|w| w.hit_plus()).unwrap_or(Level(0));
-            dam_plus += player.weapon().map(|w| w.dam_plus()).unwrap_or(HitPoint(0));
+) -> Roll {
+    let (hit_plus, dam_plus, dice) = match throw_weapon {
+        Some(item) => missile_stat(item, player.weapon().map(|w| w.get())),
+        None => {
+            let weapon = player.weapon();
+            (
+                weapon.map(|w| w.hit_plus()).unwrap_or(Level(0)),
+                weapon.map(|w| w.dam_plus()).unwrap_or(HitPoint(0)),
+                weapon
+                    .and_then(|w| w.at_weild())
+                    .unwrap_or(Dice::new(1, HitPoint(4))),
+            )
         }
-        let attack_rate = attack_rate_player(player, enemy, hit_plus);
-        (attack_rate, dam_plus)
-    } else {
-        let hit_plus = player.weapon().map(|w| w.hit_plus()).unwrap_or(Level(0));
-        let attack_rate = attack_rate_player(player, enemy, hit_plus);
-        let dam_plus = player.weapon().map(|w| w.dam_plus()).unwrap_or(HitPoint(0));
-        (attack_rate, dam_plus)
     };
-    let dice = if let Some(ref item) = throw_weapon {
-        item.at_throw()
-    } else {
-        player.weapon().and_then(|w| w.at_weild())
-    }
-    .unwrap_or(Dice::new(1, HitPoint(4)));
+    let weapon = throw_weapon.or_else(|| player.weapon().map(|w| w.get()));
+    let (slay_hit, slay_dam) = weapon
+        .and_then(|w| w.special()?.slay.as_ref())
+        .map_or((Level(0), HitPoint(0)), |slay| {
+            slay.bonus(enemy.name().as_str())
+        });
+    let attack_rate = attack_rate_player(player, enemy, hit_plus + slay_hit);
     roll(
         iter::once(&dice),
+        DamageKind::Physical,
         attack_rate,
-        dam_plus + damage_plus(player.strength().current),
+        dam_plus + slay_dam + damage_plus(player.strength().current),
         rng,
     )
+    .resisted(enemy.resistances())
 }
 
-pub fn enemy_attack(enemy: &Enemy, player: &Player, rng: &mut RngHandle) -> Option<HitPoint> {
+/// returns hit plus, damage plus and damage dice of a thrown item.
+/// As in the original rogue, a missile like an arrow gets the bonus of its launcher
+/// only when the launcher is wielded, and is as weak as when wielded otherwise.
+pub fn missile_stat(missile: &Item, wielded: Option<&Item>) -> (Level, HitPoint, Dice<HitPoint>) {
+    let (mut hit_plus, mut dam_plus) = (missile.hit_plus(), missile.dam_plus());
+    let dice = match missile.launcher() {
+        Some(launcher) => match wielded.filter(|w| w.name() == Some(launcher)) {
+            Some(launcher) => {
+                hit_plus += launcher.hit_plus();
+                dam_plus += launcher.dam_plus();
+                missile.at_throw()
+            }
+            None => missile.at_weild(),
+        },
+        None => missile.at_throw(),
+    };
+    (
+        hit_plus,
+        dam_plus,
+        dice.unwrap_or(Dice::new(1, HitPoint(4))),
+    )
+}
+
+pub fn enemy_attack(enemy: &Enemy, player: &Player, rng: &mut RngHandle) -> Roll {
     let attack_rate = attack_rate_enemy(player, enemy);
     let dam_plus = damage_plus(Enemy::STRENGTH);
     roll(
         enemy.attack().iter(),
+        enemy.damage_kind(),
         attack_rate,
         dam_plus + damage_plus(player.strength().current),
         rng,
     )
+    .resisted(player.resistances())
+}
+
+/// Ranged attacks like the dragon's flame always hit,
+/// but the player takes only half of the damage if they succeed in the saving throw.
+pub fn ranged_attack(ranged: &RangedAttack, player: &Player, rng: &mut RngHandle) -> Roll {
+    let mut damage = ranged.damage.random(rng);
+    if save_throw(VS_MAGIC, player, rng) {
+        damage = HitPoint(damage.0 / 2);
+    }
+    Roll {
+        kind: ranged.kind,
+        hit_rate: Parcent(100),
+        damage: Some(damage),
+        max_damage: ranged.damage.max(),
+    }
+    .resisted(player.resistances())
+}
+
+/// saving throw for special attacks
+pub(crate) const VS_POISON: i64 = 0;
+pub(crate) const VS_MAGIC: i64 = 3;
+
+/// returns if the player succeeded in the saving throw or not
+pub(crate) fn save_throw(which: i64, player: &Player, rng: &mut RngHandle) -> bool {
+    let need = 14 + which - player.level().0 / 2;
+    rng.range(1..=20) >= need
 }
 
 fn roll<'a>(
     dices: impl Iterator<Item = &'a Dice<HitPoint>>,
+    kind: DamageKind,
     attack_rate: Parcent,
     dam_plus: HitPoint,
     rng: &mut RngHandle,
-) -> Option<HitPoint> {
+) -> Roll {
     let mut did_hit = false;
     let mut sum = HitPoint(0);
-    for dice in dices {
+    let mut max_damage = HitPoint(0);
+    for &dice in dices {
+        max_damage += dice.max() + dam_plus;
         if !rng.parcent(attack_rate) {
             continue;
         }
         did_hit = true;
         sum += dice.random(rng) + dam_plus;
     }
-    if did_hit {
-        Some(sum)
-    } else {
-        None
+    Roll {
+        kind,
+        hit_rate: attack_rate,
+        damage: if did_hit { Some(sum) } else { None },
+        max_damage,
     }
 }
 
@@ -107,3 +237,104 @@ fn damage_plus(strength: Strength) -> HitPoint {
     }
     DATA[strength.0 as usize - 1].into()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::{weapon::Special, Config as ItemConfig, InitItem, ItemHandler, ItemToken};
+    use crate::smallstr::SmallStr;
+    fn weapon(handler: &mut ItemHandler, name: &str, hit_plus: i32, dam_plus: i32) -> ItemToken {
+        InitItem::Weapon {
+            name: SmallStr::from_str(name),
+            num_plus: 0,
+            hit_plus,
+            dam_plus,
+        }
+        .initialize(handler)
+        .unwrap()
+    }
+    #[test]
+    fn roll_event() {
+        let mut rng = RngHandle::from_seed(1);
+        let dices = [Dice::new(1, HitPoint(1)), Dice::new(2, HitPoint(6))];
+        let physical = DamageKind::Physical;
+        let hit = roll(
+            dices[..1].iter(),
+            physical,
+            Parcent(100),
+            HitPoint(2),
+            &mut rng,
+        );
+        assert_eq!(hit.damage, Some(HitPoint(3)));
+        assert!(hit.is_critical());
+        let hit = roll(dices.iter(), physical, Parcent(100), HitPoint(0), &mut rng);
+        assert_eq!(hit.max_damage, HitPoint(13));
+        let miss = roll(dices.iter(), physical, Parcent(0), HitPoint(0), &mut rng);
+        assert!(!miss.did_hit() && !miss.is_critical());
+    }
+    #[test]
+    fn arrow_with_bow() {
+        let mut handler = ItemHandler::new(ItemConfig::default(), 1);
+        let bow = weapon(&mut handler, "bow", 1, 2);
+        let arrow = weapon(&mut handler, "arrow", 0, 1);
+        let (hit, dam, dice) = missile_stat(&arrow, Some(bow.get()));
+        assert_eq!(hit, bow.hit_plus() + arrow.hit_plus());
+        assert_eq!(dam, bow.dam_plus() + arrow.dam_plus());
+        assert_eq!(Some(dice), arrow.at_throw());
+    }
+    #[test]
+    fn arrow_without_bow() {
+        let mut handler = ItemHandler::new(ItemConfig::default(), 1);
+        let mace = weapon(&mut handler, "mace", 1, 2);
+        let arrow = weapon(&mut handler, "arrow", 0, 1);
+        for wielded in [Some(mace.get()), None] {
+            let (hit, dam, dice) = missile_stat(&arrow, wielded);
+            assert_eq!(hit, arrow.hit_plus());
+            assert_eq!(dam, arrow.dam_plus());
+            assert_eq!(Some(dice), arrow.at_weild());
+        }
+    }
+    #[test]
+    fn special_weapon() {
+        let config: ItemConfig = serde_json::from_str(
+            r#"{
+                "armor": {},
+                "gold": {},
+                "weapon": {"weapons": [0, {
+                    "at_weild": {"times": 1, "max": 8},
+                    "at_throw": {"times": 1, "max": 2},
+                    "name": "vorpal-blade",
+                    "init_num": {"start": 1, "end": 2},
+                    "attr": 0,
+                    "is_initial": false,
+                    "appear_rate": 0,
+                    "worth": 100,
+                    "special": {
+                        "slay": {"enemies": ["Jabberwock"], "dam_plus": 10},
+                        "returning": true
+                    }
+                }]}
+            }"#,
+        )
+        .unwrap();
+        let mut handler = ItemHandler::new(config, 1);
+        let blade = weapon(&mut handler, "vorpal-blade", 0, 0);
+        let special = blade.special().unwrap();
+        assert!(special.returning && !special.two_handed);
+        let slay = special.slay.as_ref().unwrap();
+        assert_eq!(slay.bonus("Jabberwock"), (Level(0), HitPoint(10)));
+        assert_eq!(slay.bonus("Bat"), (Level(0), HitPoint(0)));
+        let mace = weapon(&mut handler, "mace", 0, 0);
+        assert_eq!(mace.special(), Some(&Special::NONE));
+    }
+    #[test]
+    fn dagger_needs_no_launcher() {
+        let mut handler = ItemHandler::new(ItemConfig::default(), 1);
+        let bow = weapon(&mut handler, "bow", 1, 2);
+        let dagger = weapon(&mut handler, "dagger", 0, 0);
+        let (hit, dam, dice) = missile_stat(&dagger, Some(bow.get()));
+        assert_eq!(hit, dagger.hit_plus());
+        assert_eq!(dam, dagger.dam_plus());
+        assert_eq!(Some(dice), dagger.at_throw());
+    }
+}
@@ -1,11 +1,16 @@
+pub mod effects;
 pub mod enemies;
+pub mod energy;
 pub mod fight;
 pub mod player;
-pub use self::player::{Action, Hunger, Leveling, Player};
-use crate::rng::RngHandle;
-pub use enemies::{Enemy, EnemyHandler};
+pub use self::effects::{Effect, StatusEffects};
+pub use self::energy::{Energy, Speed};
+pub use self::player::{Action, ExpFormula, Hunger, Leveling, Player};
+use crate::rng::{Parcent, RngHandle};
+pub use enemies::{Enemy, EnemyHandler, EnemyStash};
 use num_traits::PrimInt;
 use rand::distributions::uniform::SampleUniform;
+use std::collections::BTreeMap;
 use std::ops::AddAssign;
 
 /// values compatible with Hit Point
@@ -259,6 +264,42 @@ where
     }
 }
 
+/// kinds of damage, each of which can be resisted separately
+#[derive(
+    Clone, Copy, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DamageKind {
+    #[default]
+    Physical,
+    Fire,
+    Cold,
+    Lightning,
+    Poison,
+}
+
+/// how much of each kind of damage is cut off, written like `{"fire": 50, "poison": 100}`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Resistances(BTreeMap<DamageKind, Parcent>);
+
+impl Resistances {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn get(&self, kind: DamageKind) -> Parcent {
+        self.0.get(&kind).cloned().unwrap_or(Parcent(0))
+    }
+    pub fn set(&mut self, kind: DamageKind, resistance: Parcent) {
+        self.0.insert(kind, resistance);
+    }
+    /// returns the damage reduced by the resistance
+    pub fn reduce(&self, kind: DamageKind, damage: HitPoint) -> HitPoint {
+        let cut = i64::from(::std::cmp::min(self.get(kind).0, 100));
+        HitPoint(damage.0 * (100 - cut) / 100)
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum DamageReaction {
     Death,
@@ -282,4 +323,11 @@ mod test {
             assert!(2 <= hp.0 && hp.0 <= 8);
         }
     }
+    #[test]
+    fn resistances() {
+        let res: Resistances = serde_json::from_str(r#"{"fire": 50, "poison": 100}"#).unwrap();
+        assert_eq!(res.reduce(DamageKind::Fire, HitPoint(9)), HitPoint(4));
+        assert_eq!(res.reduce(DamageKind::Poison, HitPoint(9)), HitPoint(0));
+        assert_eq!(res.reduce(DamageKind::Physical, HitPoint(9)), HitPoint(9));
+    }
 }
@@ -1,9 +1,15 @@
-use super::{clamp, DamageReaction, Defense, Dice, Exp, HitPoint, Level, Maxed, Strength};
-use crate::dungeon::{Direction, DungeonPath};
+use super::{
+    clamp, DamageReaction, Defense, Dice, Effect, Energy, Exp, HitPoint, Level, Maxed, Resistances,
+    Speed, StatusEffects, Strength,
+};
+use crate::dungeon::{Coord, Direction, DungeonPath};
 use crate::error::GameResult;
 use crate::item::{
-    armor, food::Food, itembox::ItemBox, weapon, InitItem, Item, ItemHandler, ItemKind, ItemToken,
+    armor, food::Food, itembox::ItemBox, weapon, InitItem, Item, ItemAttr, ItemHandler, ItemKind,
+    ItemToken,
 };
+use crate::snapshot::DeepCloner;
+use crate::validate::Validator;
 use crate::{
     rng::RngHandle,
     smallstr::SmallStr,
@@ -15,32 +21,61 @@ use tuple_map::TupleMap2;
 /// Player configuration
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Config {
-    #[serde(default, flatten)]
+    /// experience/leveling curve, which is written as `leveling` in JSON
+    #[serde(default, rename = "leveling")]
     pub level: Leveling,
+    /// (deprecated) same as `leveling.exps`, only for backward compatibility
+    #[serde(default, skip_serializing)]
+    pub exps: Option<Vec<Exp>>,
     #[serde(default = "default_hunger_time")]
     pub hunger_time: u32,
     #[serde(default = "default_init_hp")]
     pub init_hp: HitPoint,
     #[serde(default = "default_init_str")]
     pub init_str: Strength,
+    /// number of slots in the pack, up to 26
     #[serde(default = "default_max_items")]
     pub max_items: usize,
+    /// total weight of items the player can carry
+    #[serde(default = "default_max_weight")]
+    pub max_weight: u32,
     #[serde(default = "default_init_items")]
     pub init_items: Vec<InitItem>,
     #[serde(default = "default_heal_threshold")]
     pub heal_threshold: u32,
+    /// maximum number of turns a `Rest` action lasts
+    #[serde(default = "default_max_rest_turns")]
+    pub max_rest_turns: u32,
+    #[serde(default, skip_serializing_if = "Resistances::is_empty")]
+    pub resistances: Resistances,
+    /// if specified, `init_hp`, `init_str` and `init_items` are replaced by the class's ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<Class>,
+    /// items given in addition to `init_items`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_items: Vec<InitItem>,
+    /// how fast the player acts, where 12 means once per turn
+    #[serde(default, skip_serializing_if = "Speed::is_normal")]
+    pub speed: Speed,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             level: Leveling::default(),
+            exps: None,
             hunger_time: default_hunger_time(),
             init_hp: default_init_hp(),
             init_str: default_init_str(),
             max_items: default_max_items(),
+            max_weight: default_max_weight(),
             init_items: default_init_items(),
             heal_threshold: default_heal_threshold(),
+            max_rest_turns: default_max_rest_turns(),
+            resistances: Resistances::default(),
+            class: None,
+            extra_items: Vec::new(),
+            speed: Speed::NORMAL,
         }
     }
 }
@@ -58,38 +93,129 @@ const fn default_init_str() -> Strength {
 }
 
 const fn default_max_items() -> usize {
-    27
+    26
+}
+
+const fn default_max_weight() -> u32 {
+    26
 }
 
 const fn default_heal_threshold() -> u32 {
     20
 }
 
+const fn default_max_rest_turns() -> u32 {
+    200
+}
+
 fn default_init_items() -> Vec<InitItem> {
-    let money = Item::new(ItemKind::Gold, 0).many();
-    let food = Item::new(ItemKind::Food(Food::Ration), 1).many();
-    let mut res = (money, food).map(|x| InitItem::Noinit(x)).into_vec();
+    let mut res = basic_items();
     res.push(armor::rogue_default_armor());
     weapon::rogue_init_weapons(&mut res);
     res
 }
 
+/// gold and a food every player has at first
+fn basic_items() -> Vec<InitItem> {
+    let money = Item::new(ItemKind::Gold, 0).many();
+    let food = Item::new(ItemKind::Food(Food::Ration), 1).many();
+    (money, food).map(InitItem::Noinit).into_vec()
+}
+
+/// Presets of the starting status and items
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Class {
+    /// same as the original rogue: a mace, a bow, arrows and ring mail
+    Rogue,
+    /// no weapon and no armor
+    Unarmed,
+    /// tough, with a two-handed sword and splint mail
+    Fighter,
+    /// a dagger, a bow, plenty of arrows and leather armor
+    Archer,
+}
+
+impl Class {
+    fn init_hp(self) -> HitPoint {
+        match self {
+            Class::Fighter => HitPoint(16),
+            _ => default_init_hp(),
+        }
+    }
+    fn init_str(self) -> Strength {
+        match self {
+            Class::Fighter => Strength(18),
+            _ => default_init_str(),
+        }
+    }
+    fn init_items(self) -> Vec<InitItem> {
+        let weapon = |name: &str, num_plus, hit_plus, dam_plus| InitItem::Weapon {
+            name: SmallStr::from_str(name),
+            num_plus,
+            hit_plus,
+            dam_plus,
+        };
+        let armor = |name: &str, def_plus| InitItem::Armor {
+            name: SmallStr::from_str(name),
+            def_plus,
+        };
+        let mut res = basic_items();
+        match self {
+            Class::Rogue => return default_init_items(),
+            Class::Unarmed => {}
+            Class::Fighter => {
+                res.push(armor("splint mail", 0));
+                res.push(weapon("two-handed-sword", 0, 0, 0));
+            }
+            Class::Archer => {
+                res.push(armor("leather armor", 0));
+                res.push(weapon("dagger", 0, 0, 0));
+                res.push(weapon("bow", 0, 1, 0));
+                res.push(weapon("arrow", 40, 0, 0));
+            }
+        }
+        res
+    }
+}
+
 impl Config {
-    pub fn build(self) -> Player {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        // `init_hp` is replaced by the class's one
+        let hp_ok = self.class.is_some() || self.init_hp > HitPoint(0);
+        v.check("init_hp", hp_ok, "should be positive");
+        v.field("leveling", |v| self.level.validate(v));
+        let slots_ok = self.max_items <= ItemBox::MAX_SLOTS;
+        v.check("max_items", slots_ok, "should be 26 or less");
+    }
+    pub fn build(mut self) -> Player {
+        if let Some(exps) = self.exps.take() {
+            self.level.exps = exps;
+        }
+        if let Some(class) = self.class {
+            self.init_hp = class.init_hp();
+            self.init_str = class.init_str();
+            self.init_items = class.init_items();
+        }
+        let extra_items = ::std::mem::take(&mut self.extra_items);
+        self.init_items.extend(extra_items);
+        self.level.apply_formula();
         let status = StatusInner::from_config(&self);
         Player {
             pos: DungeonPath::default(),
             status,
-            itembox: ItemBox::with_capacity(self.max_items),
+            itembox: ItemBox::with_limits(self.max_items, self.max_weight),
             config: self,
             armor: None,
             weapon: None,
+            effects: StatusEffects::default(),
+            energy: Energy::default(),
         }
     }
 }
 
 /// Representation of player
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Player {
     /// player position
     pub pos: DungeonPath,
@@ -99,11 +225,23 @@ pub struct Player {
     weapon: Option<ItemToken>,
     /// player status(for drawing)
     status: StatusInner,
+    /// timed status effects
+    effects: StatusEffects,
+    /// energy for the next action
+    energy: Energy,
     /// configuration
     config: Config,
 }
 
 impl Player {
+    pub(crate) fn deep_clone(&self, cloner: &mut DeepCloner) -> Player {
+        Player {
+            itembox: self.itembox.deep_clone(cloner),
+            armor: self.armor.as_ref().map(|item| cloner.item(item)),
+            weapon: self.weapon.as_ref().map(|item| cloner.item(item)),
+            ..self.clone()
+        }
+    }
     pub fn fill_status(&self, status: &mut Status) {
         status.hp = self.status.hp;
         status.strength = self.status.strength;
@@ -115,6 +253,9 @@ impl Player {
             x if x <= hunger * 2 => Hunger::Hungry,
             _ => Hunger::Normal,
         };
+        status.effects = self.effects;
+        status.defense = self.arm();
+        status.turns = self.status.turns;
     }
     pub fn run(&mut self, b: bool) {
         self.status.running = b;
@@ -133,6 +274,87 @@ impl Player {
     pub fn weapon(&self) -> Option<&ItemToken> {
         self.weapon.as_ref()
     }
+    /// the name of the wielded weapon, which launches missiles like arrows
+    pub fn launcher(&self) -> Option<&str> {
+        self.weapon().and_then(|w| w.name())
+    }
+    /// wield a weapon in the pack
+    pub fn wield(&mut self, mut query: impl FnMut(&Item) -> bool) -> bool {
+        let mut weapon = match self.itembox.find_by(|item| match &item.kind {
+            ItemKind::Weapon(_) => query(item),
+            _ => false,
+        }) {
+            Some(token) => token.clone(),
+            None => return false,
+        };
+        if let Some(mut old) = self.weapon.take() {
+            old.get_mut().attr.unequip();
+        }
+        weapon.get_mut().attr.equip();
+        self.weapon = Some(weapon);
+        true
+    }
+    /// take an item out of the pack, taking it off if it's equipped
+    pub(crate) fn remove_item(&mut self, letter: char) -> Option<ItemToken> {
+        let mut token = self.itembox.remove(ItemBox::slot(letter)?)?;
+        if token.attr.is_equiped() {
+            token.get_mut().attr.unequip();
+            let id = Some(token.id());
+            if self.weapon.as_ref().map(|w| w.id()) == id {
+                self.weapon = None;
+            }
+            if self.armor.as_ref().map(|a| a.id()) == id {
+                self.armor = None;
+            }
+        }
+        Some(token)
+    }
+    /// whether the pack has something to throw
+    pub(crate) fn has_missile(&self) -> bool {
+        self.itembox
+            .find_by(|item| !item.attr.is_equiped() && item.attr.contains(ItemAttr::CAN_THROW))
+            .is_some()
+    }
+    /// take one missile from the pack, preferring ones launched by the wielded weapon
+    pub(crate) fn take_missile(&mut self, items: &mut ItemHandler) -> Option<ItemToken> {
+        let launcher = self.weapon.as_ref().and_then(|w| w.name());
+        let is_missile =
+            |item: &Item| !item.attr.is_equiped() && item.attr.contains(ItemAttr::CAN_THROW);
+        let launched =
+            |item: &Item| is_missile(item) && launcher.is_some() && item.launcher() == launcher;
+        if let Some(missile) = self.itembox.take_one_by(launched, items) {
+            return Some(missile);
+        }
+        self.itembox.take_one_by(is_missile, items)
+    }
+    pub fn effects(&self) -> &StatusEffects {
+        &self.effects
+    }
+    pub(crate) fn effects_mut(&mut self) -> &mut StatusEffects {
+        &mut self.effects
+    }
+    /// the current speed, including effects like haste
+    pub fn speed(&self) -> Speed {
+        self.effects.speed(self.config.speed)
+    }
+    /// spend energy for the last action, and returns false if turns have to pass before the next one
+    pub(crate) fn spend_energy(&mut self) -> bool {
+        self.energy.spend()
+    }
+    /// gain energy for one turn
+    pub(crate) fn gain_energy(&mut self) {
+        let speed = self.speed();
+        self.energy.gain(speed);
+    }
+    pub(crate) fn hp_is_full(&self) -> bool {
+        self.status.hp.current >= self.status.hp.max
+    }
+    pub fn resistances(&self) -> &Resistances {
+        &self.config.resistances
+    }
+    pub(crate) fn max_rest_turns(&self) -> u32 {
+        self.config.max_rest_turns
+    }
     pub fn init_items(&mut self, items: &mut ItemHandler) -> GameResult<()> {
         items.init_player_items(&mut self.itembox, &self.config.init_items)?;
         if let Some(name) = self.get_initial_weapon() {
@@ -162,7 +384,8 @@ impl Player {
     }
     pub(crate) fn turn_passed(&mut self, rng: &mut RngHandle) -> Vec<PlayerEvent> {
         let mut res = vec![];
-        self.status.food_left -= 1;
+        self.status.turns += 1;
+        self.status.food_left = self.status.food_left.saturating_sub(1);
         if self.status.food_left == 0 {
             return vec![PlayerEvent::Dead];
         }
@@ -184,17 +407,61 @@ impl Player {
     }
     pub(crate) fn level_up(&mut self, exp: Exp, rng: &mut RngHandle) -> bool {
         self.status.exp += exp;
-        let diff = self
-            .config
-            .level
-            .check_level(self.status.level, self.status.exp);
+        let leveling = &self.config.level;
+        let diff = leveling.check_level(self.status.level, self.status.exp);
         if diff > 0 {
             self.status.level += Level(diff as i64);
-            self.status.hp += Dice::new(diff, HitPoint(10)).exec::<i64>(rng);
+            let hp_dice = leveling.hp_dice;
+            self.status.hp += Dice::new(diff * hp_dice.times, hp_dice.max).exec::<i64>(rng);
+            if leveling.str_gain != Strength(0) {
+                self.status.strength += Strength(leveling.str_gain.0 * diff as i64);
+            }
             return true;
         }
         false
     }
+    /// weaken the armor the player wears (e.g., by an aquator)
+    pub(crate) fn rust_armor(&mut self) -> bool {
+        match self.armor.as_mut().map(|token| &mut token.get_mut().kind) {
+            Some(ItemKind::Armor(armor)) => armor.rust(),
+            _ => false,
+        }
+    }
+    /// take gold from the player's pack and returns how much gold was taken
+    pub(crate) fn steal_gold(&mut self, amount: u32) -> u32 {
+        let gold = match self.itembox.find_by_mut(|item| item.kind == ItemKind::Gold) {
+            Some(token) => token.get_mut(),
+            None => return 0,
+        };
+        let stolen = cmp::min(gold.how_many.0, amount);
+        gold.how_many.0 -= stolen;
+        stolen
+    }
+    /// take an item which isn't equipped from the player's pack
+    pub(crate) fn steal_item(&mut self, rng: &mut RngHandle) -> Option<ItemToken> {
+        self.itembox.remove_random_by(rng, |item| {
+            item.kind != ItemKind::Gold && !item.attr.is_equiped()
+        })
+    }
+    /// lose 1 level and some max hp (e.g., by a wraith)
+    pub(crate) fn drain_level(&mut self, rng: &mut RngHandle) {
+        if self.status.level > Level(1) {
+            self.status.level -= Level(1);
+        }
+        self.status.exp = self.config.level.least_exp(self.status.level);
+        let lost = self.config.level.hp_dice.exec::<i64>(rng);
+        let hp = &mut self.status.hp;
+        hp.max = cmp::max(hp.max - lost, HitPoint(1));
+        hp.verify();
+    }
+    /// lose 1 strength (e.g., by a rattlesnake)
+    pub(crate) fn reduce_strength(&mut self) -> bool {
+        if self.status.strength.current <= Strength(3) {
+            return false;
+        }
+        self.status.strength.current -= Strength(1);
+        true
+    }
     pub fn get_initial_weapon(&self) -> Option<SmallStr> {
         self.config.init_items.iter().find_map(|item| {
             if let InitItem::Weapon { name, .. } = item {
@@ -277,6 +544,9 @@ struct StatusInner {
     food_left: u32,
     running: bool,
     quiet: u32,
+    /// turns passed since the game started
+    #[serde(default)]
+    turns: u32,
 }
 
 impl StatusInner {
@@ -289,6 +559,7 @@ impl StatusInner {
             food_left: config.hunger_time,
             running: false,
             quiet: 0,
+            turns: 0,
         }
     }
 }
@@ -301,54 +572,156 @@ pub enum Action {
     UpStair,
     DownStair,
     Search,
+    /// throw a missile toward the direction
+    Throw(Direction),
+    /// drop the item labeled by the letter
+    Drop(char),
+    /// drop the given number of items from the stack labeled by the letter
+    DropSome(char, u32),
+    /// pick up the item on the player's cell
+    PickUp,
+    /// read the scroll labeled by the letter
+    Read(char),
+    /// open the door toward the direction
+    Open(Direction),
+    /// close the door toward the direction
+    Close(Direction),
+    /// walk toward the nearest unexplored place until something is found
+    AutoExplore,
+    /// walk to the cell until something is found
+    TravelTo(Coord),
+    /// wait until HP is full or something happens
+    Rest,
     NoOp,
 }
 
+/// Leveling configuration
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Leveling {
     /// necesarry exp for level up
-    exps: Vec<Exp>,
+    #[serde(default = "default_exps")]
+    pub exps: Vec<Exp>,
+    /// if specified, `exps` is generated by this formula
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formula: Option<ExpFormula>,
+    /// hp gained per level
+    #[serde(default = "default_hp_dice")]
+    pub hp_dice: Dice<HitPoint>,
+    /// strength gained per level
+    #[serde(default)]
+    pub str_gain: Strength,
 }
 
 impl Default for Leveling {
     fn default() -> Self {
-        let exps: Vec<Exp> = vec![
-            10u32,
-            20,
-            40,
-            80,
-            160,
-            320,
-            640,
-            1300,
-            2600,
-            5200,
-            13000,
-            26000,
-            50000,
-            100_000,
-            200_000,
-            400_000,
-            800_000,
-            2_000_000,
-            4_000_000,
-            8_000_000,
-            u32::max_value(),
-        ]
-        .into_iter()
-        .map(|u| u.into())
-        .collect();
-        Leveling { exps }
+        Leveling {
+            exps: default_exps(),
+            formula: None,
+            hp_dice: default_hp_dice(),
+            str_gain: Strength(0),
+        }
     }
 }
 
+fn default_exps() -> Vec<Exp> {
+    vec![
+        10u32,
+        20,
+        40,
+        80,
+        160,
+        320,
+        640,
+        1300,
+        2600,
+        5200,
+        13000,
+        26000,
+        50000,
+        100_000,
+        200_000,
+        400_000,
+        800_000,
+        2_000_000,
+        4_000_000,
+        8_000_000,
+        u32::MAX,
+    ]
+    .into_iter()
+    .map(|u| u.into())
+    .collect()
+}
+
+const fn default_hp_dice() -> Dice<HitPoint> {
+    Dice::new(1, HitPoint(10))
+}
+
+/// Generates the exp table as `exps[i] = base * (growth / 100) ^ i`
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ExpFormula {
+    /// necesarry exp to reach level 2
+    pub base: Exp,
+    /// growth rate in parcent(e.g. 200 means doubling)
+    pub growth: u32,
+    /// max player level
+    pub max_level: u32,
+}
+
+impl ExpFormula {
+    fn validate(&self, v: &mut Validator) {
+        if self.growth <= 100 {
+            v.field("growth", |v| {
+                v.error("should be more than 100, or necessary exps don't increase")
+            });
+        } else if !is_increasing(&self.exps()) {
+            v.field("base", |v| {
+                v.error("is too small to make necessary exps increase by the growth rate")
+            });
+        }
+    }
+    fn exps(&self) -> Vec<Exp> {
+        let mut cur = u64::from(self.base.0);
+        let mut res = Vec::new();
+        for _ in 1..self.max_level {
+            res.push(Exp(cmp::min(cur, u64::from(u32::MAX)) as u32));
+            cur = cur * u64::from(self.growth) / 100;
+        }
+        res
+    }
+}
+
+fn is_increasing(exps: &[Exp]) -> bool {
+    exps.windows(2).all(|w| w[0] < w[1])
+}
+
 impl Leveling {
+    fn validate(&self, v: &mut Validator) {
+        match &self.formula {
+            Some(formula) => v.field("formula", |v| formula.validate(v)),
+            None => v.check("exps", is_increasing(&self.exps), "should be increasing"),
+        }
+    }
+    fn apply_formula(&mut self) {
+        if let Some(formula) = self.formula.as_ref() {
+            self.exps = formula.exps();
+        }
+    }
     fn check_level(&self, cur: Level, exp: Exp) -> usize {
         let cur = (cur.0 - 1) as usize;
         if cur >= self.exps.len() {
             return 0;
         }
-        self.exps[cur..].iter().position(|e| exp < *e).unwrap()
+        self.exps[cur..]
+            .iter()
+            .position(|e| exp < *e)
+            .unwrap_or(self.exps.len() - cur)
+    }
+    /// the least exp required for the level
+    fn least_exp(&self, level: Level) -> Exp {
+        match level.0 {
+            l if l <= 1 => Exp(0),
+            l => self.exps.get(l as usize - 2).cloned().unwrap_or_default(),
+        }
     }
 }
 
@@ -397,6 +770,9 @@ pub struct Status {
     pub player_level: u32,
     pub exp: Exp,
     pub hunger_level: Hunger,
+    pub effects: StatusEffects,
+    /// turns passed since the game started
+    pub turns: u32,
 }
 
 impl Status {
@@ -412,6 +788,12 @@ impl Status {
             ("player_level", self.player_level),
             ("exp", self.exp.0),
             ("hunger", self.hunger_level.to_u32()),
+            ("haste", self.effects.turns_left(Effect::Haste)),
+            ("slow", self.effects.turns_left(Effect::Slow)),
+            ("confusion", self.effects.turns_left(Effect::Confusion)),
+            ("blindness", self.effects.turns_left(Effect::Blindness)),
+            ("sleep", self.effects.turns_left(Effect::Sleep)),
+            ("turns", self.turns),
         ]
     }
     pub fn to_vec(&self) -> Vec<u32> {
@@ -426,8 +808,38 @@ impl Status {
             self.player_level,
             self.exp.0,
             self.hunger_level.to_u32(),
+            self.effects.turns_left(Effect::Haste),
+            self.effects.turns_left(Effect::Slow),
+            self.effects.turns_left(Effect::Confusion),
+            self.effects.turns_left(Effect::Blindness),
+            self.effects.turns_left(Effect::Sleep),
+            self.turns,
         ]
     }
+    /// status in 2 lines, where the second one has the turn count, hunger and effects
+    pub fn lines(&self) -> [String; 2] {
+        let first = format!(
+            "Level: {:2} Gold: {:5} Hp: {:2}({:2}) Str: {:2}({:2}) Arm: {:2} Exp: {:2}/{:2}",
+            self.dungeon_level,
+            self.gold,
+            self.hp.current,
+            self.hp.max,
+            self.strength.current,
+            self.strength.max,
+            self.defense,
+            self.player_level,
+            self.exp.0,
+        );
+        let mut second = format!("Turn: {:5} Depth: {:2}", self.turns, self.dungeon_level);
+        if self.hunger_level != Hunger::Normal {
+            second.push_str(&format!(" {}", self.hunger_level));
+        }
+        for effect in self.effects.actives() {
+            let turns = self.effects.turns_left(effect);
+            second.push_str(&format!(" {}({})", effect, turns));
+        }
+        [first, second]
+    }
 }
 
 impl fmt::Display for Status {
@@ -445,6 +857,165 @@ impl fmt::Display for Status {
             self.player_level,
             self.exp.0,
             self.hunger_level,
+        )?;
+        for effect in self.effects.actives() {
+            write!(formatter, " {}", effect)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::itembox::Entry;
+    #[test]
+    fn special_attacks() {
+        let mut items = ItemHandler::new(crate::item::Config::default(), 1);
+        let mut player = Config::default().build();
+        player.init_items(&mut items).unwrap();
+        let mut rng = RngHandle::from_seed(1);
+        // rust
+        let def = |player: &Player| match &player.armor().unwrap().get().kind {
+            ItemKind::Armor(armor) => armor.def(),
+            _ => unreachable!(),
+        };
+        let before = def(&player);
+        assert!(player.rust_armor());
+        assert_eq!(def(&player), before - Defense(1));
+        // steal gold and items, but not equipped ones
+        let gold = loop {
+            if let Some(gold) = items.setup_gold(1) {
+                break gold;
+            }
+        };
+        let amount = gold.get().how_many.0;
+        match player.itembox.entry(&gold) {
+            Some(Entry::Merge(entry)) => entry.exec(gold.get_cloned()),
+            Some(Entry::Insert(entry)) => entry.exec(gold),
+            None => panic!("no room for gold"),
+        };
+        assert_eq!(player.steal_gold(amount + 10), amount);
+        assert_eq!(player.steal_gold(10), 0);
+        while let Some(stolen) = player.steal_item(&mut rng) {
+            assert!(!stolen.get().attr.is_equiped());
+            assert_ne!(stolen.get().kind, ItemKind::Gold);
+        }
+        assert!(player
+            .itembox
+            .items()
+            .all(|item| item.attr.is_equiped() || item.kind == ItemKind::Gold));
+        assert!(player.armor().is_some() && player.weapon().is_some());
+        // drain
+        assert!(player.level_up(Exp(30), &mut rng));
+        let (level, max_hp) = (player.status.level, player.status.hp.max);
+        player.drain_level(&mut rng);
+        assert_eq!(player.status.level, level - Level(1));
+        assert_eq!(
+            player.status.exp,
+            player.config.level.least_exp(level - Level(1))
+        );
+        assert!(player.status.hp.max < max_hp);
+        assert!(player.status.hp.current <= player.status.hp.max);
+    }
+    #[test]
+    fn exp_formula() {
+        let formula = ExpFormula {
+            base: Exp(10),
+            growth: 200,
+            max_level: 5,
+        };
+        assert_eq!(formula.exps(), vec![Exp(10), Exp(20), Exp(40), Exp(80)]);
+        // the formula replaces exps and levels the player up
+        let config: Config = serde_json::from_str(
+            r#"{"leveling": {"formula": {"base": 10, "growth": 150, "max_level": 4}}}"#,
         )
+        .unwrap();
+        let mut player = config.build();
+        assert_eq!(player.config.level.exps, vec![Exp(10), Exp(15), Exp(22)]);
+        let mut rng = RngHandle::from_seed(1);
+        assert!(player.level_up(Exp(16), &mut rng));
+        assert_eq!(player.status.level, Level(3));
+    }
+    #[test]
+    fn exp_formula_violations() {
+        let paths = |json: &str| {
+            let config: Config = serde_json::from_str(json).unwrap();
+            let mut v = Validator::new();
+            config.validate(&mut v);
+            v.finish().0.into_iter().map(|v| v.path).collect::<Vec<_>>()
+        };
+        let formula = |base, growth| {
+            format!(
+                r#"{{"leveling": {{"formula": {{"base": {}, "growth": {}, "max_level": 5}}}}}}"#,
+                base, growth
+            )
+        };
+        assert!(paths(&formula(10, 150)).is_empty());
+        assert_eq!(paths(&formula(10, 100)), vec!["leveling.formula.growth"]);
+        assert_eq!(paths(&formula(10, 50)), vec!["leveling.formula.growth"]);
+        assert_eq!(paths(&formula(1, 150)), vec!["leveling.formula.base"]);
+        assert_eq!(
+            paths(r#"{"leveling": {"exps": [10, 5]}}"#),
+            vec!["leveling.exps"]
+        );
+    }
+    #[test]
+    fn check_level() {
+        let leveling = Leveling {
+            exps: vec![Exp(10), Exp(20)],
+            ..Default::default()
+        };
+        assert_eq!(leveling.check_level(Level(1), Exp(5)), 0);
+        assert_eq!(leveling.check_level(Level(1), Exp(15)), 1);
+        assert_eq!(leveling.check_level(Level(1), Exp(100)), 2);
+        assert_eq!(leveling.check_level(Level(3), Exp(100)), 0);
+    }
+    #[test]
+    fn legacy_exps() {
+        let config: Config = serde_json::from_str(r#"{"exps": [5, 10]}"#).unwrap();
+        let player = config.build();
+        assert_eq!(player.config.level.exps, vec![Exp(5), Exp(10)]);
+    }
+    #[test]
+    fn class() {
+        use crate::item::Config as ItemConfig;
+        let mut items = ItemHandler::new(ItemConfig::default(), 1);
+        let config: Config = serde_json::from_str(
+            r#"{
+                "class": "archer",
+                "extra_items": [{"Weapon": {"name": "dart", "num_plus": 0, "hit_plus": 0, "dam_plus": 0}}]
+            }"#,
+        )
+        .unwrap();
+        let mut player = config.build();
+        player.init_items(&mut items).unwrap();
+        assert_eq!(player.weapon().unwrap().name(), Some("dagger"));
+        assert!(player
+            .itembox
+            .find_by(|item| item.name() == Some("dart"))
+            .is_some());
+        let config: Config = serde_json::from_str(r#"{"class": "unarmed"}"#).unwrap();
+        let mut player = config.build();
+        player.init_items(&mut items).unwrap();
+        assert!(player.weapon().is_none() && player.armor().is_none());
+    }
+    #[test]
+    fn status_lines() {
+        use crate::item::Config as ItemConfig;
+        let mut items = ItemHandler::new(ItemConfig::default(), 1);
+        let mut player = Config::default().build();
+        player.init_items(&mut items).unwrap();
+        let mut rng = RngHandle::from_seed(1);
+        for _ in 0..3 {
+            player.turn_passed(&mut rng);
+        }
+        let mut status = Status::default();
+        player.fill_status(&mut status);
+        assert_eq!(status.turns, 3);
+        assert_eq!(status.defense, player.arm());
+        assert_ne!(status.defense, Defense(0));
+        let [_, second] = status.lines();
+        assert!(second.starts_with("Turn:     3"));
     }
 }
@@ -0,0 +1,283 @@
+//! datasets of (observation, action, reward, done) for imitation learning,
+//! recorded from agents or replays of human plays
+//!
+//! A dataset file starts with a header:
+//! `b"RGDS"`, format version (u8), width (u16), height (u16).
+//! Then chunks of records follow, each of which starts with
+//! the number of records (u32) and the length of the chunk body in bytes (u32).
+//! A record is:
+//! length of the action JSON (u16), the action as JSON, reward (f32), done (u8), and
+//! symbols of the observation before the action (u8 each, made by `RunTime::observe_symbols`).
+//! All numbers are little endian.
+use crate::agent::{run_agent_with, Agent, Trajectory};
+use crate::error::*;
+use crate::input::InputCode;
+use crate::replay::ReplayFile;
+use crate::GameConfig;
+use anyhow::{bail, Context};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"RGDS";
+pub const VERSION: u8 = 1;
+/// the number of records in a chunk, if not specified
+pub const DEFAULT_CHUNK_SIZE: u32 = 1024;
+
+/// An observation, the action taken there, and its result
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatasetRecord {
+    /// symbols in row-major order
+    pub observation: Vec<u8>,
+    pub action: InputCode,
+    pub reward: f32,
+    pub done: bool,
+}
+
+/// Writes a dataset. `finish` should be called to write the last chunk.
+pub struct DatasetWriter<W> {
+    writer: W,
+    observation_len: usize,
+    chunk_size: u32,
+    chunk: Vec<u8>,
+    records: u32,
+}
+
+impl<W: Write> DatasetWriter<W> {
+    pub fn new(writer: W, width: u16, height: u16) -> GameResult<Self> {
+        Self::with_chunk_size(writer, width, height, DEFAULT_CHUNK_SIZE)
+    }
+    pub fn with_chunk_size(
+        mut writer: W,
+        width: u16,
+        height: u16,
+        chunk_size: u32,
+    ) -> GameResult<Self> {
+        if chunk_size == 0 {
+            bail!(ErrorKind::InvalidSetting(
+                "chunk size of datasets should be positive".into()
+            ));
+        }
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        Ok(DatasetWriter {
+            writer,
+            observation_len: usize::from(width) * usize::from(height),
+            chunk_size,
+            chunk: vec![],
+            records: 0,
+        })
+    }
+    pub fn write(&mut self, record: &DatasetRecord) -> GameResult<()> {
+        if record.observation.len() != self.observation_len {
+            bail!(ErrorKind::InvalidSetting(
+                format!(
+                    "observation should have {} symbols, but it has {}",
+                    self.observation_len,
+                    record.observation.len()
+                )
+                .into()
+            ));
+        }
+        let action = serde_json::to_vec(&record.action).context("DatasetWriter::write")?;
+        let len = u16::try_from(action.len()).context("DatasetWriter::write")?;
+        self.chunk.extend_from_slice(&len.to_le_bytes());
+        self.chunk.extend_from_slice(&action);
+        self.chunk.extend_from_slice(&record.reward.to_le_bytes());
+        self.chunk.push(u8::from(record.done));
+        self.chunk.extend_from_slice(&record.observation);
+        self.records += 1;
+        if self.records >= self.chunk_size {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+    fn write_chunk(&mut self) -> GameResult<()> {
+        if self.records == 0 {
+            return Ok(());
+        }
+        let len = u32::try_from(self.chunk.len()).context("DatasetWriter::write_chunk")?;
+        self.writer.write_all(&self.records.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&self.chunk)?;
+        self.chunk.clear();
+        self.records = 0;
+        Ok(())
+    }
+    /// write the last chunk and returns the inner writer
+    pub fn finish(mut self) -> GameResult<W> {
+        self.write_chunk()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads a dataset, as an iterator of records
+pub struct DatasetReader<R> {
+    reader: R,
+    pub width: u16,
+    pub height: u16,
+    chunk: std::vec::IntoIter<DatasetRecord>,
+}
+
+impl<R: Read> DatasetReader<R> {
+    pub fn new(mut reader: R) -> GameResult<Self> {
+        let mut header = [0u8; 9];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            bail!(ErrorKind::InvalidSetting("not a dataset file".into()));
+        }
+        if header[4] != VERSION {
+            bail!(ErrorKind::InvalidSetting(
+                format!("unsupported dataset version {}", header[4]).into()
+            ));
+        }
+        Ok(DatasetReader {
+            reader,
+            width: u16::from_le_bytes([header[5], header[6]]),
+            height: u16::from_le_bytes([header[7], header[8]]),
+            chunk: Vec::new().into_iter(),
+        })
+    }
+    /// read the next chunk, or returns None at the end of the dataset
+    pub fn read_chunk(&mut self) -> GameResult<Option<Vec<DatasetRecord>>> {
+        let mut header = [0u8; 8];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let records = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut body = vec![0u8; len as usize];
+        self.reader.read_exact(&mut body)?;
+        let observation_len = usize::from(self.width) * usize::from(self.height);
+        let mut rest = &body[..];
+        let mut res = Vec::with_capacity(records as usize);
+        for _ in 0..records {
+            let len = take(&mut rest, 2)?;
+            let len = usize::from(u16::from_le_bytes([len[0], len[1]]));
+            let action = serde_json::from_slice(take(&mut rest, len)?)
+                .context("DatasetReader::read_chunk")?;
+            let reward = take(&mut rest, 4)?;
+            let reward = f32::from_le_bytes([reward[0], reward[1], reward[2], reward[3]]);
+            let done = take(&mut rest, 1)?[0] != 0;
+            let observation = take(&mut rest, observation_len)?.to_vec();
+            res.push(DatasetRecord {
+                observation,
+                action,
+                reward,
+                done,
+            });
+        }
+        Ok(Some(res))
+    }
+}
+
+impl<R: Read> Iterator for DatasetReader<R> {
+    type Item = GameResult<DatasetRecord>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.chunk.next() {
+                return Some(Ok(record));
+            }
+            match self.read_chunk() {
+                Ok(Some(chunk)) => self.chunk = chunk.into_iter(),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// split the first `n` bytes off `rest`
+fn take<'a>(rest: &mut &'a [u8], n: usize) -> GameResult<&'a [u8]> {
+    if rest.len() < n {
+        bail!(ErrorKind::InvalidSetting("broken dataset chunk".into()));
+    }
+    let slice: &'a [u8] = rest;
+    let (head, tail) = slice.split_at(n);
+    *rest = tail;
+    Ok(head)
+}
+
+/// let `agent` play `episodes` games like `agent::run_agent`, writing all steps into `writer`
+pub fn record_agent<W: Write>(
+    config: &GameConfig,
+    agent: &mut dyn Agent,
+    episodes: usize,
+    writer: &mut DatasetWriter<W>,
+) -> GameResult<Vec<Trajectory>> {
+    run_agent_with(config, agent, episodes, |state, transition| {
+        writer.write(&DatasetRecord {
+            observation: state.symbols.clone(),
+            action: transition.action,
+            reward: transition.reward,
+            done: transition.done,
+        })
+    })
+}
+
+/// replay `replay` and write all steps into `writer`, for recording human plays
+pub fn record_replay<W: Write>(
+    replay: &ReplayFile,
+    writer: &mut DatasetWriter<W>,
+) -> GameResult<()> {
+    let mut runtime = replay.config.clone().build()?;
+    let (w, h) = runtime.observation_size();
+    let mut observation = vec![0; w * h];
+    for &action in &replay.inputs {
+        runtime.observe_symbols(&mut observation)?;
+        let (reward, info) = runtime.advance(action)?;
+        writer.write(&DatasetRecord {
+            observation: observation.clone(),
+            action,
+            reward,
+            done: info.is_done(),
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::GameState;
+    use crate::character::Action;
+    #[test]
+    fn write_and_read() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.episode.max_turns = Some(3);
+        let runtime = config.clone().build().unwrap();
+        let (w, h) = runtime.observation_size();
+        let mut writer = DatasetWriter::with_chunk_size(vec![], w as u16, h as u16, 2).unwrap();
+        let mut agent = |_: &GameState| InputCode::Act(Action::Search);
+        let trajectories = record_agent(&config, &mut agent, 2, &mut writer).unwrap();
+        let buf = writer.finish().unwrap();
+        let mut reader = DatasetReader::new(&buf[..]).unwrap();
+        assert_eq!((reader.width as usize, reader.height as usize), (w, h));
+        assert_eq!(reader.read_chunk().unwrap().unwrap().len(), 2);
+        let records: Vec<_> = DatasetReader::new(&buf[..])
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records.len(), 6);
+        let steps = trajectories.iter().flat_map(|t| t.steps.iter());
+        for (record, step) in records.iter().zip(steps) {
+            assert_eq!(record.action, step.action);
+            assert_eq!(record.done, step.done);
+            assert_eq!(record.observation.len(), w * h);
+        }
+        let replay = ReplayFile::new(config, 1, vec![InputCode::Act(Action::Search); 3]);
+        let mut writer = DatasetWriter::new(vec![], w as u16, h as u16).unwrap();
+        record_replay(&replay, &mut writer).unwrap();
+        let buf = writer.finish().unwrap();
+        let replayed: Vec<_> = DatasetReader::new(&buf[..])
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(replayed, records[..3].to_vec());
+    }
+}
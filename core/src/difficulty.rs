@@ -0,0 +1,114 @@
+//! difficulty profiles, which scale enemies and items
+use crate::rng::Probability;
+
+/// Difficulty setting, written as a preset name like `"hard"`
+/// or as multipliers like `{"enemy_hp": 150, "item_appear": 50}`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Difficulty {
+    Preset(Preset),
+    Custom(Multipliers),
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::Preset(Preset::Original)
+    }
+}
+
+impl Difficulty {
+    pub fn multipliers(self) -> Multipliers {
+        match self {
+            Difficulty::Preset(preset) => preset.multipliers(),
+            Difficulty::Custom(m) => m,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    Easy,
+    Normal,
+    /// same as the original rogue
+    Original,
+    Hard,
+}
+
+impl Preset {
+    pub fn multipliers(self) -> Multipliers {
+        let (enemy_hp, enemy_damage, enemy_appear, item_appear) = match self {
+            Preset::Easy => (60, 60, 70, 150),
+            Preset::Normal => (80, 80, 90, 120),
+            Preset::Original => (100, 100, 100, 100),
+            Preset::Hard => (130, 130, 120, 70),
+        };
+        Multipliers {
+            enemy_hp,
+            enemy_damage,
+            enemy_appear,
+            item_appear,
+        }
+    }
+}
+
+/// Multipliers in percent, where 100 means the original rogue
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Multipliers {
+    /// hit points of enemies
+    #[serde(default = "default_percent")]
+    pub enemy_hp: u32,
+    /// damage enemies deal
+    #[serde(default = "default_percent")]
+    pub enemy_damage: u32,
+    /// how often enemies appear, including wandering ones
+    #[serde(default = "default_percent")]
+    pub enemy_appear: u32,
+    /// how often items appear
+    #[serde(default = "default_percent")]
+    pub item_appear: u32,
+}
+
+impl Default for Multipliers {
+    fn default() -> Self {
+        Preset::Original.multipliers()
+    }
+}
+
+const fn default_percent() -> u32 {
+    100
+}
+
+/// multiply `value` by `percent`%
+pub(crate) fn scale(value: i64, percent: u32) -> i64 {
+    value * i64::from(percent) / 100
+}
+
+/// scale a probability
+pub(crate) fn scale_probability(p: Probability, percent: u32) -> Probability {
+    Probability::truncate(scale(i64::from(p.ppm()), percent))
+}
+
+/// scale a probability written as `1 / rate_inv`, which is never less than 1
+pub(crate) fn scale_rate_inv(rate_inv: u32, percent: u32) -> u32 {
+    if percent == 0 {
+        return u32::MAX;
+    }
+    ::std::cmp::max(1, rate_inv.saturating_mul(100) / percent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn from_json() {
+        let hard: Difficulty = serde_json::from_str(r#""hard""#).unwrap();
+        assert_eq!(hard, Difficulty::Preset(Preset::Hard));
+        let custom: Difficulty = serde_json::from_str(r#"{"enemy_hp": 150}"#).unwrap();
+        let m = custom.multipliers();
+        assert_eq!((m.enemy_hp, m.enemy_damage), (150, 100));
+        assert_eq!(scale_rate_inv(2, 100), 2);
+        assert_eq!(scale_rate_inv(2, 50), 4);
+        assert_eq!(scale_rate_inv(2, 400), 1);
+    }
+}
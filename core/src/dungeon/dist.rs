@@ -0,0 +1,78 @@
+//! distance maps (a.k.a. Dijkstra maps)
+use super::{Coord, Direction, X, Y};
+use ndarray::Array2;
+use rect_iter::{Get2D, GetMut2D};
+use std::collections::VecDeque;
+
+/// the distance of cells which can't be reached from any source
+pub const UNREACHABLE: u32 = u32::MAX;
+
+/// Breadth first search from all of `sources` at once.
+/// Returns the number of steps from the nearest source for each cell,
+/// where `can_move(cd, direction)` tells if one can move from `cd` toward `direction`.
+/// Cells which can't be reached are `UNREACHABLE`.
+pub fn distance_map<I, F>(width: X, height: Y, sources: I, can_move: F) -> Array2<u32>
+where
+    I: IntoIterator<Item = Coord>,
+    F: Fn(Coord, Direction) -> bool,
+{
+    let mut dist = Array2::from_elem([height.0 as usize, width.0 as usize], UNREACHABLE);
+    let mut queue = VecDeque::new();
+    for source in sources {
+        if let Ok(d) = dist.try_get_mut_p(source) {
+            *d = 0;
+            queue.push_back(source);
+        }
+    }
+    while let Some(current) = queue.pop_front() {
+        let cdist = *dist.get_p(current);
+        for &d in Direction::NEIGHBORS.iter() {
+            let next = current + d.to_cd();
+            if let Ok(ndist) = dist.try_get_mut_p(next) {
+                if *ndist != UNREACHABLE || !can_move(current, d) {
+                    continue;
+                }
+                *ndist = cdist + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+    dist
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn map_dist(map: &[&str], sources: &[Coord]) -> Array2<u32> {
+        let is_floor = |cd: Coord| {
+            map.get(cd.y.0 as usize)
+                .and_then(|line| line.as_bytes().get(cd.x.0 as usize))
+                .is_some_and(|&c| c != b'#')
+        };
+        let (w, h) = (map[0].len() as i32, map.len() as i32);
+        distance_map(X(w), Y(h), sources.iter().cloned(), |cd, d| {
+            is_floor(cd + d.to_cd())
+        })
+    }
+    #[test]
+    fn multiple_sources() {
+        let map = [".....", ".###.", "....#"];
+        let dist = map_dist(&map, &[Coord::new(0, 0), Coord::new(4, 0)]);
+        assert_eq!(*dist.get_p(Coord::new(2, 0)), 2);
+        assert_eq!(*dist.get_p(Coord::new(4, 1)), 1);
+        assert_eq!(*dist.get_p(Coord::new(2, 2)), 3);
+        assert_eq!(*dist.get_p(Coord::new(4, 2)), UNREACHABLE);
+        assert_eq!(*dist.get_p(Coord::new(1, 1)), UNREACHABLE);
+    }
+}
+
+#[cfg(feature = "bench")]
+mod bench {
+    use super::*;
+    use test::Bencher;
+    #[bench]
+    fn distance_map_open_field(b: &mut Bencher) {
+        let sources = [Coord::new(0, 0), Coord::new(79, 23)];
+        b.iter(|| distance_map(X(80), Y(24), sources.iter().cloned(), |_, _| true));
+    }
+}
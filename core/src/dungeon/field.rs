@@ -1,10 +1,11 @@
 //! General field representation
-use super::{X, Y};
+use super::{Rect, X, Y};
 use crate::tile::{Drawable, Tile};
 use num_traits::ToPrimitive;
 use rect_iter::{Get2D, GetMut2D, IndexError, RectRange};
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::Arc;
 use tuple_map::TupleMap2;
 
 /// Generic representation of Cell
@@ -82,6 +83,24 @@ impl<S> Cell<S> {
         self.attr.contains(CellAttr::IS_LOCKED)
     }
     #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.attr.contains(CellAttr::IS_CLOSED)
+    }
+    /// open the closed door and returns if it was closed or not
+    #[inline]
+    pub fn open(&mut self) -> bool {
+        let res = self.is_closed();
+        self.attr.remove(CellAttr::IS_CLOSED);
+        res
+    }
+    /// close the door and returns if it was open or not
+    #[inline]
+    pub fn close(&mut self) -> bool {
+        let res = !self.is_closed();
+        self.attr.insert(CellAttr::IS_CLOSED);
+        res
+    }
+    #[inline]
     pub fn unlock(&mut self) {
         self.attr.remove(CellAttr::IS_LOCKED | CellAttr::IS_HIDDEN);
         self.visible(true)
@@ -116,23 +135,27 @@ bitflags! {
         /// In many rogue like, draw status can be changed by the cell has been drawn or not.
         /// So to record the cell has been drawn or not is very important.
         const HAS_DRAWN  = 0b00_001_000;
-        /// the cell is locked
+        /// the cell is locked, i.e., a hidden door which the player needs to 's'
         const IS_LOCKED  = 0b00_010_000;
         /// the cell is in dark room
         const IS_DARK    = 0b00_100_000;
+        /// the door is closed and blocks movement until opened
+        const IS_CLOSED  = 0b01_000_000;
     }
 }
 
-/// generic representation of Field
+/// generic representation of Field.
+/// Cells are shared between clones and copied on the first write, so that cloning
+/// floors(e.g., for `RunTime::clone_deterministic`) is cheap.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Field<S> {
-    inner: Vec<Cell<S>>,
+    inner: Arc<Vec<Cell<S>>>,
     width: X,
     height: Y,
 }
 
 impl<S> Field<S> {
-    pub fn size(&self) -> RectRange<i32> {
+    pub fn size(&self) -> Rect {
         RectRange::zero_start(self.width.0, self.height.0)
             .expect("[Field::size] Invalid dungeon size")
     }
@@ -142,7 +165,7 @@ impl<S> Field<S> {
     pub fn height(&self) -> Y {
         self.height
     }
-    pub fn size_ytrimed(&self) -> Option<RectRange<i32>> {
+    pub fn size_ytrimed(&self) -> Option<Rect> {
         RectRange::from_corners((0, 1), (self.width.0, self.height.0 - 1))
     }
 }
@@ -151,7 +174,7 @@ impl<S: Clone> Field<S> {
     pub fn new(width: X, height: Y, init: Cell<S>) -> Self {
         let (w, h) = (width.0 as usize, height.0 as usize);
         Field {
-            inner: vec![init; w * h],
+            inner: Arc::new(vec![init; w * h]),
             width,
             height,
         }
@@ -175,7 +198,7 @@ impl<S> Get2D for Field<S> {
     }
 }
 
-impl<S> GetMut2D for Field<S> {
+impl<S: Clone> GetMut2D for Field<S> {
     fn try_get_mut_xy<T: ToPrimitive>(
         &mut self,
         x: T,
@@ -191,7 +214,7 @@ impl<S> GetMut2D for Field<S> {
             return Err(IndexError::Y(y as i64));
         }
         let id = y * self.width.0 as usize + x;
-        Ok(&mut self.inner[id])
+        Ok(&mut Arc::make_mut(&mut self.inner)[id])
     }
 }
 
@@ -207,3 +230,18 @@ impl<S: Drawable> fmt::Display for Field<S> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn copy_on_write() {
+        let field = Field::new(X(4), Y(3), Cell::with_default_attr(0u8));
+        let mut cloned = field.clone();
+        assert!(Arc::ptr_eq(&field.inner, &cloned.inner));
+        cloned.get_mut_xy(1, 2).surface = 1;
+        assert!(!Arc::ptr_eq(&field.inner, &cloned.inner));
+        assert_eq!(field.get_xy(1, 2).surface, 0);
+        assert_eq!(cloned.get_xy(1, 2).surface, 1);
+    }
+}
@@ -0,0 +1,123 @@
+//! field of view
+use super::Coord;
+
+/// multipliers to transform the coordinates of the first octant into others
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// Recursive shadowcasting.
+/// Calls `mark` for every cell which is visible from `origin` within `radius`,
+/// regarding cells where `is_opaque` returns true as obstacles.
+/// Obstacles themselves are marked visible, so that walls are drawn.
+/// See http://www.roguebasin.com/index.php?title=FOV_using_recursive_shadowcasting
+pub fn shadowcasting<O, M>(origin: Coord, radius: u32, is_opaque: O, mut mark: M)
+where
+    O: Fn(Coord) -> bool,
+    M: FnMut(Coord),
+{
+    mark(origin);
+    let mut caster = Caster {
+        origin,
+        radius: radius as i32,
+        is_opaque: &is_opaque,
+        mark: &mut mark,
+    };
+    for &octant in OCTANTS.iter() {
+        caster.cast(1, 1.0, 0.0, octant);
+    }
+}
+
+struct Caster<'a> {
+    origin: Coord,
+    radius: i32,
+    is_opaque: &'a dyn Fn(Coord) -> bool,
+    mark: &'a mut dyn FnMut(Coord),
+}
+
+impl<'a> Caster<'a> {
+    fn cast(&mut self, row: i32, mut start: f64, end: f64, (xx, xy, yx, yy): (i32, i32, i32, i32)) {
+        if start < end {
+            return;
+        }
+        let mut new_start = 0.0;
+        for j in row..=self.radius {
+            let dy = -j;
+            let mut blocked = false;
+            for dx in -j..=0 {
+                let l_slope = (f64::from(dx) - 0.5) / (f64::from(dy) + 0.5);
+                let r_slope = (f64::from(dx) + 0.5) / (f64::from(dy) - 0.5);
+                if start < r_slope {
+                    continue;
+                }
+                if end > l_slope {
+                    break;
+                }
+                let cd = self.origin + Coord::new(dx * xx + dy * xy, dx * yx + dy * yy);
+                if dx * dx + dy * dy <= self.radius * self.radius {
+                    (self.mark)(cd);
+                }
+                let is_opaque = (self.is_opaque)(cd);
+                if blocked {
+                    if is_opaque {
+                        new_start = r_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if is_opaque && j < self.radius {
+                    blocked = true;
+                    self.cast(j + 1, start, l_slope, (xx, xy, yx, yy));
+                    new_start = r_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    fn fov(map: &[&str], origin: Coord, radius: u32) -> HashSet<Coord> {
+        let is_opaque = |cd: Coord| {
+            map.get(cd.y.0 as usize)
+                .and_then(|line| line.as_bytes().get(cd.x.0 as usize))
+                .is_none_or(|&c| c == b'#')
+        };
+        let mut res = HashSet::new();
+        shadowcasting(origin, radius, is_opaque, |cd| {
+            res.insert(cd);
+        });
+        res
+    }
+    #[test]
+    fn open_field() {
+        let map = [".........", ".........", ".........", "........."];
+        let visible = fov(&map, Coord::new(4, 1), 2);
+        assert!(visible.contains(&Coord::new(4, 3)));
+        assert!(visible.contains(&Coord::new(6, 1)));
+        assert!(!visible.contains(&Coord::new(7, 1)));
+        assert!(!visible.contains(&Coord::new(6, 3)));
+    }
+    #[test]
+    fn wall_blocks_sight() {
+        let map = ["......", "...#..", "......"];
+        let visible = fov(&map, Coord::new(1, 1), 10);
+        assert!(visible.contains(&Coord::new(3, 1)));
+        assert!(!visible.contains(&Coord::new(4, 1)));
+        assert!(!visible.contains(&Coord::new(5, 1)));
+        assert!(visible.contains(&Coord::new(3, 0)));
+        assert!(visible.contains(&Coord::new(3, 2)));
+    }
+}
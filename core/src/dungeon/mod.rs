@@ -1,17 +1,28 @@
 //! module for making and managing dungeon
-mod coord;
+mod dist;
 mod field;
+mod fov;
 mod rogue;
 
-pub use self::coord::{Coord, Direction, Positioned, X, Y};
+pub use self::dist::{distance_map, UNREACHABLE};
 pub use self::field::{Cell, CellAttr, Field};
+pub use self::fov::shadowcasting;
 use crate::character::{player::Status as PlayerStatus, EnemyHandler};
+pub use crate::geometry::{line, Coord, Direction, Line, Positioned, Rect, X, Y};
 use crate::item::{ItemHandler, ItemToken};
-use crate::{error::*, tile::Tile, GameInfo, GameMsg, GlobalConfig};
-use anyhow::Context;
+use crate::{
+    error::*,
+    rng::{RngHandle, RngState},
+    snapshot::DeepCloner,
+    tile::Tile,
+    validate::Validator,
+    GameInfo, GameMsg, GlobalConfig,
+};
+use anyhow::{bail, Context};
 use ndarray::Array2;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
+use std::fmt;
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(tag = "style")]
@@ -34,29 +45,91 @@ impl Default for DungeonStyle {
 }
 
 impl DungeonStyle {
+    /// `width` and `height` are the screen size
+    pub(crate) fn validate(&self, v: &mut Validator, width: X, height: Y) {
+        if let DungeonStyle::Rogue(config) = self {
+            config.validate(v, width, height);
+        }
+    }
+    /// returns the generator for this style
+    pub fn generator(&self) -> GameResult<&dyn DungeonGenerator> {
+        match self {
+            DungeonStyle::Rogue(config) => Ok(config),
+            DungeonStyle::NetHack => bail!(ErrorKind::Unimplemented("NetHack style dungeon")),
+            DungeonStyle::Cataclysm => bail!(ErrorKind::Unimplemented("Cataclysm style dungeon")),
+            DungeonStyle::Custom => bail!(ErrorKind::Unimplemented("Custom style dungeon")),
+        }
+    }
     pub fn build(
-        self,
+        &self,
         config_global: &GlobalConfig,
         item_handle: &mut ItemHandler,
         enemies: &mut EnemyHandler,
         game_info: &GameInfo,
         seed: u128,
     ) -> GameResult<Box<dyn Dungeon>> {
-        match self {
-            DungeonStyle::Rogue(config) => {
-                let dungeon = rogue::Dungeon::new(
-                    config,
-                    config_global,
-                    game_info,
-                    item_handle,
-                    enemies,
-                    seed,
-                )
-                .context("DungeonStyle::build")?;
-                Ok(Box::new(dungeon))
-            }
-            _ => unimplemented!(),
-        }
+        self.generator()
+            .and_then(|gen| gen.generate(config_global, item_handle, enemies, game_info, seed))
+            .context("DungeonStyle::build")
+    }
+    /// restore the dungeon saved by `Dungeon::save`
+    pub fn restore(&self, state: serde_json::Value) -> GameResult<Box<dyn Dungeon>> {
+        self.generator()
+            .and_then(|gen| gen.restore(state))
+            .context("DungeonStyle::restore")
+    }
+}
+
+/// Generator of a dungeon.
+/// Implement this to use other kinds of dungeon(e.g., caves made by cellular automata)
+/// with `GameConfig::build_with`.
+pub trait DungeonGenerator {
+    fn generate(
+        &self,
+        config_global: &GlobalConfig,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        game_info: &GameInfo,
+        seed: u128,
+    ) -> GameResult<Box<dyn Dungeon>>;
+    /// restore the dungeon saved by `Dungeon::save`, whose random number generator
+    /// is restored later from `RngStates`
+    fn restore(&self, _state: serde_json::Value) -> GameResult<Box<dyn Dungeon>> {
+        bail!(ErrorKind::Unimplemented("Restoring this dungeon"))
+    }
+}
+
+/// kinds of traps
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub enum TrapKind {
+    /// makes the player fall asleep
+    Sleep,
+    /// shoots an arrow at the player
+    Arrow,
+    /// shoots a poisoned dart, which may reduce strength
+    Dart,
+    /// rusts the player's armor
+    Rust,
+}
+
+impl TrapKind {
+    pub const ALL: [TrapKind; 4] = [
+        TrapKind::Sleep,
+        TrapKind::Arrow,
+        TrapKind::Dart,
+        TrapKind::Rust,
+    ];
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TrapKind::Sleep => "a sleeping gas trap",
+            TrapKind::Arrow => "an arrow trap",
+            TrapKind::Dart => "a poison dart trap",
+            TrapKind::Rust => "a rust trap",
+        };
+        f.write_str(name)
     }
 }
 
@@ -67,6 +140,14 @@ pub enum MoveResult {
     Reach,
 }
 
+/// if a drawn cell is what the player sees now, or what the player remembers
+/// from the last time it was seen
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum MapLayer {
+    Visible,
+    Remembered,
+}
+
 pub trait Dungeon {
     fn is_downstair(&self, path: &DungeonPath) -> bool;
     fn level(&self) -> u32;
@@ -76,6 +157,15 @@ pub trait Dungeon {
         item: &mut ItemHandler,
         enemies: &mut EnemyHandler,
     ) -> GameResult<()>;
+    /// go up to the upper level from the stair at `path`, and returns where the player appears
+    /// Returns None if the player can't go up.
+    fn upper_level(
+        &mut self,
+        path: &DungeonPath,
+        game_info: &GameInfo,
+        item: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+    ) -> GameResult<Option<DungeonPath>>;
     fn can_move_player(&self, path: &DungeonPath, direction: Direction) -> Option<DungeonPath>;
     fn move_player(
         &mut self,
@@ -84,7 +174,44 @@ pub trait Dungeon {
         enemies: &mut EnemyHandler,
     ) -> GameResult<DungeonPath>;
     fn draw_enemy(&self, player: &DungeonPath, enemy: &DungeonPath) -> bool;
+    /// if `to` can be seen from `from`, by the same rules as the player's sight
+    fn line_of_sight(&self, from: &DungeonPath, to: &DungeonPath) -> bool;
+    /// all cells which can be seen from `from`, by the same rules as `line_of_sight`
+    fn visible_cells(&self, from: &DungeonPath) -> Box<dyn Iterator<Item = Coord> + '_>;
+    /// the number of the player's steps from the nearest cell of `sources` for each cell
+    /// in the current level, where unreachable cells are `UNREACHABLE`
+    fn distance_map(&self, sources: &[DungeonPath]) -> Array2<u32>;
+    /// returns the cells a projectile passes from `from` to `to`, if `to` is within `range`
+    /// in a straight line (including diagonal ones) and nothing blocks the way
+    fn line_of_fire(
+        &self,
+        from: &DungeonPath,
+        to: &DungeonPath,
+        range: u32,
+    ) -> Option<Vec<DungeonPath>>;
+    /// returns the first step of the shortest way from `from` to `to`,
+    /// through cells the player knows
+    fn way_to(&self, from: &DungeonPath, to: Coord) -> Option<Direction>;
+    /// returns the first step toward the nearest cell the player hasn't explored
+    fn way_to_unexplored(&self, from: &DungeonPath) -> Option<Direction>;
     fn search(&mut self, path: &DungeonPath) -> GameResult<Vec<GameMsg>>;
+    /// place a wandering enemy somewhere the player can't see, and returns where it appears
+    fn spawn_wanderer(
+        &mut self,
+        player: &DungeonPath,
+        enemies: &mut EnemyHandler,
+    ) -> Option<DungeonPath>;
+    /// reveal the trap on the cell if any, and returns its kind
+    fn spring_trap(&mut self, path: &DungeonPath) -> Option<TrapKind>;
+    /// open the closed door next to the cell, and returns if it's opened or not
+    fn open_door(&mut self, path: &DungeonPath, direction: Direction) -> bool;
+    /// close the door next to the cell, and returns if it's closed or not
+    fn close_door(
+        &mut self,
+        path: &DungeonPath,
+        direction: Direction,
+        enemies: &EnemyHandler,
+    ) -> bool;
     fn select_cell(&mut self, is_character: bool) -> Option<DungeonPath>;
     fn enter_room(&mut self, path: &DungeonPath, enemies: &mut EnemyHandler) -> GameResult<()>;
     fn draw(&self, drawer: &mut dyn FnMut(Positioned<Tile>) -> GameResult<()>) -> GameResult<()>;
@@ -92,14 +219,40 @@ pub trait Dungeon {
     fn path_to_cd(&self, path: &DungeonPath) -> Coord;
     fn get_item(&self, path: &DungeonPath) -> Option<&ItemToken>;
     fn remove_item(&mut self, path: &DungeonPath) -> Option<ItemToken>;
+    /// put an item on the cell or a cell next to it, and returns where the item is put
+    /// If there's no space, the item is returned back.
+    fn put_item(&mut self, path: &DungeonPath, item: ItemToken) -> Result<DungeonPath, ItemToken>;
+    /// if the cell is in a shop, where the player can buy and sell items
+    fn is_shop(&self, path: &DungeonPath) -> bool;
+    /// returns the place of gold in the same room as path, if any
+    fn gold_in_room(&self, path: &DungeonPath) -> Option<DungeonPath>;
     fn tile(&mut self, path: &DungeonPath) -> Option<Tile>;
     fn get_history(&self, state: &PlayerStatus) -> Option<Array2<bool>>;
+    /// the number of cells the player has walked on in the current level
+    fn visited_cells(&self) -> u32;
+    fn rng(&mut self) -> &mut RngHandle;
+    fn rng_state(&self) -> RngState;
+    /// deep copy of the dungeon, in which items and enemies are copied by `cloner`
+    fn deep_clone(&self, cloner: &mut DeepCloner) -> Box<dyn Dungeon>;
+    /// save the state of the dungeon except the random number generator,
+    /// which can be restored by `DungeonGenerator::restore`
+    fn save(&self) -> GameResult<serde_json::Value> {
+        bail!(ErrorKind::Unimplemented("Saving this dungeon"))
+    }
     fn move_enemy(
         &mut self,
         path: &DungeonPath,
         dist: &DungeonPath,
         skip: &dyn Fn(&DungeonPath) -> bool,
     ) -> MoveResult;
+    /// same as `move_enemy`, but searches the path by A* and
+    /// regards cells where `skip` returns true as obstacles
+    fn move_enemy_astar(
+        &mut self,
+        path: &DungeonPath,
+        dist: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult;
     fn move_enemy_randomly(
         &mut self,
         enemy_pos: &DungeonPath,
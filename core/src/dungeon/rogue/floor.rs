@@ -1,31 +1,57 @@
 //! rogue floor
+use super::static_map::{Layout, Markers, StaticFloor};
 use super::{passages, rooms, Address, Config, Room, Surface};
-use crate::dungeon::{Cell, CellAttr, Coord, Direction, Field, Positioned, X, Y};
+use crate::dungeon::{
+    distance_map, line, shadowcasting, Cell, CellAttr, Coord, Direction, Field, Positioned,
+    TrapKind, X, Y,
+};
 use crate::enemies::EnemyHandler;
-use crate::item::{ItemHandler, ItemToken};
-use crate::{error::*, fenwick::FenwickSet, rng::RngHandle, GameMsg};
+use crate::item::{ItemAttr, ItemHandler, ItemKind, ItemToken};
+use crate::rng::{RngHandle, SliceRandom};
+use crate::snapshot::DeepCloner;
+use crate::{error::*, fenwick::FenwickSet, GameMsg};
 use anyhow::{bail, Context};
 use enum_iterator::IntoEnumIterator;
 use ndarray::Array2;
 use rect_iter::{Get2D, GetMut2D};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::{self, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::iter;
+use std::rc::Rc;
 
 /// representation of 'floor'
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Floor {
     /// rooms
     pub rooms: Vec<Room>,
     /// Coordinates of doors
+    #[serde(with = "crate::snapshot::sorted_set")]
     pub doors: HashSet<Coord>,
     /// field (level map)
     pub field: Field<Surface>,
     /// ids of rooms which are not empty
     pub non_empty_rooms: FenwickSet,
     /// items
+    #[serde(with = "crate::snapshot::sorted_map")]
     pub items: HashMap<Coord, ItemToken>,
+    /// cells the player can see now, if the field of view is computed by shadowcasting
+    #[serde(with = "crate::snapshot::sorted_set_opt")]
+    pub fov: Option<HashSet<Coord>>,
+    /// traps, which are hidden until found
+    #[serde(with = "crate::snapshot::sorted_map")]
+    pub traps: HashMap<Coord, TrapKind>,
+    /// the number of cells the player has walked on
+    pub visited: u32,
 }
 
 impl Floor {
+    pub(crate) fn deep_clone(&self, cloner: &mut DeepCloner) -> Floor {
+        let mut res = self.clone();
+        for item in res.items.values_mut() {
+            *item = cloner.item(item);
+        }
+        res
+    }
     fn new(rooms: Vec<Room>, doors: HashSet<Coord>, field: Field<Surface>) -> Self {
         let non_empty_rooms =
             rooms
@@ -42,6 +68,9 @@ impl Floor {
             field,
             non_empty_rooms,
             items: Default::default(),
+            fov: None,
+            traps: Default::default(),
+            visited: 0,
         }
     }
 
@@ -102,6 +131,61 @@ impl Floor {
             })?;
         Ok(Floor::new(rooms, doors, field))
     }
+    /// make a floor from a hand-authored map, without objects
+    pub(super) fn from_static(
+        map: &StaticFloor,
+        width: X,
+        height: Y,
+    ) -> GameResult<(Self, Markers)> {
+        let Layout {
+            rooms,
+            doors,
+            field,
+            markers,
+        } = map.layout(width, height).context("Floor::from_static")?;
+        Ok((Floor::new(rooms, doors, field), markers))
+    }
+    /// place objects marked in a hand-authored map
+    pub(super) fn place_markers(
+        &mut self,
+        markers: Markers,
+        level: u32,
+        lev_add: u32,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        rng: &mut RngHandle,
+    ) -> GameResult<()> {
+        for cd in markers.gold {
+            if let Some(gold) = item_handle.setup_gold(level) {
+                if let Some(id) = self.cd_to_room_id(cd) {
+                    self.rooms[id].has_gold = true;
+                }
+                self.set_obj(cd, false);
+                self.items.insert(cd, gold);
+            }
+        }
+        for cd in markers.items {
+            self.set_obj(cd, false);
+            self.items.insert(cd, item_handle.gen_random_item(level));
+        }
+        if !enemies.is_no_enemy() {
+            let (min, max) = (level.saturating_sub(4), level + 6);
+            for cd in markers.enemies {
+                if let Some(enemy) = enemies.gen_enemy_always(level, min..max, i64::from(lev_add)) {
+                    enemies.place(Address::new(level, cd).into(), enemy);
+                    self.set_obj(cd, true);
+                }
+            }
+        }
+        match markers.stair {
+            Some(cd) => {
+                self.field.get_mut_p(cd).surface = Surface::Stair;
+                self.set_obj(cd, false);
+                Ok(())
+            }
+            None => self.setup_stair(rng).context("Floor::place_markers"),
+        }
+    }
     /// place enemies
     pub fn place_enemies(
         &mut self,
@@ -116,18 +200,130 @@ impl Floor {
         }
         let min = level.checked_sub(4).unwrap_or(0);
         let max = level + 6;
-        for (cd, room) in self
-            .rooms
-            .iter_mut()
-            .filter_map(|room| Some((room.select_cell(rng, true)?, room)))
-        {
-            if let Some(enemy) = enemies.gen_enemy(min..max, i64::from(lev_add), room.has_gold) {
+        for room in self.rooms.iter_mut() {
+            let cd = match room.select_cell(rng, true) {
+                Some(cd) => cd,
+                None => continue,
+            };
+            if let Some(enemy) =
+                enemies.gen_enemy(level, min..max, i64::from(lev_add), room.has_gold)
+            {
+                let followers = enemies.group_size(&enemy);
                 let place = Address::new(level, cd).into();
-                enemies.place(place, enemy);
+                enemies.place(place, Rc::clone(&enemy));
                 room.fill_cell(cd, true);
+                // pack enemies are placed next to each other
+                let mut members = vec![cd];
+                for _ in 0..followers {
+                    let cd = match room.select_adjacent_cell(&members, rng) {
+                        Some(cd) => cd,
+                        None => break,
+                    };
+                    let follower = match enemies.gen_follower(&enemy, i64::from(lev_add)) {
+                        Some(follower) => follower,
+                        None => break,
+                    };
+                    enemies.place(Address::new(level, cd).into(), follower);
+                    room.fill_cell(cd, true);
+                    members.push(cd);
+                }
             }
         }
     }
+    /// place the boss of this level, if it hasn't appeared yet
+    pub fn place_boss(
+        &mut self,
+        level: u32,
+        lev_add: u32,
+        enemies: &mut EnemyHandler,
+        rng: &mut RngHandle,
+    ) {
+        let boss = match enemies.gen_boss(level, i64::from(lev_add)) {
+            Some(boss) => boss,
+            None => return,
+        };
+        if let Some(cd) = self.select_cell(rng, true) {
+            enemies.place(Address::new(level, cd).into(), boss);
+            self.set_obj(cd, true);
+        }
+    }
+    /// make a treasure room, which is packed with items and guarded by enemies
+    /// from the next level, and returns the id of the room
+    pub fn setup_treasure_room(
+        &mut self,
+        level: u32,
+        lev_add: u32,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        rng: &mut RngHandle,
+    ) -> Option<usize> {
+        const MIN_TREASURES: usize = 2;
+        const MAX_TREASURES: usize = 10;
+        let candidates: Vec<_> = (0..self.rooms.len())
+            .filter(|&i| self.rooms[i].is_normal())
+            .collect();
+        let room_id = *candidates.choose(rng)?;
+        let room = &mut self.rooms[room_id];
+        let num_items = rng.range(MIN_TREASURES..=MAX_TREASURES);
+        for _ in 0..num_items {
+            let cd = match room.select_cell(rng, false) {
+                Some(cd) => cd,
+                None => break,
+            };
+            room.fill_cell(cd, false);
+            self.items.insert(cd, item_handle.gen_random_item(level));
+        }
+        if enemies.is_no_enemy() {
+            return Some(room_id);
+        }
+        let (min, max) = ((level + 1).saturating_sub(4), level + 7);
+        for _ in 0..num_items + 2 {
+            let cd = match room.select_cell(rng, true) {
+                Some(cd) => cd,
+                None => break,
+            };
+            if let Some(enemy) = enemies.gen_enemy_always(level, min..max, i64::from(lev_add)) {
+                enemies.place(Address::new(level, cd).into(), enemy);
+                room.fill_cell(cd, true);
+            }
+        }
+        Some(room_id)
+    }
+    /// make a shop, where items are sold for gold and the player can sell items,
+    /// and returns the id of the room
+    pub fn setup_shop(
+        &mut self,
+        level: u32,
+        item_handle: &mut ItemHandler,
+        rng: &mut RngHandle,
+    ) -> Option<usize> {
+        const MIN_GOODS: usize = 3;
+        const MAX_GOODS: usize = 8;
+        let candidates: Vec<_> = (0..self.rooms.len())
+            .filter(|&i| self.rooms[i].is_normal())
+            .collect();
+        let room_id = *candidates.choose(rng)?;
+        let room = &mut self.rooms[room_id];
+        room.is_shop = true;
+        let num_items = rng.range(MIN_GOODS..=MAX_GOODS);
+        for _ in 0..num_items {
+            let cd = match room.select_cell(rng, false) {
+                Some(cd) => cd,
+                None => break,
+            };
+            room.fill_cell(cd, false);
+            let mut item = item_handle.gen_random_item(level);
+            item.get_mut().attr.or(ItemAttr::FOR_SALE);
+            self.items.insert(cd, item);
+        }
+        Some(room_id)
+    }
+    /// if `cd` is in a shop or not
+    pub(super) fn is_shop(&self, cd: Coord) -> bool {
+        self.rooms
+            .iter()
+            .any(|room| room.is_shop && room.range().is_some_and(|range| range.contains(cd)))
+    }
     /// setup items for a floor
     pub fn setup_items(
         &mut self,
@@ -152,6 +348,53 @@ impl Floor {
         }
     }
 
+    /// put an item on the cell or a cell next to it
+    pub(super) fn put_item(&mut self, cd: Coord, item: ItemToken) -> Result<Coord, ItemToken> {
+        let can_put = |cd: Coord| {
+            let on_floor = self.field.try_get_p(cd).is_ok_and(|cell| {
+                cell.surface == Surface::Floor || cell.surface == Surface::Passage
+            });
+            on_floor && !self.items.contains_key(&cd)
+        };
+        let cd = match iter::once(cd)
+            .chain(Direction::into_enum_iter().map(|d| cd + d.to_cd()))
+            .find(|&cd| can_put(cd))
+        {
+            Some(cd) => cd,
+            None => return Err(item),
+        };
+        self.set_obj(cd, false);
+        self.items.insert(cd, item);
+        Ok(cd)
+    }
+
+    /// place hidden traps, as many as the original rogue does
+    pub fn setup_traps(&mut self, level: u32, max_traps: u32, rng: &mut RngHandle) {
+        if rng.range(0..10) >= level {
+            return;
+        }
+        let num = cmp::min(rng.range(0..=level / 4) + 1, max_traps);
+        for _ in 0..num {
+            let cd = match self.select_cell(rng, false) {
+                Some(cd) => cd,
+                None => break,
+            };
+            let kind = *TrapKind::ALL.choose(rng).unwrap();
+            self.set_obj(cd, false);
+            self.traps.insert(cd, kind);
+        }
+    }
+
+    /// reveal the trap on the cell if any
+    pub(super) fn spring_trap(&mut self, cd: Coord) -> Option<TrapKind> {
+        let kind = *self.traps.get(&cd)?;
+        if let Ok(cell) = self.field.try_get_mut_p(cd) {
+            cell.surface = Surface::Trap;
+            cell.approached();
+        }
+        Some(kind)
+    }
+
     /// set stair
     pub fn setup_stair(&mut self, rng: &mut RngHandle) -> GameResult<()> {
         let cd = self
@@ -169,7 +412,7 @@ impl Floor {
     fn can_move_impl(&self, cd: Coord, direction: Direction, is_enemy: bool) -> Option<bool> {
         let cell = |cd: Coord| self.field.try_get_p(cd).ok();
         let nxt = cell(cd + direction.to_cd())?;
-        let mut res = nxt.surface.can_walk();
+        let mut res = nxt.surface.can_walk() && !nxt.is_closed();
         if !is_enemy {
             res &= !nxt.is_hidden();
             res &= !nxt.is_locked();
@@ -191,6 +434,47 @@ impl Floor {
     pub(super) fn can_move_enemy(&self, cd: Coord, direction: Direction) -> bool {
         self.can_move_impl(cd, direction, true).unwrap_or(false)
     }
+    /// returns the cells from `from`(exclusive) to `to`(inclusive), if they are in a straight line
+    /// within `range` and an enemy can go through them
+    pub(super) fn line_of_fire(&self, from: Coord, to: Coord, range: u32) -> Option<Vec<Coord>> {
+        let diff = to - from;
+        let (dx, dy) = (diff.x.0, diff.y.0);
+        if dx != 0 && dy != 0 && dx.abs() != dy.abs() {
+            return None;
+        }
+        let len = from.move_dist(to);
+        if len == 0 || len as u32 > range {
+            return None;
+        }
+        let mut prev = from;
+        let mut cells = Vec::with_capacity(len as usize);
+        for cur in line(from, to) {
+            let d = Direction::from_cd(cur - prev)?;
+            if !self.can_move_enemy(prev, d) {
+                return None;
+            }
+            cells.push(cur);
+            prev = cur;
+        }
+        Some(cells)
+    }
+    /// open or close the door next to `cd`, and returns if the door's state changed
+    pub(super) fn set_door(&mut self, cd: Coord, direction: Direction, open: bool) -> bool {
+        let door = cd + direction.to_cd();
+        if direction == Direction::Stay || !self.doors.contains(&door) {
+            return false;
+        }
+        match self.field.try_get_mut_p(door) {
+            Ok(cell) if cell.surface == Surface::Door => {
+                if open {
+                    cell.open()
+                } else {
+                    cell.close()
+                }
+            }
+            _ => false,
+        }
+    }
     fn cd_to_room_id(&self, cd: Coord) -> Option<usize> {
         self.rooms
             .iter()
@@ -261,15 +545,19 @@ impl Floor {
     }
 
     /// player walks in the cell
+    /// If `fov_radius` is specified, the field of view is computed by shadowcasting.
     pub(super) fn player_in(
         &mut self,
         cd: Coord,
         init: bool,
+        fov_radius: Option<u32>,
         enemies: &mut EnemyHandler,
     ) -> GameResult<()> {
         debug!("[Floor::player_in] cd: {:?}", cd);
         if init || self.doors.contains(&cd) {
-            self.enters_room(cd).context("Floor::player_in")?;
+            if fov_radius.is_none() {
+                self.enters_room(cd).context("Floor::player_in")?;
+            }
             if let Some(room_id) = self.cd_to_room_id(cd) {
                 let room = &self.rooms[room_id];
                 enemies.activate_area(|p| {
@@ -278,11 +566,19 @@ impl Floor {
                 });
             }
         }
-        self.field
+        let cell = self
+            .field
             .try_get_mut_p(cd)
-            .context("Floor::player_in Cannot move")?
-            .visit();
+            .context("Floor::player_in Cannot move")?;
+        if !cell.is_visited() {
+            cell.visit();
+            self.visited += 1;
+        }
         self.set_obj(cd, true);
+        if let Some(radius) = fov_radius {
+            self.update_fov(cd, radius);
+            return Ok(());
+        }
         Direction::into_enum_iter().take(9).for_each(|d| {
             let cd = cd + d.to_cd();
             if let Ok(cell) = self.field.try_get_mut_p(cd) {
@@ -294,12 +590,107 @@ impl Floor {
         Ok(())
     }
 
+    /// recompute the field of view from the player's position
+    pub(super) fn update_fov(&mut self, player: Coord, radius: u32) {
+        for cd in self.fov.take().into_iter().flatten() {
+            if let Ok(cell) = self.field.try_get_mut_p(cd) {
+                cell.left();
+            }
+        }
+        let fov = self.shadowcast(player, radius);
+        for &cd in &fov {
+            if let Ok(cell) = self.field.try_get_mut_p(cd) {
+                cell.approached();
+            }
+        }
+        self.fov = Some(fov);
+    }
+
+    /// cells in sight from `from` within `radius`, where cells in dark rooms and passages
+    /// are seen only next to `from`
+    fn shadowcast(&self, from: Coord, radius: u32) -> HashSet<Coord> {
+        let mut fov = HashSet::new();
+        shadowcasting(
+            from,
+            radius,
+            |cd| self.is_opaque(cd),
+            |cd| {
+                fov.insert(cd);
+            },
+        );
+        fov.retain(|&cd| from.is_adjacent(cd) || self.is_lit(cd));
+        fov
+    }
+
+    /// if `to` can be seen from `from`, by the same rules as the player's sight.
+    /// If `fov_radius` is None, the rules of the original rogue are used.
+    pub(super) fn line_of_sight(&self, from: Coord, to: Coord, fov_radius: Option<u32>) -> bool {
+        if from.is_adjacent(to) {
+            return true;
+        }
+        match fov_radius {
+            Some(radius) => {
+                from.euc_dist_squared(to) <= (radius * radius) as i32
+                    && self.shadowcast(from, radius).contains(&to)
+            }
+            None => {
+                let is_lit = self
+                    .cd_to_room_id(from)
+                    .is_some_and(|id| !self.rooms[id].is_dark);
+                is_lit && self.in_same_room(from, to)
+            }
+        }
+    }
+
+    /// all cells which can be seen from `from` in the order of coordinates,
+    /// by the same rules as `line_of_sight`
+    pub(super) fn visible_cells(&self, from: Coord, fov_radius: Option<u32>) -> Vec<Coord> {
+        let mut cells: Vec<_> = match fov_radius {
+            Some(radius) => self.shadowcast(from, radius).into_iter().collect(),
+            None => {
+                let room = self
+                    .cd_to_room_id(from)
+                    .and_then(|id| self.rooms[id].range())
+                    .into_iter()
+                    .flat_map(|range| range.iter().map(Coord::from));
+                Direction::into_enum_iter()
+                    .map(|d| from + d.to_cd())
+                    .chain(room)
+                    .filter(|&cd| {
+                        self.field.try_get_p(cd).is_ok() && self.line_of_sight(from, cd, None)
+                    })
+                    .collect()
+            }
+        };
+        cells.sort();
+        cells.dedup();
+        cells
+    }
+
+    fn is_opaque(&self, cd: Coord) -> bool {
+        self.field.try_get_p(cd).map_or(true, |cell| {
+            !cell.surface.can_walk() || cell.is_closed() || cell.is_hidden()
+        })
+    }
+
+    /// cells in lit rooms can be seen from afar
+    fn is_lit(&self, cd: Coord) -> bool {
+        self.cd_to_room_id(cd).is_some_and(|id| {
+            let room = &self.rooms[id];
+            room.is_normal() && !room.is_dark
+        })
+    }
+
     /// player leaves the cell
     pub(super) fn player_out(&mut self, cd: Coord) -> GameResult<()> {
         if self.doors.contains(&cd) {
             self.leaves_room(cd).context("Floor::player_out")?;
         }
         self.remove_obj(cd, true);
+        // the player may leave an item which couldn't be picked up
+        if self.items.contains_key(&cd) {
+            self.set_obj(cd, false);
+        }
         Direction::into_enum_iter().take(9).for_each(|d| {
             let cd = cd + d.to_cd();
             if let Ok(cell) = self.field.try_get_mut_p(cd) {
@@ -353,12 +744,23 @@ impl Floor {
         config: &'a Config,
     ) -> impl 'a + Iterator<Item = GameMsg> {
         let probinc = 0; // TODO: it should be changed by player status
-        Direction::into_enum_iter().take(8).filter_map(move |d| {
+        Direction::NEIGHBORS.iter().filter_map(move |&d| {
             let cd = cd + d.to_cd();
+            let trap = self.traps.get(&cd).cloned();
             let cell = self.field.try_get_mut_p(cd).ok()?;
+            if let Some(kind) = trap {
+                if cell.surface != Surface::Trap
+                    && rng.does_happen(probinc + config.trap_find_rate_inv)
+                {
+                    cell.surface = Surface::Trap;
+                    cell.approached();
+                    return Some(GameMsg::TrapFound(kind));
+                }
+            }
             if cell.is_hidden() && rng.does_happen(probinc + config.passage_unlock_rate_inv) {
                 cell.unlock();
                 cell.surface = Surface::Passage;
+                return Some(GameMsg::SecretPassage);
             }
             if cell.is_locked() && rng.does_happen(probinc + config.door_unlock_rate_inv) {
                 cell.unlock();
@@ -392,27 +794,122 @@ impl Floor {
             .unwrap_or(true)
     }
 
-    pub(super) fn make_dist_map(&self, from: Coord, is_enemy: bool) -> Array2<u32> {
+    /// if the player at `player` can see `cd` or not
+    /// In a dark room, the player can see only adjacent cells.
+    pub(super) fn can_see(&self, player: Coord, cd: Coord) -> bool {
+        if let Some(fov) = &self.fov {
+            return fov.contains(&cd);
+        }
+        self.line_of_sight(player, cd, None)
+    }
+
+    pub(super) fn gold_in_room(&self, cd: Coord) -> Option<Coord> {
+        self.items
+            .iter()
+            .find(|(&item_cd, item)| item.kind == ItemKind::Gold && self.in_same_room(cd, item_cd))
+            .map(|(&item_cd, _)| item_cd)
+    }
+
+    /// find the shortest path from `from` to `to` for enemies by A* search,
+    /// regarding cells where `blocked` returns true as obstacles.
+    /// The returned path doesn't contain `from` but contains `to`.
+    pub(super) fn astar_path(
+        &self,
+        from: Coord,
+        to: Coord,
+        blocked: impl Fn(Coord) -> bool,
+    ) -> Option<Vec<Coord>> {
         let (w, h) = (self.field.width(), self.field.height());
-        let inf = u32::max_value();
-        let mut dist = Array2::from_elem([h.0 as usize, w.0 as usize], inf);
+        let heuristic = |cd: Coord| cmp::max((cd.x - to.x).0.abs(), (cd.y - to.y).0.abs()) as u32;
+        let mut cost = Array2::from_elem([h.0 as usize, w.0 as usize], u32::MAX);
+        let mut parent = Array2::<Option<Coord>>::from_elem([h.0 as usize, w.0 as usize], None);
+        let mut queue = BinaryHeap::new();
+        *cost.get_mut_p(from) = 0;
+        queue.push(Reverse((heuristic(from), 0, from.x.0, from.y.0)));
+        while let Some(Reverse((_, current_cost, x, y))) = queue.pop() {
+            let current = Coord::new(x, y);
+            if current_cost > *cost.get_p(current) {
+                continue;
+            }
+            for &d in Direction::NEIGHBORS.iter() {
+                if !self.can_move_enemy(current, d) {
+                    continue;
+                }
+                let next = current + d.to_cd();
+                if next == to {
+                    let mut path = vec![to];
+                    let mut cd = current;
+                    while cd != from {
+                        path.push(cd);
+                        cd = parent.get_p(cd).expect("Logic Error in Floor::astar_path");
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                if blocked(next) {
+                    continue;
+                }
+                let next_cost = current_cost + 1;
+                if next_cost < *cost.get_p(next) {
+                    *cost.get_mut_p(next) = next_cost;
+                    *parent.get_mut_p(next) = Some(current);
+                    let f = next_cost + heuristic(next);
+                    queue.push(Reverse((f, next_cost, next.x.0, next.y.0)));
+                }
+            }
+        }
+        None
+    }
+
+    /// returns the first step of the shortest way to a cell where `is_goal` returns true,
+    /// through cells the player knows
+    pub(super) fn way_to(&self, from: Coord, is_goal: impl Fn(Coord) -> bool) -> Option<Direction> {
+        let (w, h) = (self.field.width(), self.field.height());
+        let mut first_step =
+            Array2::<Option<Direction>>::from_elem([h.0 as usize, w.0 as usize], None);
         let mut queue = VecDeque::new();
-        *dist.get_mut_p(from) = 0;
         queue.push_back(from);
         while let Some(current) = queue.pop_front() {
-            for d in Direction::into_enum_iter().take(8) {
-                let next = current + d.to_cd();
-                let cdist = *dist.get_p(current);
-                if let Ok(ndist) = dist.try_get_mut_p(next) {
-                    if *ndist != inf || self.can_move_impl(current, d, is_enemy) != Some(true) {
-                        continue;
-                    }
-                    queue.push_back(next);
-                    *ndist = cdist + 1;
+            for &d in Direction::NEIGHBORS.iter() {
+                let next = match self.can_move_player(current, d) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                let is_known = self.field.get_p(next).is_obj_visible();
+                if next == from || !is_known || first_step.get_p(next).is_some() {
+                    continue;
+                }
+                let step = if current == from {
+                    d
+                } else {
+                    first_step
+                        .get_p(current)
+                        .expect("Logic Error in Floor::way_to")
+                };
+                if is_goal(next) {
+                    return Some(step);
                 }
+                *first_step.get_mut_p(next) = Some(step);
+                queue.push_back(next);
             }
         }
-        dist
+        None
+    }
+
+    pub(super) fn make_dist_map(&self, from: Coord, is_enemy: bool) -> Array2<u32> {
+        self.distance_map(Some(from), is_enemy)
+    }
+
+    /// distances from the nearest cell of `sources`, for the player or enemies
+    pub(super) fn distance_map(
+        &self,
+        sources: impl IntoIterator<Item = Coord>,
+        is_enemy: bool,
+    ) -> Array2<u32> {
+        let (w, h) = (self.field.width(), self.field.height());
+        distance_map(w, h, sources, |cd, d| {
+            self.can_move_impl(cd, d, is_enemy) == Some(true)
+        })
     }
 }
 
@@ -438,6 +935,10 @@ fn gen_attr(
                 && rng.does_happen(config.locked_door_rate_inv)
             {
                 attr |= CellAttr::IS_LOCKED;
+            } else if config.closed_door_rate_inv > 0
+                && rng.does_happen(config.closed_door_rate_inv)
+            {
+                attr |= CellAttr::IS_CLOSED;
             }
         }
         Surface::Floor => {
@@ -453,6 +954,7 @@ fn gen_attr(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rng::Parcent;
     use rect_iter::RectRange;
     #[test]
     #[ignore]
@@ -487,6 +989,283 @@ mod test {
         }
     }
     #[test]
+    fn closed_door() {
+        let mut config = Config::default();
+        config.closed_door_rate_inv = 1;
+        let mut rng = RngHandle::from_seed(1);
+        let mut floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+        let door = *floor
+            .doors
+            .iter()
+            .find(|&&cd| floor.field.get_p(cd).is_closed())
+            .expect("no closed door");
+        let (cd, d) = Direction::into_enum_iter()
+            .take(4)
+            .map(|d| (door - d.to_cd(), d))
+            .find(|&(cd, _)| {
+                floor
+                    .field
+                    .try_get_p(cd)
+                    .is_ok_and(|c| c.surface.can_walk() && !c.is_closed())
+            })
+            .unwrap();
+        assert_eq!(floor.can_move_player(cd, d), None);
+        assert!(!floor.can_move_enemy(cd, d));
+        assert!(floor.set_door(cd, d, true));
+        assert!(!floor.set_door(cd, d, true));
+        assert_eq!(floor.can_move_player(cd, d), Some(door));
+        assert!(floor.set_door(cd, d, false));
+        assert_eq!(floor.can_move_player(cd, d), None);
+    }
+    #[test]
+    fn dark_room() {
+        let mut config = Config::default();
+        config.dark_room_rate = Some(Parcent(100));
+        let mut rng = RngHandle::from_seed(1);
+        let floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+        let inner_cells = |room: &Room| -> Vec<Coord> {
+            let range = room.range().unwrap();
+            range
+                .iter()
+                .filter(|&cd| !range.is_edge(cd))
+                .map(Coord::from)
+                .collect()
+        };
+        let (corner, opposite) = floor
+            .rooms
+            .iter()
+            .filter(|room| room.is_normal())
+            .inspect(|room| assert!(room.is_dark))
+            .map(inner_cells)
+            .map(|cells| (cells[0], cells[cells.len() - 1]))
+            .find(|&(a, b)| !a.is_adjacent(b))
+            .expect("no large room");
+        assert!(floor.in_same_room(corner, opposite));
+        assert!(!floor.can_see(corner, opposite));
+        assert!(floor.can_see(corner, corner + Coord::new(1, 0)));
+        config.dark_room_rate = Some(Parcent(0));
+        let floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+        assert!(floor
+            .rooms
+            .iter()
+            .filter(|room| room.is_normal())
+            .all(|room| !room.is_dark));
+    }
+    #[test]
+    fn shadowcasting_fov() {
+        let mut config = Config::default();
+        let mut rng = RngHandle::from_seed(1);
+        for (rate, is_lit) in [(0, true), (100, false)] {
+            config.dark_room_rate = Some(Parcent(rate));
+            let mut floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+            let range = floor
+                .rooms
+                .iter()
+                .find(|room| room.is_normal())
+                .and_then(|room| room.range())
+                .unwrap()
+                .clone();
+            let inner: Vec<Coord> = range
+                .iter()
+                .filter(|&cd| !range.is_edge(cd))
+                .map(Coord::from)
+                .collect();
+            let (corner, opposite) = (inner[0], inner[inner.len() - 1]);
+            floor.update_fov(corner, 100);
+            let expected = is_lit || corner.is_adjacent(opposite);
+            assert_eq!(floor.can_see(corner, opposite), expected);
+            assert!(floor.can_see(corner, corner));
+            assert_eq!(floor.field.get_p(opposite).is_visible(), expected);
+        }
+    }
+    #[test]
+    fn line_of_sight() {
+        let mut config = Config::default();
+        let mut rng = RngHandle::from_seed(1);
+        for (rate, is_lit) in [(0, true), (100, false)] {
+            config.dark_room_rate = Some(Parcent(rate));
+            let floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+            let range = floor
+                .rooms
+                .iter()
+                .filter_map(|room| room.range())
+                .find(|range| range.xlen() > 4 && range.ylen() > 4)
+                .unwrap()
+                .clone();
+            let inner: Vec<Coord> = range
+                .iter()
+                .filter(|&cd| !range.is_edge(cd))
+                .map(Coord::from)
+                .collect();
+            let (corner, opposite) = (inner[0], inner[inner.len() - 1]);
+            let outside = Coord::new(range.get_x().end + 1, range.get_y().start);
+            for &radius in &[None, Some(100)] {
+                assert_eq!(floor.line_of_sight(corner, opposite, radius), is_lit);
+                assert!(floor.line_of_sight(corner, corner + Coord::new(1, 1), radius));
+                assert!(!floor.line_of_sight(corner, outside, radius));
+                let visible = floor.visible_cells(corner, radius);
+                for cd in inner.iter().chain(Some(&outside)) {
+                    let expected = floor.line_of_sight(corner, *cd, radius);
+                    assert_eq!(visible.binary_search(cd).is_ok(), expected);
+                }
+            }
+        }
+    }
+    #[test]
+    fn treasure_room() {
+        use crate::character::enemies::Config as EnemyConfig;
+        use crate::item::Config as ItemConfig;
+        let config = Config::default();
+        let mut rng = RngHandle::from_seed(1);
+        let mut floor = Floor::gen_floor(3, &config, X(80), Y(24), &mut rng).unwrap();
+        let mut items = ItemHandler::new(ItemConfig::default(), 1);
+        let mut enemies = EnemyConfig::default().build(1);
+        let id = floor
+            .setup_treasure_room(3, 0, &mut items, &mut enemies, &mut rng)
+            .expect("no treasure room");
+        let range = floor.rooms[id].range().unwrap();
+        let cells: Vec<Coord> = range.iter().map(Coord::from).collect();
+        let num_items = cells
+            .iter()
+            .filter(|cd| floor.items.contains_key(cd))
+            .count();
+        let num_enemies = cells
+            .iter()
+            .filter(|&&cd| enemies.get_enemy(&Address::new(3, cd).into()).is_some())
+            .count();
+        assert!(num_items >= 2);
+        assert!(num_enemies >= 2);
+    }
+    #[test]
+    fn enemy_groups() {
+        use crate::character::enemies::Config as EnemyConfig;
+        let config = Config::default();
+        let mut rng = RngHandle::from_seed(1);
+        let mut floor = Floor::gen_floor(3, &config, X(80), Y(24), &mut rng).unwrap();
+        let enemy_config: EnemyConfig = serde_json::from_str(
+            r#"{
+                "appear_rate_gold": 100,
+                "appear_rate_nogold": 100,
+                "spawn_table": [{"min_depth": 1, "max_depth": 5, "weights": {"kestrel": 1}}],
+                "groups": [{"name": "kestrel", "min": 3, "max": 3}]
+            }"#,
+        )
+        .unwrap();
+        let mut enemies = enemy_config.build(1);
+        floor.place_enemies(3, 0, &mut enemies, &mut rng);
+        for room in floor.rooms.iter().filter(|room| room.is_normal()) {
+            let kestrels: Vec<Coord> = room
+                .range()
+                .unwrap()
+                .iter()
+                .map(Coord::from)
+                .filter(|&cd| enemies.get_enemy(&Address::new(3, cd).into()).is_some())
+                .collect();
+            assert!(!kestrels.is_empty() && kestrels.len() <= 3);
+            assert!(kestrels
+                .iter()
+                .all(|&a| kestrels.len() == 1
+                    || kestrels.iter().any(|&b| a != b && a.is_adjacent(b))));
+        }
+    }
+    #[test]
+    fn shop() {
+        use crate::item::Config as ItemConfig;
+        let config = Config::default();
+        let mut rng = RngHandle::from_seed(1);
+        let mut floor = Floor::gen_floor(3, &config, X(80), Y(24), &mut rng).unwrap();
+        let mut items = ItemHandler::new(ItemConfig::default(), 1);
+        let id = floor.setup_shop(1, &mut items, &mut rng).expect("no shop");
+        let range = floor.rooms[id].range().unwrap().clone();
+        let goods: Vec<_> = range
+            .iter()
+            .filter_map(|cd| floor.items.get(&Coord::from(cd)))
+            .collect();
+        assert!(goods.len() >= 3);
+        assert!(goods
+            .iter()
+            .all(|item| item.attr.contains(ItemAttr::FOR_SALE)));
+        let inside = range.iter().map(Coord::from).next().unwrap();
+        assert!(floor.is_shop(inside));
+        assert!(!floor.is_shop(Coord::new(-1, -1)));
+    }
+    #[test]
+    fn way_to() {
+        let config = Config::default();
+        let mut rng = RngHandle::from_seed(1);
+        let mut floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+        let range = floor
+            .rooms
+            .iter()
+            .filter_map(|room| room.range())
+            .find(|range| range.len() > 9)
+            .unwrap()
+            .clone();
+        let inner: Vec<Coord> = range
+            .iter()
+            .filter(|&cd| !range.is_edge(cd))
+            .map(Coord::from)
+            .collect();
+        let (start, goal) = (inner[0], inner[inner.len() - 1]);
+        assert_eq!(floor.way_to(start, |cd| cd == goal), None);
+        range
+            .iter()
+            .for_each(|cd| floor.field.get_mut_p(cd).visible(true));
+        let d = floor.way_to(start, |cd| cd == goal).unwrap();
+        let next = start + d.to_cd();
+        assert!(next.euc_dist_squared(goal) < start.euc_dist_squared(goal));
+    }
+    /// a floor with only one lit room, whose floors are (1, 2)..=(6, 5)
+    fn one_room_floor() -> Floor {
+        let map = StaticFloor {
+            map: vec![
+                "--------".to_owned(),
+                "|......|".to_owned(),
+                "|......|".to_owned(),
+                "|......|".to_owned(),
+                "|......|".to_owned(),
+                "--------".to_owned(),
+            ],
+            is_dark: false,
+        };
+        Floor::from_static(&map, X(80), Y(24)).unwrap().0
+    }
+    #[test]
+    fn line_of_fire() {
+        let floor = one_room_floor();
+        let from = Coord::new(1, 2);
+        let line = floor
+            .line_of_fire(from, from + Coord::new(2, 2), 5)
+            .unwrap();
+        assert_eq!(line, vec![from + Coord::new(1, 1), from + Coord::new(2, 2)]);
+        assert!(floor
+            .line_of_fire(from, from + Coord::new(2, 1), 5)
+            .is_none());
+        assert!(floor
+            .line_of_fire(from, from + Coord::new(2, 0), 1)
+            .is_none());
+        assert!(floor
+            .line_of_fire(from, from + Coord::new(-1, 0), 5)
+            .is_none());
+    }
+    #[test]
+    fn traps() {
+        let mut config = Config::default();
+        config.trap_find_rate_inv = 1;
+        let mut rng = RngHandle::from_seed(1);
+        let mut floor = one_room_floor();
+        floor.setup_traps(10, 4, &mut rng);
+        assert!(!floor.traps.is_empty() && floor.traps.len() <= 4);
+        let (&cd, &kind) = floor.traps.iter().next().unwrap();
+        assert_eq!(floor.field.get_p(cd).surface, Surface::Floor);
+        let msgs: Vec<_> = floor
+            .search(cd + Direction::Left.to_cd(), &mut rng, &config)
+            .collect();
+        assert!(msgs.contains(&GameMsg::TrapFound(kind)));
+        assert_eq!(floor.field.get_p(cd).surface, Surface::Trap);
+        assert_eq!(floor.spring_trap(cd), Some(kind));
+    }
+    #[test]
     fn select_cell() {
         let config = Config::default();
         let mut rng = RngHandle::new();
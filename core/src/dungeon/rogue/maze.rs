@@ -1,16 +1,15 @@
-use crate::dungeon::{Coord, Direction};
+use crate::dungeon::{Coord, Direction, Rect};
 use crate::error::*;
 use crate::fenwick::FenwickSet;
 use crate::rng::RngHandle;
 use anyhow::Context;
 use enum_iterator::IntoEnumIterator;
-use rect_iter::RectRange;
 use std::collections::HashSet;
 
 /// structure of maze
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Maze {
-    pub range: RectRange<i32>,
+    pub range: Rect,
     pub passages: FenwickSet,
 }
 
@@ -35,11 +34,7 @@ impl Maze {
 /// range: a 2D range you want to dig the maze in
 /// rng: random number generator
 /// register: a closure which register the coordinates of maze into your dungeon
-pub(super) fn dig_maze<F>(
-    range: RectRange<i32>,
-    rng: &mut RngHandle,
-    mut register: F,
-) -> GameResult<()>
+pub(super) fn dig_maze<F>(range: Rect, rng: &mut RngHandle, mut register: F) -> GameResult<()>
 where
     F: FnMut(Coord) -> GameResult<()>,
 {
@@ -53,7 +48,7 @@ where
 /// implementation of maze digging by DFS
 // in this function we don't chain error, because this is sub function of dig_maze
 fn dig_impl<F>(
-    range: &RectRange<i32>,
+    range: &Rect,
     rng: &mut RngHandle,
     register: &mut F,
     used: &mut HashSet<Coord>,
@@ -97,7 +92,7 @@ mod test {
     #[ignore]
     fn print_maze() {
         let mut rng = RngHandle::new();
-        let range = RectRange::from_ranges(20..50, 10..20).unwrap();
+        let range = Rect::from_ranges(20..50, 10..20).unwrap();
         let mut buffer = vec![vec![false; 80]; 24];
         dig_maze(range.clone(), &mut rng, |cd| {
             if !range.contains(cd) {
@@ -2,21 +2,27 @@ pub mod floor;
 pub mod maze;
 pub mod passages;
 pub mod rooms;
+pub mod static_map;
 
 use self::floor::Floor;
 pub use self::rooms::{Room, RoomKind};
-use crate::character::{player::Status as PlayerStatus, EnemyHandler};
+pub use self::static_map::StaticFloor;
+use crate::character::{player::Status as PlayerStatus, EnemyHandler, EnemyStash};
 use crate::dungeon::{
-    Coord, Direction, Dungeon as DungeonTrait, DungeonPath, MoveResult, Positioned, X, Y,
+    Coord, Direction, Dungeon as DungeonTrait, DungeonGenerator, DungeonPath, MoveResult,
+    Positioned, TrapKind, X, Y,
 };
 use crate::item::{ItemHandler, ItemToken};
+use crate::rng::{Parcent, RngHandle, RngState, Stream};
+use crate::snapshot::DeepCloner;
 use crate::tile::{Drawable, Tile};
-use crate::{error::*, rng::RngHandle, GameInfo, GameMsg, GlobalConfig};
+use crate::validate::Validator;
+use crate::{error::*, GameInfo, GameMsg, GlobalConfig};
 use anyhow::{bail, Context};
 use enum_iterator::IntoEnumIterator;
 use ndarray::Array2;
 use rect_iter::{Get2D, GetMut2D, RectRange};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use tuple_map::TupleMap2;
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -33,18 +39,32 @@ pub struct Config {
     /// enables trap or not
     #[serde(default = "default_trap")]
     pub enable_trap: bool,
+    /// maximum number of traps in a level (0 means that no trap appears)
+    #[serde(default = "default_max_traps")]
+    pub max_traps: u32,
+    /// the player finds an adjacent trap by searching with a probability of 1 / trap_find_rate_inv
+    #[serde(default = "default_trap_find_rate_inv")]
+    pub trap_find_rate_inv: u32,
     /// maximum number of empty rooms
     #[serde(default = "default_max_empty_rooms")]
     pub max_empty_rooms: u32,
     /// the level where the Amulet of Yendor is
     #[serde(default = "default_amulet_level")]
     pub amulet_level: u32,
-    /// a room changes to maze with a probability of 1 / maze_rate_inv
+    /// a dark room changes to maze with a probability of 1 / maze_rate_inv
+    /// (0 means that no maze appears)
     #[serde(default = "default_maze_rate")]
     pub maze_rate_inv: u32,
+    /// mazes appear only in levels deeper than or equal to this level
+    #[serde(default = "default_maze_min_level")]
+    pub maze_min_level: u32,
     /// if the rooms is dark or not is judged by rand[0..dark_level) < level - 1
     #[serde(default = "default_dark_level")]
     pub dark_level: u32,
+    /// if specified, a room is dark with this probability in every level,
+    /// instead of being judged by `dark_level`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dark_room_rate: Option<Parcent>,
     /// a passage is hidden with a probability of 1 / hidden_rate_inv
     #[serde(default = "default_hidden_passage_rate")]
     pub hidden_passage_rate_inv: u32,
@@ -58,6 +78,28 @@ pub struct Config {
     pub door_unlock_rate_inv: u32,
     #[serde(default = "default_passage_unlock_rate_inv")]
     pub passage_unlock_rate_inv: u32,
+    /// a level has a treasure room with a probability of 1 / treasure_room_rate_inv
+    /// (0 means that no treasure room appears)
+    #[serde(default = "default_treasure_room_rate_inv")]
+    pub treasure_room_rate_inv: u32,
+    /// a level has a shop with a probability of 1 / shop_rate_inv
+    /// (0 means that no shop appears)
+    #[serde(default = "default_shop_rate_inv")]
+    pub shop_rate_inv: u32,
+    /// a door is closed with a probability of 1 / closed_door_rate_inv
+    /// (0 means that no door is closed)
+    #[serde(default = "default_closed_door_rate_inv")]
+    pub closed_door_rate_inv: u32,
+    /// how the player sees the dungeon
+    #[serde(default = "default_fov")]
+    pub fov: Fov,
+    /// keep floors the player left, to restore them when the player comes back
+    /// (if false, a floor is generated again every time the player enters it)
+    #[serde(default = "default_keep_floors")]
+    pub keep_floors: bool,
+    /// hand-authored floors used for the first levels, instead of generated ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub static_floors: Vec<StaticFloor>,
 }
 
 const fn default_room_num_x() -> X {
@@ -77,6 +119,14 @@ const fn default_trap() -> bool {
     true
 }
 
+const fn default_max_traps() -> u32 {
+    10
+}
+
+const fn default_trap_find_rate_inv() -> u32 {
+    3
+}
+
 const fn default_max_empty_rooms() -> u32 {
     3
 }
@@ -89,6 +139,10 @@ const fn default_maze_rate() -> u32 {
     15
 }
 
+const fn default_maze_min_level() -> u32 {
+    0
+}
+
 const fn default_dark_level() -> u32 {
     10
 }
@@ -113,6 +167,26 @@ const fn default_passage_unlock_rate_inv() -> u32 {
     3
 }
 
+const fn default_treasure_room_rate_inv() -> u32 {
+    0
+}
+
+const fn default_shop_rate_inv() -> u32 {
+    0
+}
+
+const fn default_fov() -> Fov {
+    Fov::Room
+}
+
+const fn default_keep_floors() -> bool {
+    true
+}
+
+const fn default_closed_door_rate_inv() -> u32 {
+    0
+}
+
 impl Default for Config {
     fn default() -> Config {
         Config {
@@ -120,15 +194,89 @@ impl Default for Config {
             room_num_y: default_room_num_y(),
             min_room_size: default_min_room_size(),
             enable_trap: default_trap(),
+            max_traps: default_max_traps(),
+            trap_find_rate_inv: default_trap_find_rate_inv(),
             max_empty_rooms: default_max_empty_rooms(),
             amulet_level: default_amulet_level(),
             maze_rate_inv: default_maze_rate(),
+            maze_min_level: default_maze_min_level(),
             dark_level: default_dark_level(),
+            dark_room_rate: None,
             hidden_passage_rate_inv: default_hidden_passage_rate(),
             locked_door_rate_inv: default_locked_door_rate_inv(),
             max_extra_edges: default_max_extra_edges(),
             door_unlock_rate_inv: default_door_unlock_rate_inv(),
             passage_unlock_rate_inv: default_passage_unlock_rate_inv(),
+            treasure_room_rate_inv: default_treasure_room_rate_inv(),
+            shop_rate_inv: default_shop_rate_inv(),
+            closed_door_rate_inv: default_closed_door_rate_inv(),
+            fov: default_fov(),
+            keep_floors: default_keep_floors(),
+            static_floors: vec![],
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator, width: X, height: Y) {
+        v.check("room_num_x", self.room_num_x.0 > 0, "should be positive");
+        v.check("room_num_y", self.room_num_y.0 > 0, "should be positive");
+        if let Some(rate) = self.dark_room_rate {
+            v.parcent("dark_room_rate", rate);
+        }
+        v.field("static_floors", |v| {
+            for (i, floor) in self.static_floors.iter().enumerate() {
+                if let Err(msg) = floor.check_shape(width, height) {
+                    v.element(i, |v| v.error(msg));
+                }
+            }
+        });
+    }
+}
+
+impl DungeonGenerator for Config {
+    fn generate(
+        &self,
+        config_global: &GlobalConfig,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        game_info: &GameInfo,
+        seed: u128,
+    ) -> GameResult<Box<dyn DungeonTrait>> {
+        let dungeon = Dungeon::new(
+            self.clone(),
+            config_global,
+            game_info,
+            item_handle,
+            enemies,
+            seed,
+        )
+        .context("rogue::Config::generate")?;
+        Ok(Box::new(dungeon))
+    }
+    fn restore(&self, state: serde_json::Value) -> GameResult<Box<dyn DungeonTrait>> {
+        let dungeon: Dungeon = serde_json::from_value(state).context("rogue::Config::restore")?;
+        Ok(Box::new(dungeon))
+    }
+}
+
+/// field of view of the player
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fov {
+    /// as in the original rogue, a lit room is seen all at once when the player enters it,
+    /// and other cells are seen only when the player is next to them
+    Room,
+    /// cells in sight within the radius are seen by recursive shadowcasting,
+    /// though cells in dark rooms and passages are seen only next to the player
+    Shadowcasting(u32),
+}
+
+impl Fov {
+    fn radius(self) -> Option<u32> {
+        match self {
+            Fov::Room => None,
+            Fov::Shadowcasting(r) => Some(r),
         }
     }
 }
@@ -183,7 +331,7 @@ impl Surface {
 }
 
 /// representation of rogue dungeon
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Dungeon {
     /// current level
     pub level: u32,
@@ -195,10 +343,12 @@ pub struct Dungeon {
     pub config: Config,
     /// global configuration(constant)
     pub config_global: GlobalConfig,
-    /// past floors
-    pub past_floors: Vec<Floor>,
-    /// random number generator
+    /// floors the player visited except the current one, with enemies there
+    visited_floors: BTreeMap<u32, (Floor, EnemyStash)>,
+    /// random number generator, which is saved in `RngStates`
+    #[serde(skip)]
     pub rng: RngHandle,
+    #[serde(skip)]
     dist_cache: DistCache,
 }
 
@@ -223,7 +373,30 @@ impl DungeonTrait for Dungeon {
         item: &mut ItemHandler,
         enemies: &mut EnemyHandler,
     ) -> GameResult<()> {
-        self.new_level_(game_info, item, enemies, false)
+        self.change_level(self.level + 1, game_info, item, enemies)
+    }
+    fn upper_level(
+        &mut self,
+        path: &DungeonPath,
+        game_info: &GameInfo,
+        item: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+    ) -> GameResult<Option<DungeonPath>> {
+        if self.level <= 1 || !self.is_downstair(path) {
+            return Ok(None);
+        }
+        self.change_level(self.level - 1, game_info, item, enemies)
+            .context("rogue::Dungeon::upper_level")?;
+        let (w, h) = (self.config_global.width.0, self.config_global.height.0);
+        let stair = RectRange::zero_start(w, h)
+            .unwrap()
+            .into_iter()
+            .map(Coord::from)
+            .find(|&cd| self.current_floor.field.get_p(cd).surface == Surface::Stair);
+        Ok(match stair {
+            Some(cd) => Some(Address::new(self.level, cd).into()),
+            None => self.select_cell(true),
+        })
     }
     fn can_move_player(&self, path: &DungeonPath, direction: Direction) -> Option<DungeonPath> {
         let address = Address::from_path(path);
@@ -252,19 +425,98 @@ impl DungeonTrait for Dungeon {
             cd,
         };
         self.current_floor
-            .player_in(cd, false, enemies)
+            .player_in(cd, false, self.config.fov.radius(), enemies)
             .context(ERR_STR)?;
         Ok(address.into())
     }
+    fn way_to(&self, from: &DungeonPath, to: Coord) -> Option<Direction> {
+        let addr = Address::from_path(from);
+        if addr.level != self.level {
+            return None;
+        }
+        self.current_floor.way_to(addr.cd, |cd| cd == to)
+    }
+    fn way_to_unexplored(&self, from: &DungeonPath) -> Option<Direction> {
+        let addr = Address::from_path(from);
+        if addr.level != self.level {
+            return None;
+        }
+        let field = &self.current_floor.field;
+        self.current_floor.way_to(addr.cd, |cd| {
+            !field.get_p(cd).is_visited()
+                && Direction::ORTHOGONAL.iter().any(|&d| {
+                    field
+                        .try_get_p(cd + d.to_cd())
+                        .is_ok_and(|cell| !cell.is_obj_visible())
+                })
+        })
+    }
     fn search(&mut self, path: &DungeonPath) -> GameResult<Vec<GameMsg>> {
         let address = Address::from_path(path);
         if address.level != self.level {
             bail!(ErrorKind::MaybeBug("[rogue::Dungeon::search]"));
         }
-        Ok(self
+        let res: Vec<_> = self
             .current_floor
             .search(address.cd, &mut self.rng, &self.config)
-            .collect())
+            .collect();
+        let revealed = |msg: &GameMsg| matches!(msg, GameMsg::SecretDoor | GameMsg::SecretPassage);
+        if res.iter().any(revealed) {
+            self.dist_cache.clear();
+        }
+        Ok(res)
+    }
+    fn spawn_wanderer(
+        &mut self,
+        player: &DungeonPath,
+        enemies: &mut EnemyHandler,
+    ) -> Option<DungeonPath> {
+        const MAX_TRIAL: usize = 10;
+        let player = Address::from_path(player).cd;
+        let level = self.level;
+        let cd = (0..MAX_TRIAL)
+            .filter_map(|_| self.current_floor.select_cell(&mut self.rng, true))
+            .find(|&cd| {
+                !self.current_floor.can_see(player, cd)
+                    && enemies.get_enemy(&Address::new(level, cd).into()).is_none()
+            })?;
+        let min = level.saturating_sub(4);
+        let enemy = enemies.gen_enemy_always(level, min..level + 6, i64::from(self.lev_add()))?;
+        let path: DungeonPath = Address::new(level, cd).into();
+        self.current_floor.set_obj(cd, true);
+        enemies.place(path.clone(), enemy);
+        enemies.activate(path.clone());
+        Some(path)
+    }
+    fn spring_trap(&mut self, path: &DungeonPath) -> Option<TrapKind> {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return None;
+        }
+        self.current_floor.spring_trap(addr.cd)
+    }
+    fn open_door(&mut self, path: &DungeonPath, direction: Direction) -> bool {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return false;
+        }
+        self.set_door(addr.cd, direction, true)
+    }
+    fn close_door(
+        &mut self,
+        path: &DungeonPath,
+        direction: Direction,
+        enemies: &EnemyHandler,
+    ) -> bool {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return false;
+        }
+        let door = Address::new(self.level, addr.cd + direction.to_cd()).into();
+        if enemies.get_enemy(&door).is_some() {
+            return false;
+        }
+        self.set_door(addr.cd, direction, false)
     }
     fn select_cell(&mut self, is_character: bool) -> Option<DungeonPath> {
         self.current_floor
@@ -273,7 +525,8 @@ impl DungeonTrait for Dungeon {
     }
     fn enter_room(&mut self, path: &DungeonPath, enemies: &mut EnemyHandler) -> GameResult<()> {
         let address = Address::from_path(path);
-        self.current_floor.player_in(address.cd, true, enemies)
+        self.current_floor
+            .player_in(address.cd, true, self.config.fov.radius(), enemies)
     }
     fn draw(&self, drawer: &mut dyn FnMut(Positioned<Tile>) -> GameResult<()>) -> GameResult<()> {
         const ERR_STR: &str = "in rogue::Dungeon::move_player";
@@ -313,10 +566,31 @@ impl DungeonTrait for Dungeon {
         if addr.level != self.level {
             return None;
         }
-        if !self.current_floor.remove_obj(addr.cd, false) {
+        let item = self.current_floor.items.remove(&addr.cd)?;
+        self.current_floor.remove_obj(addr.cd, false);
+        Some(item)
+    }
+    fn put_item(&mut self, path: &DungeonPath, item: ItemToken) -> Result<DungeonPath, ItemToken> {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return Err(item);
+        }
+        self.current_floor
+            .put_item(addr.cd, item)
+            .map(|cd| Address::new(self.level, cd).into())
+    }
+    fn is_shop(&self, path: &DungeonPath) -> bool {
+        let addr = Address::from_path(path);
+        addr.level == self.level && self.current_floor.is_shop(addr.cd)
+    }
+    fn gold_in_room(&self, path: &DungeonPath) -> Option<DungeonPath> {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
             return None;
         }
-        self.current_floor.items.remove(&addr.cd)
+        self.current_floor
+            .gold_in_room(addr.cd)
+            .map(|cd| Address::new(self.level, cd).into())
     }
     fn tile(&mut self, path: &DungeonPath) -> Option<Tile> {
         let cd = self.path_to_cd(path);
@@ -326,16 +600,48 @@ impl DungeonTrait for Dungeon {
             .ok()
             .map(|s| s.tile())
     }
+    fn rng(&mut self) -> &mut RngHandle {
+        &mut self.rng
+    }
+    fn rng_state(&self) -> RngState {
+        self.rng.save_state()
+    }
+    fn deep_clone(&self, cloner: &mut DeepCloner) -> Box<dyn DungeonTrait> {
+        let visited_floors = self
+            .visited_floors
+            .iter()
+            .map(|(&level, (floor, stash))| {
+                (level, (floor.deep_clone(cloner), stash.deep_clone(cloner)))
+            })
+            .collect();
+        Box::new(Dungeon {
+            level: self.level,
+            max_level: self.max_level,
+            current_floor: self.current_floor.deep_clone(cloner),
+            config: self.config.clone(),
+            config_global: self.config_global.clone(),
+            visited_floors,
+            rng: self.rng.clone(),
+            // the cache only depends on the floor, so clones can compute it again
+            dist_cache: DistCache::new(),
+        })
+    }
+    fn save(&self) -> GameResult<serde_json::Value> {
+        serde_json::to_value(self).context("rogue::Dungeon::save")
+    }
     fn get_history(&self, status: &PlayerStatus) -> Option<Array2<bool>> {
         let level = status.dungeon_level;
         if level == self.level {
             Some(self.current_floor.history_map())
-        } else if let Some(floor) = self.past_floors.get(level as usize - 1) {
-            Some(floor.history_map())
         } else {
-            None
+            self.visited_floors
+                .get(&level)
+                .map(|(floor, _)| floor.history_map())
         }
     }
+    fn visited_cells(&self) -> u32 {
+        self.current_floor.visited
+    }
     fn move_enemy(
         &mut self,
         current: &DungeonPath,
@@ -373,6 +679,24 @@ impl DungeonTrait for Dungeon {
         let res = cand[0].1;
         MoveResult::CanMove(Address::new(cur.level, res).into())
     }
+    fn move_enemy_astar(
+        &mut self,
+        current: &DungeonPath,
+        dist: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult {
+        let (cur, dist) = (current, dist).map(Address::from_path);
+        if cur.level != dist.level {
+            return MoveResult::CantMove;
+        }
+        let level = cur.level;
+        let blocked = |cd| skip(&DungeonPath::from(Address::new(level, cd)));
+        match self.current_floor.astar_path(cur.cd, dist.cd, blocked) {
+            Some(ref path) if path.len() == 1 => MoveResult::Reach,
+            Some(path) => MoveResult::CanMove(Address::new(level, path[0]).into()),
+            None => MoveResult::CantMove,
+        }
+    }
     fn move_enemy_randomly(
         &mut self,
         enemy_pos: &DungeonPath,
@@ -395,12 +719,54 @@ impl DungeonTrait for Dungeon {
             MoveResult::CanMove(res)
         }
     }
+    fn line_of_fire(
+        &self,
+        from: &DungeonPath,
+        to: &DungeonPath,
+        range: u32,
+    ) -> Option<Vec<DungeonPath>> {
+        let (from, to) = (from, to).map(Address::from_path);
+        if from.level != self.level || to.level != self.level {
+            return None;
+        }
+        let line = self.current_floor.line_of_fire(from.cd, to.cd, range)?;
+        Some(
+            line.into_iter()
+                .map(|cd| Address::new(self.level, cd).into())
+                .collect(),
+        )
+    }
     fn draw_enemy(&self, player: &DungeonPath, enemy: &DungeonPath) -> bool {
         let (p, e) = (player, enemy).map(Address::from_path);
         if p.level != e.level {
             return false;
         }
-        p.cd.is_adjacent(e.cd) || self.current_floor.in_same_room(p.cd, e.cd)
+        self.current_floor.can_see(p.cd, e.cd)
+    }
+    fn line_of_sight(&self, from: &DungeonPath, to: &DungeonPath) -> bool {
+        let (from, to) = (from, to).map(Address::from_path);
+        if from.level != self.level || to.level != self.level {
+            return false;
+        }
+        let radius = self.config.fov.radius();
+        self.current_floor.line_of_sight(from.cd, to.cd, radius)
+    }
+    fn distance_map(&self, sources: &[DungeonPath]) -> Array2<u32> {
+        let sources = sources
+            .iter()
+            .map(Address::from_path)
+            .filter(|a| a.level == self.level)
+            .map(|a| a.cd);
+        self.current_floor.distance_map(sources, false)
+    }
+    fn visible_cells(&self, from: &DungeonPath) -> Box<dyn Iterator<Item = Coord> + '_> {
+        let from = Address::from_path(from);
+        if from.level != self.level {
+            return Box::new(std::iter::empty());
+        }
+        let radius = self.config.fov.radius();
+        let cells = self.current_floor.visible_cells(from.cd, radius);
+        Box::new(cells.into_iter())
     }
 }
 
@@ -414,39 +780,65 @@ impl Dungeon {
         enemies: &mut EnemyHandler,
         seed: u128,
     ) -> GameResult<Self> {
-        let rng = RngHandle::from_seed(seed);
+        let rng = RngHandle::from_stream(seed, Stream::DungeonGen);
         let mut dungeon = Dungeon {
             level: 0,
             max_level: config.amulet_level,
             current_floor: Floor::default(),
             config,
             config_global: config_global.clone(),
-            past_floors: vec![],
+            visited_floors: BTreeMap::new(),
             rng,
             dist_cache: DistCache::new(),
         };
         dungeon
-            .new_level_(game_info, item_handle, enemies, true)
+            .change_level(1, game_info, item_handle, enemies)
             .context("rogue::Dungeon::new")?;
         Ok(dungeon)
     }
 
-    fn new_level_(
+    /// move to `level`, restoring the floor there if the player has visited it
+    fn change_level(
         &mut self,
+        level: u32,
         game_info: &GameInfo,
         item_handle: &mut ItemHandler,
         enemies: &mut EnemyHandler,
-        is_initial: bool,
     ) -> GameResult<()> {
-        const ERR_STR: &str = "in rogue::Dungeon::new_level";
-        let level = {
-            self.level += 1;
-            self.level
-        };
+        const ERR_STR: &str = "in rogue::Dungeon::change_level";
+        if self.level > 0 {
+            let floor = ::std::mem::take(&mut self.current_floor);
+            if self.config.keep_floors {
+                self.visited_floors
+                    .insert(self.level, (floor, enemies.stash()));
+            } else {
+                enemies.remove_enemies();
+            }
+        }
+        self.level = level;
+        self.dist_cache.clear();
         if level > self.max_level {
             self.max_level = level;
         }
+        if let Some((floor, stash)) = self.visited_floors.remove(&level) {
+            self.current_floor = floor;
+            enemies.restore(stash);
+            return Ok(());
+        }
         let (width, height) = (self.config_global.width, self.config_global.height);
+        let static_floor = (level as usize)
+            .checked_sub(1)
+            .and_then(|i| self.config.static_floors.get(i));
+        if let Some(map) = static_floor {
+            let (mut floor, markers) = Floor::from_static(map, width, height).context(ERR_STR)?;
+            let lev_add = self.lev_add();
+            floor
+                .place_markers(markers, level, lev_add, item_handle, enemies, &mut self.rng)
+                .context(ERR_STR)?;
+            floor.place_boss(level, lev_add, enemies, &mut self.rng);
+            self.set_floor(floor);
+            return Ok(());
+        }
         let mut floor =
             Floor::gen_floor(level, &self.config, width, height, &mut self.rng).context(ERR_STR)?;
         debug!("[Dungeon::new_level] field: {}", floor.field);
@@ -457,11 +849,24 @@ impl Dungeon {
         // place stair
         floor.setup_stair(&mut self.rng).context(ERR_STR)?;
         // place enemies
-        if !is_initial {
-            enemies.remove_enemies();
-        }
         floor.place_enemies(level, self.lev_add(), enemies, &mut self.rng);
-        // place traps (STUB)
+        floor.place_boss(level, self.lev_add(), enemies, &mut self.rng);
+        let rate_inv = self.config.treasure_room_rate_inv;
+        if rate_inv > 0 && self.rng.does_happen(rate_inv) {
+            floor.setup_treasure_room(level, self.lev_add(), item_handle, enemies, &mut self.rng);
+        }
+        let rate_inv = self.config.shop_rate_inv;
+        if rate_inv > 0 && self.rng.does_happen(rate_inv) {
+            floor.setup_shop(level, item_handle, &mut self.rng);
+        }
+        if self.config.enable_trap && self.config.max_traps > 0 {
+            floor.setup_traps(level, self.config.max_traps, &mut self.rng);
+        }
+        self.set_floor(floor);
+        Ok(())
+    }
+
+    fn set_floor(&mut self, mut floor: Floor) {
         if !self.config_global.hide_dungeon {
             let xmax = self.config_global.width.0;
             let ymax = self.config_global.height.0 - 1;
@@ -473,11 +878,16 @@ impl Dungeon {
                     cell.visible(true);
                 });
         }
-        ::std::mem::swap(&mut self.current_floor, &mut floor);
-        if !is_initial {
-            self.past_floors.push(floor);
+        self.current_floor = floor;
+    }
+
+    /// open or close a door, forgetting distance maps made with the old door
+    fn set_door(&mut self, cd: Coord, direction: Direction, open: bool) -> bool {
+        let changed = self.current_floor.set_door(cd, direction, open);
+        if changed {
+            self.dist_cache.clear();
         }
-        Ok(())
+        changed
     }
 
     fn lev_add(&self) -> u32 {
@@ -489,7 +899,7 @@ impl Dungeon {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 struct DistCache {
     cache: VecDeque<(Array2<u32>, Coord)>,
 }
@@ -501,6 +911,9 @@ impl DistCache {
             cache: VecDeque::with_capacity(Self::MAX_CACHED_DIST),
         }
     }
+    fn clear(&mut self) {
+        self.cache.clear();
+    }
     fn make_dist_map(&mut self, floor: &Floor, cd: Coord, is_enemy: bool) -> &Array2<u32> {
         if let Some(pos) = self.cache.iter().position(|t| t.1 == cd) {
             return &self.cache[pos].0;
@@ -541,8 +954,15 @@ impl Address {
 
 #[cfg(test)]
 mod test {
-    use super::{Address, Coord, Direction, DungeonPath, MoveResult, TupleMap2};
-    use crate::{GameConfig, RunTime};
+    use super::{
+        Address, Config, Coord, Direction, DungeonPath, DungeonTrait, Get2D, MoveResult,
+        Positioned, RectRange, RoomKind, TupleMap2, X, Y,
+    };
+    use crate::character::{Action, Enemy};
+    use crate::input::InputCode;
+    use crate::tile::Drawable;
+    use crate::{GameConfig, GameMsg, Reaction, RunTime};
+    use std::rc::Rc;
     // tiny dungeon setting
     const CONFIG: &str = r#"
 {
@@ -564,6 +984,145 @@ mod test {
         GameConfig::from_json(CONFIG).unwrap().build().unwrap()
     }
     #[test]
+    fn upper_level() {
+        let mut runtime = setup_runtime();
+        let RunTime {
+            ref mut dungeon,
+            ref mut item,
+            ref mut enemies,
+            ref game_info,
+            ..
+        } = runtime;
+        let cells = |level: u32| -> Vec<DungeonPath> {
+            RectRange::zero_start(32, 16)
+                .unwrap()
+                .into_iter()
+                .map(|cd| Address::new(level, Coord::from(cd)).into())
+                .collect()
+        };
+        let tiles = |dungeon: &dyn DungeonTrait| {
+            let mut res = vec![];
+            dungeon
+                .draw(&mut |Positioned(cd, tile)| {
+                    res.push((cd, tile));
+                    Ok(())
+                })
+                .unwrap();
+            res
+        };
+        let before = tiles(&**dungeon);
+        let enemies_before: Vec<_> = cells(1)
+            .into_iter()
+            .filter(|p| enemies.get_enemy(p).is_some())
+            .collect();
+        dungeon.new_level(game_info, item, enemies).unwrap();
+        assert_eq!(dungeon.level(), 2);
+        let stair = cells(2)
+            .into_iter()
+            .find(|p| dungeon.is_downstair(p))
+            .unwrap();
+        let not_stair = cells(2)
+            .into_iter()
+            .find(|p| !dungeon.is_downstair(p))
+            .unwrap();
+        let res = dungeon.upper_level(&not_stair, game_info, item, enemies);
+        assert!(res.unwrap().is_none());
+        let pos = dungeon
+            .upper_level(&stair, game_info, item, enemies)
+            .unwrap()
+            .unwrap();
+        assert_eq!(dungeon.level(), 1);
+        assert!(dungeon.is_downstair(&pos));
+        assert_eq!(before, tiles(&**dungeon));
+        assert!(enemies_before
+            .iter()
+            .all(|p| enemies.get_enemy(p).is_some()));
+        assert!(dungeon
+            .upper_level(&pos, game_info, item, enemies)
+            .unwrap()
+            .is_none());
+    }
+    #[test]
+    fn static_floor() {
+        let config = r#"
+{
+    "width": 32,
+    "height": 16,
+    "seed": 5,
+    "dungeon": {
+        "style": "rogue",
+        "static_floors": [
+            {
+                "map": [
+                    "--------",
+                    "|.*....|",
+                    "|..%...|",
+                    "--------"
+                ]
+            }
+        ]
+    }
+}
+"#;
+        let runtime = GameConfig::from_json(config).unwrap().build().unwrap();
+        let stair = Address::new(1, Coord::new(3, 3)).into();
+        assert!(runtime.dungeon.is_downstair(&stair));
+        assert!(runtime
+            .dungeon
+            .get_item(&Address::new(1, Coord::new(2, 2)).into())
+            .is_some());
+        let player = runtime.dungeon.path_to_cd(&runtime.player.pos);
+        assert!(player.x.0 > 0 && player.x.0 < 7 && player.y.0 > 1 && player.y.0 < 4);
+    }
+    #[test]
+    fn build_with_generator() {
+        use crate::dungeon::DungeonStyle;
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        let mut rogue = super::Config::default();
+        rogue.room_num_x = X(2);
+        rogue.room_num_y = Y(2);
+        rogue.min_room_size = Coord::new(4, 4);
+        config.dungeon = DungeonStyle::NetHack;
+        assert!(config.clone().build().is_err());
+        let runtime = config.build_with(&rogue).unwrap();
+        assert_eq!(runtime.dungeon.level(), 1);
+    }
+    #[test]
+    fn wander_spawn() {
+        let spawned = || {
+            let mut config = GameConfig::from_json(CONFIG).unwrap();
+            config.enemies.wander_interval = 3;
+            let mut runtime = config.build().unwrap();
+            (0..30)
+                .filter_map(|_| runtime.react_to_input(InputCode::Act(Action::Search)).ok())
+                .flatten()
+                .filter_map(|r| match r {
+                    Reaction::Notify(GameMsg::EnemySpawned(name)) => Some(name),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = spawned();
+        assert!(!first.is_empty());
+        assert_eq!(first, spawned());
+    }
+    #[test]
+    fn verify_replay() {
+        use enum_iterator::IntoEnumIterator;
+        let inputs: Vec<_> = Direction::into_enum_iter()
+            .map(|d| InputCode::Act(Action::Move(d)))
+            .chain((0..5).map(|_| InputCode::Act(Action::Search)))
+            .collect();
+        let mut runtime = setup_runtime();
+        for &input in &inputs {
+            let _ = runtime.react_to_input(input);
+        }
+        let hash = runtime.state_hash().unwrap();
+        assert_ne!(hash, setup_runtime().state_hash().unwrap());
+        assert!(setup_runtime().verify_replay(&inputs, hash).is_ok());
+        assert!(setup_runtime().verify_replay(&inputs, hash ^ 1).is_err());
+    }
+    #[test]
     fn test_move_enemy() {
         let mut runtime = setup_runtime();
         let mut check_move = |from, to, direc: Direction| {
@@ -576,4 +1135,203 @@ mod test {
         };
         check_move(Coord::new(9, 9), Coord::new(28, 4), Direction::Right);
     }
+    #[test]
+    fn enemies_dont_pass_closed_doors() {
+        use crate::dungeon::DungeonStyle;
+        let mut rogue = match GameConfig::from_json(CONFIG).unwrap().dungeon {
+            DungeonStyle::Rogue(config) => config,
+            _ => unreachable!(),
+        };
+        rogue.closed_door_rate_inv = 1;
+        let RunTime {
+            ref config,
+            ref game_info,
+            ref mut item,
+            ref mut enemies,
+            ..
+        } = setup_runtime();
+        let mut dungeon = super::Dungeon::new(rogue, config, game_info, item, enemies, 1).unwrap();
+        let floor = &dungeon.current_floor;
+        let walkable = |cd: Coord| {
+            floor
+                .field
+                .try_get_p(cd)
+                .is_ok_and(|c| c.surface.can_walk() && !c.is_closed())
+        };
+        // a door with walkable cells on both sides
+        let (door, d) = floor
+            .doors
+            .iter()
+            .filter(|&&door| floor.field.get_p(door).is_closed())
+            .flat_map(|&door| {
+                [
+                    Direction::Up,
+                    Direction::Down,
+                    Direction::Left,
+                    Direction::Right,
+                ]
+                .iter()
+                .map(move |&d| (door, d))
+            })
+            .find(|&(door, d)| walkable(door - d.to_cd()) && walkable(door + d.to_cd()))
+            .expect("no closed door");
+        let path = |cd| DungeonPath::from(Address::new(1, cd));
+        let (inside, outside) = (door - d.to_cd(), door + d.to_cd());
+        let through_door = MoveResult::CanMove(path(door));
+        assert!(dungeon.open_door(&path(inside), d));
+        assert_eq!(
+            dungeon.move_enemy(&path(outside), &path(inside), &|_| false),
+            through_door
+        );
+        assert!(dungeon.close_door(&path(inside), d, enemies));
+        assert_ne!(
+            dungeon.move_enemy(&path(outside), &path(inside), &|_| false),
+            through_door
+        );
+        assert!(dungeon.open_door(&path(inside), d));
+        assert_eq!(
+            dungeon.move_enemy(&path(outside), &path(inside), &|_| false),
+            through_door
+        );
+    }
+    #[test]
+    fn test_move_enemy_astar() {
+        let mut runtime = setup_runtime();
+        let player = runtime.player.pos.clone();
+        let mut cur = runtime.dungeon.select_cell(true).unwrap();
+        if cur == player {
+            return;
+        }
+        for _ in 0..1000 {
+            match runtime.dungeon.move_enemy_astar(&cur, &player, &|_p| false) {
+                MoveResult::CanMove(next) => {
+                    let (c, n) = (&cur, &next).map(Address::from_path);
+                    assert!(c.cd.is_adjacent(n.cd));
+                    cur = next;
+                }
+                MoveResult::Reach => return,
+                MoveResult::CantMove => panic!("A* couldn't find the path"),
+            }
+        }
+        panic!("A* didn't reach the player");
+    }
+    /// a game where an enemy with `attr` is chasing the player from the right side
+    /// of a wide room, and the top-left corner of the room.
+    /// If `gold` is given, gold is placed there (relative to the corner).
+    fn chased_by(attr: &str, gold: Option<Coord>) -> (RunTime, Rc<Enemy>, Coord) {
+        let config = format!(
+            r#"{{
+                "seed": 1,
+                "hide_dungeon": false,
+                "enemies": {{"enemies": [{{
+                    "attack": [{{"times": 1, "max": 1}}],
+                    "attr": {},
+                    "defense": 10,
+                    "exp": 1,
+                    "gold": 0,
+                    "level": 1,
+                    "name": "dummy",
+                    "tile": 68,
+                    "rarelity": 1
+                }}]}}
+            }}"#,
+            attr
+        );
+        let mut runtime = GameConfig::from_json(&config).unwrap().build().unwrap();
+        let mut dungeon = {
+            let RunTime {
+                ref config,
+                ref game_info,
+                ref mut item,
+                ref mut enemies,
+                ..
+            } = runtime;
+            super::Dungeon::new(Config::default(), config, game_info, item, enemies, 1).unwrap()
+        };
+        runtime.enemies.remove_enemies();
+        let floor = &mut dungeon.current_floor;
+        for cd in floor.items.keys().cloned().collect::<Vec<_>>() {
+            floor.remove_obj(cd, false);
+            floor.items.remove(&cd);
+        }
+        let corner = floor
+            .rooms
+            .iter()
+            .filter_map(|room| match &room.kind {
+                RoomKind::Normal { range } if range.xlen() >= 16 && range.ylen() >= 4 => {
+                    Some(Coord::new(range.get_x().start + 1, range.get_y().start + 1))
+                }
+                _ => None,
+            })
+            .next()
+            .expect("no wide room");
+        if let Some(gold) = gold {
+            let token = (1..).find_map(|l| runtime.item.setup_gold(l)).unwrap();
+            floor.set_obj(corner + gold, false);
+            floor.items.insert(corner + gold, token);
+        }
+        runtime.dungeon = Box::new(dungeon);
+        runtime.player.pos = Address::new(1, corner).into();
+        let enemy = (0..)
+            .find_map(|_| runtime.enemies.gen_enemy(1, 0..1, 0, true))
+            .unwrap();
+        let path: DungeonPath = Address::new(1, corner + Coord::new(9, 0)).into();
+        runtime.enemies.place(path.clone(), Rc::clone(&enemy));
+        runtime.enemies.activate(path);
+        (runtime, enemy, corner)
+    }
+    /// move enemies once, and returns where the enemy is relative to the corner
+    fn move_once(runtime: &mut RunTime, corner: Coord) -> Coord {
+        let player = runtime.player.pos.clone();
+        runtime.enemies.move_actives(&player, &mut *runtime.dungeon);
+        RectRange::from_corners(corner, corner + Coord::new(14, 2))
+            .unwrap()
+            .into_iter()
+            .map(Coord::from)
+            .find(|&cd| {
+                let path = Address::new(1, cd).into();
+                runtime.enemies.get_enemy(&path).is_some()
+            })
+            .unwrap()
+            - corner
+    }
+    #[test]
+    fn flying_enemies_move_twice() {
+        let (mut runtime, _, corner) = chased_by(r#"["MEAN"]"#, None);
+        assert_eq!(move_once(&mut runtime, corner), Coord::new(8, 0));
+        let (mut runtime, _, corner) = chased_by(r#"["MEAN", "FLYING"]"#, None);
+        assert_eq!(move_once(&mut runtime, corner), Coord::new(7, 0));
+        assert_eq!(move_once(&mut runtime, corner), Coord::new(5, 0));
+    }
+    #[test]
+    fn greedy_enemies_go_for_gold() {
+        let gold = Coord::new(13, 1);
+        let (mut runtime, _, corner) = chased_by(r#"["MEAN"]"#, Some(gold));
+        assert_eq!(move_once(&mut runtime, corner), Coord::new(8, 0));
+        let gold_path = Address::new(1, corner + gold).into();
+        assert!(runtime.dungeon.get_item(&gold_path).is_some());
+        let (mut runtime, _, corner) = chased_by(r#"["MEAN", "GREEDY"]"#, Some(gold));
+        for _ in 0..3 {
+            move_once(&mut runtime, corner);
+        }
+        // the enemy picks up the gold and then chases the player
+        assert_eq!(move_once(&mut runtime, corner), gold);
+        assert!(runtime.dungeon.get_item(&gold_path).is_none());
+        assert_eq!(move_once(&mut runtime, corner).x, X(12));
+    }
+    #[test]
+    fn invisible_enemies_are_not_drawn() {
+        for &(attr, visible) in &[(r#"["MEAN"]"#, true), (r#"["INVISIBLE"]"#, false)] {
+            let (runtime, enemy, corner) = chased_by(attr, None);
+            let cd = corner + Coord::new(9, 0);
+            let mut drawn = false;
+            runtime
+                .draw_screen(|Positioned(c, tile)| {
+                    drawn |= c == cd && tile == enemy.tile();
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(drawn, visible);
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use super::{Room, RoomKind, Surface};
-use crate::dungeon::{Coord, Direction, Positioned, X, Y};
+use crate::dungeon::{Coord, Direction, Positioned, Rect, X, Y};
 use crate::{
     error::*,
     fenwick::FenwickSet,
@@ -178,7 +178,7 @@ fn select_start_or_end(room: &Room, direction: Direction, rng: &mut RngHandle) -
     }
 }
 
-fn edges(range: &RectRange<i32>, direction: Direction, is_inclusive: bool) -> Vec<Coord> {
+fn edges(range: &Rect, direction: Direction, is_inclusive: bool) -> Vec<Coord> {
     let offset = if is_inclusive { 1 } else { 0 };
     let bound_x = X(range.get_x().end - offset);
     let bound_y = Y(range.get_y().end - offset);
@@ -303,7 +303,9 @@ mod test {
     use rect_iter::{Get2D, GetMut2D};
     use std::collections::VecDeque;
     fn to_buffer() -> Vec<Vec<Surface>> {
-        let rooms = rooms::test::gen(10);
+        to_buffer_with(rooms::test::gen(10))
+    }
+    fn to_buffer_with(rooms: Vec<Room>) -> Vec<Vec<Surface>> {
         let mut buffer = rooms::test::draw_to_buffer(&rooms);
         let mut rng = RngHandle::new();
         dig_passges(
@@ -342,38 +344,47 @@ mod test {
     #[test]
     fn connectivity() {
         for _ in 0..1000 {
-            let buffer = to_buffer();
-            let (xlen, ylen) = (buffer[0].len(), buffer.len());
-            let start = RectRange::zero_start(xlen, ylen)
-                .unwrap()
-                .into_iter()
-                .find(|&t| *buffer.get_p(t) == Surface::Floor)
-                .map(|t| Coord::new(t.0 as i32, t.1 as i32));
-            let start = match start {
-                Some(s) => s,
-                None => continue, // all floor is maze
-            };
-            let mut visited = vec![vec![false; xlen]; ylen];
-            *visited.get_mut_p(start) = true;
-            let mut queue = VecDeque::new();
-            queue.push_back(start);
-            while let Some(cd) = queue.pop_front() {
-                for dir in Direction::into_enum_iter().take(4) {
-                    let nxt = cd + dir.to_cd();
-                    if let Ok(s) = buffer.try_get_p(nxt) {
-                        if s.can_walk() && !*visited.get_p(nxt) {
-                            *visited.get_mut_p(nxt) = true;
-                            queue.push_back(nxt);
-                        }
+            check_connectivity(to_buffer());
+        }
+    }
+    #[test]
+    fn maze_connectivity() {
+        let config = rooms::test::maze_config();
+        for _ in 0..100 {
+            check_connectivity(to_buffer_with(rooms::test::gen_with(10, &config)));
+        }
+    }
+    fn check_connectivity(buffer: Vec<Vec<Surface>>) {
+        let (xlen, ylen) = (buffer[0].len(), buffer.len());
+        let start = RectRange::zero_start(xlen, ylen)
+            .unwrap()
+            .into_iter()
+            .find(|&t| buffer.get_p(t).can_walk())
+            .map(|t| Coord::new(t.0 as i32, t.1 as i32));
+        let start = match start {
+            Some(s) => s,
+            None => return,
+        };
+        let mut visited = vec![vec![false; xlen]; ylen];
+        *visited.get_mut_p(start) = true;
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(cd) = queue.pop_front() {
+            for &dir in Direction::ORTHOGONAL.iter() {
+                let nxt = cd + dir.to_cd();
+                if let Ok(s) = buffer.try_get_p(nxt) {
+                    if s.can_walk() && !*visited.get_p(nxt) {
+                        *visited.get_mut_p(nxt) = true;
+                        queue.push_back(nxt);
                     }
                 }
             }
-            RectRange::zero_start(xlen, ylen)
-                .unwrap()
-                .into_iter()
-                .for_each(|cd| {
-                    assert_eq!(buffer.get_p(cd).can_walk(), *visited.get_p(cd));
-                });
         }
+        RectRange::zero_start(xlen, ylen)
+            .unwrap()
+            .into_iter()
+            .for_each(|cd| {
+                assert_eq!(buffer.get_p(cd).can_walk(), *visited.get_p(cd));
+            });
     }
 }
@@ -1,5 +1,5 @@
 use super::{maze, Config, Surface};
-use crate::dungeon::{Coord, Positioned, X, Y};
+use crate::dungeon::{Coord, Direction, Positioned, Rect, X, Y};
 use crate::{error::*, fenwick::FenwickSet, rng::RngHandle};
 use anyhow::{bail, Context};
 use fixedbitset::FixedBitSet;
@@ -11,7 +11,7 @@ use tuple_map::TupleMap2;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum RoomKind {
     /// normal room
-    Normal { range: RectRange<i32> },
+    Normal { range: Rect },
     /// maze room
     Maze(Box<maze::Maze>),
     /// passage only(gone room)
@@ -29,11 +29,14 @@ pub struct Room {
     /// it's unique in same floor
     pub id: usize,
     /// a range punctuated when generating rooms
-    pub assigned_area: RectRange<i32>,
+    pub assigned_area: Rect,
     /// if the player has visited the room or notify
     pub is_visited: bool,
     /// if this room has gold or not
     pub has_gold: bool,
+    /// if this room is a shop or not
+    #[serde(default)]
+    pub is_shop: bool,
     /// cells which has no object
     empty_cells: FenwickSet,
     /// cells which has no enemy
@@ -41,7 +44,7 @@ pub struct Room {
 }
 
 impl Room {
-    fn new(kind: RoomKind, is_dark: bool, id: usize, assigned: RectRange<i32>) -> Self {
+    pub(super) fn new(kind: RoomKind, is_dark: bool, id: usize, assigned: Rect) -> Self {
         let empty_cells = gen_empty_cells(&kind);
         Room {
             kind,
@@ -51,6 +54,7 @@ impl Room {
             nocharacter_cells: empty_cells.clone(),
             empty_cells,
             has_gold: false,
+            is_shop: false,
             is_visited: false,
         }
     }
@@ -81,7 +85,7 @@ impl Room {
         }
     }
     /// Returns the 'room' range
-    pub fn range(&self) -> Option<&RectRange<i32>> {
+    pub fn range(&self) -> Option<&Rect> {
         match self.kind {
             RoomKind::Normal { ref range } => Some(range),
             RoomKind::Maze(ref maze) => Some(&maze.range),
@@ -142,18 +146,34 @@ impl Room {
             self.select_cell_impl(&self.empty_cells, rng)
         }
     }
+    /// select a cell without characters next to any of `around`
+    pub fn select_adjacent_cell(&self, around: &[Coord], rng: &mut RngHandle) -> Option<Coord> {
+        let range = self.range()?;
+        let candidates: Vec<_> = around
+            .iter()
+            .flat_map(|&cd| Direction::NEIGHBORS.iter().map(move |&d| cd + d.to_cd()))
+            .filter(|&cd| {
+                range
+                    .index(cd)
+                    .is_some_and(|id| self.nocharacter_cells.contains(id))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[rng.range(0..candidates.len())])
+    }
 }
 
 fn gen_empty_cells(kind: &RoomKind) -> FenwickSet {
     match kind {
         RoomKind::Normal { range } => {
-            let len = range.len();
-            let mut set = FenwickSet::with_capacity(len);
-            range.iter().enumerate().for_each(|(i, cd)| {
-                if !range.is_edge(cd) {
-                    set.insert(i);
-                }
-            });
+            let (w, h) = (range.xlen() as usize, range.ylen() as usize);
+            let mut set = FenwickSet::with_capacity(w * h);
+            // cells except the edges, row by row
+            for y in 1..h.saturating_sub(1) {
+                set.insert_range(y * w + 1..(y * w + w).saturating_sub(1));
+            }
             set
         }
         RoomKind::Maze(ref maze) => maze.passages.clone(),
@@ -234,8 +254,12 @@ pub(super) fn make_room(
             assigned_range,
         ));
     }
-    let is_dark = rng.range(0..config.dark_level) < level;
-    let kind = if is_dark && rng.does_happen(config.maze_rate_inv) {
+    let is_dark = match config.dark_room_rate {
+        Some(rate) => rng.parcent(rate),
+        None => rng.range(0..config.dark_level) < level,
+    };
+    let can_be_maze = is_dark && config.maze_rate_inv > 0 && level >= config.maze_min_level;
+    let kind = if can_be_maze && rng.does_happen(config.maze_rate_inv) {
         // maze
         let range =
             RectRange::from_corners(lower_left, lower_left + room_size - Coord::new(1, 1)).unwrap();
@@ -277,9 +301,30 @@ pub(super) mod test {
     pub fn gen(level: u32) -> Vec<Room> {
         let mut config = Config::default();
         config.maze_rate_inv = 5;
+        gen_with(level, &config)
+    }
+    pub fn gen_with(level: u32, config: &Config) -> Vec<Room> {
         let (w, h) = (X(80), Y(24));
         let mut rng = RngHandle::new();
-        gen_rooms(level, &config, w, h, &mut rng).unwrap()
+        gen_rooms(level, config, w, h, &mut rng).unwrap()
+    }
+    pub fn maze_config() -> Config {
+        let mut config = Config::default();
+        config.maze_rate_inv = 1;
+        config.maze_min_level = 5;
+        config.dark_room_rate = Some(crate::rng::Parcent(100));
+        config
+    }
+    #[test]
+    fn maze_min_level() {
+        let config = maze_config();
+        let is_maze = |room: &Room| matches!(room.kind, RoomKind::Maze(_));
+        for _ in 0..10 {
+            assert!(!gen_with(4, &config).iter().any(is_maze));
+            let rooms = gen_with(5, &config);
+            assert!(rooms.iter().any(is_maze));
+            assert!(!rooms.iter().any(|room| room.is_normal()));
+        }
     }
     pub fn draw_to_buffer(rooms: &[Room]) -> Vec<Vec<Surface>> {
         let mut buffer = vec![vec![Surface::None; 80]; 24];
@@ -307,12 +352,11 @@ pub(super) mod test {
     #[test]
     fn pos_check() {
         let (xrooms, yrooms) = (3, 3);
-        use enum_iterator::IntoEnumIterator;
         for i in 0..100 {
             let rooms = gen(i % 20);
             for (x, y) in RectRange::zero_start(xrooms, yrooms).unwrap() {
                 let room1 = &rooms[x + xrooms * y];
-                Direction::into_enum_iter().take(4).for_each(|d| {
+                Direction::ORTHOGONAL.iter().for_each(|&d| {
                     let (nx, ny) = d.to_cd().into_tuple2().add((x as i32, y as i32));
                     if nx < 0 || ny < 0 || nx >= xrooms as i32 || ny >= yrooms as i32 {
                         return;
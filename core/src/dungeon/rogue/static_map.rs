@@ -0,0 +1,246 @@
+//! hand-authored floors loaded from the config
+use super::{Room, RoomKind, Surface};
+use crate::dungeon::{Cell, CellAttr, Coord, Field, X, Y};
+use crate::error::*;
+use anyhow::bail;
+use rect_iter::{GetMut2D, RectRange};
+use std::collections::{HashSet, VecDeque};
+
+/// A floor described by ASCII art, like
+/// ```text
+/// --------
+/// |.*..M.+#####
+/// |..%...|    #
+/// --------    #
+/// ```
+/// Lines are drawn from the 2nd line of the screen, because the 1st line is for messages.
+///
+/// Legend:
+/// - ' ': nothing
+/// - '-', '|': walls
+/// - '.': floor of a room
+/// - '#': passage
+/// - '+': door
+/// - '%': stair (placed randomly if omitted)
+/// - '*': a random item on a floor
+/// - '$': gold on a floor
+/// - 'M': an enemy on a floor
+///
+/// Floors connected to each other make a room.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct StaticFloor {
+    pub map: Vec<String>,
+    /// if the rooms are dark or not
+    #[serde(default)]
+    pub is_dark: bool,
+}
+
+/// places of objects marked in a static floor
+#[derive(Clone, Debug, Default)]
+pub(super) struct Markers {
+    pub stair: Option<Coord>,
+    pub items: Vec<Coord>,
+    pub gold: Vec<Coord>,
+    pub enemies: Vec<Coord>,
+}
+
+pub(super) struct Layout {
+    pub rooms: Vec<Room>,
+    pub doors: HashSet<Coord>,
+    pub field: Field<Surface>,
+    pub markers: Markers,
+}
+
+impl StaticFloor {
+    /// check if the map fits in the screen and has only known characters, and no floor is on
+    /// the edges of the map, so that all rooms can be enclosed by walls
+    pub(crate) fn check_shape(&self, width: X, height: Y) -> Result<(), String> {
+        if self.map.len() + 2 > height.0 as usize {
+            return Err("too many lines".to_owned());
+        }
+        for (y, line) in self.map.iter().enumerate() {
+            if line.chars().count() > width.0 as usize {
+                return Err(format!("line {} is too long", y));
+            }
+            for (x, c) in line.chars().enumerate() {
+                if !" -|#+.*$M%".contains(c) {
+                    return Err(format!("unknown character {:?}", c));
+                }
+                let on_edge =
+                    x == 0 || x + 1 == width.0 as usize || y == 0 || y + 1 == self.map.len();
+                if on_edge && ".*$M%".contains(c) {
+                    return Err(format!(
+                        "floor {:?} at line {}, column {} is on the edge of the map",
+                        c, y, x
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+    pub(super) fn layout(&self, width: X, height: Y) -> GameResult<Layout> {
+        if let Err(msg) = self.check_shape(width, height) {
+            bail!(ErrorKind::InvalidSetting(
+                format!("[StaticFloor::layout] {}", msg).into()
+            ));
+        }
+        let mut field = Field::new(width, height, Cell::with_default_attr(Surface::None));
+        let mut doors = HashSet::new();
+        let mut floors = HashSet::new();
+        let mut markers = Markers::default();
+        for (y, line) in self.map.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let cd = Coord::new(x as i32, y as i32 + 1);
+                let surface = match c {
+                    ' ' => Surface::None,
+                    '-' => Surface::WallX,
+                    '|' => Surface::WallY,
+                    '#' => Surface::Passage,
+                    '+' => Surface::Door,
+                    _ => Surface::Floor,
+                };
+                match c {
+                    '+' => {
+                        doors.insert(cd);
+                    }
+                    '*' => markers.items.push(cd),
+                    '$' => markers.gold.push(cd),
+                    'M' => markers.enemies.push(cd),
+                    '%' => markers.stair = Some(cd),
+                    _ => {}
+                }
+                if surface == Surface::Floor {
+                    floors.insert(cd);
+                }
+                let cell = field.get_mut_p(cd);
+                cell.surface = surface;
+                if surface == Surface::Floor && self.is_dark {
+                    cell.attr |= CellAttr::IS_DARK;
+                }
+            }
+        }
+        let rooms = self.rooms(floors);
+        if rooms.is_empty() {
+            bail!(ErrorKind::InvalidSetting(
+                "[StaticFloor::layout] no room".into()
+            ));
+        }
+        Ok(Layout {
+            rooms,
+            doors,
+            field,
+            markers,
+        })
+    }
+    /// split floors into rooms, each of which is enclosed by the bounding box of its floors
+    fn rooms(&self, mut floors: HashSet<Coord>) -> Vec<Room> {
+        let mut rooms = vec![];
+        while let Some(&start) = floors.iter().min() {
+            floors.remove(&start);
+            let mut component = vec![start];
+            let mut queue = VecDeque::new();
+            queue.push_back(start);
+            while let Some(cd) = queue.pop_front() {
+                for d in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    let next = cd + Coord::new(d.0, d.1);
+                    if floors.remove(&next) {
+                        component.push(next);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            let (xmin, xmax) = minmax(component.iter().map(|cd| cd.x.0));
+            let (ymin, ymax) = minmax(component.iter().map(|cd| cd.y.0));
+            let range = RectRange::from_ranges(xmin - 1..xmax + 2, ymin - 1..ymax + 2).unwrap();
+            let mut room = Room::new(
+                RoomKind::Normal {
+                    range: range.clone(),
+                },
+                self.is_dark,
+                rooms.len(),
+                range.clone(),
+            );
+            // cells in the range which are not floors can't have any object
+            range
+                .iter()
+                .map(Coord::from)
+                .filter(|cd| !component.contains(cd))
+                .for_each(|cd| {
+                    room.fill_cell(cd, true);
+                });
+            rooms.push(room);
+        }
+        rooms
+    }
+}
+
+fn minmax(iter: impl Iterator<Item = i32>) -> (i32, i32) {
+    iter.fold((i32::MAX, i32::MIN), |(min, max), v| {
+        (min.min(v), max.max(v))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rect_iter::Get2D;
+    const MAP: &str = r#"
+{
+    "map": [
+        "--------",
+        "|.*..M.+#####",
+        "|..%...|    #",
+        "--------  --+--",
+        "          |.$.|",
+        "          -----"
+    ]
+}
+"#;
+    #[test]
+    fn layout() {
+        let map: StaticFloor = serde_json::from_str(MAP).unwrap();
+        let Layout {
+            rooms,
+            doors,
+            field,
+            markers,
+        } = map.layout(X(80), Y(24)).unwrap();
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(doors.len(), 2);
+        assert_eq!(field.get_p(Coord::new(8, 2)).surface, Surface::Passage);
+        assert_eq!(markers.stair, Some(Coord::new(3, 3)));
+        assert_eq!(markers.items, vec![Coord::new(2, 2)]);
+        assert_eq!(markers.gold, vec![Coord::new(12, 5)]);
+        assert_eq!(markers.enemies, vec![Coord::new(5, 2)]);
+        assert_eq!(
+            rooms[0].range(),
+            Some(&RectRange::from_ranges(0..8, 1..5).unwrap())
+        );
+        assert!(rooms.iter().all(|room| !room.is_dark));
+        let too_long = StaticFloor {
+            map: vec![".".repeat(81)],
+            is_dark: false,
+        };
+        assert!(too_long.layout(X(80), Y(24)).is_err());
+    }
+    #[test]
+    fn floor_on_edge() {
+        for map in &[vec!["..%.."], vec!["---", "|.|", "|%."]] {
+            let map = StaticFloor {
+                map: map.iter().map(|s| s.to_string()).collect(),
+                is_dark: false,
+            };
+            let err = map.check_shape(X(80), Y(24)).unwrap_err();
+            assert!(err.contains("is on the edge of the map"), "{}", err);
+            assert!(map.layout(X(80), Y(24)).is_err());
+        }
+        let json = r#"{"dungeon": {"style": "rogue", "static_floors": [{"map": ["..%.."]}]}}"#;
+        let err = crate::GameConfig::from_json(json).unwrap_err();
+        match err.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::InvalidConfig(violations)) => {
+                assert_eq!(violations.0[0].path, "dungeon.static_floors[0]");
+            }
+            _ => panic!("unexpected error {}", err),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! episode limits for agents, which end games without killing the player,
+//! and seed schedules, which choose seeds of episodes
+use crate::character::player::{Hunger, Status};
+use crate::rng;
+use crate::ui::{MordalKind, UiState};
+use crate::validate::Validator;
+use crate::Reaction;
+use std::fmt;
+
+/// Conditions to end an episode. All limits are disabled by default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Limits {
+    /// end the episode when this many turns passed
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+    /// end the episode when the player gets this hungry
+    #[serde(default)]
+    pub hunger: Option<Hunger>,
+    /// end the episode when the player went down stairs this many times
+    #[serde(default)]
+    pub stairs: Option<u32>,
+}
+
+/// Why an episode ended, other than the player's death
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum EndReason {
+    MaxTurns(u32),
+    Hunger(Hunger),
+    Stairs(u32),
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EndReason::MaxTurns(turns) => write!(f, "{} turns passed", turns),
+            EndReason::Hunger(hunger) => write!(f, "you got {:?}", hunger),
+            EndReason::Stairs(n) => write!(f, "you went down {} stairs", n),
+        }
+    }
+}
+
+impl EndReason {
+    /// true if the episode was cut off by a time limit, not ended by reaching a goal
+    /// like going down stairs.
+    /// In gymnasium's terms, only `MaxTurns` truncates episodes and others terminate them.
+    pub fn is_truncation(&self) -> bool {
+        matches!(self, EndReason::MaxTurns(_))
+    }
+}
+
+/// How the seed of each episode is chosen, which overrides `GameConfig::seed` and
+/// `GameConfig::seed_range`.
+/// It's written like `{"fixed": 3}`, `{"incrementing": 100}`, `"random"` or `{"list": [1, 5, 9]}`.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedSchedule {
+    /// the same seed for all episodes
+    Fixed(u128),
+    /// the seed of the first episode, which is incremented for each episode
+    Incrementing(u128),
+    /// a fresh seed from entropy for each episode
+    Random,
+    /// seeds of episodes, which are used cyclically
+    List(Vec<u128>),
+}
+
+impl SeedSchedule {
+    /// the seed of the `episode`-th(0-indexed) episode
+    pub fn seed(&self, episode: usize) -> u128 {
+        match self {
+            SeedSchedule::Fixed(seed) => *seed,
+            SeedSchedule::Incrementing(start) => start.wrapping_add(episode as u128),
+            SeedSchedule::Random => rng::gen_seed(),
+            SeedSchedule::List(seeds) => seeds[episode % seeds.len()],
+        }
+    }
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        if let SeedSchedule::List(seeds) = self {
+            v.check("list", !seeds.is_empty(), "the list of seeds is empty");
+        }
+    }
+}
+
+impl Limits {
+    /// returns a reason if `status` reaches any limit
+    pub fn check(&self, status: &Status) -> Option<EndReason> {
+        if let Some(max) = self.max_turns {
+            if status.turns >= max {
+                return Some(EndReason::MaxTurns(max));
+            }
+        }
+        if let Some(ref hunger) = self.hunger {
+            if status.hunger_level.to_u32() >= hunger.to_u32() {
+                return Some(EndReason::Hunger(hunger.clone()));
+            }
+        }
+        if let Some(n) = self.stairs {
+            if status.dungeon_level.saturating_sub(1) >= n {
+                return Some(EndReason::Stairs(n));
+            }
+        }
+        None
+    }
+    /// `Reaction::EpisodeEnd` if the episode ends by `reactions`, which is not emitted
+    /// when the player died or the episode has already ended
+    pub(crate) fn end_reaction(
+        &self,
+        status: &Status,
+        reactions: &[Reaction],
+        ended: &mut Option<EndReason>,
+    ) -> Option<Reaction> {
+        let dead = reactions.iter().any(|r| {
+            matches!(
+                r,
+                Reaction::UiTransition(UiState::Mordal(MordalKind::Grave(_)))
+            )
+        });
+        if dead || ended.is_some() {
+            return None;
+        }
+        let reason = self.check(status)?;
+        *ended = Some(reason.clone());
+        Some(Reaction::EpisodeEnd(reason))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::Action;
+    use crate::input::InputCode;
+    use crate::GameConfig;
+    #[test]
+    fn max_turns() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.episode.max_turns = Some(3);
+        let mut runtime = config.build().unwrap();
+        let mut ends = vec![];
+        for i in 0..5 {
+            let res = runtime
+                .react_to_input(InputCode::Act(Action::Search))
+                .unwrap();
+            if res.contains(&Reaction::EpisodeEnd(EndReason::MaxTurns(3))) {
+                ends.push(i);
+            }
+        }
+        assert_eq!(ends, vec![2]);
+        assert_eq!(runtime.episode_end(), Some(&EndReason::MaxTurns(3)));
+    }
+    #[test]
+    fn check() {
+        let limits: Limits = serde_json::from_str(r#"{"hunger": "Hungry", "stairs": 2}"#).unwrap();
+        let mut status = Status::default();
+        status.dungeon_level = 1;
+        assert_eq!(limits.check(&status), None);
+        status.dungeon_level = 3;
+        assert_eq!(limits.check(&status), Some(EndReason::Stairs(2)));
+        status.hunger_level = Hunger::Weak;
+        assert_eq!(
+            limits.check(&status),
+            Some(EndReason::Hunger(Hunger::Hungry))
+        );
+    }
+    #[test]
+    fn seed_schedule() {
+        let config = GameConfig::from_json(r#"{"seed_schedule": {"list": [3, 8]}}"#).unwrap();
+        let seeds: Vec<_> = (0..3).map(|i| config.for_episode(i).seed).collect();
+        assert_eq!(seeds, vec![Some(3), Some(8), Some(3)]);
+        let config =
+            GameConfig::from_json(r#"{"seed": 1, "seed_schedule": {"incrementing": 10}}"#).unwrap();
+        assert_eq!(config.for_episode(2).seed, Some(12));
+        let mut config = GameConfig::from_json(r#"{"seed_schedule": "random"}"#).unwrap();
+        assert!(config.for_episode(0).seed.is_some());
+        config.set_seed(5);
+        assert_eq!(config.for_episode(1).seed, Some(5));
+        assert!(GameConfig::from_json(r#"{"seed_schedule": {"list": []}}"#).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::input::{InputCode, Key};
+use crate::validate::Violations;
 use rect_iter::IndexError;
 use serde_json::Error as JsonError;
 use std::borrow::Cow;
@@ -19,10 +20,18 @@ pub enum ErrorKind {
     IncompleteInput,
     #[error("Invalid Setting: {}", _0)]
     InvalidSetting(Cow<'static, str>),
+    #[error("Invalid config:\n{}", _0)]
+    InvalidConfig(Violations),
     #[error("Json parsing error: {}", _0)]
     Json(JsonError),
     #[error("Invalid conversion")]
     InvalidConversion,
+    #[error(
+        "Replay mismatch: expected state hash {:016x}, but got {:016x}",
+        expected,
+        actual
+    )]
+    ReplayMismatch { expected: u64, actual: u64 },
     #[error("Maybe bug: {}", _0)]
     MaybeBug(&'static str),
     // STUB,
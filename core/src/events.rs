@@ -0,0 +1,111 @@
+//! typed events emitted by `RunTime`, so that instrumentation, achievements and
+//! reward calculators can be written without modifying the turn processing
+use crate::character::fight::CombatEvent;
+use crate::character::player::Status;
+use crate::item::{ItemId, ItemKind};
+use crate::ui::{MordalKind, UiState};
+use crate::{GameMsg, Reaction};
+
+/// An event happened while processing an input
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    /// time passed from `turn`, which comes before the other events of the input
+    TurnStart {
+        turn: u32,
+    },
+    Combat(CombatEvent),
+    /// the player picked up `num` items of `kind`, where `id` is the id in the pack
+    ItemPickup {
+        id: ItemId,
+        kind: ItemKind,
+        num: u32,
+    },
+    /// the player moved from the dungeon level `from` to `to`
+    LevelChange {
+        from: u32,
+        to: u32,
+    },
+    /// the player died, with the message on the grave
+    Death {
+        message: String,
+    },
+}
+
+impl GameEvent {
+    /// events of an input, from the statuses before and after it and its reactions
+    pub(crate) fn collect(before: &Status, after: &Status, reactions: &[Reaction]) -> Vec<Self> {
+        let mut res = vec![];
+        if after.turns > before.turns {
+            res.push(GameEvent::TurnStart { turn: before.turns });
+        }
+        for reaction in reactions {
+            match reaction {
+                Reaction::Notify(GameMsg::Combat(event)) => {
+                    res.push(GameEvent::Combat(event.clone()))
+                }
+                Reaction::Notify(GameMsg::GotItem { id, kind, num }) => {
+                    res.push(GameEvent::ItemPickup {
+                        id: *id,
+                        kind: kind.clone(),
+                        num: *num,
+                    })
+                }
+                _ => {}
+            }
+        }
+        if after.dungeon_level != before.dungeon_level {
+            res.push(GameEvent::LevelChange {
+                from: before.dungeon_level,
+                to: after.dungeon_level,
+            });
+        }
+        let death = reactions.iter().find_map(|reaction| match reaction {
+            Reaction::UiTransition(UiState::Mordal(MordalKind::Grave(msg))) => Some(msg),
+            _ => None,
+        });
+        if let Some(msg) = death {
+            res.push(GameEvent::Death {
+                message: msg.to_string(),
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::{InputCode, System};
+    use crate::GameConfig;
+    #[test]
+    fn queue() {
+        let mut runtime = GameConfig::default().build().unwrap();
+        runtime
+            .react_to_input(InputCode::Sys(System::Inventory))
+            .unwrap();
+        assert!(runtime.drain_events().is_empty());
+        runtime.subscribe_events();
+        let turns = runtime.player_status().turns;
+        runtime
+            .react_to_input(InputCode::Sys(System::Continue))
+            .unwrap();
+        assert!(runtime.drain_events().is_empty());
+        let mut before = runtime.player_status();
+        before.dungeon_level -= 1;
+        let reactions = vec![Reaction::UiTransition(UiState::die(
+            "killed by bat".to_owned(),
+        ))];
+        let mut after = runtime.player_status();
+        after.turns += 1;
+        assert_eq!(
+            GameEvent::collect(&before, &after, &reactions),
+            vec![
+                GameEvent::TurnStart { turn: turns },
+                GameEvent::LevelChange { from: 0, to: 1 },
+                GameEvent::Death {
+                    message: "killed by bat".to_owned()
+                },
+            ]
+        );
+    }
+}
@@ -1,12 +1,18 @@
 use crate::rng::Rng;
 #[cfg(test)]
 use crate::rng::RngHandle;
+use num_traits::{PrimInt, Signed};
+use std::cmp;
 use std::ops::Range;
 
+/// sets with capacities up to this use a plain fenwick tree,
+/// and larger ones use chunked bits, which need about 1/16 of the memory
+const COMPACT_THRESHOLD: usize = 1 << 16;
+
 /// a set implementation using Fenwick Tree
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FenwickSet {
-    inner: FenwickTree,
+    inner: Repr,
     num_elements: usize,
     max_val_excluded: usize,
 }
@@ -20,13 +26,8 @@ impl Default for FenwickSet {
 impl FenwickSet {
     /// create a new set with capacity [0..n)
     pub fn with_capacity(n: usize) -> Self {
-        assert!(
-            n <= 50_000_000,
-            "We can't construct too big FenwickSet: size {}",
-            n
-        );
         FenwickSet {
-            inner: FenwickTree::new(n),
+            inner: Repr::new(n),
             num_elements: 0,
             max_val_excluded: n,
         }
@@ -35,11 +36,17 @@ impl FenwickSet {
     /// and already have elements [range.start..range.end)
     pub fn from_range(range: Range<usize>) -> Self {
         let mut set = FenwickSet::with_capacity(range.end);
-        range.for_each(|i| {
-            set.insert(i);
-        });
+        set.insert_range(range);
         set
     }
+    /// create a new set with capacity [0..n) from bits of elements in 64-bit words
+    fn from_words(words: Vec<u64>, n: usize) -> Self {
+        FenwickSet {
+            num_elements: words.iter().map(|w| w.count_ones() as usize).sum(),
+            inner: Repr::from_words(words, n),
+            max_val_excluded: n,
+        }
+    }
     /// Insert an element `elem` into set
     /// if `elem` is already in the set, return false.
     /// if not, return true.
@@ -47,7 +54,7 @@ impl FenwickSet {
         if elem >= self.max_val_excluded || self.contains(elem) {
             false
         } else {
-            self.inner.add(elem, 1);
+            self.inner.set(elem, true);
             self.num_elements += 1;
             true
         }
@@ -59,21 +66,95 @@ impl FenwickSet {
         if elem >= self.max_val_excluded || !self.contains(elem) || self.num_elements == 0 {
             false
         } else {
-            self.inner.add(elem, -1);
+            self.inner.set(elem, false);
             self.num_elements -= 1;
             true
         }
     }
+    /// Insert all elements in `range` into set, and return how many elements are inserted.
+    /// Elements out of the capacity are ignored.
+    pub fn insert_range(&mut self, range: Range<usize>) -> usize {
+        self.update_range(range, true)
+    }
+    /// Remove all elements in `range` from set, and return how many elements are removed.
+    pub fn remove_range(&mut self, range: Range<usize>) -> usize {
+        self.update_range(range, false)
+    }
+    fn update_range(&mut self, range: Range<usize>, insert: bool) -> usize {
+        let range = range.start..cmp::min(range.end, self.max_val_excluded);
+        if range.start >= range.end {
+            return 0;
+        }
+        // updating elements one by one costs O(k log n), and rebuilding the tree costs O(n)
+        let depth = (usize::BITS - self.max_val_excluded.leading_zeros()) as usize;
+        if range.len() * depth <= self.max_val_excluded {
+            return range
+                .filter(|&i| {
+                    if insert {
+                        self.insert(i)
+                    } else {
+                        self.remove(i)
+                    }
+                })
+                .count();
+        }
+        let mut words = self.inner.words(self.max_val_excluded);
+        for i in range {
+            if insert {
+                words[i / 64] |= 1 << (i % 64);
+            } else {
+                words[i / 64] &= !(1 << (i % 64));
+            }
+        }
+        let before = self.num_elements;
+        *self = FenwickSet::from_words(words, self.max_val_excluded);
+        cmp::max(before, self.num_elements) - cmp::min(before, self.num_elements)
+    }
+    /// elements in `self` or `other`, with the larger capacity of the two
+    pub fn union(&self, other: &FenwickSet) -> FenwickSet {
+        let capacity = cmp::max(self.max_val_excluded, other.max_val_excluded);
+        self.combine(other, capacity, |a, b| a | b)
+    }
+    /// elements in both `self` and `other`, with the capacity of `self`
+    pub fn intersection(&self, other: &FenwickSet) -> FenwickSet {
+        self.combine(other, self.max_val_excluded, |a, b| a & b)
+    }
+    /// elements in `self` but not in `other`, with the capacity of `self`
+    pub fn difference(&self, other: &FenwickSet) -> FenwickSet {
+        self.combine(other, self.max_val_excluded, |a, b| a & !b)
+    }
+    /// combine two sets word by word, where bits out of each capacity are 0
+    fn combine(
+        &self,
+        other: &FenwickSet,
+        capacity: usize,
+        f: impl Fn(u64, u64) -> u64,
+    ) -> FenwickSet {
+        let (a, b) = (
+            self.inner.words(self.max_val_excluded),
+            other.inner.words(other.max_val_excluded),
+        );
+        let words = (0..num_words(capacity))
+            .map(|i| {
+                let word = |w: &[u64]| w.get(i).cloned().unwrap_or(0);
+                f(word(&a), word(&b))
+            })
+            .collect();
+        FenwickSet::from_words(words, capacity)
+    }
     /// Checks if the set cotains a element `elem`
     pub fn contains(&self, elem: usize) -> bool {
         if elem >= self.max_val_excluded {
             return false;
         }
-        self.inner.sum_range(elem..elem + 1) == 1
+        self.inner.contains(elem)
     }
     /// return nth-smallest element in the set
     pub fn nth(&self, n: usize) -> Option<usize> {
-        let res = self.inner.lower_bound(n as i32 + 1);
+        if n >= self.num_elements {
+            return None;
+        }
+        let res = self.inner.nth(n);
         if res >= self.max_val_excluded {
             None
         } else {
@@ -94,9 +175,8 @@ impl FenwickSet {
     }
     pub fn iter<'a>(&'a self) -> FwsIter<'a> {
         FwsIter {
-            fwt: &self.inner,
+            set: self,
             current: 0,
-            before: 0,
         }
     }
 }
@@ -106,100 +186,233 @@ impl IntoIterator for FenwickSet {
     type IntoIter = FwsIntoIter;
     fn into_iter(self) -> Self::IntoIter {
         FwsIntoIter {
-            fwt: self.inner,
+            set: self,
             current: 0,
-            before: 0,
         }
     }
 }
 
 /// Iterator for FenwickSet which has entitty
 pub struct FwsIntoIter {
-    fwt: FenwickTree,
-    current: isize,
-    before: i32,
+    set: FenwickSet,
+    current: usize,
 }
 
 impl Iterator for FwsIntoIter {
     type Item = usize;
     fn next(&mut self) -> Option<usize> {
-        fws_iter_next(&self.fwt, &mut self.current, &mut self.before)
+        fws_iter_next(&self.set, &mut self.current)
     }
 }
 
 /// Iterator for FenwickSet which has reference
 pub struct FwsIter<'a> {
-    fwt: &'a FenwickTree,
-    current: isize,
-    before: i32,
+    set: &'a FenwickSet,
+    current: usize,
 }
 
 impl<'a> Iterator for FwsIter<'a> {
     type Item = usize;
     fn next(&mut self) -> Option<usize> {
-        fws_iter_next(&self.fwt, &mut self.current, &mut self.before)
+        fws_iter_next(self.set, &mut self.current)
     }
 }
 
 #[inline]
-fn fws_iter_next(fwt: &FenwickTree, current: &mut isize, before: &mut i32) -> Option<usize> {
-    while *current < fwt.len {
-        *current += 1;
-        let sum = fwt.sum(*current as usize);
-        let diff = sum - *before;
-        *before = sum;
-        if diff == 1 {
-            return Some(*current as usize - 1);
+fn fws_iter_next(set: &FenwickSet, current: &mut usize) -> Option<usize> {
+    let res = set.inner.next_from(*current, set.max_val_excluded)?;
+    *current = res + 1;
+    Some(res)
+}
+
+fn num_words(n: usize) -> usize {
+    n.div_ceil(64)
+}
+
+/// representations of sets, chosen by their capacities
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    /// a fenwick tree of 0/1 counts of elements
+    Tree(FenwickTree),
+    /// bits of elements in 64-bit blocks with a fenwick tree of the counts of each block
+    Chunked(ChunkedBits),
+}
+
+impl Repr {
+    fn new(n: usize) -> Self {
+        if n <= COMPACT_THRESHOLD {
+            Repr::Tree(FenwickTree::new(n))
+        } else {
+            Repr::Chunked(ChunkedBits {
+                blocks: vec![0; num_words(n)],
+                counts: FenwickTree::new(num_words(n)),
+            })
+        }
+    }
+    fn from_words(words: Vec<u64>, n: usize) -> Self {
+        if n <= COMPACT_THRESHOLD {
+            let counts: Vec<i32> = (0..n)
+                .map(|i| (words[i / 64] >> (i % 64) & 1) as i32)
+                .collect();
+            Repr::Tree(FenwickTree::from_counts(&counts))
+        } else {
+            let counts: Vec<i64> = words.iter().map(|w| i64::from(w.count_ones())).collect();
+            Repr::Chunked(ChunkedBits {
+                counts: FenwickTree::from_counts(&counts),
+                blocks: words,
+            })
+        }
+    }
+    /// bits of elements in 64-bit words, for the capacity `n`
+    fn words(&self, n: usize) -> Vec<u64> {
+        match self {
+            Repr::Tree(tree) => {
+                let mut words = vec![0; num_words(n)];
+                for (i, _) in tree
+                    .counts()
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(_, c)| c == 1)
+                {
+                    words[i / 64] |= 1 << (i % 64);
+                }
+                words
+            }
+            Repr::Chunked(bits) => bits.blocks.clone(),
+        }
+    }
+    fn contains(&self, elem: usize) -> bool {
+        match self {
+            Repr::Tree(tree) => tree.sum_range(elem..elem + 1) == 1,
+            Repr::Chunked(bits) => bits.blocks[elem / 64] >> (elem % 64) & 1 == 1,
+        }
+    }
+    /// insert or remove `elem`, which should change the set
+    fn set(&mut self, elem: usize, on: bool) {
+        match self {
+            Repr::Tree(tree) => tree.add(elem, if on { 1 } else { -1 }),
+            Repr::Chunked(bits) => {
+                bits.blocks[elem / 64] ^= 1 << (elem % 64);
+                bits.counts.add(elem / 64, if on { 1 } else { -1 });
+            }
+        }
+    }
+    /// nth-smallest element, or a value larger than the capacity if there isn't
+    fn nth(&self, n: usize) -> usize {
+        match self {
+            Repr::Tree(tree) => tree.lower_bound(n as i32 + 1),
+            Repr::Chunked(bits) => {
+                let block = bits.counts.lower_bound(n as i64 + 1);
+                let mut word = match bits.blocks.get(block) {
+                    Some(&word) => word,
+                    None => return usize::MAX,
+                };
+                let rest = n - bits.counts.sum(block) as usize;
+                for _ in 0..rest {
+                    word &= word - 1;
+                }
+                block * 64 + word.trailing_zeros() as usize
+            }
+        }
+    }
+    /// the smallest element not less than `start`
+    fn next_from(&self, start: usize, n: usize) -> Option<usize> {
+        match self {
+            Repr::Tree(_) => (start..n).find(|&i| self.contains(i)),
+            Repr::Chunked(bits) => {
+                let mut block = start / 64;
+                let mut word = bits.blocks.get(block)? & (!0 << (start % 64));
+                while word == 0 {
+                    block += 1;
+                    word = *bits.blocks.get(block)?;
+                }
+                Some(block * 64 + word.trailing_zeros() as usize)
+            }
         }
     }
-    None
+}
+
+/// bits in 64-bit blocks, with a fenwick tree of the number of bits in each block
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ChunkedBits {
+    blocks: Vec<u64>,
+    counts: FenwickTree<i64>,
 }
 
 /// simple 0-indexed fenwick tree
 #[derive(Clone, Debug, Serialize, Deserialize)]
-struct FenwickTree {
-    inner: Vec<i32>,
+struct FenwickTree<T = i32> {
+    inner: Vec<T>,
     len: isize,
 }
 
-impl FenwickTree {
+impl<T: PrimInt + Signed> FenwickTree<T> {
     fn new(length: usize) -> Self {
         FenwickTree {
-            inner: vec![0; length + 1],
+            inner: vec![T::zero(); length + 1],
             len: length as isize,
         }
     }
+    /// build a tree of `counts` in O(n)
+    fn from_counts(counts: &[T]) -> Self {
+        let len = counts.len();
+        let mut inner = vec![T::zero(); len + 1];
+        inner[1..].copy_from_slice(counts);
+        for i in 1..=len {
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= len {
+                inner[parent] = inner[parent] + inner[i];
+            }
+        }
+        FenwickTree {
+            inner,
+            len: len as isize,
+        }
+    }
+    /// the array the tree is built from, in O(n)
+    fn counts(&self) -> Vec<T> {
+        let mut inner = self.inner.clone();
+        let len = self.len as usize;
+        for i in (1..=len).rev() {
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= len {
+                inner[parent] = inner[parent] - inner[i];
+            }
+        }
+        inner.split_off(1)
+    }
     /// add plus to array[idx]
-    fn add(&mut self, idx: usize, plus: i32) {
+    fn add(&mut self, idx: usize, plus: T) {
         let mut idx = (idx + 1) as isize;
         while idx <= self.len {
-            self.inner[idx as usize] += plus;
+            self.inner[idx as usize] = self.inner[idx as usize] + plus;
             idx += idx & -idx;
         }
     }
     /// return sum of range 0..range_max
-    fn sum(&self, range_max: usize) -> i32 {
-        let mut sum = 0;
+    fn sum(&self, range_max: usize) -> T {
+        let mut sum = T::zero();
         let mut idx = range_max as isize;
         while idx > 0 {
-            sum += self.inner[idx as usize];
+            sum = sum + self.inner[idx as usize];
             idx -= idx & -idx;
         }
         sum
     }
     /// return sum of range 0..range_max
-    fn sum_range(&self, range: Range<usize>) -> i32 {
+    fn sum_range(&self, range: Range<usize>) -> T {
         let sum1 = self.sum(range.end);
         if range.start == 0 {
-            return sum1;
+            sum1
         } else {
             let sum2 = self.sum(range.start);
             sum1 - sum2
         }
     }
     /// return minimum i where array[0] + array[1] + ... + array[i] >= query (1 <= i <= N)
-    fn lower_bound(&self, mut query: i32) -> usize {
-        if query <= 0 {
+    fn lower_bound(&self, mut query: T) -> usize {
+        if query <= T::zero() {
             return 0;
         }
         let mut k = 1;
@@ -215,7 +428,7 @@ impl FenwickTree {
             }
             let val = self.inner[nxt as usize];
             if val < query {
-                query -= val;
+                query = query - val;
                 cur += k;
             }
         }
@@ -294,6 +507,70 @@ mod fenwick_set_test {
         }
     }
     #[test]
+    fn bulk_update() {
+        let max = 1000;
+        let mut rng = RngHandle::new();
+        let mut fws = FenwickSet::with_capacity(max);
+        let mut bts = BTreeSet::new();
+        for _ in 0..100 {
+            let (a, b) = (rng.range(0..max + 10), rng.range(0..max + 10));
+            let range = cmp::min(a, b)..cmp::max(a, b);
+            if rng.range(0..2) == 0 {
+                let inserted = range.clone().filter(|&i| i < max && bts.insert(i)).count();
+                assert_eq!(fws.insert_range(range), inserted);
+            } else {
+                let removed = range.clone().filter(|i| bts.remove(i)).count();
+                assert_eq!(fws.remove_range(range), removed);
+            }
+            assert_eq!(fws.len(), bts.len());
+        }
+        assert!(fws.iter().eq(bts.iter().cloned()));
+    }
+    #[test]
+    fn set_algebra() {
+        let a = FenwickSet::from_range(10..50);
+        let mut b = FenwickSet::with_capacity(100);
+        b.insert_range(40..80);
+        let elems = |s: FenwickSet| s.into_iter().collect::<Vec<_>>();
+        assert_eq!(elems(a.union(&b)), (10..80).collect::<Vec<_>>());
+        assert_eq!(elems(a.intersection(&b)), (40..50).collect::<Vec<_>>());
+        assert_eq!(elems(a.difference(&b)), (10..40).collect::<Vec<_>>());
+        assert_eq!(elems(b.difference(&a)), (50..80).collect::<Vec<_>>());
+        assert_eq!(a.union(&b).len(), 70);
+    }
+    #[test]
+    fn huge_capacity() {
+        let max = 100_000_000;
+        let mut fws = FenwickSet::with_capacity(max);
+        for &i in &[3, 64, 12_345_678, max - 1] {
+            assert!(fws.insert(i));
+        }
+        assert!(!fws.insert(max));
+        assert_eq!(fws.nth(2), Some(12_345_678));
+        assert_eq!(fws.nth(3), Some(max - 1));
+        assert_eq!(fws.remove_range(0..100), 2);
+        assert_eq!(
+            fws.into_iter().collect::<Vec<_>>(),
+            vec![12_345_678, max - 1]
+        );
+    }
+    #[test]
+    fn same_as_tree() {
+        let max = COMPACT_THRESHOLD + 100;
+        let mut rng = RngHandle::new();
+        let mut chunked = FenwickSet::with_capacity(max);
+        let mut tree = FenwickSet::with_capacity(COMPACT_THRESHOLD);
+        for _ in 0..10000 {
+            let num = rng.range(0..COMPACT_THRESHOLD);
+            assert_eq!(chunked.insert(num), tree.insert(num));
+        }
+        assert_eq!(chunked.len(), tree.len());
+        for n in (0..tree.len()).step_by(97) {
+            assert_eq!(chunked.nth(n), tree.nth(n));
+        }
+        assert!(chunked.iter().eq(tree.iter()));
+    }
+    #[test]
     fn from_range() {
         let (start, end) = (40, 500);
         let fws = FenwickSet::from_range(start..end);
@@ -325,6 +602,17 @@ mod fenwick_tree_test {
         assert_eq!(sum, fenwick.sum_range(range));
     }
     #[test]
+    fn counts() {
+        let counts: Vec<i32> = (0..37).map(|i| i * 7 % 5).collect();
+        let fenwick = FenwickTree::from_counts(&counts);
+        let mut naive = FenwickTree::new(counts.len());
+        for (i, &c) in counts.iter().enumerate() {
+            naive.add(i, c);
+        }
+        assert_eq!(fenwick.inner, naive.inner);
+        assert_eq!(fenwick.counts(), counts);
+    }
+    #[test]
     fn lower_bound() {
         let max = 100;
         let mut fenwick = FenwickTree::new(max);
@@ -1,8 +1,10 @@
+//! geometric primitives shared by the dungeon, enemies and fights:
+//! coordinates, directions, rectangles and lines
 use derive_more::{
     Add, AddAssign, Div, DivAssign, From, Into, Mul, MulAssign, Neg, Sub, SubAssign,
 };
 use enum_iterator::IntoEnumIterator;
-use rect_iter::{FromTuple2, IntoTuple2};
+use rect_iter::{FromTuple2, IntoTuple2, RectRange};
 use serde::{Deserialize, Serialize};
 use std::{convert::TryInto, fmt};
 use tuple_map::TupleMap2;
@@ -182,6 +184,9 @@ impl Into<(usize, usize)> for Coord {
 
 pub struct Positioned<T>(pub Coord, pub T);
 
+/// a rectangle, like the area of a room
+pub type Rect = RectRange<i32>;
+
 #[derive(
     Clone,
     Copy,
@@ -226,6 +231,28 @@ impl fmt::Display for Direction {
 }
 
 impl Direction {
+    /// the four orthogonal directions
+    pub const ORTHOGONAL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+    /// all directions to adjacent cells, in the same order as `into_enum_iter`
+    pub const NEIGHBORS: [Direction; 8] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+        Direction::LeftUp,
+        Direction::RightUp,
+        Direction::LeftDown,
+        Direction::RightDown,
+    ];
+    /// the direction of a step, where `step` is the difference of two adjacent cells
+    pub fn from_cd(step: Coord) -> Option<Direction> {
+        Direction::into_enum_iter().find(|d| d.to_cd() == step)
+    }
     pub fn to_cd(self) -> Coord {
         use self::Direction::*;
         match self {
@@ -312,3 +339,80 @@ where
         Some(cur)
     }
 }
+
+/// Cells on the line from `from` to `to` by Bresenham's algorithm,
+/// which doesn't contain `from` but contains `to`.
+/// Adjacent cells on the line are always next to each other, including diagonally.
+pub fn line(from: Coord, to: Coord) -> Line {
+    let (dx, dy) = ((to.x - from.x).0, (to.y - from.y).0);
+    Line {
+        cur: from,
+        to,
+        dx: dx.abs(),
+        dy: -dy.abs(),
+        step: Coord::new(dx.signum(), dy.signum()),
+        err: dx.abs() - dy.abs(),
+    }
+}
+
+/// Iterator returned by `line`
+#[derive(Clone, Debug)]
+pub struct Line {
+    cur: Coord,
+    to: Coord,
+    dx: i32,
+    dy: i32,
+    step: Coord,
+    err: i32,
+}
+
+impl Iterator for Line {
+    type Item = Coord;
+    fn next(&mut self) -> Option<Coord> {
+        if self.cur == self.to {
+            return None;
+        }
+        let e2 = self.err * 2;
+        if e2 >= self.dy {
+            self.err += self.dy;
+            self.cur.x += self.step.x;
+        }
+        if e2 <= self.dx {
+            self.err += self.dx;
+            self.cur.y += self.step.y;
+        }
+        Some(self.cur)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn direction_from_cd() {
+        for &d in Direction::NEIGHBORS.iter() {
+            assert_eq!(Direction::from_cd(d.to_cd()), Some(d));
+        }
+        assert_eq!(Direction::from_cd(Coord::new(2, 0)), None);
+        let all: Vec<_> = Direction::into_enum_iter().take(8).collect();
+        assert_eq!(all, Direction::NEIGHBORS.to_vec());
+    }
+    #[test]
+    fn bresenham_line() {
+        let from = Coord::new(1, 1);
+        let diag: Vec<_> = line(from, Coord::new(4, 4)).collect();
+        assert_eq!(
+            diag,
+            vec![Coord::new(2, 2), Coord::new(3, 3), Coord::new(4, 4)]
+        );
+        let shallow: Vec<_> = line(from, Coord::new(5, 3)).collect();
+        assert_eq!(shallow.len(), 4);
+        assert_eq!(shallow.last(), Some(&Coord::new(5, 3)));
+        let mut prev = from;
+        for cd in shallow {
+            assert!(prev.is_adjacent(cd) && prev != cd);
+            prev = cd;
+        }
+        assert_eq!(line(from, from).count(), 0);
+    }
+}
@@ -1,8 +1,10 @@
 //! a module for handling user input
 use crate::character::Action;
 use crate::dungeon::Direction;
+use crate::error::*;
+use anyhow::bail;
 use regex::Regex;
-use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde::de::{Deserialize, Deserializer, Error as _, MapAccess, Visitor};
 use serde::ser::{Serialize, SerializeMap, Serializer};
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -18,6 +20,57 @@ impl KeyMap {
     pub fn get(&self, key: Key) -> Option<InputCode> {
         self.inner.get(&key).map(|code| code.to_owned())
     }
+    /// bind `key` to `code`, and returns the old binding
+    pub fn bind(&mut self, key: Key, code: InputCode) -> Option<InputCode> {
+        self.inner.insert(key, code)
+    }
+    /// remove the binding of `key`, and returns it
+    pub fn unbind(&mut self, key: Key) -> Option<InputCode> {
+        self.inner.remove(&key)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (Key, InputCode)> + '_ {
+        self.inner.iter().map(|(&k, &v)| (k, v))
+    }
+    /// apply changes in `patch`, failing if it has conflicting changes
+    pub fn apply(&mut self, patch: &KeyMapPatch) -> GameResult<()> {
+        let mut unbind = Vec::with_capacity(patch.unbind.len());
+        for name in &patch.unbind {
+            let key = match Key::from_name(name) {
+                Some(key) => key,
+                None => bail!(ErrorKind::InvalidSetting(
+                    format!("invalid key name {} in keymap", name).into()
+                )),
+            };
+            if patch.bind.inner.contains_key(&key) {
+                bail!(ErrorKind::InvalidSetting(
+                    format!("key {} is both bound and unbound in keymap", name).into()
+                ));
+            }
+            unbind.push(key);
+        }
+        if patch.clear {
+            self.inner.clear();
+        }
+        for key in unbind {
+            self.unbind(key);
+        }
+        self.inner.extend(patch.bind.iter());
+        Ok(())
+    }
+}
+
+/// Changes to a keymap, to customize key bindings (e.g., WASD keys instead of vi-keys)
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct KeyMapPatch {
+    /// remove all bindings before applying other changes
+    #[serde(default)]
+    pub clear: bool,
+    /// names of keys whose bindings are removed
+    #[serde(default)]
+    pub unbind: Vec<String>,
+    /// new bindings
+    #[serde(default)]
+    pub bind: KeyMap,
 }
 
 impl Default for KeyMap {
@@ -55,6 +108,10 @@ impl Default for KeyMap {
             (Key::Char('s'), InputCode::Act(Action::Search)),
             (Key::Char('.'), InputCode::Act(Action::NoOp)),
             (Key::Char('>'), InputCode::Act(Action::DownStair)),
+            (Key::Char('<'), InputCode::Act(Action::UpStair)),
+            (Key::Char(','), InputCode::Act(Action::PickUp)),
+            (Key::Char('x'), InputCode::Act(Action::AutoExplore)),
+            (Key::Char('Z'), InputCode::Act(Action::Rest)),
             (Key::Up, InputCode::Act(Action::Move(Up))),
             (Key::Down, InputCode::Act(Action::Move(Down))),
             (Key::Left, InputCode::Act(Action::Move(Left))),
@@ -134,12 +191,22 @@ impl<'de> Visitor<'de> for KeyMapVisitor {
         M: MapAccess<'de>,
     {
         let mut inner = HashMap::new();
-        while let Some((k, v)) = access.next_entry()? {
-            let key = match Key::from_str(k) {
-                Some(k) => k,
-                None => panic!("invalid key name {} in KeyMap", k),
+        while let Some((k, v)) = access.next_entry::<String, _>()? {
+            let key = match Key::from_name(&k) {
+                Some(key) => key,
+                None => {
+                    return Err(M::Error::custom(format!(
+                        "invalid key name {} in KeyMap",
+                        k
+                    )))
+                }
             };
-            inner.insert(key, v);
+            if inner.insert(key, v).is_some() {
+                return Err(M::Error::custom(format!(
+                    "key {} is bound twice in KeyMap",
+                    k
+                )));
+            }
         }
         Ok(KeyMap { inner })
     }
@@ -162,6 +229,43 @@ pub enum InputCode {
     Sys(System),
 }
 
+impl InputCode {
+    /// what the input does, for help screens
+    pub fn description(&self) -> String {
+        match self {
+            InputCode::Act(act) => act.description(),
+            InputCode::Both { act, sys } => {
+                format!("{} / {}", act.description(), sys.description())
+            }
+            InputCode::Sys(sys) => sys.description().to_owned(),
+        }
+    }
+}
+
+impl Action {
+    /// what the action does, for help screens
+    pub fn description(&self) -> String {
+        match self {
+            Action::Move(d) => format!("move {}", d),
+            Action::MoveUntil(d) => format!("run {}", d),
+            Action::UpStair => "go up the stairs".to_owned(),
+            Action::DownStair => "go down the stairs".to_owned(),
+            Action::Search => "search for secret doors and traps".to_owned(),
+            Action::Throw(d) => format!("throw a missile {}", d),
+            Action::Drop(c) => format!("drop the item {}", c),
+            Action::DropSome(c, n) => format!("drop {} of the item {}", n, c),
+            Action::PickUp => "pick up the item here".to_owned(),
+            Action::Read(c) => format!("read the scroll {}", c),
+            Action::Open(d) => format!("open the door {}", d),
+            Action::Close(d) => format!("close the door {}", d),
+            Action::AutoExplore => "explore automatically".to_owned(),
+            Action::TravelTo(cd) => format!("travel to ({}, {})", cd.x.0, cd.y.0),
+            Action::Rest => "rest until healed".to_owned(),
+            Action::NoOp => "do nothing for a turn".to_owned(),
+        }
+    }
+}
+
 /// System input
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
 pub enum System {
@@ -175,6 +279,22 @@ pub enum System {
     Yes,
 }
 
+impl System {
+    /// what the input does, for help screens
+    pub fn description(&self) -> &'static str {
+        match self {
+            System::Cancel => "cancel",
+            System::Continue => "continue",
+            System::Enter => "enter",
+            System::Inventory => "show the inventory",
+            System::No => "no",
+            System::Save => "save the game",
+            System::Quit => "quit the game",
+            System::Yes => "yes",
+        }
+    }
+}
+
 /// a representation of Keyboard input
 /// almost same as termion::event::Key
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
@@ -200,7 +320,7 @@ pub enum Key {
 }
 
 impl Key {
-    fn to_str(&self) -> Cow<'static, str> {
+    pub(crate) fn to_str(self) -> Cow<'static, str> {
         use self::Key::*;
         match self {
             BackTab => "BackTab".into(),
@@ -223,12 +343,14 @@ impl Key {
             Esc => "Esc".into(),
         }
     }
-    fn from_str(s: &str) -> Option<Self> {
+    /// parse a key name, like "j", "F1" or "Ctrl+a"
+    pub fn from_name(s: &str) -> Option<Self> {
         use self::Key::*;
         let f = Regex::new(r"F([0-9]+)").unwrap();
         let alt = Regex::new(r"Alt\s*\+\s*(.+)").unwrap();
         let ctrl = Regex::new(r"Ctrl\s*\+\s*(.+)").unwrap();
         match s {
+            "BackTab" => Some(BackTab),
             "Backspace" => Some(Backspace),
             "Left" => Some(Left),
             "Right" => Some(Right),
@@ -260,6 +382,12 @@ impl Key {
     }
 }
 
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
 #[cfg(test)]
 mod keymap_test {
     use super::*;
@@ -268,18 +396,52 @@ mod keymap_test {
     use std::io::prelude::*;
     use std::path::Path;
     #[test]
-    fn from_str_() {
-        let f1 = Key::from_str("F1").unwrap();
+    fn from_name() {
+        let f1 = Key::from_name("F1").unwrap();
         assert_eq!(f1, Key::F(1));
-        assert_eq!(Key::from_str("FO"), None);
-        let alt5 = Key::from_str("Alt+5").unwrap();
+        assert_eq!(Key::from_name("FO"), None);
+        let alt5 = Key::from_name("Alt+5").unwrap();
         assert_eq!(alt5, Key::Alt('5'));
-        let ctrl_a = Key::from_str("Ctrl+a").unwrap();
+        let ctrl_a = Key::from_name("Ctrl+a").unwrap();
         assert_eq!(ctrl_a, Key::Ctrl('a'));
-        let j = Key::from_str("j").unwrap();
+        let j = Key::from_name("j").unwrap();
         assert_eq!(j, Key::Char('j'));
     }
     #[test]
+    fn patch() {
+        let patch = r#"{
+            "unbind": ["h", "j", "k", "l"],
+            "bind": {"w": {"Act": {"Move": "Up"}}, "a": {"Act": {"Move": "Left"}}}
+        }"#;
+        let patch: KeyMapPatch = json::from_str(patch).unwrap();
+        let mut keymap = KeyMap::default();
+        keymap.apply(&patch).unwrap();
+        assert_eq!(keymap.get(Key::Char('h')), None);
+        assert_eq!(
+            keymap.get(Key::Char('w')),
+            Some(InputCode::Act(Action::Move(Direction::Up)))
+        );
+        assert_eq!(
+            keymap.get(Key::Up),
+            Some(InputCode::Act(Action::Move(Direction::Up)))
+        );
+        let conflict = r#"{"unbind": ["w"], "bind": {"w": {"Act": "Search"}}}"#;
+        let conflict: KeyMapPatch = json::from_str(conflict).unwrap();
+        assert!(keymap.apply(&conflict).is_err());
+        let twice = r#"{"w": {"Act": "Search"}, "w": {"Act": "Rest"}}"#;
+        assert!(json::from_str::<KeyMap>(twice).is_err());
+        assert!(json::from_str::<KeyMap>(r#"{"Foo": {"Act": "Search"}}"#).is_err());
+    }
+    #[test]
+    fn description() {
+        let keymap = KeyMap::default();
+        let h = keymap.get(Key::Char('h')).unwrap();
+        assert_eq!(h.description(), "move left");
+        let y = keymap.get(Key::Char('y')).unwrap();
+        assert_eq!(y.description(), "move left up / yes");
+        assert_eq!(Key::Ctrl('p').to_string(), "Ctrl+p");
+    }
+    #[test]
     fn serde() {
         let keymap = KeyMap::default();
         let ser = json::to_string(&keymap).unwrap();
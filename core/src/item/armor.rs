@@ -1,9 +1,11 @@
+use super::enchant::{self, Enchant, Rates};
 use super::handler::{Handler, ItemInner, ItemStat};
 use super::{InitItem, Item, ItemAttr, ItemKind, ItemNum};
 use crate::{
     character::Defense,
     rng::{Parcent, RngHandle},
     smallstr::SmallStr,
+    validate::Validator,
 };
 use std::fmt;
 
@@ -43,14 +45,38 @@ const fn default_powerup_rate() -> Parcent {
 }
 
 fn is_default_cursed_rate(u: &Parcent) -> bool {
-    cfg!(not(test)) && *u == default_cursed_rate()
+    crate::skip_defaults() && *u == default_cursed_rate()
 }
 
 fn is_default_powerup_rate(u: &Parcent) -> bool {
-    cfg!(not(test)) && *u == default_powerup_rate()
+    crate::skip_defaults() && *u == default_powerup_rate()
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.field("armors", |v| {
+            for (i, preset) in self.armors.iter().enumerate() {
+                if let Preset::Builtin(b) = *preset {
+                    v.element(i, |v| v.builtin(b, BUILTIN_ARMORS.len()));
+                }
+            }
+        });
+        v.parcent("cursed_rate", self.cursed_rate);
+        v.parcent("powerup_rate", self.powerup_rate);
+    }
+    /// builtin armors are expanded to their definitions
+    pub(super) fn canonical(&self) -> Self {
+        let armors = self.armors.iter().map(|preset| match *preset {
+            Preset::Builtin(i) if i < BUILTIN_ARMORS.len() => {
+                Preset::Custom(BUILTIN_ARMORS[i].clone())
+            }
+            ref preset => preset.clone(),
+        });
+        Config {
+            armors: armors.collect(),
+            ..self.clone()
+        }
+    }
     pub(super) fn build(self) -> Handler<ArmorStatus> {
         let Config {
             cursed_rate,
@@ -58,8 +84,10 @@ impl Config {
             armors,
         } = self;
         Handler {
-            cursed_rate,
-            powerup_rate,
+            rates: Rates {
+                cursed: cursed_rate.into(),
+                powerup: powerup_rate.into(),
+            },
             stats: armors.into_iter().map(Preset::build).collect(),
         }
     }
@@ -100,25 +128,41 @@ impl Armor {
     pub fn name(&self) -> &str {
         self.name.as_ref()
     }
+    pub fn worth(&self) -> ItemNum {
+        self.worth
+    }
     pub fn def(&self) -> Defense {
         self.def + self.def_plus
     }
+    /// weaken the armor by rust, like aquators do in Rogue.
+    /// Leather armor never rusts.
+    pub(crate) fn rust(&mut self) -> bool {
+        if self.name == "leather armor" || self.def() <= Defense(1) {
+            return false;
+        }
+        self.def_plus -= Defense(1);
+        true
+    }
 }
 
 impl fmt::Display for Armor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        super::display_plus_types(self.def_plus.0.into(), f)?;
+        enchant::display_pluses(&self.pluses(), f)?;
         write!(f, " {}", self.name)
     }
 }
 
-impl ItemInner for Armor {
-    fn get_cursed(&mut self, rng: &mut RngHandle) {
-        self.def_plus -= Defense(rng.range(1..=4));
+impl Enchant for Armor {
+    type Bonus = i32;
+    fn enchant(&mut self, amount: i32) {
+        self.def_plus += Defense(amount);
     }
-    fn get_powerup(&mut self, rng: &mut RngHandle) {
-        self.def_plus += Defense(rng.range(1..=4));
+    fn pluses(&self) -> Vec<i64> {
+        vec![self.def_plus.0.into()]
     }
+}
+
+impl ItemInner for Armor {
     fn into_item(self, attr: ItemAttr, how_many: ItemNum) -> Item {
         Item {
             kind: ItemKind::Armor(self),
@@ -0,0 +1,90 @@
+//! enchantments, which make generated items cursed or powered up
+use super::ItemAttr;
+use crate::rng::{Probability, RngHandle};
+use num_traits::{NumCast, One, PrimInt};
+use rand::distributions::uniform::SampleUniform;
+use std::fmt;
+use std::ops::Neg;
+
+/// the max amount of an enchantment
+const MAX_AMOUNT: u8 = 4;
+
+/// Items which have bonuses(like `+1,+2` of weapons) changed by enchantments
+pub(super) trait Enchant {
+    type Bonus: PrimInt + SampleUniform + Neg<Output = Self::Bonus>;
+    /// add `amount`(negative when cursed) to the bonus
+    fn enchant(&mut self, amount: Self::Bonus);
+    /// bonuses displayed before the name
+    fn pluses(&self) -> Vec<i64>;
+}
+
+/// How often an item category gets enchanted
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(super) struct Rates {
+    pub cursed: Probability,
+    pub powerup: Probability,
+}
+
+impl Rates {
+    /// curse `item` at the rate of `cursed`, or power it up at the rate of `powerup`
+    pub fn apply<E: Enchant>(&self, item: &mut E, attr: &mut ItemAttr, rng: &mut RngHandle) {
+        let amount = |rng: &mut RngHandle| {
+            rng.range(<E::Bonus as One>::one()..=<E::Bonus as NumCast>::from(MAX_AMOUNT).unwrap())
+        };
+        if rng.chance(self.cursed) {
+            attr.or(ItemAttr::IS_CURSED);
+            item.enchant(-amount(rng));
+        } else if rng.chance(self.powerup) {
+            item.enchant(amount(rng));
+        }
+    }
+}
+
+/// display `pluses` like `+1,-2`
+pub(super) fn display_pluses(pluses: &[i64], f: &mut fmt::Formatter) -> fmt::Result {
+    for (i, &plus) in pluses.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        if plus < 0 {
+            write!(f, "-{}", -plus)?;
+        } else {
+            write!(f, "+{}", plus)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::{armor, weapon, ItemKind};
+    use crate::rng::Parcent;
+    #[test]
+    fn enchant_rates() {
+        let mut config = weapon::Config::default();
+        config.cursed_rate = Probability::ONE;
+        let handler = config.build();
+        let mut rng = RngHandle::from_seed(1);
+        for _ in 0..20 {
+            let item = handler.gen_item(&mut rng);
+            assert!(item.attr.contains(ItemAttr::IS_CURSED));
+            match item.kind {
+                ItemKind::Weapon(w) => assert!(w.hit_plus.0 < 0),
+                _ => panic!("not a weapon: {:?}", item),
+            }
+        }
+        let mut config = armor::Config::default();
+        config.cursed_rate = Parcent(0);
+        config.powerup_rate = Parcent(100);
+        let handler = config.build();
+        for _ in 0..20 {
+            let item = handler.gen_item(&mut rng);
+            assert!(!item.attr.contains(ItemAttr::IS_CURSED));
+            match item.kind {
+                ItemKind::Armor(a) => assert!(a.def_plus.0 > 0),
+                _ => panic!("not an armor: {:?}", item),
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::ItemNum;
+use crate::difficulty;
 use crate::rng::RngHandle;
 
 /// Gold configuration
@@ -15,8 +16,8 @@ pub struct Config {
 }
 
 impl Config {
-    pub(super) fn gen(&self, rng: &mut RngHandle, level: u32) -> Option<ItemNum> {
-        if !rng.does_happen(self.rate_inv) {
+    pub(super) fn gen(&self, rng: &mut RngHandle, level: u32, appear: u32) -> Option<ItemNum> {
+        if !rng.does_happen(difficulty::scale_rate_inv(self.rate_inv, appear)) {
             return None;
         }
         let num = rng.range(0..self.base + self.per_level * level) + self.minimum;
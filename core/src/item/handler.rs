@@ -1,14 +1,13 @@
+use super::enchant::{Enchant, Rates};
 use super::{Item, ItemAttr, ItemNum};
 use crate::rng::{Parcent, RngHandle};
 
 pub(super) trait ItemInner {
-    fn get_cursed(&mut self, _rng: &mut RngHandle) {}
-    fn get_powerup(&mut self, _rng: &mut RngHandle) {}
     fn into_item(self, attr: ItemAttr, num: ItemNum) -> Item;
 }
 
 pub(super) trait ItemStat {
-    type Item: ItemInner;
+    type Item: ItemInner + Enchant;
     fn appear_rate(&self) -> Parcent;
     fn build(self, rng: &mut RngHandle) -> (Self::Item, ItemAttr, ItemNum);
     fn name(&self) -> &str;
@@ -23,10 +22,10 @@ where
     let rate = rng.range(1..100);
     let mut sum = 0;
     for (i, p) in iter.enumerate() {
-        if sum < rate && rate <= sum {
+        sum += p.appear_rate().0;
+        if rate <= sum {
             return i;
         }
-        sum += p.appear_rate().0;
     }
     0
 }
@@ -34,8 +33,7 @@ where
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub(super) struct Handler<S> {
     pub stats: Vec<S>,
-    pub cursed_rate: Parcent,
-    pub powerup_rate: Parcent,
+    pub rates: Rates,
 }
 
 impl<S: Clone + ItemStat> Handler<S> {
@@ -43,12 +41,7 @@ impl<S: Clone + ItemStat> Handler<S> {
         let idx = select_item(rng, self.stats.iter());
         let status = self.stats[idx].clone();
         let (mut item, mut attr, num) = status.build(rng);
-        if rng.parcent(self.cursed_rate) {
-            attr.or(ItemAttr::IS_CURSED);
-            item.get_cursed(rng);
-        } else if rng.parcent(self.powerup_rate) {
-            item.get_powerup(rng);
-        }
+        self.rates.apply(&mut item, &mut attr, rng);
         item.into_item(attr, num)
     }
     pub fn gen_item_by(
@@ -0,0 +1,485 @@
+//! identification of magic items like potions or scrolls
+use super::ItemKind;
+use crate::{rng::RngHandle, smallstr::SmallStr};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// kinds of items which are unidentified at first
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ItemClass {
+    Potion,
+    Ring,
+    Scroll,
+    Wand,
+}
+
+impl ItemClass {
+    pub fn from_kind(kind: &ItemKind) -> Option<(ItemClass, &str)> {
+        match kind {
+            ItemKind::Potion(name) => Some((ItemClass::Potion, name.as_str())),
+            ItemKind::Ring(name) => Some((ItemClass::Ring, name.as_str())),
+            ItemKind::Scroll(name) => Some((ItemClass::Scroll, name.as_str())),
+            ItemKind::Wand(name) => Some((ItemClass::Wand, name.as_str())),
+            _ => None,
+        }
+    }
+    /// classes of items which the scroll identifies, if it's a scroll of identify.
+    /// Weapons and armors are always identified, so scrolls for them identify nothing.
+    pub fn identified_by(scroll: &str) -> Option<&'static [ItemClass]> {
+        match scroll {
+            "identify potion" => Some(&[ItemClass::Potion]),
+            "identify scroll" => Some(&[ItemClass::Scroll]),
+            "identify ring, wand or staff" => Some(&[ItemClass::Ring, ItemClass::Wand]),
+            "identify weapon" | "identify armor" => Some(&[]),
+            _ => None,
+        }
+    }
+    fn true_names(self) -> &'static [&'static str] {
+        match self {
+            ItemClass::Potion => &POTIONS,
+            ItemClass::Ring => &RINGS,
+            ItemClass::Scroll => &SCROLLS,
+            ItemClass::Wand => &WANDS,
+        }
+    }
+}
+
+impl fmt::Display for ItemClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ItemClass::Potion => "potion",
+            ItemClass::Ring => "ring",
+            ItemClass::Scroll => "scroll",
+            ItemClass::Wand => "wand",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// capability to identify items, used by the scroll of identify and
+/// by auto-identification on use
+pub trait Identify {
+    /// identify the item and returns if it was unidentified or not
+    fn identify(&mut self, kind: &ItemKind) -> bool;
+    fn is_identified(&self, kind: &ItemKind) -> bool;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+struct Entry {
+    class: ItemClass,
+    name: SmallStr,
+    /// the name shown while the item is unidentified (e.g., "blue")
+    appearance: SmallStr,
+    identified: bool,
+    /// the item was used, but its effect wasn't observed
+    #[serde(default)]
+    tried: bool,
+    /// the nickname given by the player
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    called: Option<SmallStr>,
+}
+
+/// global table of identified/unidentified names of items
+/// This is a part of game state and has to be saved with the game.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct IdentifyTable {
+    entries: Vec<Entry>,
+}
+
+impl IdentifyTable {
+    /// assign random appearances to all items
+    pub fn new(rng: &mut RngHandle) -> Self {
+        let titles: Vec<SmallStr> = (0..SCROLLS.len()).map(|_| scroll_title(rng)).collect();
+        let mut entries = vec![];
+        let mut assign = |class: ItemClass, appearances: Vec<SmallStr>| {
+            let names = class.true_names();
+            let selected = rng.select(0..appearances.len()).take(names.len());
+            entries.extend(names.iter().zip(selected).map(|(name, i)| Entry {
+                class,
+                name: SmallStr::from_str(name),
+                appearance: appearances[i].clone(),
+                identified: false,
+                tried: false,
+                called: None,
+            }));
+        };
+        let from_static =
+            |v: &[&str]| -> Vec<SmallStr> { v.iter().map(|s| SmallStr::from_str(s)).collect() };
+        assign(ItemClass::Potion, from_static(&COLORS));
+        assign(ItemClass::Ring, from_static(&STONES));
+        assign(ItemClass::Wand, from_static(&WOODS));
+        assign(ItemClass::Scroll, titles);
+        IdentifyTable { entries }
+    }
+    /// returns the name of `num` items to display, if the item needs identification
+    pub fn name(&self, kind: &ItemKind, num: u32) -> Option<String> {
+        let entry = self.entry(kind)?;
+        let (class, s) = (entry.class, if num == 1 { "" } else { "s" });
+        if entry.identified {
+            return Some(format!("{}{} of {}", class, s, entry.name));
+        }
+        let mut res = match (class, &entry.called) {
+            (_, Some(called)) => format!("{}{} called {}", class, s, called),
+            (ItemClass::Scroll, None) => format!("{}{} titled '{}'", class, s, entry.appearance),
+            (_, None) => format!("{} {}{}", entry.appearance, class, s),
+        };
+        if entry.tried {
+            res.push_str(" (tried)");
+        }
+        Some(res)
+    }
+    /// record that the item was used, and identify it if its effect was observed.
+    /// Returns if the item is newly identified or not.
+    pub fn used(&mut self, kind: &ItemKind, observed: bool) -> bool {
+        if observed {
+            return self.identify(kind);
+        }
+        if let Some(entry) = self.entry_mut(kind) {
+            entry.tried = true;
+        }
+        false
+    }
+    /// give a nickname to the unidentified item, or remove it if `name` is None.
+    /// Returns false if the item doesn't need identification or is already identified.
+    pub fn call(&mut self, kind: &ItemKind, name: Option<SmallStr>) -> bool {
+        match self.entry_mut(kind) {
+            Some(entry) if !entry.identified => {
+                entry.called = name;
+                true
+            }
+            _ => false,
+        }
+    }
+    fn entry(&self, kind: &ItemKind) -> Option<&Entry> {
+        let (class, name) = ItemClass::from_kind(kind)?;
+        self.entries
+            .iter()
+            .find(|e| e.class == class && e.name == name)
+    }
+    fn entry_mut(&mut self, kind: &ItemKind) -> Option<&mut Entry> {
+        let (class, name) = ItemClass::from_kind(kind)?;
+        self.entries
+            .iter_mut()
+            .find(|e| e.class == class && e.name == name)
+    }
+}
+
+impl Identify for IdentifyTable {
+    fn identify(&mut self, kind: &ItemKind) -> bool {
+        match self.entry_mut(kind) {
+            Some(entry) if !entry.identified => {
+                entry.identified = true;
+                true
+            }
+            _ => false,
+        }
+    }
+    /// items which don't need identification (e.g., weapons) are regarded as identified
+    fn is_identified(&self, kind: &ItemKind) -> bool {
+        self.entry(kind).is_none_or(|e| e.identified)
+    }
+}
+
+fn scroll_title(rng: &mut RngHandle) -> SmallStr {
+    let words = rng.range(1..4);
+    let title = (0..words)
+        .map(|_| {
+            let syllables = rng.range(1..4);
+            (0..syllables)
+                .map(|_| SYLLABLES[rng.range(0..SYLLABLES.len())])
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    SmallStr::from_string(title)
+}
+
+const POTIONS: [&str; 14] = [
+    "confusion",
+    "hallucination",
+    "poison",
+    "gain strength",
+    "see invisible",
+    "healing",
+    "monster detection",
+    "magic detection",
+    "raise level",
+    "extra healing",
+    "haste self",
+    "restore strength",
+    "blindness",
+    "levitation",
+];
+
+const RINGS: [&str; 14] = [
+    "protection",
+    "add strength",
+    "sustain strength",
+    "searching",
+    "see invisible",
+    "adornment",
+    "aggravate monster",
+    "dexterity",
+    "increase damage",
+    "regeneration",
+    "slow digestion",
+    "teleportation",
+    "stealth",
+    "maintain armor",
+];
+
+const SCROLLS: [&str; 18] = [
+    "monster confusion",
+    "magic mapping",
+    "hold monster",
+    "sleep",
+    "enchant armor",
+    "identify potion",
+    "identify scroll",
+    "identify weapon",
+    "identify armor",
+    "identify ring, wand or staff",
+    "scare monster",
+    "food detection",
+    "teleportation",
+    "enchant weapon",
+    "create monster",
+    "remove curse",
+    "aggravate monsters",
+    "protect armor",
+];
+
+const WANDS: [&str; 14] = [
+    "light",
+    "invisibility",
+    "lightning",
+    "fire",
+    "cold",
+    "polymorph",
+    "magic missile",
+    "haste monster",
+    "slow monster",
+    "drain life",
+    "nothing",
+    "teleport away",
+    "teleport to",
+    "cancellation",
+];
+
+const COLORS: [&str; 27] = [
+    "amber",
+    "aquamarine",
+    "black",
+    "blue",
+    "brown",
+    "clear",
+    "crimson",
+    "cyan",
+    "ecru",
+    "gold",
+    "green",
+    "grey",
+    "magenta",
+    "orange",
+    "pink",
+    "plaid",
+    "purple",
+    "red",
+    "silver",
+    "tan",
+    "tangerine",
+    "topaz",
+    "turquoise",
+    "vermilion",
+    "violet",
+    "white",
+    "yellow",
+];
+
+const STONES: [&str; 26] = [
+    "agate",
+    "alexandrite",
+    "amethyst",
+    "carnelian",
+    "diamond",
+    "emerald",
+    "germanium",
+    "granite",
+    "garnet",
+    "jade",
+    "kryptonite",
+    "lapis lazuli",
+    "moonstone",
+    "obsidian",
+    "onyx",
+    "opal",
+    "pearl",
+    "peridot",
+    "ruby",
+    "sapphire",
+    "stibotantalite",
+    "tiger eye",
+    "topaz",
+    "turquoise",
+    "taaffeite",
+    "zircon",
+];
+
+const WOODS: [&str; 22] = [
+    "avocado wood",
+    "balsa",
+    "bamboo",
+    "banyan",
+    "birch",
+    "cedar",
+    "cherry",
+    "cypress",
+    "dogwood",
+    "driftwood",
+    "ebony",
+    "elm",
+    "eucalyptus",
+    "hemlock",
+    "holly",
+    "ironwood",
+    "mahogany",
+    "maple",
+    "oaken",
+    "redwood",
+    "rosewood",
+    "teak",
+];
+
+const SYLLABLES: [&str; 40] = [
+    "a", "ab", "ag", "aks", "ala", "an", "ankh", "app", "arg", "arze", "ash", "ban", "bar", "bat",
+    "bek", "bie", "bin", "bit", "bjor", "blu", "bot", "bu", "byt", "comp", "con", "cos", "cre",
+    "dalf", "dan", "den", "der", "doe", "dok", "eep", "el", "eng", "er", "ere", "erk", "esh",
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::Action;
+    use crate::input::InputCode;
+    use crate::item::{food::Food, Config, Item, ItemHandler};
+    use crate::{GameConfig, GameMsg, Reaction};
+    use std::collections::HashSet;
+    #[test]
+    fn appearances_are_unique() {
+        let mut rng = RngHandle::from_seed(1);
+        let table = IdentifyTable::new(&mut rng);
+        let potions: HashSet<_> = POTIONS
+            .iter()
+            .map(|name| table.name(&ItemKind::Potion(SmallStr::from_str(name)), 1))
+            .collect();
+        assert_eq!(potions.len(), POTIONS.len());
+        assert!(potions.iter().all(|name| name.is_some()));
+    }
+    #[test]
+    fn used_and_called() {
+        let mut rng = RngHandle::from_seed(1);
+        let mut table = IdentifyTable::new(&mut rng);
+        let blindness = ItemKind::Potion(SmallStr::from_str("blindness"));
+        let appearance = table.name(&blindness, 1).unwrap();
+        assert!(!table.used(&blindness, false));
+        assert_eq!(
+            table.name(&blindness, 1).unwrap(),
+            format!("{} (tried)", appearance)
+        );
+        assert!(table.call(&blindness, Some(SmallStr::from_str("dark"))));
+        assert_eq!(
+            table.name(&blindness, 2).unwrap(),
+            "potions called dark (tried)"
+        );
+        // nicknames are saved with the table
+        let json = serde_json::to_string(&table).unwrap();
+        let restored: IdentifyTable = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, table);
+        assert!(table.used(&blindness, true));
+        assert_eq!(table.name(&blindness, 1).unwrap(), "potion of blindness");
+        assert!(!table.call(&blindness, None));
+    }
+    #[test]
+    fn identify() {
+        let mut rng = RngHandle::from_seed(1);
+        let mut table = IdentifyTable::new(&mut rng);
+        let healing = ItemKind::Potion(SmallStr::from_str("healing"));
+        assert!(!table.is_identified(&healing));
+        assert_ne!(table.name(&healing, 1).unwrap(), "potion of healing");
+        assert!(table.identify(&healing));
+        assert!(!table.identify(&healing));
+        assert_eq!(table.name(&healing, 1).unwrap(), "potion of healing");
+        assert_eq!(table.name(&healing, 2).unwrap(), "potions of healing");
+        let gold = ItemKind::Gold;
+        assert!(!table.call(&gold, Some(SmallStr::from_str("money"))));
+        assert!(table.is_identified(&gold));
+        assert!(table.name(&gold, 1).is_none());
+        let json = serde_json::to_string(&table).unwrap();
+        let restored: IdentifyTable = serde_json::from_str(&json).unwrap();
+        assert_eq!(table, restored);
+    }
+    #[test]
+    fn articles() {
+        let handler = ItemHandler::new(Config::default(), 1);
+        let names: Vec<_> = POTIONS
+            .iter()
+            .map(|name| handler.kind_name(&ItemKind::Potion(SmallStr::from_str(name)), 1))
+            .collect();
+        for name in &names {
+            let (article, rest) = name.split_once(' ').unwrap();
+            let vowel = rest.starts_with(|c| "aeiou".contains(c));
+            assert_eq!(article, if vowel { "an" } else { "a" }, "{}", name);
+        }
+        assert!(names.iter().any(|name| name.starts_with("an ")));
+        let ration = ItemKind::Food(Food::Ration);
+        assert_eq!(handler.kind_name(&ration, 2), "2 foods");
+        assert_eq!(handler.item_name(&Item::new(ration, 1)), "A food");
+    }
+    #[test]
+    fn read_identify_scroll() {
+        let mut runtime = GameConfig::from_json(r#"{"seed": 1}"#)
+            .unwrap()
+            .build()
+            .unwrap();
+        let scroll = |name| ItemKind::Scroll(SmallStr::from_str(name));
+        let potion = ItemKind::Potion(SmallStr::from_str("healing"));
+        for item in vec![
+            Item::new(scroll("identify potion"), 1),
+            Item::new(scroll("sleep"), 1),
+            Item::new(potion.clone(), 2).many(),
+        ] {
+            let token = runtime.item.gen_item(item);
+            assert!(runtime.player.itembox.add(token));
+        }
+        let letter = |runtime: &crate::RunTime, kind: &ItemKind| {
+            ('a'..='z')
+                .find(|&c| {
+                    runtime
+                        .itembox()
+                        .get(c)
+                        .is_some_and(|t| t.get().kind == *kind)
+                })
+                .unwrap()
+        };
+        let read = |c| InputCode::Act(Action::Read(c));
+        assert_ne!(runtime.kind_name(&potion, 2), "2 potions of healing");
+        let identify = letter(&runtime, &scroll("identify potion"));
+        let res = runtime.react_to_input(read(identify)).unwrap();
+        assert!(res.contains(&Reaction::Notify(GameMsg::Identified(potion.clone()))));
+        assert_eq!(runtime.kind_name(&potion, 2), "2 potions of healing");
+        assert_eq!(
+            runtime.kind_name(&scroll("identify potion"), 1),
+            "a scroll of identify potion"
+        );
+        assert!(runtime.itembox().get(identify).is_none());
+        // scrolls without observable effects are only marked as tried
+        let sleep = letter(&runtime, &scroll("sleep"));
+        runtime.react_to_input(read(sleep)).unwrap();
+        assert!(runtime.kind_name(&scroll("sleep"), 1).ends_with("(tried)"));
+        // only scrolls can be read
+        let potions = letter(&runtime, &potion);
+        let res = runtime.react_to_input(read(potions)).unwrap();
+        assert_eq!(res, vec![Reaction::Notify(GameMsg::CantRead(potion))]);
+    }
+}
@@ -1,24 +1,115 @@
 //! utility for managing character's items
 use log::debug;
 
-use super::{Item, ItemToken};
-use crate::fenwick::FenwickSet;
+use super::{Item, ItemHandler, ItemId, ItemKind, ItemNum, ItemToken};
+use crate::snapshot::DeepCloner;
+use crate::{
+    fenwick::FenwickSet,
+    rng::{RngHandle, SliceRandom},
+};
+use std::cmp;
 use std::collections::BTreeMap;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ItemBox {
     empty_chars: FenwickSet,
     items: BTreeMap<usize, ItemToken>,
+    max_weight: u32,
 }
 
 impl ItemBox {
+    pub(crate) fn deep_clone(&self, cloner: &mut DeepCloner) -> ItemBox {
+        let mut res = self.clone();
+        for item in res.items.values_mut() {
+            *item = cloner.item(item);
+        }
+        res
+    }
+    /// maximum number of slots, each of which is labeled by 'a'-'z'
+    pub const MAX_SLOTS: usize = 26;
+    /// an item box without weight limit
     pub fn with_capacity(max_len: usize) -> Self {
+        Self::with_limits(max_len, u32::MAX)
+    }
+    /// an item box with at most `max_len` slots, which can hold items up to `max_weight`
+    pub fn with_limits(max_len: usize, max_weight: u32) -> Self {
+        let max_len = cmp::min(max_len, Self::MAX_SLOTS);
         ItemBox {
             empty_chars: FenwickSet::from_range(0..max_len),
             items: BTreeMap::new(),
+            max_weight,
+        }
+    }
+    /// returns the letter of the slot
+    pub fn letter(slot: usize) -> Option<char> {
+        match slot {
+            0..=25 => Some((b'a' + slot as u8) as char),
+            _ => None,
+        }
+    }
+    /// returns the slot of the letter
+    pub fn slot(letter: char) -> Option<usize> {
+        match letter {
+            'a'..='z' => Some(letter as usize - 'a' as usize),
+            _ => None,
         }
     }
+    /// total weight of the items in the box
+    pub fn weight(&self) -> u32 {
+        self.items().map(Item::weight).sum()
+    }
+    /// if the box can hold additional `weight` or not
+    fn can_carry(&self, weight: u32) -> bool {
+        self.weight().saturating_add(weight) <= self.max_weight
+    }
+    /// returns the item in the slot labeled by the letter
+    pub fn get(&self, letter: char) -> Option<&ItemToken> {
+        self.items.get(&Self::slot(letter)?)
+    }
+    /// returns all items with their letters, which are stable while the items are in the box
+    pub fn lettered(&self) -> impl Iterator<Item = (char, &ItemToken)> {
+        self.items
+            .iter()
+            .filter_map(|(&slot, token)| Some((Self::letter(slot)?, token)))
+    }
+    /// returns the letter of the item with `id`
+    pub fn letter_of(&self, id: ItemId) -> Option<char> {
+        self.items
+            .iter()
+            .find(|(_, token)| token.id() == id)
+            .and_then(|(&slot, _)| Self::letter(slot))
+    }
+    /// the amount of gold in the box
+    pub fn gold(&self) -> u32 {
+        self.find_by(|item| item.kind == ItemKind::Gold)
+            .map_or(0, |token| token.how_many.0)
+    }
+    /// pay `amount` gold, and returns if there was enough gold or not
+    pub(crate) fn pay_gold(&mut self, amount: u32) -> bool {
+        let (&ch, token) = match self
+            .items
+            .iter_mut()
+            .find(|(_, item)| item.kind == ItemKind::Gold)
+        {
+            Some(t) => t,
+            None => return amount == 0,
+        };
+        if token.how_many.0 < amount {
+            return false;
+        }
+        token.get_mut().how_many.0 -= amount;
+        if token.how_many.0 == 0 {
+            self.remove(ch);
+        }
+        true
+    }
+    pub fn is_full(&self) -> bool {
+        self.empty_chars.len() == 0
+    }
     pub fn add(&mut self, item: ItemToken) -> bool {
+        if !self.can_carry(item.weight()) {
+            return false;
+        }
         let ch = match self.empty_chars.nth(0) {
             Some(id) => id,
             None => return false,
@@ -30,11 +121,20 @@ impl ItemBox {
     pub fn entry(&mut self, token: &ItemToken) -> Option<Entry> {
         if token.is_many() {
             if let Some(merge_id) = self.check_merge(token.get()) {
+                let stack = self.items[&merge_id].get();
+                let mut merged = stack.clone();
+                merged.how_many += token.how_many;
+                if !self.can_carry(merged.weight() - stack.weight()) {
+                    return None;
+                }
                 return Some(Entry::Merge(MergeEntry(
                     self.items.get_mut(&merge_id).unwrap(),
                 )));
             }
         }
+        if !self.can_carry(token.weight()) {
+            return None;
+        }
         let insert_pos = self.empty_chars.nth(0)?;
         Some(Entry::Insert(InsertEntry(insert_pos, self)))
     }
@@ -50,11 +150,71 @@ impl ItemBox {
             .find(|(_, item)| query(item.get()))
             .map(|(_, i)| i)
     }
+    pub fn find_by_mut(&mut self, mut query: impl FnMut(&Item) -> bool) -> Option<&mut ItemToken> {
+        self.items
+            .iter_mut()
+            .find(|(_, item)| query(item.get()))
+            .map(|(_, i)| i)
+    }
+    pub fn remove(&mut self, ch: usize) -> Option<ItemToken> {
+        let item = self.items.remove(&ch)?;
+        self.empty_chars.insert(ch);
+        Some(item)
+    }
+    /// put an item back to the slot, if the slot is empty
+    pub(crate) fn put_back(&mut self, ch: usize, item: ItemToken) -> bool {
+        if self.items.contains_key(&ch) || !self.empty_chars.contains(ch) {
+            return false;
+        }
+        self.insert(ch, item);
+        true
+    }
+    /// take one item from the first stack which satisfies the query
+    pub fn take_one_by(
+        &mut self,
+        mut query: impl FnMut(&Item) -> bool,
+        handler: &mut ItemHandler,
+    ) -> Option<ItemToken> {
+        let ch = *self.items.iter().find(|(_, item)| query(item.get()))?.0;
+        self.split(ch, ItemNum(1), handler)
+    }
+    /// split `num` items off the stack in the slot as a new item.
+    /// The whole stack is removed if it has `num` items or less.
+    pub fn split(
+        &mut self,
+        ch: usize,
+        num: ItemNum,
+        handler: &mut ItemHandler,
+    ) -> Option<ItemToken> {
+        let token = self.items.get_mut(&ch)?;
+        if token.how_many > num {
+            token.get_mut().how_many -= num;
+            let mut item = token.get_cloned();
+            item.how_many = num;
+            item.attr.unequip();
+            return Some(handler.gen_item(item));
+        }
+        self.remove(ch)
+    }
+    /// remove an item randomly selected from items which satisfy the query
+    pub fn remove_random_by(
+        &mut self,
+        rng: &mut RngHandle,
+        mut query: impl FnMut(&Item) -> bool,
+    ) -> Option<ItemToken> {
+        let candidates: Vec<_> = self
+            .items
+            .iter()
+            .filter(|(_, item)| query(item.get()))
+            .map(|(ch, _)| *ch)
+            .collect();
+        let ch = *candidates.choose(rng)?;
+        self.remove(ch)
+    }
     fn check_merge(&self, got_item: &Item) -> Option<usize> {
-        // check if we can merge item or not
         self.items
             .iter()
-            .find(|(_, token)| token.get().kind == got_item.kind)
+            .find(|(_, token)| token.get().stacks_with(got_item))
             .map(|t| *t.0)
     }
     fn insert(&mut self, ch: usize, item: ItemToken) {
@@ -73,6 +233,10 @@ pub enum Entry<'a> {
 pub struct MergeEntry<'a>(&'a mut ItemToken);
 
 impl<'a> MergeEntry<'a> {
+    /// id of the stack into which the item is merged
+    pub fn id(&self) -> ItemId {
+        self.0.id()
+    }
     pub fn exec(self, item: Item) -> Item {
         self.0.get_mut().merge(item.clone(), |a, b| a | b);
         item
@@ -89,3 +253,143 @@ impl<'a> InsertEntry<'a> {
         res
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::{food::Food, Config, InitItem, ItemKind};
+    use crate::SmallStr;
+    #[test]
+    fn letters_are_stable() {
+        let mut handler = ItemHandler::new(Config::default(), 1);
+        let mut itembox = ItemBox::with_capacity(3);
+        for _ in 0..3 {
+            let item = handler.gen_item(Item::new(ItemKind::Gold, 1));
+            assert!(itembox.add(item));
+        }
+        assert!(itembox.is_full());
+        let letters: Vec<_> = itembox.lettered().map(|(c, _)| c).collect();
+        assert_eq!(letters, vec!['a', 'b', 'c']);
+        itembox.remove(ItemBox::slot('b').unwrap()).unwrap();
+        assert!(itembox.get('b').is_none());
+        assert!(itembox.get('c').is_some());
+        assert_eq!(ItemBox::letter(25), Some('z'));
+        assert_eq!(ItemBox::letter(26), None);
+        assert_eq!(ItemBox::slot('B'), None);
+    }
+    #[test]
+    fn weight_limit() {
+        let mut handler = ItemHandler::new(Config::default(), 1);
+        let mut itembox = ItemBox::with_limits(30, 3);
+        assert!(itembox.add(handler.gen_item(Item::new(ItemKind::Gold, 100).many())));
+        let food = Item::new(ItemKind::Food(Food::Ration), 2).many();
+        assert!(itembox.add(handler.gen_item(food.clone())));
+        assert_eq!(itembox.weight(), 2);
+        // merging 2 more rations exceeds the limit
+        assert!(itembox.entry(&handler.gen_item(food)).is_none());
+        let ration = Item::new(ItemKind::Food(Food::Ration), 1).many();
+        assert!(itembox.entry(&handler.gen_item(ration)).is_some());
+        // a stack of arrows weighs as one item
+        let arrows = InitItem::Weapon {
+            name: SmallStr::from_static("arrow"),
+            num_plus: 20,
+            hit_plus: 0,
+            dam_plus: 0,
+        }
+        .initialize(&mut handler)
+        .unwrap();
+        assert!(arrows.how_many > ItemNum(1));
+        assert!(itembox.add(arrows));
+        assert_eq!(itembox.weight(), 3);
+        // gold weighs nothing
+        assert!(itembox
+            .entry(&handler.gen_item(Item::new(ItemKind::Gold, 1).many()))
+            .is_some());
+        // slots beyond 'z' are unavailable
+        assert_eq!(
+            ItemBox::with_capacity(30).empty_chars.len(),
+            ItemBox::MAX_SLOTS
+        );
+    }
+    #[test]
+    fn registry() {
+        let mut handler = ItemHandler::new(Config::default(), 1);
+        let mut itembox = ItemBox::with_capacity(3);
+        let gold = handler.gen_item(Item::new(ItemKind::Gold, 1).many());
+        let id = gold.id();
+        assert!(itembox.add(gold));
+        let other = handler.gen_item(Item::new(ItemKind::Gold, 2).many());
+        match itembox.entry(&other) {
+            Some(Entry::Merge(entry)) => {
+                assert_eq!(entry.id(), id);
+                entry.exec(other.get_cloned());
+            }
+            _ => panic!("gold should be merged"),
+        }
+        let other_id = other.id();
+        drop(other);
+        assert!(handler.get(other_id).is_none());
+        assert_eq!(handler.get(id).unwrap().how_many, ItemNum(3));
+        assert_eq!(handler.ids(), vec![id]);
+        // used up items don't stay in the registry
+        for _ in 0..100 {
+            handler.gen_item(Item::new(ItemKind::Gold, 1).many());
+        }
+        assert_eq!(handler.items.borrow().len(), 1);
+        assert_eq!(itembox.letter_of(id), Some('a'));
+        assert_eq!(id.to_string(), "#0");
+    }
+    #[test]
+    fn pay_gold() {
+        let mut handler = ItemHandler::new(Config::default(), 1);
+        let mut itembox = ItemBox::with_capacity(3);
+        assert!(itembox.add(handler.gen_gold(30)));
+        assert!(!itembox.pay_gold(40));
+        assert!(itembox.pay_gold(10));
+        assert_eq!(itembox.gold(), 20);
+        assert!(itembox.pay_gold(20));
+        assert_eq!(itembox.gold(), 0);
+        assert!(itembox.get('a').is_none());
+    }
+    #[test]
+    fn split_and_merge() {
+        let mut handler = ItemHandler::new(Config::default(), 1);
+        let mut itembox = ItemBox::with_capacity(3);
+        let original = Item::new(ItemKind::Gold, 17).many();
+        assert_eq!(original.to_string(), "17 gold pieces");
+        assert!(itembox.add(handler.gen_item(original.clone())));
+        let split = itembox.split(0, ItemNum(5), &mut handler).unwrap();
+        let stack = itembox.get('a').unwrap();
+        assert_eq!(split.how_many, ItemNum(5));
+        assert_eq!(stack.how_many, ItemNum(12));
+        assert_ne!(split.id(), stack.id());
+        // stacks of different quantities aren't equal, but can be merged
+        assert_ne!(*split.get(), *stack.get());
+        assert!(stack.stacks_with(&split));
+        match itembox.entry(&split) {
+            Some(Entry::Merge(entry)) => entry.exec(split.get_cloned()),
+            _ => panic!("the split stack should be merged back"),
+        };
+        assert_eq!(*itembox.get('a').unwrap().get(), original);
+        let all = itembox.split(0, ItemNum(20), &mut handler).unwrap();
+        assert_eq!(*all.get(), original);
+        assert!(itembox.get('a').is_none());
+        assert_eq!(Item::new(ItemKind::Gold, 1).to_string(), "A gold piece");
+    }
+    #[test]
+    fn merge_only_many() {
+        let mut handler = ItemHandler::new(Config::default(), 1);
+        let mut itembox = ItemBox::with_capacity(3);
+        let gold = handler.gen_item(Item::new(ItemKind::Gold, 1).many());
+        assert!(itembox.add(gold.clone()));
+        match itembox.entry(&gold) {
+            Some(Entry::Merge(_)) => {}
+            _ => panic!("gold should be merged"),
+        }
+        let single = handler.gen_item(Item::new(ItemKind::Gold, 1));
+        match itembox.entry(&single) {
+            Some(Entry::Insert(_)) => {}
+            _ => panic!("items without IS_MANY shouldn't be merged"),
+        }
+    }
+}
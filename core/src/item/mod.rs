@@ -1,24 +1,41 @@
 //! module for item
 pub mod armor;
+mod enchant;
 pub mod food;
 mod gold;
 mod handler;
+pub mod identify;
 pub mod itembox;
+pub mod price;
 pub mod weapon;
 
 use self::armor::{Armor, ArmorStatus};
 use self::food::Food;
 use self::handler::Handler;
 use self::handler::ItemStat;
+pub use self::identify::{Identify, IdentifyTable, ItemClass};
 pub use self::itembox::ItemBox;
 use self::weapon::{Weapon, WeaponStatus};
 use crate::character::{Dice, HitPoint, Level};
+use crate::difficulty::Multipliers;
+use crate::snapshot::DeepCloner;
+use crate::spawn::{self, DepthTable};
 use crate::tile::{Drawable, Tile};
-use crate::{error::*, rng::RngHandle, smallstr::SmallStr};
+use crate::validate::Validator;
+use crate::{
+    error::*,
+    rng::{RngHandle, RngState, Stream},
+    smallstr::SmallStr,
+};
 use anyhow::bail;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::{Deref, DerefMut};
 use std::rc::{Rc, Weak};
-use std::{cell::UnsafeCell, collections::BTreeMap, fmt};
+use std::{
+    cell::{RefCell, UnsafeCell},
+    collections::BTreeMap,
+    fmt,
+};
 
 /// Item configuration
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
@@ -26,22 +43,70 @@ pub struct Config {
     armor: armor::Config,
     gold: gold::Config,
     weapon: weapon::Config,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "crate::is_default")]
+    prices: price::Prices,
+    /// weights of item categories (`"weapon"` or `"armor"`) by depth,
+    /// which replace the even chance of them unless all weights are 0
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    spawn_table: Vec<DepthTable>,
 }
 
+impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.field("armor", |v| self.armor.validate(v));
+        v.field("weapon", |v| self.weapon.validate(v));
+        v.field("prices", |v| self.prices.validate(v));
+        v.field("spawn_table", |v| {
+            spawn::validate(&self.spawn_table, v, &|name| {
+                RANDOM_ITEM_CATEGORIES.contains(&name)
+            })
+        });
+    }
+    /// builtin weapons and armors are expanded to their definitions
+    pub(crate) fn canonical(&self) -> Self {
+        Config {
+            armor: self.armor.canonical(),
+            gold: self.gold.clone(),
+            weapon: self.weapon.canonical(),
+            prices: self.prices.clone(),
+            spawn_table: self.spawn_table.clone(),
+        }
+    }
+}
+
+/// names of item categories which can be used in `spawn_table`
+const RANDOM_ITEM_CATEGORIES: [&str; 2] = ["weapon", "armor"];
+
 /// item tag
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ItemKind {
     Armor(Armor),
     Food(Food),
     Gold,
-    Potion,
-    Ring,
-    Scroll,
-    Wand,
+    Potion(SmallStr),
+    Ring(SmallStr),
+    Scroll(SmallStr),
+    Wand(SmallStr),
     Weapon(Weapon),
 }
 
 impl ItemKind {
+    /// the name of `num` items without the number or the article, e.g., "potions"
+    fn noun(&self, num: u32) -> String {
+        let s = if num == 1 { "" } else { "s" };
+        match self {
+            ItemKind::Armor(armor) => format!("{}{}", armor, s),
+            ItemKind::Food(food) => format!("{}{}", food, s),
+            ItemKind::Gold => format!("gold piece{}", s),
+            ItemKind::Potion(_) => format!("potion{}", s),
+            ItemKind::Ring(_) => format!("ring{}", s),
+            ItemKind::Scroll(_) => format!("scroll{}", s),
+            ItemKind::Wand(_) => format!("wand{}", s),
+            ItemKind::Weapon(w) => format!("{}{}", w, s),
+        }
+    }
     /// construct item from ItemNum & default attribute setting
     pub fn numbered(self, num: ItemNum) -> Item {
         let attr = match self {
@@ -62,10 +127,10 @@ impl Drawable for ItemKind {
             ItemKind::Armor(_) => b']',
             ItemKind::Food(_) => b':',
             ItemKind::Gold => b'*',
-            ItemKind::Potion => b'!',
-            ItemKind::Ring => b'=',
-            ItemKind::Scroll => b'?',
-            ItemKind::Wand => b'/',
+            ItemKind::Potion(_) => b'!',
+            ItemKind::Ring(_) => b'=',
+            ItemKind::Scroll(_) => b'?',
+            ItemKind::Wand(_) => b'/',
             ItemKind::Weapon(_) => b')',
         }
         .into()
@@ -127,6 +192,8 @@ impl ItemAttr {
     /// we can merge 2 sets of the item or not
     pub const IS_MANY: ItemAttr = ItemAttr(0b00_000_100);
     pub const IS_EQUIPPED: ItemAttr = ItemAttr(0b00_001_000);
+    /// the item is sold in a shop
+    pub const FOR_SALE: ItemAttr = ItemAttr(0b00_010_000);
 }
 
 impl ItemAttr {
@@ -145,16 +212,24 @@ impl ItemAttr {
     pub fn or(&mut self, other: ItemAttr) {
         self.0 |= other.0;
     }
+    pub fn remove(&mut self, other: ItemAttr) {
+        self.0 &= !other.0;
+    }
     pub fn equip(&mut self) {
         self.0 |= ItemAttr::IS_EQUIPPED.0;
     }
-    fn is_equiped(&self) -> bool {
+    pub fn unequip(&mut self) {
+        self.0 &= !ItemAttr::IS_EQUIPPED.0;
+    }
+    pub fn is_equiped(&self) -> bool {
         (self.0 & ItemAttr::IS_EQUIPPED.0) != 0
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
-pub struct ItemId(u32);
+/// Stable id of an item, which is unique in a game and never changes while the item exists.
+/// Stacked items keep the id of the stack they are merged into.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ItemId(pub u32);
 
 impl ItemId {
     fn increment(&mut self) {
@@ -162,6 +237,12 @@ impl ItemId {
     }
 }
 
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub enum InitItem {
     Noinit(Item),
@@ -251,6 +332,23 @@ impl Item {
     pub fn is_many(&self) -> bool {
         self.attr.contains(ItemAttr::IS_MANY)
     }
+    /// how heavy the item is in a pack.
+    /// Gold weighs nothing and a stack of missiles weighs as one item, like in the original rogue.
+    pub fn weight(&self) -> u32 {
+        if self.kind == ItemKind::Gold {
+            0
+        } else if self.attr.contains(ItemAttr::IS_MANY | ItemAttr::CAN_THROW) {
+            1
+        } else {
+            self.how_many.0
+        }
+    }
+    /// if `other` can be merged into this stack or not, regardless of how many they are.
+    /// Only stacks of the same kind with the same curse can be merged.
+    pub fn stacks_with(&self, other: &Item) -> bool {
+        let is_cursed = |item: &Item| item.attr.contains(ItemAttr::IS_CURSED);
+        self.is_many() && self.kind == other.kind && is_cursed(self) == is_cursed(other)
+    }
     pub fn hit_plus(&self) -> Level {
         match &self.kind {
             ItemKind::Weapon(w) => w.hit_plus,
@@ -276,6 +374,12 @@ impl Item {
             _ => None,
         }
     }
+    pub fn special(&self) -> Option<&weapon::Special> {
+        match &self.kind {
+            ItemKind::Weapon(w) => Some(w.special()),
+            _ => None,
+        }
+    }
     pub fn at_throw(&self) -> Option<Dice<HitPoint>> {
         match &self.kind {
             ItemKind::Weapon(w) => Some(w.at_throw.clone()),
@@ -304,21 +408,12 @@ impl Drawable for Item {
 
 impl fmt::Display for Item {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.kind.noun(self.how_many.0);
         if self.how_many == ItemNum(1) {
-            write!(f, "A ")?;
+            write!(f, "{} {}", capitalize(article(&name)), name)?;
         } else {
-            write!(f, "{} ", self.how_many.0)?;
+            write!(f, "{} {}", self.how_many.0, name)?;
         }
-        match &self.kind {
-            ItemKind::Armor(armor) => write!(f, "{}", armor),
-            ItemKind::Food(food) => write!(f, "{}", food),
-            ItemKind::Gold => write!(f, "golds"),
-            ItemKind::Potion => write!(f, "potion"), // STUB
-            ItemKind::Ring => write!(f, "ring"),     // STUB
-            ItemKind::Scroll => write!(f, "scroll"), // STUB
-            ItemKind::Wand => write!(f, "wand"),     // STUB
-            ItemKind::Weapon(w) => write!(f, "{}", w),
-        }?;
         if self.attr.is_equiped() {
             write!(f, " [equipped]")?;
         }
@@ -326,10 +421,41 @@ impl fmt::Display for Item {
     }
 }
 
+/// the indefinite article for the name, e.g., "an" for "amber potion"
+fn article(name: &str) -> &'static str {
+    match name.chars().next() {
+        Some(c) if "aeiouAEIOU".contains(c) => "an",
+        _ => "a",
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// registry of all existing items, from which a token removes its item when it's the last one
+pub(crate) type Registry = RefCell<BTreeMap<ItemId, Weak<UnsafeCell<Item>>>>;
+
 #[derive(Clone, Debug)]
 pub struct ItemToken {
     inner: Rc<UnsafeCell<Item>>,
     id: ItemId,
+    registry: Weak<Registry>,
+}
+
+impl Drop for ItemToken {
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.inner) > 1 {
+            return;
+        }
+        if let Some(registry) = self.registry.upgrade() {
+            registry.borrow_mut().remove(&self.id);
+        }
+    }
 }
 
 impl Deref for ItemToken {
@@ -346,6 +472,15 @@ impl DerefMut for ItemToken {
 }
 
 impl ItemToken {
+    pub(crate) fn register(item: Item, id: ItemId, registry: &Rc<Registry>) -> Self {
+        let inner = Rc::new(UnsafeCell::new(item));
+        registry.borrow_mut().insert(id, Rc::downgrade(&inner));
+        ItemToken {
+            inner,
+            id,
+            registry: Rc::downgrade(registry),
+        }
+    }
     #[inline(always)]
     pub fn get(&self) -> &Item {
         unsafe { &*UnsafeCell::get(&self.inner) }
@@ -357,57 +492,188 @@ impl ItemToken {
     pub fn get_cloned(&self) -> Item {
         self.get().clone()
     }
+    /// copy the item into a new token with the same id, registered to `registry`
+    pub(crate) fn deep_clone(&self, registry: &Rc<Registry>) -> ItemToken {
+        Self::register(self.get_cloned(), self.id, registry)
+    }
     /// Returns the unique id of item
     pub fn id(&self) -> ItemId {
         self.id.clone()
     }
 }
 
+/// An item is saved with its id, so that the item shared by some places
+/// (e.g. an equipped weapon in the item box) is restored as a shared one
+impl Serialize for ItemToken {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.id, self.get()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemToken {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (id, item) = <(ItemId, Item)>::deserialize(deserializer)?;
+        Ok(crate::snapshot::restore_item(id, item))
+    }
+}
+
 /// generate and management all items
+#[derive(Serialize, Deserialize)]
 pub struct ItemHandler {
-    /// stores all items in the game
-    /// only for save/load
-    items: BTreeMap<ItemId, Weak<UnsafeCell<Item>>>,
+    /// registry of all items in the game, which is rebuilt from restored items
+    #[serde(skip, default = "crate::snapshot::restored_items")]
+    items: Rc<Registry>,
     config: Config,
+    /// saved in `RngStates`
+    #[serde(skip)]
     rng: RngHandle,
     armor_handle: Handler<ArmorStatus>,
     weapon_handle: Handler<WeaponStatus>,
+    identify_table: IdentifyTable,
     next_id: ItemId,
+    /// how often items appear, in percent
+    item_appear: u32,
 }
 
 impl ItemHandler {
     /// generate new ItemHandler
     pub fn new(config_: Config, seed: u128) -> Self {
-        let config = config_.clone();
+        // canonical, so that equivalent configurations make the same state
+        let config = config_.canonical();
         let Config {
             armor,
             gold: _,
             weapon,
+            prices: _,
+            spawn_table: _,
         } = config_;
         ItemHandler {
-            items: BTreeMap::new(),
+            items: Rc::default(),
             config,
-            rng: RngHandle::from_seed(seed),
+            rng: RngHandle::from_stream(seed, Stream::Items),
             armor_handle: armor.build(),
             weapon_handle: weapon.build(),
+            identify_table: IdentifyTable::new(&mut RngHandle::from_stream(seed, Stream::Identify)),
             next_id: ItemId(0),
+            item_appear: 100,
         }
     }
+    pub(crate) fn rng(&mut self) -> &mut RngHandle {
+        &mut self.rng
+    }
+    pub(crate) fn deep_clone(&self, cloner: &mut DeepCloner) -> ItemHandler {
+        // items only held outside of the game state are cloned here
+        for id in self.ids() {
+            if let Some(item) = self.get(id) {
+                cloner.item(&item);
+            }
+        }
+        ItemHandler {
+            items: cloner.item_registry(),
+            config: self.config.clone(),
+            rng: self.rng.clone(),
+            armor_handle: self.armor_handle.clone(),
+            weapon_handle: self.weapon_handle.clone(),
+            identify_table: self.identify_table.clone(),
+            next_id: self.next_id,
+            item_appear: self.item_appear,
+        }
+    }
+    pub(crate) fn rng_state(&self) -> RngState {
+        self.rng.save_state()
+    }
+    pub fn set_difficulty(&mut self, difficulty: &Multipliers) {
+        self.item_appear = difficulty.item_appear;
+    }
+    pub fn identify_table(&self) -> &IdentifyTable {
+        &self.identify_table
+    }
+    /// restore the identification state from a saved game
+    pub fn set_identify_table(&mut self, table: IdentifyTable) {
+        self.identify_table = table;
+    }
+    /// record that the item was used, and identify it if its effect was observed
+    pub fn used(&mut self, kind: &ItemKind, observed: bool) -> bool {
+        self.identify_table.used(kind, observed)
+    }
+    /// give a nickname to the unidentified item, or remove it if `name` is None
+    pub fn call(&mut self, kind: &ItemKind, name: Option<SmallStr>) -> bool {
+        self.identify_table.call(kind, name)
+    }
+    /// returns the name of the item considering whether it's identified or not,
+    /// e.g., "An amber potion"
+    pub fn item_name(&self, item: &Item) -> String {
+        let mut name = capitalize(&self.kind_name(&item.kind, item.how_many.0));
+        if item.attr.is_equiped() {
+            name.push_str(" [equipped]");
+        }
+        name
+    }
+    /// returns the name of `num` items of the kind to use in messages,
+    /// considering whether it's identified or not, e.g., "an amber potion" or "2 maces"
+    pub fn kind_name(&self, kind: &ItemKind, num: u32) -> String {
+        let name = self
+            .identify_table
+            .name(kind, num)
+            .unwrap_or_else(|| kind.noun(num));
+        if num == 1 {
+            format!("{} {}", article(&name), name)
+        } else {
+            format!("{} {}", num, name)
+        }
+    }
+    /// the item of `id`, if it still exists in the dungeon or in someone's pack
+    pub fn get(&self, id: ItemId) -> Option<ItemToken> {
+        let inner = self.items.borrow().get(&id)?.upgrade()?;
+        Some(ItemToken {
+            inner,
+            id,
+            registry: Rc::downgrade(&self.items),
+        })
+    }
+    /// ids of all existing items, in the order they were generated
+    pub fn ids(&self) -> Vec<ItemId> {
+        self.items.borrow().keys().cloned().collect()
+    }
     /// generate and register an item
     fn gen_item(&mut self, item: Item) -> ItemToken {
         let id = self.next_id;
         debug!("[gen_item] now new item {:?} is generated", item);
-        // register the generated item
-        let item_rc = Rc::new(UnsafeCell::new(item));
-        self.items.insert(id, Rc::downgrade(&item_rc));
         self.next_id.increment();
-        ItemToken { inner: item_rc, id }
+        ItemToken::register(item, id, &self.items)
+    }
+    /// the pricing table
+    pub fn prices(&self) -> &price::Prices {
+        &self.config.prices
+    }
+    /// generate gold, e.g. paid by a shopkeeper
+    pub(crate) fn gen_gold(&mut self, num: u32) -> ItemToken {
+        self.gen_item(ItemKind::Gold.numbered(ItemNum(num)).many())
     }
     /// Sets up gold for 1 room
     pub fn setup_gold(&mut self, level: u32) -> Option<ItemToken> {
-        let num = self.config.gold.gen(&mut self.rng, level)?;
+        let num = self
+            .config
+            .gold
+            .gen(&mut self.rng, level, self.item_appear)?;
         Some(self.gen_item(ItemKind::Gold.numbered(num).many()))
     }
+    /// generate a random weapon or armor for the floor of `depth`
+    pub fn gen_random_item(&mut self, depth: u32) -> ItemToken {
+        let is_weapon = match spawn::lookup(&self.config.spawn_table, depth) {
+            Some(table) => match table.choose(&mut self.rng) {
+                Some(name) => name == "weapon",
+                None => self.rng.does_happen(2),
+            },
+            None => self.rng.does_happen(2),
+        };
+        let item = if is_weapon {
+            self.weapon_handle.gen_item(&mut self.rng)
+        } else {
+            self.armor_handle.gen_item(&mut self.rng)
+        };
+        self.gen_item(item)
+    }
     /// Sets up player items
     pub fn init_player_items(&mut self, pack: &mut ItemBox, items: &[InitItem]) -> GameResult<()> {
         for item in items.iter() {
@@ -422,10 +688,11 @@ impl ItemHandler {
     }
 }
 
-fn display_plus_types(i: i64, f: &mut fmt::Formatter) -> fmt::Result {
-    if i < 0 {
-        write!(f, "-{}", -i)
-    } else {
-        write!(f, "+{}", i)
+impl Identify for ItemHandler {
+    fn identify(&mut self, kind: &ItemKind) -> bool {
+        self.identify_table.identify(kind)
+    }
+    fn is_identified(&self, kind: &ItemKind) -> bool {
+        self.identify_table.is_identified(kind)
     }
 }
@@ -0,0 +1,126 @@
+//! prices of items, used for scoring and shops
+use super::{Item, ItemKind};
+use crate::rng::Parcent;
+use crate::validate::Validator;
+
+/// Pricing table. Prices are in gold pieces per item.
+/// Weapons and armors are priced by their `worth` and bonuses.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Prices {
+    #[serde(default = "default_food")]
+    pub food: u32,
+    #[serde(default = "default_potion")]
+    pub potion: u32,
+    #[serde(default = "default_scroll")]
+    pub scroll: u32,
+    #[serde(default = "default_ring")]
+    pub ring: u32,
+    #[serde(default = "default_wand")]
+    pub wand: u32,
+    /// added for each `+1` of weapons and armors, and subtracted for each `-1`
+    #[serde(default = "default_per_plus")]
+    pub per_plus: u32,
+    /// shopkeepers buy items at this rate of their prices
+    #[serde(default = "default_sell_rate")]
+    pub sell_rate: Parcent,
+}
+
+impl Default for Prices {
+    fn default() -> Self {
+        Prices {
+            food: default_food(),
+            potion: default_potion(),
+            scroll: default_scroll(),
+            ring: default_ring(),
+            wand: default_wand(),
+            per_plus: default_per_plus(),
+            sell_rate: default_sell_rate(),
+        }
+    }
+}
+
+const fn default_food() -> u32 {
+    2
+}
+
+const fn default_potion() -> u32 {
+    50
+}
+
+const fn default_scroll() -> u32 {
+    60
+}
+
+const fn default_ring() -> u32 {
+    200
+}
+
+const fn default_wand() -> u32 {
+    150
+}
+
+const fn default_per_plus() -> u32 {
+    10
+}
+
+const fn default_sell_rate() -> Parcent {
+    Parcent(50)
+}
+
+impl Prices {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.parcent("sell_rate", self.sell_rate);
+    }
+    /// the price of one of `kind`
+    pub fn unit_price(&self, kind: &ItemKind) -> u32 {
+        let with_pluses = |worth: u32, pluses: i64| {
+            let pluses = pluses * i64::from(self.per_plus);
+            (i64::from(worth) + pluses).max(0) as u32
+        };
+        match kind {
+            ItemKind::Armor(armor) => with_pluses(armor.worth().0, armor.def_plus.0.into()),
+            ItemKind::Food(_) => self.food,
+            ItemKind::Gold => 1,
+            ItemKind::Potion(_) => self.potion,
+            ItemKind::Ring(_) => self.ring,
+            ItemKind::Scroll(_) => self.scroll,
+            ItemKind::Wand(_) => self.wand,
+            ItemKind::Weapon(weapon) => {
+                with_pluses(weapon.worth().0, weapon.hit_plus.0 + weapon.dam_plus.0)
+            }
+        }
+    }
+    /// the price of all of `item`
+    pub fn price(&self, item: &Item) -> u32 {
+        self.unit_price(&item.kind).saturating_mul(item.how_many.0)
+    }
+    /// the gold a shopkeeper pays for `item`
+    pub fn sell_price(&self, item: &Item) -> u32 {
+        (u64::from(self.price(item)) * u64::from(self.sell_rate.0) / 100) as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::{Config, InitItem, ItemHandler};
+    use crate::SmallStr;
+    #[test]
+    fn prices() {
+        let prices = Prices::default();
+        let mut handler = ItemHandler::new(Config::default(), 1);
+        let mace = InitItem::Weapon {
+            name: SmallStr::from_str("mace"),
+            num_plus: 0,
+            hit_plus: 1,
+            dam_plus: 1,
+        };
+        let mace = mace.initialize(&mut handler).unwrap();
+        assert_eq!(prices.price(&mace), 8 + 2 * 10);
+        assert_eq!(prices.sell_price(&mace), 14);
+        let gold = Item::new(ItemKind::Gold, 30);
+        assert_eq!(prices.price(&gold), 30);
+        let potions = Item::new(ItemKind::Potion(SmallStr::from_str("healing")), 3);
+        assert_eq!(prices.price(&potions), 150);
+    }
+}
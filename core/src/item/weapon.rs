@@ -1,7 +1,9 @@
+use super::enchant::{self, Enchant, Rates};
 use super::handler::{Handler, ItemInner, ItemStat};
 use super::{InitItem, Item, ItemAttr, ItemKind, ItemNum};
 use crate::character::{Dice, HitPoint, Level};
-use crate::rng::{Parcent, RngHandle};
+use crate::rng::{Parcent, Probability, RngHandle};
+use crate::validate::Validator;
 use crate::SmallStr;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -15,10 +17,10 @@ pub struct Config {
     pub weapons: Vec<Preset>,
     #[serde(default = "default_cursed_rate")]
     #[serde(skip_serializing_if = "is_default_cursed_rate")]
-    pub cursed_rate: Parcent,
+    pub cursed_rate: Probability,
     #[serde(default = "default_powerup_rate")]
     #[serde(skip_serializing_if = "is_default_powerup_rate")]
-    pub powerup_rate: Parcent,
+    pub powerup_rate: Probability,
 }
 
 impl Default for Config {
@@ -32,6 +34,30 @@ impl Default for Config {
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.field("weapons", |v| {
+            for (i, preset) in self.weapons.iter().enumerate() {
+                if let Preset::Builtin(b) = *preset {
+                    v.element(i, |v| v.builtin(b, BUILTIN_WEAPONS.len()));
+                }
+            }
+        });
+        v.probability("cursed_rate", self.cursed_rate);
+        v.probability("powerup_rate", self.powerup_rate);
+    }
+    /// builtin weapons are expanded to their definitions
+    pub(super) fn canonical(&self) -> Self {
+        let weapons = self.weapons.iter().map(|preset| match *preset {
+            Preset::Builtin(i) if i < BUILTIN_WEAPONS.len() => {
+                Preset::Custom(BUILTIN_WEAPONS[i].clone())
+            }
+            ref preset => preset.clone(),
+        });
+        Config {
+            weapons: weapons.collect(),
+            ..self.clone()
+        }
+    }
     pub(super) fn build(self) -> Handler<WeaponStatus> {
         let Config {
             weapons,
@@ -39,27 +65,29 @@ impl Config {
             powerup_rate,
         } = self;
         Handler {
-            cursed_rate,
-            powerup_rate,
+            rates: Rates {
+                cursed: cursed_rate,
+                powerup: powerup_rate,
+            },
             stats: weapons.into_iter().map(Preset::build).collect(),
         }
     }
 }
 
-const fn default_cursed_rate() -> Parcent {
-    Parcent(10)
+const fn default_cursed_rate() -> Probability {
+    Probability::from_percent(10)
 }
 
-const fn default_powerup_rate() -> Parcent {
-    Parcent(5)
+const fn default_powerup_rate() -> Probability {
+    Probability::from_percent(5)
 }
 
-fn is_default_cursed_rate(u: &Parcent) -> bool {
-    cfg!(not(test)) && *u == default_cursed_rate()
+fn is_default_cursed_rate(u: &Probability) -> bool {
+    crate::skip_defaults() && *u == default_cursed_rate()
 }
 
-fn is_default_powerup_rate(u: &Parcent) -> bool {
-    cfg!(not(test)) && *u == default_powerup_rate()
+fn is_default_powerup_rate(u: &Probability) -> bool {
+    crate::skip_defaults() && *u == default_powerup_rate()
 }
 
 fn default_weapons() -> Vec<Preset> {
@@ -91,24 +119,86 @@ pub struct Weapon {
     pub(super) dam_plus: HitPoint,
     worth: ItemNum,
     launcher: Option<SmallStr>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Special::is_none")]
+    special: Special,
+}
+
+/// Special properties of a weapon, all of which are disabled by default
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Special {
+    /// bonuses against specific enemies
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slay: Option<Slay>,
+    /// a thrown weapon comes back to the thrower's pack
+    #[serde(default)]
+    pub returning: bool,
+    /// the weapon needs both hands.
+    /// There are no shields in the game yet, so this is only informative.
+    #[serde(default)]
+    pub two_handed: bool,
+}
+
+impl Special {
+    pub const NONE: Special = Special {
+        slay: None,
+        returning: false,
+        two_handed: false,
+    };
+    fn is_none(&self) -> bool {
+        *self == Special::NONE
+    }
+}
+
+/// Bonuses of a weapon against specific enemies, like vorpal blades
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Slay {
+    /// names of enemies
+    pub enemies: Vec<SmallStr>,
+    #[serde(default)]
+    pub hit_plus: Level,
+    #[serde(default)]
+    pub dam_plus: HitPoint,
+}
+
+impl Slay {
+    /// bonuses against the enemy named `enemy`
+    pub fn bonus(&self, enemy: &str) -> (Level, HitPoint) {
+        if self.enemies.iter().any(|name| name == enemy) {
+            (self.hit_plus, self.dam_plus)
+        } else {
+            (Level(0), HitPoint(0))
+        }
+    }
 }
 
 impl Weapon {
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
+    pub fn worth(&self) -> ItemNum {
+        self.worth
+    }
     pub fn launcher(&self) -> Option<&str> {
         self.launcher.as_ref().map(SmallStr::as_str)
     }
+    pub fn special(&self) -> &Special {
+        &self.special
+    }
 }
 
-impl ItemInner for Weapon {
-    fn get_cursed(&mut self, rng: &mut RngHandle) {
-        self.hit_plus -= Level(rng.range(1..=4));
+impl Enchant for Weapon {
+    type Bonus = i64;
+    fn enchant(&mut self, amount: i64) {
+        self.hit_plus += Level(amount);
     }
-    fn get_powerup(&mut self, rng: &mut RngHandle) {
-        self.hit_plus += Level(rng.range(1..=4));
+    fn pluses(&self) -> Vec<i64> {
+        vec![self.hit_plus.0, self.dam_plus.0]
     }
+}
+
+impl ItemInner for Weapon {
     fn into_item(self, attr: ItemAttr, how_many: ItemNum) -> Item {
         Item {
             kind: ItemKind::Weapon(self),
@@ -120,9 +210,7 @@ impl ItemInner for Weapon {
 
 impl fmt::Display for Weapon {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        super::display_plus_types(self.hit_plus.0, f)?;
-        write!(f, ",")?;
-        super::display_plus_types(self.dam_plus.0, f)?;
+        enchant::display_pluses(&self.pluses(), f)?;
         write!(f, " {}", self.name)
     }
 }
@@ -138,6 +226,9 @@ pub struct WeaponStatus {
     appear_rate: Parcent,
     worth: ItemNum,
     launcher: Option<SmallStr>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Special::is_none")]
+    special: Special,
 }
 
 impl ItemStat for WeaponStatus {
@@ -154,6 +245,7 @@ impl ItemStat for WeaponStatus {
             init_num,
             worth,
             launcher,
+            special,
             ..
         } = self;
         let num = rng.range(init_num);
@@ -165,6 +257,7 @@ impl ItemStat for WeaponStatus {
             dam_plus: 0.into(),
             worth,
             launcher,
+            special,
         };
         (weapon, attr, num.into())
     }
@@ -206,6 +299,7 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special::NONE,
     },
     WeaponStatus {
         at_weild: hp_dice!(3, 4),
@@ -217,6 +311,7 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special::NONE,
     },
     WeaponStatus {
         at_weild: hp_dice!(1, 1),
@@ -228,6 +323,7 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special::NONE,
     },
     WeaponStatus {
         at_weild: hp_dice!(1, 1),
@@ -239,6 +335,7 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: Some(SmallStr::from_static("bow")),
+        special: Special::NONE,
     },
     WeaponStatus {
         at_weild: hp_dice!(1, 6),
@@ -250,6 +347,7 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special::NONE,
     },
     WeaponStatus {
         at_weild: hp_dice!(4, 4),
@@ -261,6 +359,10 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special {
+            two_handed: true,
+            ..Special::NONE
+        },
     },
     WeaponStatus {
         at_weild: hp_dice!(1, 1),
@@ -272,6 +374,7 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special::NONE,
     },
     WeaponStatus {
         at_weild: hp_dice!(1, 2),
@@ -283,6 +386,7 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special::NONE,
     },
     WeaponStatus {
         at_weild: hp_dice!(2, 3),
@@ -294,5 +398,6 @@ const BUILTIN_WEAPONS: [WeaponStatus; 9] = [
         appear_rate: Parcent(11),
         worth: ItemNum(8),
         launcher: None,
+        special: Special::NONE,
     },
 ];
@@ -10,28 +10,55 @@ extern crate serde;
 extern crate test;
 
 mod actions;
-use std::fs::File;
-use std::io::{self, Read};
+pub mod agent;
+use std::cell::Cell;
+use std::hash::Hasher;
+use std::io::Write;
 pub mod character;
+pub mod dataset;
+pub mod difficulty;
 pub mod dungeon;
+pub mod episode;
 pub mod error;
+pub mod events;
 mod fenwick;
+pub mod geometry;
 pub mod input;
 pub mod item;
-mod rng;
+pub mod observation;
+#[cfg(feature = "parallel")]
+pub mod pool;
+pub mod preset;
+pub mod replay;
+pub mod reward;
+pub mod rng;
+pub mod settings;
+pub mod share;
 mod smallstr;
+pub mod snapshot;
+pub mod spawn;
+pub mod stats;
+pub mod step;
+pub mod summary;
 pub mod symbol;
 pub mod tile;
+pub mod trace;
 pub mod ui;
+pub mod validate;
 
-use crate::character::{enemies, player, EnemyHandler, Player};
-use crate::dungeon::{Direction, Dungeon, DungeonStyle, Positioned, X, Y};
+use crate::character::fight::{CombatEvent, Projectile};
+use crate::character::{enemies, player, Effect, EnemyHandler, Player};
+use crate::dungeon::{
+    Coord, Direction, Dungeon, DungeonGenerator, DungeonStyle, MapLayer, Positioned, TrapKind, X, Y,
+};
 use anyhow::{bail, Context};
+use difficulty::Difficulty;
 use error::*;
 use input::{InputCode, Key, KeyMap};
-use item::{ItemHandler, ItemKind};
+use item::{Item, ItemHandler, ItemId, ItemKind};
 use log::{debug, trace};
 use ndarray::Array2;
+use rng::RngState;
 use serde::{Deserialize, Serialize};
 pub use smallstr::SmallStr;
 use tile::{Drawable, Tile};
@@ -59,6 +86,11 @@ pub struct GameConfig {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub seed_range: Option<[u128; 2]>,
+    /// how the seed of each episode is chosen by `for_episode`, overriding `seed` and
+    /// `seed_range`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub seed_schedule: Option<episode::SeedSchedule>,
     /// dungeon configuration
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
@@ -79,6 +111,26 @@ pub struct GameConfig {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub enemies: enemies::Config,
+    /// difficulty profile, which scales enemies and items
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub difficulty: Difficulty,
+    /// screen configuration, like colors
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub screen: ui::ScreenConfig,
+    /// observation configuration for agents
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub observation: observation::Config,
+    /// how rewards are computed for agents
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub reward: reward::RewardSpec,
+    /// limits to end episodes for agents
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub episode: episode::Limits,
     /// hide dungeon or not
     /// this setting is only for debugging and don't use it when you play game
     #[serde(default = "default_hide_dungeon")]
@@ -87,11 +139,22 @@ pub struct GameConfig {
 
 unsafe impl Send for GameConfig {}
 
+thread_local! {
+    /// if true, settings are serialized even if they are default
+    static SERIALIZE_DEFAULTS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// settings are omitted in serialization if they are default, except in tests and
+/// `GameConfig::to_canonical_json`
+pub(crate) fn skip_defaults() -> bool {
+    cfg!(not(test)) && !SERIALIZE_DEFAULTS.with(Cell::get)
+}
+
 fn is_default<T>(s: &T) -> bool
 where
     T: Default + PartialEq,
 {
-    cfg!(not(test)) && *s == T::default()
+    skip_defaults() && *s == T::default()
 }
 
 const fn default_screen_width() -> i32 {
@@ -99,7 +162,7 @@ const fn default_screen_width() -> i32 {
 }
 
 fn is_default_width(w: &i32) -> bool {
-    cfg!(not(test)) && *w == DEFAULT_WIDTH
+    skip_defaults() && *w == DEFAULT_WIDTH
 }
 
 const fn default_screen_height() -> i32 {
@@ -107,7 +170,7 @@ const fn default_screen_height() -> i32 {
 }
 
 fn is_default_height(h: &i32) -> bool {
-    cfg!(not(test)) && *h == DEFAULT_HEIGHT
+    skip_defaults() && *h == DEFAULT_HEIGHT
 }
 
 const fn default_hide_dungeon() -> bool {
@@ -121,11 +184,17 @@ impl Default for GameConfig {
             height: DEFAULT_HEIGHT,
             seed: Default::default(),
             seed_range: Default::default(),
+            seed_schedule: Default::default(),
             dungeon: DungeonStyle::default(),
             item: item::Config::default(),
             keymap: KeyMap::default(),
             player: player::Config::default(),
             enemies: enemies::Config::default(),
+            difficulty: Difficulty::default(),
+            screen: ui::ScreenConfig::default(),
+            observation: observation::Config::default(),
+            reward: reward::RewardSpec::default(),
+            episode: episode::Limits::default(),
             hide_dungeon: default_hide_dungeon(),
         }
     }
@@ -140,18 +209,131 @@ pub const MIN_WIDTH: i32 = 32;
 pub const MIN_HEIGHT: i32 = 16;
 
 impl GameConfig {
-    /// construct Game configuration from json string
+    /// construct Game configuration from json string, reporting all fields which are broken
+    /// or violate constraints(see `violations`) at once.
+    /// If it has `"preset": name`, it's merged on the preset(see `preset::merge`),
+    /// so that it only has to specify differences from the preset.
     pub fn from_json(json: &str) -> GameResult<Self> {
-        serde_json::from_str(json).context("GameConfig::from_json")
+        Self::from_json_impl(json, None)
+    }
+    /// same as `from_json`, but merges the json on the preset named `preset`,
+    /// which takes priority over the preset specified in the json
+    pub fn from_json_with_preset(preset: &str, json: &str) -> GameResult<Self> {
+        Self::from_json_impl(json, Some(preset))
+    }
+    /// the builtin configuration named `name`(see `preset::value`)
+    pub fn preset(name: &str) -> GameResult<Self> {
+        Self::from_value(preset::value(name)?)
+    }
+    fn from_json_impl(json: &str, preset: Option<&str>) -> GameResult<Self> {
+        let mut value: serde_json::Value =
+            serde_json::from_str(json).context("GameConfig::from_json")?;
+        let specified = match value.as_object_mut().and_then(|obj| obj.remove("preset")) {
+            Some(serde_json::Value::String(name)) => Some(name),
+            Some(_) => bail!(ErrorKind::InvalidConfig(validate::Violations(vec![
+                validate::Violation {
+                    path: "preset".to_owned(),
+                    message: "preset should be a name".to_owned(),
+                }
+            ]))),
+            None => None,
+        };
+        match preset.or(specified.as_deref()) {
+            Some(name) => {
+                let mut base = preset::value(name)?;
+                preset::merge(&mut base, value);
+                Self::from_value(base)
+            }
+            None => Self::from_value(value),
+        }
+    }
+    fn from_value(value: serde_json::Value) -> GameResult<Self> {
+        let config: GameConfig = match serde_json::from_value(value.clone()) {
+            Ok(config) => config,
+            Err(_) => {
+                let (mut violations, config) = validate::type_violations::<GameConfig>(&value);
+                // fields which could be deserialized are still checked semantically
+                if let Some(config) = config {
+                    violations.0.extend(config.violations().0);
+                }
+                bail!(ErrorKind::InvalidConfig(violations));
+            }
+        };
+        config.validate()?;
+        Ok(config)
+    }
+    /// all violations of semantic constraints, like screen size bounds,
+    /// builtin indices in range, and percentages between 0 and 100
+    pub fn violations(&self) -> validate::Violations {
+        let mut v = validate::Validator::new();
+        v.check(
+            "width",
+            MIN_WIDTH <= self.width && self.width <= MAX_WIDTH,
+            &format!(
+                "screen width should be between {} and {}",
+                MIN_WIDTH, MAX_WIDTH
+            ),
+        );
+        v.check(
+            "height",
+            MIN_HEIGHT <= self.height && self.height <= MAX_HEIGHT,
+            &format!(
+                "screen height should be between {} and {}",
+                MIN_HEIGHT, MAX_HEIGHT
+            ),
+        );
+        if let Some([start, end]) = self.seed_range {
+            v.check("seed_range", start < end, "the range is empty");
+        }
+        if let Some(schedule) = &self.seed_schedule {
+            v.field("seed_schedule", |v| schedule.validate(v));
+        }
+        let (width, height) = (X(self.width), Y(self.height));
+        v.field("dungeon", |v| self.dungeon.validate(v, width, height));
+        v.field("item", |v| self.item.validate(v));
+        v.field("player", |v| self.player.validate(v));
+        v.field("enemies", |v| self.enemies.validate(v));
+        v.finish()
+    }
+    /// fails with all violations if there are any
+    pub fn validate(&self) -> GameResult<()> {
+        let violations = self.violations();
+        if !violations.is_empty() {
+            bail!(ErrorKind::InvalidConfig(violations));
+        }
+        Ok(())
+    }
+    /// the config of the `episode`-th(0-indexed) episode, whose seed is chosen by
+    /// `seed_schedule` if it's specified
+    pub fn for_episode(&self, episode: usize) -> GameConfig {
+        let mut config = self.clone();
+        if let Some(schedule) = &self.seed_schedule {
+            config.seed = Some(schedule.seed(episode));
+        }
+        config
+    }
+    /// use `seed` for all following episodes, disabling `seed_schedule`
+    pub fn set_seed(&mut self, seed: u128) {
+        self.seed = Some(seed);
+        self.seed_schedule = None;
     }
     pub fn to_json(&self) -> GameResult<String> {
         serde_json::to_string_pretty(self).context("GameConfig::to_json")
     }
+    /// json with all settings including default ones, where builtin weapons, armors and
+    /// enemies are expanded to their definitions, to show every knob available.
+    /// It can be parsed as a config which makes the same game.
+    pub fn to_canonical_json(&self) -> GameResult<String> {
+        let mut config = self.clone();
+        config.item = config.item.canonical();
+        config.enemies = config.enemies.canonical();
+        SERIALIZE_DEFAULTS.with(|s| s.set(true));
+        let res = serde_json::to_string_pretty(&config);
+        SERIALIZE_DEFAULTS.with(|s| s.set(false));
+        res.context("GameConfig::to_canonical_json")
+    }
     pub fn symbol_max(&self) -> Option<symbol::Symbol> {
-        match self.enemies.tile_max() {
-            Some(t) => symbol::Symbol::from_tile(t.into()),
-            None => symbol::Symbol::from_tile(b'A'.into()).map(|s| s.decrement()),
-        }
+        symbol::Symbol::max_with_enemies(self.enemies.tile_max())
     }
     fn to_global(&self) -> GameResult<GlobalConfig> {
         let seed = if let Some(s) = self.seed {
@@ -163,44 +345,34 @@ impl GameConfig {
                 rng::gen_seed()
             }
         };
-        let (w, h) = (self.width, self.height);
-        if w < MIN_WIDTH {
-            bail!(ErrorKind::InvalidSetting(
-                "screen width is too narrow".into()
-            ));
-        }
-        if w > MAX_WIDTH {
-            bail!(ErrorKind::InvalidSetting("screen width is too wide".into()));
-        }
-        if h < MIN_HEIGHT {
-            bail!(ErrorKind::InvalidSetting(
-                "screen height is too narrow".into()
-            ));
-        }
-        if h > MAX_HEIGHT {
-            bail!(ErrorKind::InvalidSetting(
-                "screen height is too wide".into()
-            ));
-        }
         Ok(GlobalConfig {
-            width: w.into(),
-            height: h.into(),
+            width: self.width.into(),
+            height: self.height.into(),
             seed,
             hide_dungeon: self.hide_dungeon,
         })
     }
     /// get runtime from config
     pub fn build(self) -> GameResult<RunTime> {
-        const ERR_STR: &str = "GameConfig::build";
+        let style = self.dungeon.clone();
+        let generator = style.generator().context("GameConfig::build")?;
+        self.build_with(generator)
+    }
+    /// get runtime from config, using `generator` instead of `self.dungeon`
+    pub fn build_with(self, generator: &dyn DungeonGenerator) -> GameResult<RunTime> {
+        const ERR_STR: &str = "GameConfig::build_with";
+        self.validate().context(ERR_STR)?;
         let game_info = GameInfo::new();
         let config = self.to_global().context(ERR_STR)?;
         debug!("Building dungeon with seed {}", config.seed);
         // TODO: invalid checking
+        let difficulty = self.difficulty.multipliers();
         let mut item = ItemHandler::new(self.item.clone(), config.seed);
+        item.set_difficulty(&difficulty);
         let mut enemies = self.enemies.build(config.seed);
-        let mut dungeon = self
-            .dungeon
-            .build(&config, &mut item, &mut enemies, &game_info, config.seed)
+        enemies.set_difficulty(&difficulty);
+        let mut dungeon = generator
+            .generate(&config, &mut item, &mut enemies, &game_info, config.seed)
             .context(ERR_STR)?;
         // TODO: invalid checking
         let mut player = self.player.build();
@@ -224,6 +396,11 @@ impl GameConfig {
             ui: UiState::Dungeon,
             saved_inputs: vec![],
             keymap: self.keymap,
+            observation: self.observation,
+            reward: self.reward,
+            episode: self.episode,
+            trace: None,
+            events: None,
         })
     }
 }
@@ -239,6 +416,13 @@ pub struct RunTime {
     saved_inputs: Vec<InputCode>,
     enemies: EnemyHandler,
     pub keymap: KeyMap,
+    observation: observation::Config,
+    reward: reward::RewardSpec,
+    episode: episode::Limits,
+    /// if Some, observations and inputs are recorded every turn
+    trace: Option<trace::TraceWriter<Box<dyn Write>>>,
+    /// if Some, events are queued here until `drain_events` is called
+    events: Option<Vec<events::GameEvent>>,
 }
 
 impl RunTime {
@@ -275,20 +459,125 @@ impl RunTime {
             if let Some(item) = self.dungeon.get_item(&path) {
                 return drawer(Positioned(cd, item.tile()));
             }
-            if let Some(enemy) = self.enemies.get_enemy(&path) {
-                if self.dungeon.draw_enemy(&self.player.pos, &path) {
-                    return drawer(Positioned(cd, enemy.tile()));
-                }
+            if let Some(enemy) = self.visible_enemy(&path) {
+                return drawer(Positioned(cd, enemy.tile()));
+            }
+            Ok(())
+        })
+    }
+    /// cells the player sees now, which are only the player's own cell while blind
+    pub fn cells_in_sight(&self) -> std::collections::HashSet<Coord> {
+        let mut cells: std::collections::HashSet<_> =
+            if self.player.effects().is_active(Effect::Blindness) {
+                Default::default()
+            } else {
+                self.dungeon.visible_cells(&self.player.pos).collect()
+            };
+        cells.insert(self.player_position());
+        cells
+    }
+    /// same as `draw_screen`, but also tells if each tile is in sight or only remembered.
+    /// Blank tiles are always `MapLayer::Visible`.
+    pub fn draw_screen_layered(
+        &self,
+        mut drawer: impl FnMut(Positioned<Tile>, MapLayer) -> GameResult<()>,
+    ) -> GameResult<()> {
+        let sight = self.cells_in_sight();
+        self.draw_screen(|Positioned(cd, tile)| {
+            let layer = if tile.to_byte() == b' ' || sight.contains(&cd) {
+                MapLayer::Visible
+            } else {
+                MapLayer::Remembered
+            };
+            drawer(Positioned(cd, tile), layer)
+        })
+    }
+    /// the enemy at `path`, if the player can see it
+    fn visible_enemy(&self, path: &dungeon::DungeonPath) -> Option<&character::Enemy> {
+        self.enemies.get_enemy(path).filter(|enemy| {
+            !enemy.is_invisible()
+                && !self.player.effects().is_active(Effect::Blindness)
+                && self.dungeon.draw_enemy(&self.player.pos, path)
+        })
+    }
+    /// fill `grid`, a row-major H×W array, with symbol indices of the screen(see `symbol.rs`).
+    /// Tiles which have no symbols are filled with `trace::NO_SYMBOL`.
+    pub fn symbol_grid(&self, grid: &mut [u8]) -> GameResult<()> {
+        let (w, h) = self.screen_size();
+        let (w, h) = (w.0 as usize, h.0 as usize);
+        if grid.len() != w * h {
+            bail!(ErrorKind::InvalidSetting(
+                format!("symbol grid should have {}×{} cells", h, w).into()
+            ));
+        }
+        let blank = symbol::Symbol::from_tile(b' '.into()).map_or(0, symbol::Symbol::to_byte);
+        grid.iter_mut().for_each(|s| *s = blank);
+        self.draw_screen(|Positioned(cd, tile)| {
+            let (x, y) = (cd.x.0 as usize, cd.y.0 as usize);
+            if x < w && y < h {
+                grid[y * w + x] = symbol::Symbol::from_tile(tile)
+                    .map_or(trace::NO_SYMBOL, symbol::Symbol::to_byte);
             }
             Ok(())
         })
     }
+    /// draw the explored map and the player, without items and enemies
+    pub fn draw_map(
+        &self,
+        mut drawer: impl FnMut(Positioned<Tile>) -> GameResult<()>,
+    ) -> GameResult<()> {
+        self.dungeon.draw(&mut drawer)?;
+        drawer(Positioned(self.player_position(), self.player.tile()))
+    }
+    /// rows of the screen drawn by `draw_screen`, where undrawn cells are spaces
+    pub(crate) fn screen_rows(&self) -> GameResult<Vec<String>> {
+        let (w, h) = self.screen_size();
+        let (w, h) = (w.0 as usize, h.0 as usize);
+        let mut screen = vec![vec![' '; w]; h];
+        self.draw_screen(|Positioned(cd, tile)| {
+            let (x, y) = (cd.x.0 as usize, cd.y.0 as usize);
+            if x < w && y < h {
+                screen[y][x] = tile.to_char();
+            }
+            Ok(())
+        })?;
+        Ok(screen
+            .into_iter()
+            .map(|row| row.into_iter().collect())
+            .collect())
+    }
+    /// the screen followed by 2 lines of the status, as plain text without escape codes
+    pub fn render_ascii(&self) -> GameResult<String> {
+        let mut rows = self.screen_rows()?;
+        rows.extend(self.player_status().lines().iter().cloned());
+        Ok(rows.join("\n"))
+    }
     pub fn react_to_input(&mut self, input: InputCode) -> GameResult<Vec<Reaction>> {
+        let mut res = Vec::new();
+        self.react_to_input_into(input, &mut res)?;
+        Ok(res)
+    }
+    /// same as `react_to_input`, but clears `res` and writes reactions into it,
+    /// so that callers stepping games many times can reuse the buffer
+    pub fn react_to_input_into(
+        &mut self,
+        input: InputCode,
+        res: &mut Vec<Reaction>,
+    ) -> GameResult<()> {
         trace!("[react_to_input] input: {:?} ui: {:?}", input, self.ui);
+        res.clear();
         self.saved_inputs.push(input);
-        let (next_ui, res) = match self.ui {
+        let before = self.events.as_ref().map(|_| self.player_status());
+        let record = match self.trace {
+            Some(_) => Some(trace::TraceRecord::observe(self, input)?),
+            None => None,
+        };
+        let next_ui = match self.ui {
             UiState::Dungeon => match input {
-                InputCode::Sys(sys) => (None, self.check_interrupting(sys)?),
+                InputCode::Sys(sys) => {
+                    res.append(&mut self.check_interrupting(sys)?);
+                    None
+                }
                 InputCode::Act(act) | InputCode::Both { act, .. } => actions::process_action(
                     act,
                     &mut self.game_info,
@@ -296,28 +585,62 @@ impl RunTime {
                     &mut self.item,
                     &mut self.player,
                     &mut self.enemies,
+                    res,
                 )?,
             },
             UiState::Mordal(ref mut kind) => match input {
-                InputCode::Sys(sys) | InputCode::Both { sys, .. } => {
-                    let res = kind.process(sys);
-                    match res {
-                        MordalMsg::Cancel => (
-                            Some(UiState::Dungeon),
-                            vec![Reaction::UiTransition(UiState::Dungeon)],
-                        ),
-                        MordalMsg::Save => bail!(ErrorKind::Unimplemented("Save command")),
-                        MordalMsg::Quit => (None, vec![Reaction::Notify(GameMsg::Quit)]),
-                        MordalMsg::None => (None, vec![]),
+                InputCode::Sys(sys) | InputCode::Both { sys, .. } => match kind.process(sys) {
+                    MordalMsg::Cancel => {
+                        res.push(Reaction::UiTransition(UiState::Dungeon));
+                        Some(UiState::Dungeon)
                     }
-                }
+                    MordalMsg::Save => bail!(ErrorKind::Unimplemented("Save command")),
+                    MordalMsg::Quit => {
+                        res.push(Reaction::Notify(GameMsg::Quit));
+                        None
+                    }
+                    MordalMsg::None => None,
+                },
                 InputCode::Act(_) => bail!(ErrorKind::IgnoredInput(input)),
             },
         };
+        if let (Some(trace), Some(record)) = (&mut self.trace, record) {
+            trace.write(&record)?;
+        }
         if let Some(next_ui) = next_ui {
             self.ui = next_ui;
         }
-        Ok(res)
+        self.game_info.record(res, self.dungeon.level());
+        let status = self.player_status();
+        let visited = self.dungeon.visited_cells();
+        self.game_info.stats.explore(status.dungeon_level, visited);
+        if let Some(end) = self
+            .episode
+            .end_reaction(&status, res, &mut self.game_info.episode_end)
+        {
+            res.push(end);
+        }
+        if let (Some(events), Some(before)) = (&mut self.events, before) {
+            events.extend(events::GameEvent::collect(&before, &status, res));
+        }
+        Ok(())
+    }
+    /// start queueing events(see `events.rs`), which are taken by `drain_events`
+    pub fn subscribe_events(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+    /// take all events queued since the last call,
+    /// which are always empty before `subscribe_events` is called
+    pub fn drain_events(&mut self) -> Vec<events::GameEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+    /// counters accumulated through the game, like kills by enemies and damage taken
+    pub fn statistics(&self) -> &stats::Statistics {
+        &self.game_info.stats
+    }
+    /// why the episode ended, if any limit in `GameConfig::episode` is reached
+    pub fn episode_end(&self) -> Option<&episode::EndReason> {
+        self.game_info.episode_end.as_ref()
     }
     pub fn react_to_key(&mut self, key: Key) -> GameResult<Vec<Reaction>> {
         match self.keymap.get(key) {
@@ -339,18 +662,31 @@ impl RunTime {
             None => Err(ErrorKind::InvalidInput(key).into()),
         }
     }
+    /// screen coordinates a missile thrown toward `direction` would pass
+    pub fn missile_path(&self, direction: Direction) -> Vec<Coord> {
+        actions::missile_path(direction, &*self.dungeon, &self.player.pos, &self.enemies)
+            .iter()
+            .map(|path| self.dungeon.path_to_cd(path))
+            .collect()
+    }
+    /// screen coordinate of the player
+    pub fn player_position(&self) -> Coord {
+        self.dungeon.path_to_cd(&self.player.pos)
+    }
+    pub fn ui(&self) -> &UiState {
+        &self.ui
+    }
     pub fn screen_size(&self) -> (X, Y) {
         (self.config.width, self.config.height)
     }
+    /// same as `GameConfig::symbol_max` of the config this game is built from
+    pub fn symbol_max(&self) -> Option<symbol::Symbol> {
+        symbol::Symbol::max_with_enemies(self.enemies.tile_max())
+    }
     pub fn player_status(&self) -> player::Status {
         let mut status = player::Status::default();
         self.player.fill_status(&mut status);
-        status.gold = self
-            .player
-            .itembox
-            .tokens()
-            .find(|token| token.get().kind == ItemKind::Gold)
-            .map_or(0, |token| token.get().how_many.0);
+        status.gold = self.player.itembox.gold();
         status.dungeon_level = self.dungeon.level();
         status
     }
@@ -361,6 +697,109 @@ impl RunTime {
         serde_json::to_string_pretty(&self.saved_inputs)
             .context("Runtime::saved_inputs_json: Failed to serialize")
     }
+    /// deep copy of the game including all random number generators, which behaves exactly
+    /// the same as the original under the same inputs, for planning algorithms like MCTS.
+    /// The trace is not copied.
+    pub fn clone_deterministic(&self) -> RunTime {
+        let mut cloner = snapshot::DeepCloner::new();
+        RunTime {
+            game_info: self.game_info.clone(),
+            config: self.config.clone(),
+            dungeon: self.dungeon.deep_clone(&mut cloner),
+            item: self.item.deep_clone(&mut cloner),
+            player: self.player.deep_clone(&mut cloner),
+            ui: self.ui.clone(),
+            saved_inputs: self.saved_inputs.clone(),
+            enemies: self.enemies.deep_clone(&mut cloner),
+            keymap: self.keymap.clone(),
+            observation: self.observation.clone(),
+            reward: self.reward.clone(),
+            episode: self.episode.clone(),
+            trace: None,
+            events: None,
+        }
+    }
+    /// start recording the observation and the input every turn into `writer`
+    pub fn start_trace(&mut self, writer: Box<dyn Write>) -> GameResult<()> {
+        let (w, h) = self.screen_size();
+        self.trace = Some(trace::TraceWriter::new(writer, w.0 as u16, h.0 as u16)?);
+        Ok(())
+    }
+    /// stop recording the trace and flush it
+    pub fn finish_trace(&mut self) -> GameResult<()> {
+        if let Some(mut trace) = self.trace.take() {
+            trace.flush()?;
+        }
+        Ok(())
+    }
+    /// take snapshots of all random number generators in the game
+    pub fn save_rng_states(&self) -> RngStates {
+        let (enemies, combat) = self.enemies.rng_states();
+        RngStates {
+            dungeon: self.dungeon.rng_state(),
+            item: self.item.rng_state(),
+            enemies,
+            combat,
+        }
+    }
+    /// restore all random number generators in the game from snapshots
+    pub fn restore_rng_states(&mut self, states: &RngStates) {
+        self.dungeon.rng().restore_state(&states.dungeon);
+        self.item.rng().restore_state(&states.item);
+        self.enemies.rng().restore_state(&states.enemies);
+        self.enemies.combat_rng().restore_state(&states.combat);
+    }
+    /// hash of the whole game state, including enemies and items the player can't see,
+    /// remembered cells of all floors, and the states of random number generators
+    /// It's stable across platforms and versions, as long as the game behaves the same.
+    pub fn state_hash(&self) -> GameResult<u64> {
+        let mut hasher = replay::StateHasher::new();
+        serde_json::to_writer(&mut hasher, &self.state_ref()?).context("RunTime::state_hash")?;
+        Ok(hasher.finish())
+    }
+    /// same as `react_to_input`, but inputs ignored by the game (e.g. invalid keys)
+    /// don't cause errors, as in replays
+    pub fn replay_input(&mut self, input: InputCode) -> GameResult<Vec<Reaction>> {
+        let mut res = Vec::new();
+        self.replay_input_into(input, &mut res)?;
+        Ok(res)
+    }
+    /// same as `replay_input`, but writes reactions into `res` like `react_to_input_into`
+    pub fn replay_input_into(
+        &mut self,
+        input: InputCode,
+        res: &mut Vec<Reaction>,
+    ) -> GameResult<()> {
+        match self.react_to_input_into(input, res) {
+            Ok(()) => Ok(()),
+            Err(e) => match e.downcast_ref::<ErrorKind>() {
+                Some(kind) if kind.can_allow() => {
+                    res.clear();
+                    Ok(())
+                }
+                _ => Err(e),
+            },
+        }
+    }
+    /// make a replay file from the inputs so far
+    /// `config` should be the configuration this game is built from.
+    pub fn replay_file(&self, config: GameConfig) -> replay::ReplayFile {
+        replay::ReplayFile::new(config, self.config.seed, self.saved_inputs.clone())
+    }
+    /// replay `inputs` and check if the state hash becomes `expected_hash`
+    pub fn verify_replay(&mut self, inputs: &[InputCode], expected_hash: u64) -> GameResult<()> {
+        for &input in inputs {
+            self.replay_input(input).context("RunTime::verify_replay")?;
+        }
+        let actual = self.state_hash()?;
+        if actual != expected_hash {
+            bail!(ErrorKind::ReplayMismatch {
+                expected: expected_hash,
+                actual,
+            });
+        }
+        Ok(())
+    }
     pub fn history(&self, player_stat: &player::Status) -> Option<Array2<bool>> {
         self.dungeon.get_history(&player_stat)
     }
@@ -368,6 +807,46 @@ impl RunTime {
         debug!("itembox {:?}", self.player.itembox);
         &self.player.itembox
     }
+    /// the name of the item to display, considering whether it's identified or not
+    pub fn item_name(&self, item: &Item) -> String {
+        self.item.item_name(item)
+    }
+    /// the name of `num` items of the kind to use in messages, e.g., "an amber potion"
+    pub fn kind_name(&self, kind: &ItemKind, num: u32) -> String {
+        self.item.kind_name(kind, num)
+    }
+    /// give a nickname to the kind of the unidentified item labeled by `letter`,
+    /// which is shown instead of its appearance. An empty name removes the nickname.
+    pub fn call_item(&mut self, letter: char, name: &str) -> GameResult<()> {
+        let kind = match self.player.itembox.get(letter) {
+            Some(token) => token.kind.clone(),
+            None => bail!(ErrorKind::InvalidSetting(
+                format!("You don't have item {}", letter).into()
+            )),
+        };
+        let name = Some(name.trim())
+            .filter(|name| !name.is_empty())
+            .map(SmallStr::from_str);
+        if !self.item.call(&kind, name) {
+            bail!(ErrorKind::InvalidSetting(
+                format!("You already know what item {} is", letter).into()
+            ));
+        }
+        Ok(())
+    }
+    /// the item of `id`(e.g. from `GameMsg::GotItem`), if it still exists
+    pub fn item(&self, id: ItemId) -> Option<Item> {
+        self.item.get(id).map(|token| token.get_cloned())
+    }
+}
+
+/// Snapshots of all random number generators in the game, which should be saved with the game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RngStates {
+    pub dungeon: RngState,
+    pub item: RngState,
+    pub enemies: RngState,
+    pub combat: RngState,
 }
 
 pub fn json_to_inputs(json: &str) -> GameResult<Vec<InputCode>> {
@@ -385,20 +864,94 @@ pub enum Reaction {
     UiTransition(UiState),
     /// Game Messages,
     Notify(GameMsg),
+    /// The episode ended by a limit, not by death
+    EpisodeEnd(episode::EndReason),
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum GameMsg {
     CantMove(Direction),
     CantGetItem(ItemKind),
-    GotItem { kind: ItemKind, num: u32 },
+    /// `id` is the id of the item in the pack, which is the stack's id if it's merged
+    GotItem {
+        id: ItemId,
+        kind: ItemKind,
+        num: u32,
+    },
     HitTo(SmallStr),
     HitFrom(SmallStr),
     MissTo(SmallStr),
     MissFrom(SmallStr),
     Killed(SmallStr),
+    Combat(CombatEvent),
+    Projectile(Projectile),
+    EnemySpawned(SmallStr),
     NoDownStair,
+    NoUpStair,
+    NoWay,
+    RestInterrupted,
+    NothingToThrow,
+    NothingToPickUp,
+    PackIsFull {
+        kind: ItemKind,
+        num: u32,
+    },
+    /// the player walked onto an item sold in a shop
+    ForSale {
+        kind: ItemKind,
+        num: u32,
+        price: u32,
+    },
+    Bought {
+        id: ItemId,
+        kind: ItemKind,
+        num: u32,
+        price: u32,
+    },
+    CantAfford {
+        kind: ItemKind,
+        num: u32,
+        price: u32,
+    },
+    Sold {
+        kind: ItemKind,
+        num: u32,
+        price: u32,
+    },
+    Dropped {
+        kind: ItemKind,
+        num: u32,
+    },
+    EnemyDropped {
+        enemy: SmallStr,
+        kind: ItemKind,
+        num: u32,
+    },
+    CantDropHere,
+    /// a thrown weapon came back to the pack
+    Returned(ItemKind),
+    Read(ItemKind),
+    CantRead(ItemKind),
+    /// the player came to know what the item is
+    Identified(ItemKind),
+    NoSuchItem(char),
     SecretDoor,
+    SecretPassage,
+    TrapFound(TrapKind),
+    Trapped(TrapKind),
+    DoorOpened,
+    DoorClosed,
+    NoDoor,
+    ArmorWeakened,
+    GoldStolen(u32),
+    ItemStolen {
+        kind: ItemKind,
+        num: u32,
+    },
+    LevelDrained,
+    StrengthDrained,
+    EffectStarted(Effect),
+    EffectEnded(Effect),
     Quit,
 }
 
@@ -415,19 +968,43 @@ pub struct GlobalConfig {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GameInfo {
     is_cleared: bool,
+    /// number of enemies the player has killed
+    #[serde(default)]
+    kills: u32,
+    #[serde(default)]
+    deepest_level: u32,
+    /// set when the episode ended by a limit
+    #[serde(default)]
+    episode_end: Option<episode::EndReason>,
+    #[serde(default)]
+    stats: stats::Statistics,
 }
 
 impl GameInfo {
     fn new() -> Self {
-        GameInfo { is_cleared: false }
+        GameInfo {
+            is_cleared: false,
+            kills: 0,
+            deepest_level: 0,
+            episode_end: None,
+            stats: stats::Statistics::default(),
+        }
+    }
+    /// update statistics by reactions to an input
+    fn record(&mut self, reactions: &[Reaction], level: u32) {
+        self.kills += reactions
+            .iter()
+            .filter(|r| matches!(r, Reaction::Notify(GameMsg::Killed(_))))
+            .count() as u32;
+        self.deepest_level = std::cmp::max(self.deepest_level, level);
+        self.stats.record(reactions, level);
     }
 }
 
-pub fn read_file(name: &str) -> io::Result<String> {
-    let mut file = File::open(name)?;
-    let mut buf = String::new();
-    file.read_to_string(&mut buf)?;
-    Ok(buf)
+/// not available on wasm, which has no file system
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_file(name: &str) -> std::io::Result<String> {
+    std::fs::read_to_string(name)
 }
 
 #[cfg(test)]
@@ -455,4 +1032,89 @@ mod config_test {
         let config: GameConfig = serde_json::from_str("{}").unwrap();
         assert_eq!(config, GameConfig::default());
     }
+    #[test]
+    fn screen_colors() {
+        let config: GameConfig =
+            serde_json::from_str(r#"{"screen": {"colors": {"@": "cyan"}}}"#).unwrap();
+        let table = config.screen.color_table();
+        assert_eq!(table[&'@'], tile::Color::CYAN);
+        assert_eq!(table[&'*'], tile::Color::YELLOW);
+    }
+    fn violation_paths(json: &str) -> Vec<String> {
+        let err = GameConfig::from_json(json).unwrap_err();
+        match err.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::InvalidConfig(violations)) => {
+                violations.0.iter().map(|v| v.path.clone()).collect()
+            }
+            _ => panic!("unexpected error {}", err),
+        }
+    }
+    #[test]
+    fn violations() {
+        let json = r#"{
+            "width": 10,
+            "item": {"armor": {}, "gold": {}, "weapon": {"weapons": [0, 100], "cursed_rate": 120}},
+            "enemies": {"enemies": [{"builtin": 30, "carry": 50}], "appear_rate_gold": 200}
+        }"#;
+        assert_eq!(
+            violation_paths(json),
+            vec![
+                "width",
+                "item.weapon.weapons[1]",
+                "item.weapon.cursed_rate",
+                "enemies.enemies[0].builtin",
+                "enemies.appear_rate_gold",
+            ]
+        );
+        let json = r#"{"width": "wide", "seed": -1, "player": {}}"#;
+        assert_eq!(violation_paths(json), vec!["seed", "width"]);
+        // missing fields are reported at the object
+        let json = r#"{"item": {"weapon": {}}, "player": {"hunger_time": "long"}}"#;
+        assert_eq!(violation_paths(json), vec!["item", "player.hunger_time"]);
+        // type errors in nested sections are reported with their paths,
+        // and the other sections are still checked semantically
+        let json = r#"{
+            "width": 10,
+            "item": {"armor": {}, "gold": {"rate_inv": "often"}, "weapon": {"cursed_rate": -5}},
+            "enemies": {"enemies": [0, {"builtin": 1, "carry": "always"}], "appear_rate_gold": 200}
+        }"#;
+        assert_eq!(
+            violation_paths(json),
+            vec![
+                "enemies.enemies[1]",
+                "item.gold.rate_inv",
+                "item.weapon.cursed_rate",
+                "width",
+                "enemies.appear_rate_gold",
+            ]
+        );
+        assert!(GameConfig::from_json(r#"{"width": 40}"#).is_ok());
+        let mut config = GameConfig::default();
+        config.height = 100;
+        assert!(config.build().is_err());
+    }
+    #[test]
+    fn canonical() {
+        let mut config = GameConfig::default();
+        config.seed = Some(5);
+        let json = config.to_canonical_json().unwrap();
+        for knob in &[
+            "cursed_rate",
+            "powerup_rate",
+            "wander_interval",
+            "at_weild",
+            "rarelity",
+        ] {
+            assert!(json.contains(knob), "{} is not in {}", knob, json);
+        }
+        let canonical = GameConfig::from_json(&json).unwrap();
+        // keys of the keymap are not sorted, so compare them as json values
+        let value = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+        assert_eq!(value(&canonical.to_canonical_json().unwrap()), value(&json));
+        let (original, canonical) = (config.build().unwrap(), canonical.build().unwrap());
+        assert_eq!(
+            original.state_hash().unwrap(),
+            canonical.state_hash().unwrap()
+        );
+    }
 }
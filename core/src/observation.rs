@@ -0,0 +1,427 @@
+//! observations for agents, built on symbol grids(see `symbol.rs`)
+use crate::character::player::Hunger;
+use crate::dungeon::{MapLayer, Positioned};
+use crate::error::*;
+use crate::symbol::{Layout, OneHot, Symbol};
+use crate::tile::{Drawable, Tile};
+use crate::RunTime;
+use anyhow::{bail, Context};
+use serde_json::json;
+
+/// dungeon level which the depth plane is scaled by, the default level of the amulet
+const DEPTH_SCALE: f32 = 26.0;
+
+/// version of the format of `RunTime::state_as_json`
+pub const STATE_JSON_VERSION: u32 = 1;
+
+/// Configuration of observations
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Config {
+    /// if Some, observations are cropped to a (2r + 1) × (2r + 1) window
+    /// centered on the player, where cells outside the screen are blank
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub egocentric_radius: Option<u32>,
+    /// extra channels following the symbol channels
+    #[serde(default, skip_serializing_if = "Channels::is_none")]
+    pub channels: Channels,
+}
+
+/// Extra channels of observations, each of which is a plane of the observation size
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Channels {
+    /// current HP / max HP
+    #[serde(default)]
+    pub hp: bool,
+    /// 0 if not hungry, 0.5 if hungry, and 1 if weak
+    #[serde(default)]
+    pub hunger: bool,
+    /// 1 for cells the player has explored
+    #[serde(default)]
+    pub explored: bool,
+    /// 1 for cells where the player sees enemies
+    #[serde(default)]
+    pub monsters: bool,
+    /// dungeon level / 26
+    #[serde(default)]
+    pub depth: bool,
+    /// 1 for cells the player sees now
+    #[serde(default)]
+    pub visible: bool,
+    /// 1 for explored cells the player doesn't see now
+    #[serde(default)]
+    pub remembered: bool,
+}
+
+impl Channels {
+    /// the number of enabled channels
+    pub fn count(&self) -> usize {
+        [
+            self.hp,
+            self.hunger,
+            self.explored,
+            self.monsters,
+            self.depth,
+            self.visible,
+            self.remembered,
+        ]
+        .iter()
+        .filter(|&&b| b)
+        .count()
+    }
+    fn is_none(&self) -> bool {
+        self.count() == 0
+    }
+}
+
+impl Config {
+    /// (width, height) of observations, for a screen of `width` x `height`
+    pub fn size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self.egocentric_radius {
+            Some(r) => {
+                let len = r as usize * 2 + 1;
+                (len, len)
+            }
+            None => (width, height),
+        }
+    }
+}
+
+/// symbol for cells outside of the screen
+pub fn pad_symbol() -> u8 {
+    Symbol::from_tile(b' '.into()).map_or(0, Symbol::to_byte)
+}
+
+/// copy a (2 * `radius` + 1)-sided square window of `grid` centered on `center`
+/// into `out`, filling cells outside of `grid` with `pad`
+pub fn crop(
+    grid: &[u8],
+    (w, h): (usize, usize),
+    (cx, cy): (i32, i32),
+    radius: u32,
+    pad: u8,
+    out: &mut [u8],
+) {
+    let r = radius as i32;
+    let len = r * 2 + 1;
+    for dy in 0..len {
+        for dx in 0..len {
+            let (x, y) = (cx - r + dx, cy - r + dy);
+            let inside = x >= 0 && y >= 0 && (x as usize) < w && (y as usize) < h;
+            out[(dy * len + dx) as usize] = if inside {
+                grid[y as usize * w + x as usize]
+            } else {
+                pad
+            };
+        }
+    }
+}
+
+impl RunTime {
+    /// (width, height) of observations made by `observe_symbols`
+    pub fn observation_size(&self) -> (usize, usize) {
+        let (w, h) = self.screen_size();
+        self.observation.size(w.0 as usize, h.0 as usize)
+    }
+    /// the number of channels of `observe`, with `symbols` symbol channels
+    pub fn observation_channels(&self, symbols: u8) -> usize {
+        usize::from(symbols) + self.observation.channels.count()
+    }
+    /// copy a grid of the screen into `out`, cropping it if `egocentric_radius` is set
+    fn crop_screen(&self, grid: &[u8], pad: u8, out: &mut [u8]) -> GameResult<()> {
+        let (ow, oh) = self.observation_size();
+        if out.len() != ow * oh {
+            bail!(ErrorKind::InvalidSetting(
+                format!("observation should have {}×{} cells", oh, ow).into()
+            ));
+        }
+        let (w, h) = self.screen_size();
+        match self.observation.egocentric_radius {
+            Some(radius) => {
+                let player = self.player_position();
+                let size = (w.0 as usize, h.0 as usize);
+                crop(grid, size, (player.x.0, player.y.0), radius, pad, out);
+            }
+            None => out.copy_from_slice(grid),
+        }
+        Ok(())
+    }
+    /// 1 for cells where `f` holds for the drawn tile and its layer, and 0 for the others
+    fn tile_mask(&self, map_only: bool, f: impl Fn(Tile, MapLayer) -> bool) -> GameResult<Vec<u8>> {
+        let (w, h) = self.screen_size();
+        let (w, h) = (w.0 as usize, h.0 as usize);
+        let mut mask = vec![0; w * h];
+        let sight = self.cells_in_sight();
+        let mut draw = |Positioned(cd, tile): Positioned<Tile>| -> GameResult<()> {
+            let (x, y) = (cd.x.0 as usize, cd.y.0 as usize);
+            let layer = if sight.contains(&cd) {
+                MapLayer::Visible
+            } else {
+                MapLayer::Remembered
+            };
+            if x < w && y < h && f(tile, layer) {
+                mask[y * w + x] = 1;
+            }
+            Ok(())
+        };
+        if map_only {
+            self.draw_map(&mut draw)?;
+        } else {
+            self.draw_screen(&mut draw)?;
+        }
+        let mut out = vec![0; self.observation_size().0 * self.observation_size().1];
+        self.crop_screen(&mask, 0, &mut out)?;
+        Ok(out)
+    }
+    /// fill `out` with symbol indices of the screen, or of the window around the player
+    /// if `egocentric_radius` is set, in row-major order
+    pub fn observe_symbols(&self, out: &mut [u8]) -> GameResult<()> {
+        if self.observation.egocentric_radius.is_none() {
+            return self.symbol_grid(out);
+        }
+        let (w, h) = self.screen_size();
+        let mut grid = vec![0; w.0 as usize * h.0 as usize];
+        self.symbol_grid(&mut grid)?;
+        self.crop_screen(&grid, pad_symbol(), out)
+    }
+    /// fill `out` with one-hot symbol channels of `observe_symbols` followed by
+    /// extra channels enabled in the configuration, in `layout`
+    pub fn observe<T: OneHot>(&self, symbols: u8, layout: Layout, out: &mut [T]) -> GameResult<()> {
+        let (w, h) = self.observation_size();
+        let channels = self.observation_channels(symbols);
+        if out.len() != channels * w * h {
+            bail!(ErrorKind::InvalidSetting(
+                format!("observation should have {}×{}×{} cells", channels, h, w).into()
+            ));
+        }
+        out.iter_mut().for_each(|o| *o = T::ZERO);
+        let mut grid = vec![0; w * h];
+        self.observe_symbols(&mut grid)?;
+        for (i, &sym) in grid.iter().enumerate() {
+            if sym >= symbols {
+                bail!(ErrorKind::InvalidSetting(
+                    format!("symbol {} exceeds the number of symbols {}", sym, symbols).into()
+                ));
+            }
+            out[layout.index(channels, h, w, [usize::from(sym), i / w, i % w])] = T::ONE;
+        }
+        let config = self.observation.channels;
+        let status = self.player_status();
+        let mut planes = vec![];
+        if config.hp {
+            let ratio = status.hp.current.0 as f32 / status.hp.max.0.max(1) as f32;
+            planes.push(vec![T::from_ratio(ratio); w * h]);
+        }
+        if config.hunger {
+            let hunger = match status.hunger_level {
+                Hunger::Normal => 0.0,
+                Hunger::Hungry => 0.5,
+                Hunger::Weak => 1.0,
+            };
+            planes.push(vec![T::from_ratio(hunger); w * h]);
+        }
+        let to_plane = |mask: Vec<u8>| -> Vec<T> {
+            mask.into_iter()
+                .map(|m| if m == 1 { T::ONE } else { T::ZERO })
+                .collect()
+        };
+        if config.explored {
+            planes.push(to_plane(self.tile_mask(true, |t, _| t.to_byte() != b' ')?));
+        }
+        if config.monsters {
+            planes.push(to_plane(
+                self.tile_mask(false, |t, _| t.to_byte().is_ascii_uppercase())?,
+            ));
+        }
+        if config.depth {
+            let depth = status.dungeon_level as f32 / DEPTH_SCALE;
+            planes.push(vec![T::from_ratio(depth); w * h]);
+        }
+        if config.visible {
+            planes.push(to_plane(
+                self.tile_mask(true, |_, l| l == MapLayer::Visible)?,
+            ));
+        }
+        if config.remembered {
+            planes.push(to_plane(self.tile_mask(true, |t, l| {
+                t.to_byte() != b' ' && l == MapLayer::Remembered
+            })?));
+        }
+        for (c, plane) in planes.into_iter().enumerate() {
+            let c = usize::from(symbols) + c;
+            for (i, v) in plane.into_iter().enumerate() {
+                out[layout.index(channels, h, w, [c, i / w, i % w])] = v;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RunTime {
+    /// structured description of the visible state, for agents not written in Rust.
+    /// It has `version`(`STATE_JSON_VERSION`), `player`, `enemies` with positions,
+    /// `inventory`, and `map`, the rows of the screen.
+    pub fn state_as_json(&self) -> GameResult<String> {
+        let map = self.screen_rows()?;
+        let status: serde_json::Map<_, _> = self
+            .player_status()
+            .to_dict_vec()
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), json!(v)))
+            .collect();
+        let player = self.player_position();
+        let enemies: Vec<_> = self
+            .dungeon
+            .draw_ranges()
+            .into_iter()
+            .filter(|path| *path != self.player.pos)
+            .filter_map(|path| {
+                let enemy = self.visible_enemy(&path)?;
+                let cd = self.dungeon.path_to_cd(&path);
+                Some(json!({
+                    "name": enemy.name().to_string(),
+                    "symbol": enemy.tile().to_char().to_string(),
+                    "x": cd.x.0,
+                    "y": cd.y.0,
+                }))
+            })
+            .collect();
+        let inventory: Vec<_> = self
+            .itembox()
+            .lettered()
+            .map(|(letter, item)| {
+                json!({
+                    "letter": letter.to_string(),
+                    "name": item.get().to_string(),
+                })
+            })
+            .collect();
+        let state = json!({
+            "version": STATE_JSON_VERSION,
+            "player": {"x": player.x.0, "y": player.y.0, "status": status},
+            "enemies": enemies,
+            "inventory": inventory,
+            "map": map,
+        });
+        serde_json::to_string(&state).context("RunTime::state_as_json")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::GameConfig;
+    #[test]
+    fn crop_with_padding() {
+        #[rustfmt::skip]
+        let grid = [
+            1, 2, 3,
+            4, 5, 6,
+        ];
+        let mut out = [0; 9];
+        crop(&grid, (3, 2), (0, 0), 1, 9, &mut out);
+        assert_eq!(out, [9, 9, 9, 9, 1, 2, 9, 4, 5]);
+        crop(&grid, (3, 2), (2, 1), 1, 9, &mut out);
+        assert_eq!(out, [2, 3, 9, 5, 6, 9, 9, 9, 9]);
+    }
+    #[test]
+    fn egocentric() {
+        let mut config = GameConfig::default();
+        config.observation.egocentric_radius = Some(3);
+        let runtime = config.build().unwrap();
+        assert_eq!(runtime.observation_size(), (7, 7));
+        let mut out = vec![0; 49];
+        runtime.observe_symbols(&mut out).unwrap();
+        let player = Symbol::from_tile(b'@'.into()).unwrap().to_byte();
+        assert_eq!(out[3 * 7 + 3], player);
+        assert!(runtime.observe_symbols(&mut out[1..]).is_err());
+    }
+    #[test]
+    fn state_json() {
+        let runtime = GameConfig::default().build().unwrap();
+        let json = runtime.state_as_json().unwrap();
+        let state: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(state["version"], STATE_JSON_VERSION);
+        let (x, y) = (
+            state["player"]["x"].as_u64().unwrap(),
+            state["player"]["y"].as_u64().unwrap(),
+        );
+        let map = state["map"].as_array().unwrap();
+        assert_eq!(map.len(), runtime.screen_size().1 .0 as usize);
+        let row = map[y as usize].as_str().unwrap();
+        assert_eq!(row.chars().nth(x as usize), Some('@'));
+        assert_eq!(state["player"]["status"]["dungeon_level"], 1);
+        assert!(!state["inventory"].as_array().unwrap().is_empty());
+    }
+    #[test]
+    fn render_ascii() {
+        let runtime = GameConfig::default().build().unwrap();
+        let text = runtime.render_ascii().unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        let (w, h) = runtime.screen_size();
+        assert_eq!(lines.len(), h.0 as usize + 2);
+        assert!(lines[..h.0 as usize]
+            .iter()
+            .all(|l| l.chars().count() == w.0 as usize));
+        let player = runtime.player_position();
+        assert_eq!(
+            lines[player.y.0 as usize].chars().nth(player.x.0 as usize),
+            Some('@')
+        );
+        assert!(lines[h.0 as usize].starts_with("Level:"));
+        assert!(!text.contains('\x1b'));
+    }
+    #[test]
+    fn channels() {
+        let mut config = GameConfig::default();
+        config.observation.channels = Channels {
+            hp: true,
+            explored: true,
+            depth: true,
+            ..Default::default()
+        };
+        let symbols = config.symbol_max().unwrap().to_byte() + 1;
+        let runtime = config.build().unwrap();
+        let (w, h) = runtime.observation_size();
+        let channels = runtime.observation_channels(symbols);
+        assert_eq!(channels, usize::from(symbols) + 3);
+        let mut out = vec![0.0f32; channels * w * h];
+        runtime.observe(symbols, Layout::Nchw, &mut out).unwrap();
+        let plane = |c: usize| &out[c * w * h..(c + 1) * w * h];
+        let s = usize::from(symbols);
+        // HP is full at first
+        assert!(plane(s).iter().all(|&v| v == 1.0));
+        let player = runtime.player_position();
+        assert_eq!(
+            plane(s + 1)[(player.y.0 as usize) * w + player.x.0 as usize],
+            1.0
+        );
+        assert!(plane(s + 2).iter().all(|&v| v == 1.0 / DEPTH_SCALE));
+        // each cell has exactly one symbol
+        let ones = out[..s * w * h].iter().filter(|&&v| v == 1.0).count();
+        assert_eq!(ones, w * h);
+    }
+    #[test]
+    fn sight_layers() {
+        let mut config = GameConfig::default();
+        config.observation.channels = Channels {
+            visible: true,
+            remembered: true,
+            ..Default::default()
+        };
+        let symbols = config.symbol_max().unwrap().to_byte() + 1;
+        let runtime = config.build().unwrap();
+        let (w, h) = runtime.observation_size();
+        let channels = runtime.observation_channels(symbols);
+        let mut out = vec![0u8; channels * w * h];
+        runtime.observe(symbols, Layout::Nchw, &mut out).unwrap();
+        let s = usize::from(symbols);
+        let (visible, remembered) = (
+            &out[s * w * h..(s + 1) * w * h],
+            &out[(s + 1) * w * h..(s + 2) * w * h],
+        );
+        let player = runtime.player_position();
+        let i = (player.y.0 as usize) * w + player.x.0 as usize;
+        assert_eq!((visible[i], remembered[i]), (1, 0));
+        assert!(visible.iter().zip(remembered).all(|(&v, &r)| v + r <= 1));
+    }
+}
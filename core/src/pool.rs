@@ -0,0 +1,259 @@
+//! a pool of independent games stepped in parallel, for training agents with batches
+use crate::error::*;
+use crate::input::InputCode;
+use crate::step::StepInfo;
+use crate::summary::GameSummary;
+use crate::symbol::Layout;
+use crate::{GameConfig, RunTime};
+use anyhow::{bail, Context};
+use rayon::prelude::*;
+
+/// A game and the config to reset it
+struct Env {
+    runtime: RunTime,
+    config: GameConfig,
+    /// the index of the current episode in the seed schedule
+    episode: usize,
+    /// how many episodes of the seed schedule are skipped on reset
+    stride: usize,
+}
+
+// EnvPool never hands out references into its runtimes, only owned values like `StepInfo`
+// and `GameSummary`, so Rc pointers in a runtime never leave it, and moving the whole game
+// to another thread is safe
+unsafe impl Send for Env {}
+
+impl Env {
+    fn new(config: GameConfig, episode: usize, stride: usize) -> GameResult<Self> {
+        Ok(Env {
+            runtime: config.for_episode(episode).build()?,
+            config,
+            episode,
+            stride,
+        })
+    }
+    fn reset(&mut self) -> GameResult<()> {
+        self.episode += self.stride;
+        self.runtime = self.config.for_episode(self.episode).build()?;
+        Ok(())
+    }
+    fn observe(&self, symbols: u8, out: &mut [f32]) -> GameResult<()> {
+        self.runtime.observe(symbols, Layout::Nchw, out)
+    }
+}
+
+/// Results of `EnvPool::step_all`, where the i-th element is for the i-th game
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchResult {
+    pub rewards: Vec<f32>,
+    pub dones: Vec<bool>,
+    pub infos: Vec<StepInfo>,
+}
+
+/// N games with the same observation shape, which are stepped on the rayon thread pool.
+/// Observations of all games are written into a contiguous buffer of shape
+/// [N, channels, height, width].
+pub struct EnvPool {
+    envs: Vec<Env>,
+    symbols: u8,
+    shape: [usize; 3],
+    observations: Vec<f32>,
+    auto_reset: bool,
+}
+
+impl EnvPool {
+    /// make a game for each config.
+    /// If configs have seed schedules, the i-th game plays the i-th, (i + N)-th, ... episodes
+    /// of its schedule, so that N games with the same schedule share the episodes.
+    pub fn new(configs: Vec<GameConfig>) -> GameResult<Self> {
+        if configs.is_empty() {
+            bail!(ErrorKind::InvalidSetting(
+                "EnvPool needs at least 1 game".into()
+            ));
+        }
+        let n = configs.len();
+        let envs = configs
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, config)| Env::new(config, i, n))
+            .collect::<GameResult<Vec<_>>>()
+            .context("EnvPool::new")?;
+        let symbols = envs[0].runtime.symbols()?;
+        let shape = envs[0].runtime.observation_shape()?;
+        for env in &envs[1..] {
+            if env.runtime.symbols()? != symbols || env.runtime.observation_shape()? != shape {
+                bail!(ErrorKind::InvalidSetting(
+                    "All games in EnvPool should have the same observation shape".into()
+                ));
+            }
+        }
+        let len = shape.iter().product::<usize>();
+        let mut pool = EnvPool {
+            observations: vec![0.0; len * envs.len()],
+            envs,
+            symbols,
+            shape,
+            auto_reset: true,
+        };
+        pool.observe_all()?;
+        Ok(pool)
+    }
+    /// make `n` games from `config`, with seeds `seed`, `seed + 1`, ...
+    /// If `config.seed` is None, each game has a random seed.
+    pub fn with_seeds(config: &GameConfig, n: usize) -> GameResult<Self> {
+        let configs = (0..n)
+            .map(|i| {
+                let mut config = config.clone();
+                config.seed = config.seed.map(|s| s.wrapping_add(i as u128));
+                config
+            })
+            .collect();
+        Self::new(configs)
+    }
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+    /// [channels, height, width] of the observation of each game
+    pub fn observation_shape(&self) -> [usize; 3] {
+        self.shape
+    }
+    /// observations of all games, in the shape of [N, channels, height, width]
+    pub fn observations(&self) -> &[f32] {
+        &self.observations
+    }
+    /// summary of the i-th game.
+    /// Runtimes themselves aren't exposed, since items or enemies cloned out of them
+    /// would share Rc pointers with games moved to other threads.
+    pub fn summary(&self, i: usize) -> Option<GameSummary> {
+        self.envs.get(i).map(|env| env.runtime.summary())
+    }
+    /// if true(default), games are reset in `step_all` right after their episodes end,
+    /// so that the observations are of the new episodes
+    pub fn set_auto_reset(&mut self, auto_reset: bool) {
+        self.auto_reset = auto_reset;
+    }
+    fn observation_len(&self) -> usize {
+        self.shape.iter().product()
+    }
+    fn observe_all(&mut self) -> GameResult<()> {
+        let (len, symbols) = (self.observation_len(), self.symbols);
+        self.envs
+            .par_iter_mut()
+            .zip(self.observations.par_chunks_mut(len))
+            .try_for_each(|(env, out)| env.observe(symbols, out))
+    }
+    /// reset the i-th game
+    pub fn reset(&mut self, i: usize) -> GameResult<()> {
+        let (len, n) = (self.observation_len(), self.envs.len());
+        let env = match self.envs.get_mut(i) {
+            Some(env) => env,
+            None => bail!(ErrorKind::InvalidSetting(
+                format!("EnvPool has only {} games", n).into()
+            )),
+        };
+        env.reset()?;
+        env.observe(self.symbols, &mut self.observations[i * len..(i + 1) * len])
+    }
+    /// reset all games
+    pub fn reset_all(&mut self) -> GameResult<()> {
+        self.envs.par_iter_mut().try_for_each(Env::reset)?;
+        self.observe_all()
+    }
+    /// apply `actions[i]` to the i-th game in parallel, and write new observations
+    pub fn step_all(&mut self, actions: &[InputCode]) -> GameResult<BatchResult> {
+        if actions.len() != self.envs.len() {
+            bail!(ErrorKind::InvalidSetting(
+                format!(
+                    "EnvPool::step_all needs {} actions, but got {}",
+                    self.envs.len(),
+                    actions.len()
+                )
+                .into()
+            ));
+        }
+        let (len, symbols, auto_reset) = (self.observation_len(), self.symbols, self.auto_reset);
+        let results = self
+            .envs
+            .par_iter_mut()
+            .zip(self.observations.par_chunks_mut(len))
+            .zip(actions.par_iter())
+            .map(|((env, out), &action)| {
+                let (reward, info) = env.runtime.advance(action)?;
+                if auto_reset && info.is_done() {
+                    env.reset()?;
+                }
+                env.observe(symbols, out)?;
+                Ok((reward, info))
+            })
+            .collect::<GameResult<Vec<_>>>()?;
+        let mut res = BatchResult::default();
+        for (reward, info) in results {
+            res.rewards.push(reward);
+            res.dones.push(info.is_done());
+            res.infos.push(info);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::Action;
+    use crate::dungeon::Direction;
+    #[test]
+    fn same_as_runtime() {
+        let mut config = GameConfig::default();
+        config.seed = Some(10);
+        config.episode.max_turns = Some(20);
+        let mut pool = EnvPool::with_seeds(&config, 3).unwrap();
+        let mut runtimes: Vec<_> = (0..3)
+            .map(|i| {
+                let mut config = config.clone();
+                config.seed = Some(10 + i);
+                config.build().unwrap()
+            })
+            .collect();
+        let len = pool.observation_shape().iter().product::<usize>();
+        assert_eq!(pool.observations().len(), len * 3);
+        let actions = [
+            InputCode::Act(Action::Move(Direction::Left)),
+            InputCode::Act(Action::Search),
+            InputCode::Act(Action::AutoExplore),
+        ];
+        for turn in 0..25 {
+            let res = pool.step_all(&actions).unwrap();
+            for (i, runtime) in runtimes.iter_mut().enumerate() {
+                let step = runtime.step(actions[i]).unwrap();
+                assert_eq!(res.rewards[i], step.reward);
+                assert_eq!(res.dones[i], step.done);
+                if step.done {
+                    // the pool resets games whose episodes ended
+                    *runtime = pool.envs[i].config.clone().build().unwrap();
+                }
+                let obs = &pool.observations()[i * len..(i + 1) * len];
+                assert_eq!(obs, &runtime.observation().unwrap()[..], "turn {}", turn);
+            }
+        }
+        assert!(pool.step_all(&actions[..2]).is_err());
+    }
+    #[test]
+    fn seed_schedule() {
+        let mut config = GameConfig::default();
+        config.seed_schedule = Some(crate::episode::SeedSchedule::List(vec![3, 4, 5, 6]));
+        let mut pool = EnvPool::new(vec![config.clone(), config]).unwrap();
+        let seeds = |pool: &EnvPool| {
+            (0..2)
+                .map(|i| pool.summary(i).unwrap().seed)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(seeds(&pool), vec![3, 4]);
+        pool.reset_all().unwrap();
+        assert_eq!(seeds(&pool), vec![5, 6]);
+        pool.reset(0).unwrap();
+        assert_eq!(seeds(&pool), vec![3, 6]);
+    }
+}
@@ -0,0 +1,115 @@
+//! named built-in configurations, on which user configurations can be layered
+use crate::error::*;
+use crate::{MIN_HEIGHT, MIN_WIDTH};
+use anyhow::bail;
+use serde_json::{json, Value};
+
+/// names of all presets
+pub const NAMES: &[&str] = &["original-rogue", "tiny-10x10", "no-monsters", "item-rich"];
+
+/// the preset named `name`, as a JSON configuration
+///
+/// - `original-rogue`: the default configuration, same as the original rogue
+/// - `tiny-10x10`: floors of at most three rooms about 10 columns wide each.
+///   Screens can't be smaller than `MIN_WIDTH` x `MIN_HEIGHT`, so the screen is the smallest
+///   one and the rooms are assigned 10x14 cells of it.
+/// - `no-monsters`: no enemy appears
+/// - `item-rich`: items and gold appear three times as often
+pub fn value(name: &str) -> GameResult<Value> {
+    let value = match name {
+        "original-rogue" => json!({}),
+        "tiny-10x10" => json!({
+            "width": MIN_WIDTH,
+            "height": MIN_HEIGHT,
+            "dungeon": {
+                "style": "rogue",
+                "room_num_x": 3,
+                "room_num_y": 1,
+                "max_empty_rooms": 0,
+            },
+        }),
+        "no-monsters" => json!({
+            "enemies": {"enemies": []},
+        }),
+        "item-rich" => json!({
+            "difficulty": {"item_appear": 300},
+        }),
+        _ => bail!(ErrorKind::InvalidSetting(
+            format!(
+                "Unknown preset {}, expected one of {}",
+                name,
+                NAMES.join(", ")
+            )
+            .into()
+        )),
+    };
+    Ok(value)
+}
+
+/// overwrite `base` by `overrides`.
+/// Objects are merged field by field recursively, and other values are replaced.
+pub fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(field) => merge(field, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::HitPoint;
+    use crate::GameConfig;
+    #[test]
+    fn presets() {
+        for name in NAMES {
+            let config = GameConfig::preset(name).unwrap();
+            config.build().unwrap();
+        }
+        assert_eq!(
+            GameConfig::preset("original-rogue").unwrap(),
+            GameConfig::default()
+        );
+        assert!(GameConfig::preset("nethack").is_err());
+    }
+    #[test]
+    fn merge_overrides() {
+        let mut base = value("tiny-10x10").unwrap();
+        merge(
+            &mut base,
+            json!({"dungeon": {"max_traps": 2}, "width": 40, "seed": 1}),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "width": 40,
+                "height": MIN_HEIGHT,
+                "seed": 1,
+                "dungeon": {
+                    "style": "rogue",
+                    "room_num_x": 3,
+                    "room_num_y": 1,
+                    "max_empty_rooms": 0,
+                    "max_traps": 2,
+                },
+            })
+        );
+        let config =
+            GameConfig::from_json(r#"{"preset": "no-monsters", "player": {"init_hp": 20}}"#)
+                .unwrap();
+        assert!(config.enemies.enemies.is_empty());
+        assert_eq!(config.player.init_hp, HitPoint(20));
+        let config = GameConfig::from_json_with_preset("item-rich", r#"{"seed": 3}"#).unwrap();
+        assert_eq!(config.difficulty.multipliers().item_appear, 300);
+        assert_eq!(config.seed, Some(3));
+    }
+}
@@ -0,0 +1,199 @@
+//! replay files and utilities to validate replays
+use crate::error::*;
+use crate::input::InputCode;
+use crate::{GameConfig, RunTime};
+use anyhow::{bail, Context};
+use serde_json::Value;
+use std::hash::Hasher;
+use std::io;
+
+/// A replay file, which contains everything to reproduce a game
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReplayFile {
+    /// version of the file format
+    pub version: u32,
+    /// version of rogue-gym-core which recorded the replay
+    pub crate_version: String,
+    /// configuration of the game, whose `seed` is the same as `self.seed`
+    pub config: GameConfig,
+    pub seed: u128,
+    pub inputs: Vec<InputCode>,
+    /// state hashes after some inputs, to find where replays diverge
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub state_hashes: Vec<StateHash>,
+}
+
+/// The state hash after the first `inputs` inputs
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct StateHash {
+    pub inputs: usize,
+    pub hash: u64,
+}
+
+impl ReplayFile {
+    /// current version of the file format
+    pub const VERSION: u32 = 1;
+    pub fn new(mut config: GameConfig, seed: u128, inputs: Vec<InputCode>) -> Self {
+        config.seed = Some(seed);
+        ReplayFile {
+            version: Self::VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            config,
+            seed,
+            inputs,
+            state_hashes: Vec::new(),
+        }
+    }
+    /// load a replay file, rejecting incompatible versions
+    pub fn from_json(json: &str) -> GameResult<Self> {
+        let value: Value = serde_json::from_str(json).context("ReplayFile::from_json")?;
+        Self::from_value(value)
+    }
+    /// same as `from_json`, but also accepts the old format, which is a bare list of inputs,
+    /// using `config` as the configuration
+    pub fn from_json_or_inputs(json: &str, config: &GameConfig) -> GameResult<Self> {
+        let value: Value = serde_json::from_str(json).context("ReplayFile::from_json_or_inputs")?;
+        if !value.is_array() {
+            return Self::from_value(value);
+        }
+        let inputs = serde_json::from_value(value).context("ReplayFile::from_json_or_inputs")?;
+        let seed = match config.seed {
+            Some(seed) => seed,
+            None => bail!(ErrorKind::InvalidSetting(
+                "seed is needed to load a replay file of the old format".into()
+            )),
+        };
+        Ok(Self::new(config.clone(), seed, inputs))
+    }
+    pub(crate) fn from_value(value: Value) -> GameResult<Self> {
+        match value.get("version").and_then(Value::as_u64) {
+            Some(v) if v == u64::from(Self::VERSION) => {}
+            Some(v) => bail!(ErrorKind::InvalidSetting(
+                format!("unsupported replay file version {}", v).into()
+            )),
+            None => bail!(ErrorKind::InvalidSetting(
+                "replay file has no version".into()
+            )),
+        }
+        serde_json::from_value(value).context("ReplayFile::from_value")
+    }
+    pub fn to_json(&self) -> GameResult<String> {
+        serde_json::to_string_pretty(self).context("ReplayFile::to_json")
+    }
+    /// replay the game and record state hashes every `interval` inputs and at the end
+    pub fn record_hashes(&mut self, interval: usize) -> GameResult<()> {
+        let mut runtime = self.config.clone().build()?;
+        self.state_hashes.clear();
+        for (i, &input) in self.inputs.iter().enumerate() {
+            runtime.replay_input(input)?;
+            let inputs = i + 1;
+            if (interval > 0 && inputs % interval == 0) || inputs == self.inputs.len() {
+                let hash = runtime.state_hash()?;
+                self.state_hashes.push(StateHash { inputs, hash });
+            }
+        }
+        Ok(())
+    }
+    /// replay the game and check all recorded state hashes
+    pub fn verify(&self) -> GameResult<()> {
+        self.run().map(|_| ())
+    }
+    /// replay the game checking recorded state hashes on the way, and returns the final state
+    pub fn run(&self) -> GameResult<RunTime> {
+        let mut runtime = self.config.clone().build()?;
+        let mut done = 0;
+        for sh in &self.state_hashes {
+            let inputs = self.inputs.get(done..sh.inputs).ok_or_else(|| {
+                ErrorKind::InvalidSetting("invalid state hashes in replay file".into())
+            })?;
+            runtime.verify_replay(inputs, sh.hash)?;
+            done = sh.inputs;
+        }
+        for &input in &self.inputs[done..] {
+            runtime.replay_input(input)?;
+        }
+        Ok(runtime)
+    }
+}
+
+/// 64bit FNV-1a hasher.
+/// Unlike `DefaultHasher`, the result is stable across Rust versions and platforms,
+/// so state hashes can be saved and compared later.
+#[derive(Clone, Copy, Debug)]
+pub struct StateHasher(u64);
+
+impl StateHasher {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    pub fn new() -> Self {
+        StateHasher(Self::OFFSET)
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+/// to hash serialized states without buffering them
+impl io::Write for StateHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Hasher::write(self, buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn fnv1a() {
+        let hash = |s: &str| {
+            let mut hasher = StateHasher::new();
+            hasher.write(s.as_bytes());
+            hasher.finish()
+        };
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(hash("ab"), hash("ba"));
+    }
+    #[test]
+    fn replay_file() {
+        use crate::character::Action;
+        let config = GameConfig::default();
+        let inputs = vec![InputCode::Act(Action::Search); 3];
+        let old = serde_json::to_string(&inputs).unwrap();
+        assert!(ReplayFile::from_json_or_inputs(&old, &config).is_err());
+        let mut config = config;
+        config.seed = Some(10);
+        let mut replay = ReplayFile::from_json_or_inputs(&old, &config).unwrap();
+        assert_eq!(replay, ReplayFile::new(config.clone(), 10, inputs));
+        replay.record_hashes(2).unwrap();
+        assert_eq!(replay.state_hashes.len(), 2);
+        assert!(replay.verify().is_ok());
+        let last = replay.state_hashes.last().unwrap().hash;
+        assert_eq!(replay.run().unwrap().state_hash().unwrap(), last);
+        let json = replay.to_json().unwrap();
+        assert_eq!(ReplayFile::from_json(&json).unwrap(), replay);
+        assert!(
+            ReplayFile::from_json(&json.replace(r#""version": 1"#, r#""version": 2"#)).is_err()
+        );
+        assert!(ReplayFile::from_json(&old).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! rewards for reinforcement learning, shared by all frontends
+use crate::character::player::Status;
+use crate::error::GameResult;
+use crate::input::InputCode;
+use crate::ui::{MordalKind, UiState};
+use crate::{GameMsg, Reaction, RunTime};
+
+/// How rewards are computed, where each field is the weight of an event.
+/// The default gives the amount of gold the player got, as the original rogue-gym.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RewardSpec {
+    /// reward per gold
+    #[serde(default = "default_gold")]
+    pub gold: f32,
+    /// reward per dungeon level the player goes down, which is a penalty when going up
+    #[serde(default)]
+    pub depth: f32,
+    /// reward per enemy the player killed
+    #[serde(default)]
+    pub kill: f32,
+    /// reward when the player dies, which should be negative
+    #[serde(default)]
+    pub death: f32,
+}
+
+// rewards are finite numbers from configuration files, so they are never NaN
+impl Eq for RewardSpec {}
+
+const fn default_gold() -> f32 {
+    1.0
+}
+
+impl Default for RewardSpec {
+    fn default() -> Self {
+        RewardSpec {
+            gold: default_gold(),
+            depth: 0.0,
+            kill: 0.0,
+            death: 0.0,
+        }
+    }
+}
+
+impl RewardSpec {
+    /// reward of a step, from the status before and after it and reactions in it
+    pub fn reward(&self, before: &Status, after: &Status, reactions: &[Reaction]) -> f32 {
+        let gold = after.gold as f32 - before.gold as f32;
+        let depth = after.dungeon_level as f32 - before.dungeon_level as f32;
+        let (mut kills, mut dead) = (0, false);
+        for reaction in reactions {
+            match reaction {
+                Reaction::Notify(GameMsg::Killed(_)) => kills += 1,
+                Reaction::UiTransition(UiState::Mordal(MordalKind::Grave(_))) => dead = true,
+                _ => {}
+            }
+        }
+        let mut reward = gold * self.gold + depth * self.depth + kills as f32 * self.kill;
+        if dead {
+            reward += self.death;
+        }
+        reward
+    }
+}
+
+impl RunTime {
+    /// same as `react_to_input`, but also returns the reward for the input,
+    /// as specified in `GameConfig::reward`
+    pub fn react_with_reward(&mut self, input: InputCode) -> GameResult<(Vec<Reaction>, f32)> {
+        let before = self.player_status();
+        let res = self.react_to_input(input)?;
+        let reward = self.reward.reward(&before, &self.player_status(), &res);
+        Ok((res, reward))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SmallStr;
+    #[test]
+    fn reward() {
+        let spec = RewardSpec {
+            gold: 1.0,
+            depth: 50.0,
+            kill: 2.0,
+            death: -100.0,
+        };
+        let before = Status::default();
+        let mut after = before.clone();
+        after.gold = 10;
+        after.dungeon_level = 1;
+        let killed = Reaction::Notify(GameMsg::Killed(SmallStr::from_static("Bat")));
+        assert_eq!(
+            spec.reward(&before, &after, std::slice::from_ref(&killed)),
+            62.0
+        );
+        let grave = Reaction::UiTransition(UiState::die("Killed by Bat".to_owned()));
+        assert_eq!(spec.reward(&before, &before, &[grave]), -100.0);
+        assert_eq!(
+            RewardSpec::default().reward(&before, &after, &[killed]),
+            10.0
+        );
+        let spec: RewardSpec = serde_json::from_str(r#"{"kill": 1.5}"#).unwrap();
+        assert_eq!(spec.gold, 1.0);
+        assert_eq!(spec.kill, 1.5);
+    }
+}
@@ -5,8 +5,10 @@ use rand::{
 };
 pub(crate) use rand::{seq::SliceRandom, Rng};
 use rand_xorshift::XorShiftRng;
-use serde::{Deserialize, Serialize};
+use serde::de::{Error, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp;
+use std::fmt;
 use std::mem;
 use std::ops::{Bound, Range, RangeBounds};
 
@@ -28,6 +30,10 @@ fn bounds_to_range<T: PrimInt>(r: impl RangeBounds<T>) -> Range<T> {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RngHandle(XorShiftRng);
 
+/// A snapshot of the internal state of `RngHandle`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RngState(XorShiftRng);
+
 impl Default for RngHandle {
     fn default() -> Self {
         Self::new()
@@ -44,6 +50,52 @@ pub fn gen_ranged_seed(start: u128, end: u128) -> u128 {
     rng.gen_range(start, end)
 }
 
+/// Independent streams of random numbers, each of which is used by one subsystem.
+/// Each stream is seeded by a seed derived from the game seed, so that changing
+/// the configuration of one subsystem (e.g. adding a weapon) doesn't perturb the others.
+/// The dungeon stream uses the game seed as it is, so that a seed produces the same dungeon
+/// as before streams were introduced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum Stream {
+    /// dungeon generation
+    DungeonGen,
+    /// item generation
+    Items,
+    /// the table of unidentified item names
+    Identify,
+    /// fights and other random events happening to the player
+    Combat,
+    /// enemies' movement, appearance and drops
+    EnemyAi,
+}
+
+impl Stream {
+    pub const ALL: [Stream; 5] = [
+        Stream::DungeonGen,
+        Stream::Items,
+        Stream::Identify,
+        Stream::Combat,
+        Stream::EnemyAi,
+    ];
+    /// derive the seed of this stream from the game seed
+    pub fn seed(self, seed: u128) -> u128 {
+        if self == Stream::DungeonGen {
+            return seed;
+        }
+        let salt = (self as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let lo = splitmix64(seed as u64 ^ salt);
+        let hi = splitmix64((seed >> 64) as u64 ^ salt.rotate_left(32));
+        u128::from(hi) << 64 | u128::from(lo)
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 impl RngHandle {
     fn gen_seed(seed: u128) -> [u8; 16] {
         unsafe { mem::transmute::<_, [u8; 16]>(seed) }
@@ -53,11 +105,24 @@ impl RngHandle {
         let seed = Self::gen_seed(seed);
         RngHandle(XorShiftRng::from_seed(seed))
     }
+    /// create new Rng for the stream from the game seed
+    pub fn from_stream(seed: u128, stream: Stream) -> Self {
+        Self::from_seed(stream.seed(seed))
+    }
     /// create new Rng by random seed
     pub fn new() -> Self {
         let seed: [u8; 16] = thread_rng().gen();
         RngHandle(XorShiftRng::from_seed(seed))
     }
+    /// take a snapshot of the internal state
+    pub fn save_state(&self) -> RngState {
+        RngState(self.0.clone())
+    }
+    /// restore the internal state, so that the same random numbers as
+    /// when `save_state` was called are generated again
+    pub fn restore_state(&mut self, state: &RngState) {
+        self.0 = state.0.clone();
+    }
     /// select some values randomly from given range
     pub fn select<T: PrimInt>(&mut self, range: impl RangeBounds<T>) -> RandomSelecter<T> {
         let range = bounds_to_range(range);
@@ -96,6 +161,16 @@ impl RngHandle {
         p.valid_check();
         self.range(1..=100) <= p.0
     }
+    /// judge an event with probability `p` happens or not
+    pub fn chance(&mut self, p: Probability) -> bool {
+        p.valid_check();
+        // whole percentages use the same random numbers as `parcent`
+        if p.0.is_multiple_of(Probability::PER_PERCENT) {
+            self.range(1..=100) <= p.0 / Probability::PER_PERCENT
+        } else {
+            self.range(0..Probability::SCALE) < p.0
+        }
+    }
 }
 
 impl RngCore for RngHandle {
@@ -154,6 +229,114 @@ impl Parcent {
     }
 }
 
+/// A probability in parts per million, which can express fractional rates like 2.5% exactly.
+/// In configuration, it's written as a percentage like `25` or `2.5`.
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Probability(u32);
+
+impl Probability {
+    /// parts per million which mean 100%
+    pub const SCALE: u32 = 1_000_000;
+    const PER_PERCENT: u32 = Self::SCALE / 100;
+    pub const ZERO: Probability = Probability(0);
+    pub const ONE: Probability = Probability(Self::SCALE);
+    pub const fn from_percent(p: u32) -> Probability {
+        Probability(p * Self::PER_PERCENT)
+    }
+    pub const fn from_permille(p: u32) -> Probability {
+        Probability(p * (Self::SCALE / 1000))
+    }
+    pub const fn from_ppm(p: u32) -> Probability {
+        Probability(p)
+    }
+    /// clamp `ppm` parts per million into 0%..=100%
+    pub fn truncate(ppm: i64) -> Probability {
+        Probability(cmp::min(i64::from(Self::SCALE), cmp::max(0, ppm)) as u32)
+    }
+    pub fn ppm(self) -> u32 {
+        self.0
+    }
+    pub fn as_percent(self) -> f64 {
+        f64::from(self.0) / f64::from(Self::PER_PERCENT)
+    }
+    pub fn is_valid(self) -> bool {
+        self.0 <= Self::SCALE
+    }
+    fn valid_check(self) {
+        debug_assert!(
+            self.is_valid(),
+            "Invalid probability {}%",
+            self.as_percent()
+        );
+    }
+}
+
+impl From<Parcent> for Probability {
+    fn from(p: Parcent) -> Probability {
+        Probability::from_percent(p.0)
+    }
+}
+
+impl Serialize for Probability {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // whole percentages are written as integers, as `Parcent` does
+        if self.0.is_multiple_of(Self::PER_PERCENT) {
+            serializer.serialize_u32(self.0 / Self::PER_PERCENT)
+        } else {
+            serializer.serialize_f64(self.as_percent())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Probability {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ProbabilityVisitor)
+    }
+}
+
+struct ProbabilityVisitor;
+
+impl<'de> Visitor<'de> for ProbabilityVisitor {
+    type Value = Probability;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a non-negative percentage")
+    }
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        v.checked_mul(u64::from(Probability::PER_PERCENT))
+            .filter(|&ppm| ppm <= u64::from(u32::MAX))
+            .map(|ppm| Probability(ppm as u32))
+            .ok_or_else(|| E::custom(format!("too large percentage {}", v)))
+    }
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        if v < 0 {
+            return Err(E::custom(format!("negative percentage {}", v)));
+        }
+        self.visit_u64(v as u64)
+    }
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        let ppm = (v * f64::from(Probability::PER_PERCENT)).round();
+        if !(0.0..=f64::from(u32::MAX)).contains(&ppm) {
+            return Err(E::custom(format!("invalid percentage {}", v)));
+        }
+        Ok(Probability(ppm as u32))
+    }
+}
+
 #[cfg(test)]
 mod selecter_test {
     use super::*;
@@ -197,3 +380,56 @@ mod selecter_bench {
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn save_and_restore() {
+        let mut rng = RngHandle::from_seed(1);
+        rng.range(0..100);
+        let state = rng.save_state();
+        let json = serde_json::to_string(&state).unwrap();
+        let first: Vec<u32> = (0..10).map(|_| rng.range(0..100)).collect();
+        let state: RngState = serde_json::from_str(&json).unwrap();
+        let mut other = RngHandle::from_seed(2);
+        other.restore_state(&state);
+        let second: Vec<u32> = (0..10).map(|_| other.range(0..100)).collect();
+        assert_eq!(first, second);
+    }
+    #[test]
+    fn probability() {
+        let p: Probability = serde_json::from_str("2.5").unwrap();
+        assert_eq!(p, Probability::from_permille(25));
+        assert_eq!(serde_json::to_string(&p).unwrap(), "2.5");
+        let p: Probability = serde_json::from_str("30").unwrap();
+        assert_eq!(p, Probability::from(Parcent(30)));
+        assert_eq!(serde_json::to_string(&p).unwrap(), "30");
+        assert!(serde_json::from_str::<Probability>("-1").is_err());
+        // whole percentages draw the same random numbers as `Parcent`
+        let (mut a, mut b) = (RngHandle::from_seed(1), RngHandle::from_seed(1));
+        for _ in 0..100 {
+            assert_eq!(a.parcent(Parcent(30)), b.chance(p));
+        }
+        let mut rng = RngHandle::from_seed(1);
+        let hits = (0..100_000)
+            .filter(|_| rng.chance(Probability::from_permille(25)))
+            .count();
+        assert!(2000 < hits && hits < 3000, "{} hits", hits);
+    }
+    #[test]
+    fn streams() {
+        let numbers = |stream| -> Vec<u32> {
+            let mut rng = RngHandle::from_stream(1, stream);
+            (0..10).map(|_| rng.range(0..100)).collect()
+        };
+        for (i, &stream) in Stream::ALL.iter().enumerate() {
+            assert_eq!(numbers(stream), numbers(stream));
+            for &other in &Stream::ALL[i + 1..] {
+                assert_ne!(numbers(stream), numbers(other));
+            }
+        }
+        assert_ne!(Stream::Items.seed(1), Stream::Items.seed(2));
+        assert_eq!(Stream::DungeonGen.seed(1), 1);
+    }
+}
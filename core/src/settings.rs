@@ -0,0 +1,114 @@
+//! settings which can be changed in the middle of a game
+use crate::error::*;
+use crate::{preset, GameConfig, RunTime};
+use anyhow::{bail, Context};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+/// top-level fields of `GameConfig` which can be changed in the middle of a game,
+/// since they affect neither the dungeon nor the results of inputs.
+/// Other fields like `seed` and `dungeon` are fixed when the game starts.
+pub const MUTABLE_FIELDS: &[&str] = &["screen", "keymap"];
+
+/// check if the setting at `path`(like `screen.colors.@`) can be changed in the middle of a game
+pub fn is_mutable(path: &str) -> bool {
+    let field = segments(path).into_iter().next().unwrap_or_default();
+    MUTABLE_FIELDS.contains(&&*field)
+}
+
+/// split `path` by dots, where a segment can start with a dot for keys like `.`
+fn segments(path: &str) -> Vec<String> {
+    let mut res = vec![];
+    let mut current = String::new();
+    for c in path.chars() {
+        if c == '.' && !current.is_empty() {
+            res.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    res.push(current);
+    res
+}
+
+/// `field` overwritten by `patch`
+fn patched<T: Serialize + DeserializeOwned>(field: &T, patch: Value) -> GameResult<T> {
+    let mut value = serde_json::to_value(field).context("settings::patched")?;
+    preset::merge(&mut value, patch);
+    serde_json::from_value(value)
+        .map_err(|e| ErrorKind::InvalidSetting(e.to_string().into()).into())
+}
+
+impl GameConfig {
+    /// change the setting at `path`(like `screen.colors.@`) to `value`.
+    /// `value` is parsed as JSON, or used as a string if it isn't JSON.
+    /// Fails if the setting is immutable(see `MUTABLE_FIELDS`).
+    pub fn set(&mut self, path: &str, value: &str) -> GameResult<()> {
+        let mut segments = segments(path);
+        let field = segments.remove(0);
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()));
+        let patch = segments.into_iter().rev().fold(value, |value, key| {
+            let mut map = Map::new();
+            map.insert(key, value);
+            Value::Object(map)
+        });
+        match &*field {
+            "screen" => self.screen = patched(&self.screen, patch)?,
+            "keymap" => self.keymap = patched(&self.keymap, patch)?,
+            _ => bail!(ErrorKind::InvalidSetting(
+                format!(
+                    "{} can't be changed in the middle of a game, only {} can be",
+                    field,
+                    MUTABLE_FIELDS.join(" and ")
+                )
+                .into()
+            )),
+        }
+        Ok(())
+    }
+}
+
+impl RunTime {
+    /// apply mutable settings(see `MUTABLE_FIELDS`) of `config` to the running game
+    pub fn apply_settings(&mut self, config: &GameConfig) {
+        self.keymap = config.keymap.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::Action;
+    use crate::input::{InputCode, Key};
+    use crate::tile::Color;
+    #[test]
+    fn set() {
+        let mut config = GameConfig::default();
+        config.set("screen.colors.@", "cyan").unwrap();
+        config.set("screen.colors..", "\"yellow\"").unwrap();
+        assert_eq!(config.screen.colors[&'@'], Color::CYAN);
+        assert_eq!(config.screen.colors[&'.'], Color::YELLOW);
+        assert!(config.set("screen.colors.@", "no-such-color").is_err());
+        config.set("keymap.w", r#"{"Act": "Search"}"#).unwrap();
+        let mut runtime = config.clone().build().unwrap();
+        assert_eq!(
+            runtime.keymap.get(Key::Char('w')),
+            Some(InputCode::Act(Action::Search))
+        );
+        // other bindings are kept
+        assert_eq!(
+            runtime.keymap.get(Key::Char('s')),
+            GameConfig::default().keymap.get(Key::Char('s'))
+        );
+        config.set("keymap.w", r#"{"Act": "DownStair"}"#).unwrap();
+        runtime.apply_settings(&config);
+        assert_eq!(
+            runtime.keymap.get(Key::Char('w')),
+            Some(InputCode::Act(Action::DownStair))
+        );
+        assert!(config.set("seed", "3").is_err());
+        assert!(config.set("dungeon.max_traps", "3").is_err());
+        assert!(is_mutable("screen.colors.."));
+        assert!(!is_mutable("seed"));
+    }
+}
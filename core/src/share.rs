@@ -0,0 +1,181 @@
+//! dungeon codes, which are short strings to share dungeons.
+//!
+//! A code is the base32(RFC 4648 alphabet, without padding) of bytes:
+//! format version (u8), the lower 32 bits of `GameConfig::config_hash` (u32, big endian),
+//! and the seed (big endian, without leading zero bytes).
+use crate::error::*;
+use crate::replay::StateHasher;
+use crate::settings::MUTABLE_FIELDS;
+use crate::{GameConfig, RunTime};
+use anyhow::{bail, Context};
+use serde_json::Value;
+use std::fmt;
+use std::hash::Hasher;
+use std::str::FromStr;
+
+const VERSION: u8 = 1;
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// top-level fields of `GameConfig` which don't change the game, and so are not hashed
+const UNHASHED_FIELDS: &[&str] = &[
+    "seed",
+    "seed_range",
+    "seed_schedule",
+    "observation",
+    "reward",
+];
+
+impl GameConfig {
+    /// hash of settings which change the game, except the seed.
+    /// Settings only for screens and agents(like `keymap` and `reward`) are not hashed,
+    /// and builtin presets are hashed as their definitions, like `to_canonical_json`.
+    /// It's stable across platforms and versions, as long as the config has the same settings.
+    pub fn config_hash(&self) -> GameResult<u64> {
+        let json = self.to_canonical_json()?;
+        let mut value: Value = serde_json::from_str(&json).context("GameConfig::config_hash")?;
+        if let Value::Object(fields) = &mut value {
+            for field in UNHASHED_FIELDS.iter().chain(MUTABLE_FIELDS) {
+                fields.remove(*field);
+            }
+        }
+        // keys of objects are sorted, so the same config is always serialized to the same bytes
+        let bytes = serde_json::to_vec(&value).context("GameConfig::config_hash")?;
+        let mut hasher = StateHasher::new();
+        hasher.write(&bytes);
+        Ok(hasher.finish())
+    }
+}
+
+/// A seed and a hash of the config which made a dungeon
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DungeonCode {
+    pub seed: u128,
+    /// the lower 32 bits of `GameConfig::config_hash`
+    pub config_hash: u32,
+}
+
+impl DungeonCode {
+    pub fn new(config: &GameConfig, seed: u128) -> GameResult<Self> {
+        Ok(DungeonCode {
+            seed,
+            config_hash: config.config_hash()? as u32,
+        })
+    }
+    /// set the seed to `config`, if the code is made with the same config
+    pub fn apply(&self, config: &mut GameConfig) -> GameResult<()> {
+        if config.config_hash()? as u32 != self.config_hash {
+            bail!(ErrorKind::InvalidSetting(
+                "The dungeon code was made with a different config".into()
+            ));
+        }
+        config.set_seed(self.seed);
+        Ok(())
+    }
+}
+
+impl fmt::Display for DungeonCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bytes = vec![VERSION];
+        bytes.extend_from_slice(&self.config_hash.to_be_bytes());
+        let seed = self.seed.to_be_bytes();
+        let zeros = seed.iter().take_while(|&&b| b == 0).count();
+        bytes.extend_from_slice(&seed[zeros..]);
+        let (mut buf, mut bits) = (0u32, 0);
+        for b in bytes {
+            buf = (buf << 8) | u32::from(b);
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                write!(f, "{}", ALPHABET[((buf >> bits) & 31) as usize] as char)?;
+            }
+        }
+        if bits > 0 {
+            write!(
+                f,
+                "{}",
+                ALPHABET[((buf << (5 - bits)) & 31) as usize] as char
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DungeonCode {
+    type Err = anyhow::Error;
+    fn from_str(code: &str) -> GameResult<Self> {
+        let invalid = || ErrorKind::InvalidSetting(format!("Invalid dungeon code {}", code).into());
+        let mut bytes = vec![];
+        let (mut buf, mut bits) = (0u32, 0);
+        for c in code.trim().chars() {
+            let c = c.to_ascii_uppercase() as u8;
+            let value = ALPHABET.iter().position(|&a| a == c).ok_or_else(invalid)?;
+            buf = (buf << 5) | value as u32;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buf >> bits) as u8);
+            }
+        }
+        if bytes.len() < 5 || bytes.len() > 5 + 16 || bits >= 5 || buf & ((1 << bits) - 1) != 0 {
+            bail!(invalid());
+        }
+        if bytes[0] != VERSION {
+            bail!(ErrorKind::InvalidSetting(
+                format!("Unsupported version {} of dungeon codes", bytes[0]).into()
+            ));
+        }
+        let config_hash = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        let seed = bytes[5..]
+            .iter()
+            .fold(0u128, |seed, &b| (seed << 8) | u128::from(b));
+        Ok(DungeonCode { seed, config_hash })
+    }
+}
+
+impl RunTime {
+    /// the code to share the dungeon of this game, which was made by `config`
+    pub fn dungeon_code(&self, config: &GameConfig) -> GameResult<DungeonCode> {
+        DungeonCode::new(config, self.config.seed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn encode_and_decode() {
+        for &seed in &[0, 1, 255, 256, 1 << 40, u128::MAX] {
+            let code = DungeonCode {
+                seed,
+                config_hash: 0xdead_beef,
+            };
+            let s = code.to_string();
+            assert_eq!(s.parse::<DungeonCode>().unwrap(), code);
+            assert_eq!(s.to_lowercase().parse::<DungeonCode>().unwrap(), code);
+        }
+        assert!("".parse::<DungeonCode>().is_err());
+        assert!("AE!".parse::<DungeonCode>().is_err());
+        assert!("CAAAAAAA".parse::<DungeonCode>().is_err());
+    }
+    #[test]
+    fn share() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1234);
+        let runtime = config.clone().build().unwrap();
+        let code = runtime.dungeon_code(&config).unwrap().to_string();
+        // settings for screens don't matter
+        let mut other = GameConfig::default();
+        other.set("screen.colors.@", "cyan").unwrap();
+        code.parse::<DungeonCode>()
+            .unwrap()
+            .apply(&mut other)
+            .unwrap();
+        assert_eq!(other.seed, Some(1234));
+        let shared = other.build().unwrap();
+        assert_eq!(shared.state_hash().unwrap(), runtime.state_hash().unwrap());
+        let mut different = GameConfig::default();
+        different.width = 60;
+        let code = code.parse::<DungeonCode>().unwrap();
+        assert!(code.apply(&mut different).is_err());
+    }
+}
@@ -0,0 +1,476 @@
+//! deep copies and snapshots of the game state
+use crate::character::{enemies::EnemyId, Enemy, EnemyHandler, Player};
+use crate::error::*;
+use crate::item::{Item, ItemHandler, ItemId, ItemToken, Registry};
+use crate::replay::{ReplayFile, StateHash};
+use crate::ui::UiState;
+use crate::{GameConfig, GameInfo, GlobalConfig, RngStates, RunTime};
+use anyhow::{bail, Context};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Deep copies items and enemies, keeping track of the copied ones so that
+/// an item or enemy shared by some places in the original state
+/// (e.g. an equipped weapon in the item box) is shared in the copy, too.
+#[derive(Default)]
+pub struct DeepCloner {
+    items: BTreeMap<ItemId, ItemToken>,
+    item_registry: Rc<Registry>,
+    enemies: BTreeMap<EnemyId, Rc<Enemy>>,
+}
+
+impl DeepCloner {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+    pub(crate) fn item(&mut self, item: &ItemToken) -> ItemToken {
+        self.items
+            .entry(item.id())
+            .or_insert_with(|| item.deep_clone(&self.item_registry))
+            .clone()
+    }
+    /// the registry the copied items belong to
+    pub(crate) fn item_registry(&self) -> Rc<Registry> {
+        Rc::clone(&self.item_registry)
+    }
+    pub(crate) fn enemy(&mut self, enemy: &Rc<Enemy>) -> Rc<Enemy> {
+        self.enemies
+            .entry(enemy.id())
+            .or_insert_with(|| Rc::new(Enemy::clone(enemy)))
+            .clone()
+    }
+    pub(crate) fn enemy_map<K: Clone + Ord>(
+        &mut self,
+        map: &BTreeMap<K, Rc<Enemy>>,
+    ) -> BTreeMap<K, Rc<Enemy>> {
+        map.iter()
+            .map(|(k, enemy)| (k.clone(), self.enemy(enemy)))
+            .collect()
+    }
+}
+
+thread_local! {
+    /// interns items and enemies while a snapshot is restored
+    static RESTORER: RefCell<Option<DeepCloner>> = const { RefCell::new(None) };
+}
+
+/// run `f` restoring items and enemies shared by some places in the saved state as shared ones
+fn with_restorer<T>(f: impl FnOnce() -> T) -> T {
+    RESTORER.with(|r| *r.borrow_mut() = Some(DeepCloner::new()));
+    let res = f();
+    // tokens are dropped after the borrow ends, since they touch the registry on drop
+    let restorer = RESTORER.with(|r| r.borrow_mut().take());
+    drop(restorer);
+    res
+}
+
+/// restore a saved item, which is shared with the one of the same id
+pub(crate) fn restore_item(id: ItemId, item: Item) -> ItemToken {
+    RESTORER.with(|r| match r.borrow_mut().as_mut() {
+        Some(restorer) => {
+            let registry = &restorer.item_registry;
+            restorer
+                .items
+                .entry(id)
+                .or_insert_with(|| ItemToken::register(item, id, registry))
+                .clone()
+        }
+        None => ItemToken::register(item, id, &Rc::default()),
+    })
+}
+
+/// the registry restored items belong to
+pub(crate) fn restored_items() -> Rc<Registry> {
+    RESTORER.with(|r| match r.borrow().as_ref() {
+        Some(restorer) => restorer.item_registry(),
+        None => Rc::default(),
+    })
+}
+
+fn restore_enemy(enemy: Enemy) -> Rc<Enemy> {
+    RESTORER.with(|r| match r.borrow_mut().as_mut() {
+        Some(restorer) => restorer
+            .enemies
+            .entry(enemy.id())
+            .or_insert_with(|| Rc::new(enemy))
+            .clone(),
+        None => Rc::new(enemy),
+    })
+}
+
+/// (de)serializes a map of enemies as a list of pairs, since keys of JSON maps must be strings
+pub(crate) mod enemy_map {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    pub fn serialize<K, S>(map: &BTreeMap<K, Rc<Enemy>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter().map(|(k, enemy)| (k, &**enemy)))
+    }
+    pub fn deserialize<'de, K, D>(deserializer: D) -> Result<BTreeMap<K, Rc<Enemy>>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        D: Deserializer<'de>,
+    {
+        let pairs = Vec::<(K, Enemy)>::deserialize(deserializer)?;
+        Ok(pairs
+            .into_iter()
+            .map(|(k, enemy)| (k, restore_enemy(enemy)))
+            .collect())
+    }
+}
+
+/// (de)serializes a hash map as a list of pairs sorted by keys, to make saved states canonical
+pub(crate) mod sorted_map {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+    use std::hash::Hash;
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize + Ord,
+        V: Serialize,
+        S: Serializer,
+    {
+        let mut pairs: Vec<_> = map.iter().collect();
+        pairs.sort_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_seq(pairs)
+    }
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Vec::<(K, V)>::deserialize(deserializer).map(|pairs| pairs.into_iter().collect())
+    }
+}
+
+/// (de)serializes a hash set as a sorted list, to make saved states canonical
+pub(crate) mod sorted_set {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashSet;
+    use std::hash::Hash;
+    pub(super) fn sorted<T: Ord>(set: &HashSet<T>) -> Vec<&T> {
+        let mut res: Vec<_> = set.iter().collect();
+        res.sort();
+        res
+    }
+    pub fn serialize<T, S>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + Ord,
+        S: Serializer,
+    {
+        sorted(set).serialize(serializer)
+    }
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<HashSet<T>, D::Error>
+    where
+        T: Deserialize<'de> + Eq + Hash,
+        D: Deserializer<'de>,
+    {
+        Vec::<T>::deserialize(deserializer).map(|v| v.into_iter().collect())
+    }
+}
+
+/// same as `sorted_set`, for an optional set
+pub(crate) mod sorted_set_opt {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashSet;
+    use std::hash::Hash;
+    pub fn serialize<T, S>(set: &Option<HashSet<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + Ord,
+        S: Serializer,
+    {
+        set.as_ref()
+            .map(super::sorted_set::sorted)
+            .serialize(serializer)
+    }
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<HashSet<T>>, D::Error>
+    where
+        T: Deserialize<'de> + Eq + Hash,
+        D: Deserializer<'de>,
+    {
+        Option::<Vec<T>>::deserialize(deserializer).map(|v| v.map(|v| v.into_iter().collect()))
+    }
+}
+
+/// the whole state of the game, except the configuration it's built from and inputs
+#[derive(Serialize)]
+pub(crate) struct StateRef<'a> {
+    game_info: &'a GameInfo,
+    config: &'a GlobalConfig,
+    dungeon: Value,
+    item: &'a ItemHandler,
+    player: &'a Player,
+    enemies: &'a EnemyHandler,
+    ui: &'a UiState,
+    rng: RngStates,
+}
+
+/// owned version of `StateRef`
+#[derive(Deserialize)]
+struct State {
+    game_info: GameInfo,
+    config: GlobalConfig,
+    dungeon: Value,
+    item: ItemHandler,
+    player: Player,
+    enemies: EnemyHandler,
+    ui: UiState,
+    rng: RngStates,
+}
+
+/// A snapshot of the game, from which the game can be resumed without replaying it.
+/// It's saved as a replay file of the inputs so far with the whole state,
+/// so it can be also used as a replay file.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// replay of the game so far, ending with the hash of the saved state
+    pub replay: ReplayFile,
+    state: Value,
+}
+
+impl Snapshot {
+    pub fn from_json(json: &str) -> GameResult<Self> {
+        let mut value: Value = serde_json::from_str(json).context("Snapshot::from_json")?;
+        let state = match value.as_object_mut().and_then(|map| map.remove("state")) {
+            Some(state) => state,
+            None => bail!(ErrorKind::InvalidSetting("snapshot has no state".into())),
+        };
+        let replay = ReplayFile::from_value(value)?;
+        Ok(Snapshot { replay, state })
+    }
+    pub fn to_json(&self) -> GameResult<String> {
+        let mut value = serde_json::to_value(&self.replay).context("Snapshot::to_json")?;
+        if let Some(map) = value.as_object_mut() {
+            map.insert("state".to_owned(), self.state.clone());
+        }
+        serde_json::to_string_pretty(&value).context("Snapshot::to_json")
+    }
+}
+
+impl RunTime {
+    pub(crate) fn state_ref(&self) -> GameResult<StateRef<'_>> {
+        Ok(StateRef {
+            game_info: &self.game_info,
+            config: &self.config,
+            dungeon: self.dungeon.save()?,
+            item: &self.item,
+            player: &self.player,
+            enemies: &self.enemies,
+            ui: &self.ui,
+            rng: self.save_rng_states(),
+        })
+    }
+    /// make a snapshot of the current state, which can be restored by `RunTime::resume`.
+    /// `config` should be the configuration this game is built from.
+    pub fn snapshot(&self, config: GameConfig) -> GameResult<Snapshot> {
+        let mut replay = self.replay_file(config);
+        replay.state_hashes.push(StateHash {
+            inputs: self.saved_inputs.len(),
+            hash: self.state_hash()?,
+        });
+        let state = serde_json::to_value(self.state_ref()?).context("RunTime::snapshot")?;
+        Ok(Snapshot { replay, state })
+    }
+    /// restore the state from a snapshot, checking the state hash saved in it
+    pub fn resume(snapshot: &Snapshot) -> GameResult<RunTime> {
+        const ERR_STR: &str = "RunTime::resume";
+        let config = snapshot.replay.config.clone();
+        let (state, dungeon) = with_restorer(|| -> GameResult<_> {
+            let state: State = serde_json::from_value(snapshot.state.clone()).context(ERR_STR)?;
+            let dungeon = config.dungeon.restore(state.dungeon.clone())?;
+            Ok((state, dungeon))
+        })?;
+        let mut runtime = RunTime {
+            game_info: state.game_info,
+            config: state.config,
+            dungeon,
+            item: state.item,
+            player: state.player,
+            ui: state.ui,
+            saved_inputs: snapshot.replay.inputs.clone(),
+            enemies: state.enemies,
+            keymap: config.keymap,
+            observation: config.observation,
+            reward: config.reward,
+            episode: config.episode,
+            trace: None,
+            events: None,
+        };
+        runtime.restore_rng_states(&state.rng);
+        if let Some(expected) = snapshot.replay.state_hashes.last() {
+            let actual = runtime.state_hash()?;
+            if actual != expected.hash {
+                bail!(ErrorKind::ReplayMismatch {
+                    expected: expected.hash,
+                    actual,
+                });
+            }
+        }
+        Ok(runtime)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{Agent, GreedyAgent, RandomAgent};
+    use crate::dungeon::{Coord, DungeonPath, Positioned};
+    use crate::item::{Identify, ItemKind};
+    use crate::SmallStr;
+    #[test]
+    fn snapshot_and_resume() {
+        for seed in 0..5 {
+            let mut config = GameConfig::default();
+            config.seed = Some(seed);
+            let mut runtime = config.clone().build().unwrap();
+            play(&mut runtime, &mut GreedyAgent::new(), 100);
+            let json = runtime.snapshot(config).unwrap().to_json().unwrap();
+            let mut resumed = RunTime::resume(&Snapshot::from_json(&json).unwrap()).unwrap();
+            assert_eq!(resumed.saved_inputs(), runtime.saved_inputs());
+            assert_eq!(resumed.state_hash().unwrap(), runtime.state_hash().unwrap());
+            let original = play(&mut runtime, &mut RandomAgent::new(seed), 300);
+            let resumed = play(&mut resumed, &mut RandomAgent::new(seed), 300);
+            assert_eq!(
+                original, resumed,
+                "resumed game diverged with seed {}",
+                seed
+            );
+            // a snapshot is also a replay file
+            ReplayFile::from_json(&json).unwrap().verify().unwrap();
+        }
+    }
+    #[test]
+    fn resume_without_replay() {
+        let mut config = GameConfig::default();
+        config.seed = Some(3);
+        let mut runtime = config.clone().build().unwrap();
+        play(&mut runtime, &mut GreedyAgent::new(), 50);
+        let mut snapshot = runtime.snapshot(config).unwrap();
+        // the state is restored directly, so inputs don't matter
+        snapshot.replay.inputs.clear();
+        let resumed = RunTime::resume(&snapshot).unwrap();
+        assert!(resumed.saved_inputs().is_empty());
+        assert_eq!(resumed.state_hash().unwrap(), runtime.state_hash().unwrap());
+        // but a broken state is detected by the state hash
+        snapshot.replay.state_hashes.last_mut().unwrap().hash ^= 1;
+        let err = RunTime::resume(&snapshot).err().unwrap();
+        match err.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::ReplayMismatch { .. }) => {}
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+    #[test]
+    fn identify_table_is_saved() {
+        let mut config = GameConfig::default();
+        config.seed = Some(2);
+        let mut runtime = config.clone().build().unwrap();
+        let healing = ItemKind::Potion(SmallStr::from_str("healing"));
+        let blindness = ItemKind::Potion(SmallStr::from_str("blindness"));
+        assert!(runtime.item.used(&healing, true));
+        assert!(runtime
+            .item
+            .call(&blindness, Some(SmallStr::from_str("dark"))));
+        let json = runtime.snapshot(config).unwrap().to_json().unwrap();
+        let resumed = RunTime::resume(&Snapshot::from_json(&json).unwrap()).unwrap();
+        assert_eq!(resumed.kind_name(&healing, 1), "a potion of healing");
+        assert_eq!(resumed.kind_name(&blindness, 2), "2 potions called dark");
+        assert_eq!(resumed.item.identify_table(), runtime.item.identify_table());
+    }
+    /// tiles on the screen
+    fn screen(runtime: &RunTime) -> Vec<(Coord, u8)> {
+        let mut res = vec![];
+        runtime
+            .draw_screen(|Positioned(cd, tile)| {
+                res.push((cd, tile.to_byte()));
+                Ok(())
+            })
+            .unwrap();
+        res
+    }
+    #[test]
+    fn state_hash_covers_unseen_state() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        let runtime = config.clone().build().unwrap();
+        let snapshot = runtime.snapshot(config).unwrap();
+        let (hash, screen_before) = (runtime.state_hash().unwrap(), screen(&runtime));
+        let level = runtime.dungeon.level() as i32;
+        let (mut items, mut enemies) = (0, 0);
+        for (x, y) in (0..runtime.config.width.0)
+            .flat_map(|x| (0..runtime.config.height.0).map(move |y| (x, y)))
+        {
+            let path: DungeonPath = [level, x, y].into();
+            let item = runtime.dungeon.get_item(&path).is_some();
+            let enemy = runtime.enemies.get_enemy(&path).is_some();
+            if !(item || enemy) {
+                continue;
+            }
+            // removing an item or an enemy the player can't see changes only the hash
+            let mut copied = RunTime::resume(&snapshot).unwrap();
+            copied.dungeon.remove_item(&path);
+            copied.enemies.remove(path);
+            if screen(&copied) != screen_before {
+                continue;
+            }
+            assert_ne!(copied.state_hash().unwrap(), hash);
+            items += item as u32;
+            enemies += enemy as u32;
+        }
+        assert!(
+            items > 0 && enemies > 0,
+            "{} items, {} enemies",
+            items,
+            enemies
+        );
+    }
+    /// play `steps` steps with `agent` and returns state hashes and rewards after each step
+    fn play(runtime: &mut RunTime, agent: &mut dyn Agent, steps: usize) -> Vec<(u64, f32)> {
+        let mut res = vec![];
+        for _ in 0..steps {
+            let action = agent.act(&runtime.game_state().unwrap());
+            let (reward, info) = runtime.advance(action).unwrap();
+            res.push((runtime.state_hash().unwrap(), reward));
+            if info.is_done() {
+                break;
+            }
+        }
+        res
+    }
+    #[test]
+    fn clone_follows_original() {
+        for seed in 0..5 {
+            let mut config = GameConfig::default();
+            config.seed = Some(seed);
+            let mut runtime = config.build().unwrap();
+            play(&mut runtime, &mut GreedyAgent::new(), 50);
+            let mut cloned = runtime.clone_deterministic();
+            assert_eq!(cloned.state_hash().unwrap(), runtime.state_hash().unwrap());
+            let original = play(&mut runtime, &mut RandomAgent::new(seed), 300);
+            let cloned = play(&mut cloned, &mut RandomAgent::new(seed), 300);
+            assert_eq!(original, cloned, "clone diverged with seed {}", seed);
+        }
+    }
+    #[test]
+    fn clone_is_independent() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        let mut runtime = config.build().unwrap();
+        play(&mut runtime, &mut GreedyAgent::new(), 30);
+        let hash = runtime.state_hash().unwrap();
+        let mut cloned = runtime.clone_deterministic();
+        // the clone descends and explores, which must not touch the original
+        play(&mut cloned, &mut GreedyAgent::new(), 300);
+        assert_ne!(cloned.state_hash().unwrap(), hash);
+        assert_eq!(runtime.state_hash().unwrap(), hash);
+        // and the original still follows a fresh clone after that
+        let mut fresh = runtime.clone_deterministic();
+        let original = play(&mut runtime, &mut RandomAgent::new(3), 100);
+        assert_eq!(original, play(&mut fresh, &mut RandomAgent::new(3), 100));
+    }
+}
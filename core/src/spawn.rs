@@ -0,0 +1,84 @@
+//! per-depth spawn tables, which override which enemies and items appear at each depth
+use crate::rng::RngHandle;
+use crate::validate::Validator;
+use std::collections::BTreeMap;
+
+/// Relative weights of things that appear in a range of depths.
+/// e.g. `{"min_depth": 1, "max_depth": 3, "weights": {"bat": 1}}` means that only bats
+/// appear on floors 1 to 3.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DepthTable {
+    /// the shallowest depth where the table is used
+    pub min_depth: u32,
+    /// the deepest depth where the table is used
+    pub max_depth: u32,
+    /// names and their relative weights
+    pub weights: BTreeMap<String, u32>,
+}
+
+impl DepthTable {
+    pub fn contains(&self, depth: u32) -> bool {
+        self.min_depth <= depth && depth <= self.max_depth
+    }
+    /// choose a name in proportion to its weight
+    pub(crate) fn choose(&self, rng: &mut RngHandle) -> Option<&str> {
+        let total: u32 = self.weights.values().sum();
+        if total == 0 {
+            return None;
+        }
+        let mut rest = rng.range(0..total);
+        for (name, &weight) in &self.weights {
+            if rest < weight {
+                return Some(name);
+            }
+            rest -= weight;
+        }
+        None
+    }
+    /// check the depth range, and that all names are in `known`
+    fn validate(&self, v: &mut Validator, known: &dyn Fn(&str) -> bool) {
+        v.check(
+            "max_depth",
+            self.min_depth <= self.max_depth,
+            "max_depth should not be less than min_depth",
+        );
+        v.field("weights", |v| {
+            for name in self.weights.keys().filter(|name| !known(name)) {
+                v.error(format!("unknown name {}", name));
+            }
+        });
+    }
+}
+
+/// the first table which covers `depth`
+pub(crate) fn lookup(tables: &[DepthTable], depth: u32) -> Option<&DepthTable> {
+    tables.iter().find(|t| t.contains(depth))
+}
+
+pub(crate) fn validate(tables: &[DepthTable], v: &mut Validator, known: &dyn Fn(&str) -> bool) {
+    for (i, table) in tables.iter().enumerate() {
+        v.element(i, |v| table.validate(v, known));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn choose() {
+        let table: DepthTable = serde_json::from_str(
+            r#"{"min_depth": 1, "max_depth": 3, "weights": {"bat": 0, "rat": 2}}"#,
+        )
+        .unwrap();
+        assert!(table.contains(3) && !table.contains(4));
+        let mut rng = RngHandle::from_seed(1);
+        for _ in 0..20 {
+            assert_eq!(table.choose(&mut rng), Some("rat"));
+        }
+        let mut v = Validator::new();
+        validate(&[table], &mut v, &|name| name == "bat");
+        let violations = v.finish();
+        assert_eq!(violations.0.len(), 1);
+        assert_eq!(violations.0[0].path, "[0].weights");
+    }
+}
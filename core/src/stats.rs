@@ -0,0 +1,108 @@
+//! per-game statistics, for players and analyses of agents' behavior
+use crate::character::fight::{CombatEvent, Combatant};
+use crate::{GameMsg, Reaction};
+use std::cmp;
+use std::collections::BTreeMap;
+
+/// Counters accumulated through a game
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Statistics {
+    /// the number of cells the player has walked on, by dungeon levels
+    #[serde(default)]
+    pub explored: BTreeMap<u32, u32>,
+    /// the number of killed enemies by their names
+    #[serde(default)]
+    pub kills: BTreeMap<String, u32>,
+    /// the number of items used up, which are thrown ones for now
+    #[serde(default)]
+    pub items_used: u32,
+    /// total damage the player dealt to enemies
+    #[serde(default)]
+    pub damage_dealt: i64,
+    /// total damage the player took from enemies
+    #[serde(default)]
+    pub damage_taken: i64,
+    #[serde(default)]
+    pub deepest_level: u32,
+}
+
+impl Statistics {
+    /// the number of cells the player has walked on in all levels
+    pub fn tiles_explored(&self) -> u32 {
+        self.explored.values().sum()
+    }
+    /// update counters by reactions to an input
+    pub(crate) fn record(&mut self, reactions: &[Reaction], level: u32) {
+        for reaction in reactions {
+            match reaction {
+                Reaction::Notify(GameMsg::Killed(enemy)) => {
+                    *self.kills.entry(enemy.to_string()).or_insert(0) += 1;
+                }
+                Reaction::Notify(GameMsg::Combat(CombatEvent {
+                    attacker,
+                    defender,
+                    roll,
+                    ..
+                })) => {
+                    let damage = roll.damage.map_or(0, |d| d.0);
+                    if *attacker == Combatant::Player {
+                        self.damage_dealt += damage;
+                    }
+                    if *defender == Combatant::Player {
+                        self.damage_taken += damage;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.deepest_level = cmp::max(self.deepest_level, level);
+    }
+    /// update the number of explored cells in `level`, which never decreases
+    pub(crate) fn explore(&mut self, level: u32, cells: u32) {
+        let explored = self.explored.entry(level).or_insert(0);
+        *explored = cmp::max(*explored, cells);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::{fight::Roll, DamageKind, HitPoint};
+    use crate::rng::Parcent;
+    use crate::smallstr::SmallStr;
+    #[test]
+    fn record() {
+        let roll = |damage| Roll {
+            kind: DamageKind::Physical,
+            hit_rate: Parcent(100),
+            damage: Some(HitPoint(damage)),
+            max_damage: HitPoint(10),
+        };
+        let bat = || Combatant::Enemy(SmallStr::from_str("Bat"));
+        let reactions = vec![
+            Reaction::Notify(GameMsg::Combat(CombatEvent {
+                attacker: bat(),
+                defender: Combatant::Player,
+                roll: roll(3),
+                killed: false,
+            })),
+            Reaction::Notify(GameMsg::Combat(CombatEvent {
+                attacker: Combatant::Player,
+                defender: bat(),
+                roll: roll(5),
+                killed: true,
+            })),
+            Reaction::Notify(GameMsg::Killed(SmallStr::from_str("Bat"))),
+        ];
+        let mut stats = Statistics::default();
+        stats.record(&reactions, 2);
+        stats.record(&reactions[2..], 1);
+        stats.explore(1, 30);
+        stats.explore(1, 20);
+        stats.explore(2, 10);
+        assert_eq!(stats.kills["Bat"], 2);
+        assert_eq!((stats.damage_dealt, stats.damage_taken), (5, 3));
+        assert_eq!(stats.deepest_level, 2);
+        assert_eq!(stats.tiles_explored(), 40);
+    }
+}
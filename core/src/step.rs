@@ -0,0 +1,511 @@
+//! gym-style step API, which applies an input and computes what agents need at once
+use crate::character::fight::{CombatEvent, Combatant};
+use crate::character::player::Status;
+use crate::character::Action;
+use crate::dungeon::Direction;
+use crate::episode::EndReason;
+use crate::error::*;
+use crate::input::InputCode;
+use crate::item::{itembox::ItemBox, ItemHandler, ItemId, ItemKind};
+use crate::symbol::Layout;
+use crate::ui::{MordalKind, UiState};
+use crate::{GameMsg, Reaction, RunTime};
+use anyhow::Context;
+use enum_iterator::IntoEnumIterator;
+use std::collections::HashMap;
+
+/// Result of `RunTime::step`
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepResult {
+    /// one-hot observation made by `RunTime::observe` in NCHW layout,
+    /// of shape `RunTime::observation_shape`
+    pub observation: Vec<f32>,
+    /// reward specified by `GameConfig::reward`
+    pub reward: f32,
+    /// true if the player died or the episode ended by a limit
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+/// Additional information about a step
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StepInfo {
+    /// all reactions to the input, which are empty if the input was ignored
+    pub reactions: Vec<Reaction>,
+    pub dead: bool,
+    /// why the episode ended, if it ended by a limit in `GameConfig::episode`
+    pub episode_end: Option<EndReason>,
+    /// what happened in the step, in order
+    pub events: Vec<StepEvent>,
+}
+
+/// A semantic event happened in a step, for reward shaping and analysis
+/// without parsing messages
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StepEvent {
+    /// the player picked up the item of `id`(see `ItemId`)
+    PickedUp {
+        id: ItemId,
+        item: String,
+        num: u32,
+    },
+    Killed {
+        enemy: String,
+    },
+    /// the player took `amount` damage from `from`
+    Damaged {
+        from: String,
+        amount: i64,
+    },
+    Trapped {
+        trap: String,
+    },
+    Descended {
+        depth: u32,
+    },
+    Ascended {
+        depth: u32,
+    },
+    LeveledUp {
+        level: u32,
+    },
+}
+
+/// Buffers reused by `RunTime::step_into`, which keep their capacities across steps
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReactionBuffer {
+    /// reactions to the last input
+    pub reactions: Vec<Reaction>,
+    /// what happened in the last step, in order
+    pub events: Vec<StepEvent>,
+}
+
+impl StepEvent {
+    /// events in `reactions` and the status change.
+    /// Items are named by `items`, considering whether they are identified or not.
+    pub fn collect(
+        before: &Status,
+        after: &Status,
+        reactions: &[Reaction],
+        items: &ItemHandler,
+    ) -> Vec<StepEvent> {
+        let mut res = vec![];
+        StepEvent::collect_into(before, after, reactions, items, &mut res);
+        res
+    }
+    /// same as `collect`, but appends events to `res`
+    pub fn collect_into(
+        before: &Status,
+        after: &Status,
+        reactions: &[Reaction],
+        items: &ItemHandler,
+        res: &mut Vec<StepEvent>,
+    ) {
+        let events = reactions
+            .iter()
+            .filter_map(|reaction| match reaction {
+                Reaction::Notify(msg) => Some(msg),
+                _ => None,
+            })
+            .filter_map(|msg| match msg {
+                GameMsg::GotItem { id, kind, num } => Some(StepEvent::PickedUp {
+                    id: *id,
+                    item: items.kind_name(kind, *num),
+                    num: *num,
+                }),
+                GameMsg::Killed(enemy) => Some(StepEvent::Killed {
+                    enemy: enemy.to_string(),
+                }),
+                GameMsg::Combat(CombatEvent {
+                    attacker: Combatant::Enemy(enemy),
+                    defender: Combatant::Player,
+                    roll,
+                    ..
+                }) => roll.damage.map(|dam| StepEvent::Damaged {
+                    from: enemy.to_string(),
+                    amount: dam.0,
+                }),
+                GameMsg::Trapped(trap) => Some(StepEvent::Trapped {
+                    trap: trap.to_string(),
+                }),
+                _ => None,
+            });
+        res.extend(events);
+        let depth = after.dungeon_level;
+        if depth > before.dungeon_level {
+            res.push(StepEvent::Descended { depth });
+        } else if depth < before.dungeon_level {
+            res.push(StepEvent::Ascended { depth });
+        }
+        if after.player_level > before.player_level {
+            res.push(StepEvent::LeveledUp {
+                level: after.player_level,
+            });
+        }
+    }
+}
+
+impl StepInfo {
+    /// true if the player died or the episode ended by a limit
+    pub fn is_done(&self) -> bool {
+        self.dead || self.episode_end.is_some()
+    }
+    /// true if the player died or the episode ended by a goal(see `EndReason::is_truncation`)
+    pub fn terminated(&self) -> bool {
+        self.is_done() && !self.truncated()
+    }
+    /// true if the episode was cut off by `max_turns`
+    pub fn truncated(&self) -> bool {
+        self.episode_end
+            .as_ref()
+            .is_some_and(EndReason::is_truncation)
+    }
+}
+
+impl RunTime {
+    /// the number of symbols in observations of `step`
+    pub fn symbols(&self) -> GameResult<u8> {
+        let max = self
+            .symbol_max()
+            .context("No symbol for the largest enemy tile")?;
+        Ok(max.to_byte() + 1)
+    }
+    /// [channels, height, width] of observations of `step`
+    pub fn observation_shape(&self) -> GameResult<[usize; 3]> {
+        let (w, h) = self.observation_size();
+        Ok([self.observation_channels(self.symbols()?), h, w])
+    }
+    /// the current observation, same as the one `step` returns
+    pub fn observation(&self) -> GameResult<Vec<f32>> {
+        let symbols = self.symbols()?;
+        let (w, h) = self.observation_size();
+        let mut res = vec![0.0; self.observation_channels(symbols) * h * w];
+        self.observe(symbols, Layout::Nchw, &mut res)?;
+        Ok(res)
+    }
+    /// apply `action` and returns the observation, reward and whether the episode ended.
+    /// Inputs unavailable in the current state are ignored, as in `replay_input`.
+    pub fn step(&mut self, action: InputCode) -> GameResult<StepResult> {
+        let (reward, info) = self.advance(action)?;
+        Ok(StepResult {
+            observation: self.observation()?,
+            reward,
+            done: info.is_done(),
+            info,
+        })
+    }
+    /// `step` without making the observation, for callers writing observations
+    /// into their own buffers with `observe`
+    pub fn advance(&mut self, action: InputCode) -> GameResult<(f32, StepInfo)> {
+        let mut buf = ReactionBuffer::default();
+        let (reward, _) = self.step_into(action, &mut buf)?;
+        let info = StepInfo {
+            reactions: buf.reactions,
+            dead: self.is_dead(),
+            episode_end: self.episode_end().cloned(),
+            events: buf.events,
+        };
+        Ok((reward, info))
+    }
+    /// `advance` writing reactions and events into `buf` instead of allocating them,
+    /// for agents stepping games many times. Returns the reward and whether the episode ended.
+    pub fn step_into(
+        &mut self,
+        action: InputCode,
+        buf: &mut ReactionBuffer,
+    ) -> GameResult<(f32, bool)> {
+        let before = self.player_status();
+        self.replay_input_into(action, &mut buf.reactions)
+            .context("RunTime::step")?;
+        let after = self.player_status();
+        let reward = self.reward.reward(&before, &after, &buf.reactions);
+        buf.events.clear();
+        StepEvent::collect_into(&before, &after, &buf.reactions, &self.item, &mut buf.events);
+        Ok((reward, self.is_dead() || self.episode_end().is_some()))
+    }
+    fn is_dead(&self) -> bool {
+        matches!(self.ui, UiState::Mordal(MordalKind::Grave(_)))
+    }
+}
+
+/// all actions without parameters other than directions and item letters,
+/// in the order of `RunTime::action_set`
+fn canonical_actions() -> Vec<Action> {
+    let mut res = vec![Action::NoOp];
+    res.extend(Direction::into_enum_iter().map(Action::Move));
+    res.extend(Direction::into_enum_iter().map(Action::MoveUntil));
+    res.extend_from_slice(&[
+        Action::Search,
+        Action::DownStair,
+        Action::UpStair,
+        Action::PickUp,
+        Action::AutoExplore,
+        Action::Rest,
+    ]);
+    res.extend(Direction::into_enum_iter().map(Action::Throw));
+    res.extend(Direction::into_enum_iter().map(Action::Open));
+    res.extend(Direction::into_enum_iter().map(Action::Close));
+    res.extend((0..).map_while(ItemBox::letter).map(Action::Drop));
+    res.extend((0..).map_while(ItemBox::letter).map(Action::Read));
+    res
+}
+
+impl RunTime {
+    /// actions bound in the keymap, in a fixed order which doesn't depend on keys.
+    /// Inputs without actions (e.g. opening the inventory) are not included.
+    pub fn action_set(&self) -> Vec<InputCode> {
+        let mut keys: Vec<_> = self.keymap.iter().collect();
+        keys.sort_by_key(|(key, _)| key.to_str());
+        let mut bound = HashMap::new();
+        for (_, code) in keys {
+            let act = match code {
+                InputCode::Act(act) | InputCode::Both { act, .. } => act,
+                InputCode::Sys(_) => continue,
+            };
+            // prefer inputs without system commands, which have no side effects in menus
+            match bound.get(&act) {
+                Some(InputCode::Act(_)) => {}
+                Some(_) if !matches!(code, InputCode::Act(_)) => {}
+                _ => {
+                    bound.insert(act, code);
+                }
+            }
+        }
+        canonical_actions()
+            .into_iter()
+            .filter_map(|act| bound.get(&act).cloned())
+            .collect()
+    }
+    /// mask of `action_set`, which is false for actions that do nothing now,
+    /// like going down without stairs or picking up nothing
+    pub fn valid_actions(&self) -> Vec<bool> {
+        let actions = self.action_set();
+        if self.ui != UiState::Dungeon {
+            return actions
+                .iter()
+                .map(|code| matches!(code, InputCode::Both { .. }))
+                .collect();
+        }
+        let pos = &self.player.pos;
+        let item_here = self.dungeon.get_item(pos).is_some();
+        actions
+            .into_iter()
+            .map(|code| match code {
+                InputCode::Act(act) | InputCode::Both { act, .. } => match act {
+                    Action::Move(d) | Action::MoveUntil(d) => {
+                        self.dungeon.can_move_player(pos, d).is_some()
+                    }
+                    Action::DownStair => self.dungeon.is_downstair(pos),
+                    Action::UpStair => self.dungeon.level() > 1 && self.dungeon.is_downstair(pos),
+                    Action::PickUp => item_here,
+                    Action::AutoExplore => self.dungeon.way_to_unexplored(pos).is_some(),
+                    Action::Throw(_) => self.player.has_missile(),
+                    Action::Drop(letter) => !item_here && self.player.itembox.get(letter).is_some(),
+                    Action::Read(letter) => self
+                        .player
+                        .itembox
+                        .get(letter)
+                        .is_some_and(|item| matches!(item.kind, ItemKind::Scroll(_))),
+                    Action::DropSome(letter, num) => {
+                        !item_here && num > 0 && self.player.itembox.get(letter).is_some()
+                    }
+                    _ => true,
+                },
+                InputCode::Sys(_) => true,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::fight::Roll;
+    use crate::character::player::Hunger;
+    use crate::character::{DamageKind, HitPoint};
+    use crate::dungeon::TrapKind;
+    use crate::input::{KeyMap, System};
+    use crate::item::Config as ItemConfig;
+    use crate::rng::Parcent;
+    use crate::{GameConfig, SmallStr};
+    #[test]
+    fn step() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.episode.max_turns = Some(2);
+        let mut runtime = config.build().unwrap();
+        let [c, h, w] = runtime.observation_shape().unwrap();
+        assert_eq!((h, w), (24, 80));
+        let first = runtime.step(InputCode::Act(Action::Search)).unwrap();
+        assert_eq!(first.observation.len(), c * h * w);
+        assert_eq!(first.observation, runtime.observation().unwrap());
+        assert_eq!(first.reward, 0.0);
+        assert!(!first.done);
+        let second = runtime.step(InputCode::Act(Action::Search)).unwrap();
+        assert!(second.done);
+        assert!(!second.info.dead);
+        assert_eq!(second.info.episode_end, Some(EndReason::MaxTurns(2)));
+        assert!(second.info.truncated());
+        assert!(!second.info.terminated());
+    }
+    #[test]
+    fn terminated_by_hunger() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.player.hunger_time = 100;
+        config.enemies.enemies.clear();
+        config.episode.max_turns = Some(1000);
+        config.episode.hunger = Some(Hunger::Hungry);
+        let mut runtime = config.build().unwrap();
+        let info = (0..100)
+            .map(|_| runtime.advance(InputCode::Act(Action::Search)).unwrap().1)
+            .find(StepInfo::is_done)
+            .unwrap();
+        assert_eq!(info.episode_end, Some(EndReason::Hunger(Hunger::Hungry)));
+        assert!(info.terminated());
+        assert!(!info.truncated());
+    }
+    #[test]
+    fn step_into() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        let (mut a, mut b) = (config.clone().build().unwrap(), config.build().unwrap());
+        let mut buf = ReactionBuffer::default();
+        for action in a.action_set().into_iter().cycle().take(50) {
+            let (reward, info) = a.advance(action).unwrap();
+            let (reward_into, done) = b.step_into(action, &mut buf).unwrap();
+            assert_eq!((reward, info.is_done()), (reward_into, done));
+            assert_eq!(info.reactions, buf.reactions);
+            assert_eq!(info.events, buf.events);
+        }
+        assert_eq!(a.state_hash().unwrap(), b.state_hash().unwrap());
+    }
+    #[test]
+    fn events() {
+        let mut before = Status::default();
+        before.dungeon_level = 1;
+        before.player_level = 1;
+        let mut after = before.clone();
+        after.dungeon_level = 2;
+        let bat = SmallStr::from_static("Bat");
+        let reactions = vec![
+            Reaction::Redraw,
+            Reaction::Notify(GameMsg::Killed(bat.clone())),
+            Reaction::Notify(GameMsg::Combat(CombatEvent {
+                attacker: Combatant::Enemy(bat),
+                defender: Combatant::Player,
+                roll: Roll {
+                    kind: DamageKind::default(),
+                    hit_rate: Parcent(100),
+                    damage: Some(HitPoint(3)),
+                    max_damage: HitPoint(4),
+                },
+                killed: false,
+            })),
+            Reaction::Notify(GameMsg::GotItem {
+                id: ItemId(3),
+                kind: ItemKind::Gold,
+                num: 5,
+            }),
+            Reaction::Notify(GameMsg::Trapped(TrapKind::Arrow)),
+        ];
+        let items = ItemHandler::new(ItemConfig::default(), 1);
+        let events = StepEvent::collect(&before, &after, &reactions, &items);
+        assert_eq!(
+            events,
+            vec![
+                StepEvent::Killed {
+                    enemy: "Bat".to_owned()
+                },
+                StepEvent::Damaged {
+                    from: "Bat".to_owned(),
+                    amount: 3
+                },
+                StepEvent::PickedUp {
+                    id: ItemId(3),
+                    item: "5 gold pieces".to_owned(),
+                    num: 5
+                },
+                StepEvent::Trapped {
+                    trap: "an arrow trap".to_owned()
+                },
+                StepEvent::Descended { depth: 2 },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&events[4]).unwrap(),
+            r#"{"kind":"descended","depth":2}"#
+        );
+    }
+    #[test]
+    fn action_set() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.keymap = KeyMap::ai();
+        let mut runtime = config.build().unwrap();
+        let actions = runtime.action_set();
+        assert_eq!(actions.len(), 19);
+        assert_eq!(actions[0], InputCode::Act(Action::NoOp));
+        assert_eq!(actions[1], InputCode::Act(Action::Move(Direction::Up)));
+        let down = actions
+            .iter()
+            .position(|&a| a == InputCode::Act(Action::DownStair))
+            .unwrap();
+        let search = actions
+            .iter()
+            .position(|&a| a == InputCode::Act(Action::Search))
+            .unwrap();
+        for _ in 0..10 {
+            let valid = runtime.valid_actions();
+            assert_eq!(valid.len(), actions.len());
+            assert!(valid[search]);
+            let pos = runtime.player.pos.clone();
+            assert_eq!(valid[down], runtime.dungeon.is_downstair(&pos));
+            for (&action, &valid) in actions.iter().zip(&valid) {
+                if let InputCode::Act(Action::Move(d)) = action {
+                    let can_move = runtime.dungeon.can_move_player(&pos, d).is_some();
+                    assert_eq!(valid, can_move);
+                }
+            }
+            runtime.step(InputCode::Act(Action::AutoExplore)).unwrap();
+        }
+        runtime.keymap = KeyMap::default();
+        assert!(runtime.action_set().contains(&InputCode::Both {
+            act: Action::Move(Direction::LeftUp),
+            sys: System::Yes,
+        }));
+    }
+    #[test]
+    fn observation_on_random_walk() {
+        let build = |seed| {
+            let mut config = GameConfig::default();
+            config.seed = Some(seed);
+            config.build().unwrap()
+        };
+        let mut runtime = build(1);
+        let mut rng = crate::rng::RngHandle::from_seed(1);
+        let mut buf = ReactionBuffer::default();
+        let actions = runtime.action_set();
+        let symbols = runtime.symbols().unwrap();
+        let [c, h, w] = runtime.observation_shape().unwrap();
+        let mut out = vec![0.0f32; c * h * w];
+        for turn in 0..1000 {
+            let action = actions[rng.range(0..actions.len())];
+            let (_, done) = runtime.step_into(action, &mut buf).unwrap();
+            if done {
+                runtime = build(rng.range(2..1000));
+            }
+            if turn % 100 != 0 {
+                continue;
+            }
+            runtime.observe(symbols, Layout::Nchw, &mut out).unwrap();
+            assert_eq!(runtime.observation().unwrap(), out);
+            // each cell has exactly one symbol
+            for i in 0..h * w {
+                let sum: f32 = (0..usize::from(symbols)).map(|s| out[s * h * w + i]).sum();
+                assert_eq!(sum, 1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! summary of a game, for tombstones and score lists
+use crate::stats::Statistics;
+use crate::ui::{MordalKind, UiState};
+use crate::RunTime;
+use std::cmp;
+
+/// A summary of the game, which is made when the game ends
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct GameSummary {
+    /// how the player died, like "Killed by Bat", or None if the player is alive
+    pub cause_of_death: Option<String>,
+    pub gold: u32,
+    pub deepest_level: u32,
+    pub player_level: u32,
+    pub kills: u32,
+    pub turns: u32,
+    pub seed: u128,
+    /// bosses configured for the game, and whether they were killed or not
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bosses: Vec<BossRecord>,
+    #[serde(default)]
+    pub statistics: Statistics,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BossRecord {
+    pub name: String,
+    pub killed: bool,
+}
+
+impl GameSummary {
+    pub fn is_dead(&self) -> bool {
+        self.cause_of_death.is_some()
+    }
+    /// lines to show under the tombstone
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Gold: {}", self.gold),
+            format!("Deepest level: {}", self.deepest_level),
+            format!("Player level: {}", self.player_level),
+            format!("Kills: {}", self.kills),
+            format!("Turns: {}", self.turns),
+        ];
+        for boss in &self.bosses {
+            let state = if boss.killed { "killed" } else { "alive" };
+            lines.push(format!("{}: {}", boss.name, state));
+        }
+        lines
+    }
+}
+
+impl RunTime {
+    /// summary of the game so far
+    pub fn summary(&self) -> GameSummary {
+        let status = self.player_status();
+        let cause_of_death = match &self.ui {
+            UiState::Mordal(MordalKind::Grave(msg)) => Some(msg.to_string()),
+            _ => None,
+        };
+        GameSummary {
+            cause_of_death,
+            gold: status.gold,
+            deepest_level: cmp::max(self.game_info.deepest_level, status.dungeon_level),
+            player_level: status.player_level,
+            kills: self.game_info.kills,
+            turns: status.turns,
+            seed: self.config.seed,
+            bosses: self
+                .enemies
+                .bosses()
+                .map(|(name, killed)| BossRecord {
+                    name: name.to_owned(),
+                    killed,
+                })
+                .collect(),
+            statistics: self.statistics().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::character::Action;
+    use crate::input::InputCode;
+    use crate::{Direction, GameConfig};
+    use enum_iterator::IntoEnumIterator;
+    #[test]
+    fn summary() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        let mut runtime = config.build().unwrap();
+        for _ in 0..5 {
+            runtime
+                .react_to_input(InputCode::Act(Action::Search))
+                .unwrap();
+        }
+        let summary = runtime.summary();
+        assert!(!summary.is_dead());
+        assert_eq!(summary.turns, 5);
+        assert_eq!(summary.deepest_level, 1);
+        assert_eq!(summary.kills, 0);
+        assert!(summary.bosses.is_empty());
+        assert_eq!(summary.statistics.deepest_level, 1);
+        assert!(summary.statistics.tiles_explored() > 0);
+    }
+    #[test]
+    fn starve_while_walking() {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.player.hunger_time = 3;
+        config.enemies.enemies.clear();
+        let mut runtime = config.build().unwrap();
+        for d in Direction::into_enum_iter().cycle().take(8) {
+            runtime
+                .react_to_input(InputCode::Act(Action::MoveUntil(d)))
+                .unwrap();
+            if runtime.summary().is_dead() {
+                break;
+            }
+        }
+        assert!(runtime.summary().is_dead());
+    }
+}
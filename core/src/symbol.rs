@@ -14,6 +14,13 @@ impl Symbol {
     pub fn decrement(self) -> Self {
         Symbol(self.0 - 1)
     }
+    /// the largest symbol in a game where the largest enemy tile is `tile_max`
+    pub(crate) fn max_with_enemies(tile_max: Option<u8>) -> Option<Symbol> {
+        match tile_max {
+            Some(t) => Symbol::from_tile(t.into()),
+            None => Symbol::from_tile(b'A'.into()).map(|s| s.decrement()),
+        }
+    }
     pub fn from_tile(t: Tile) -> Option<Symbol> {
         let sym = |u| Some(Symbol(u));
         match t.to_byte() {
@@ -23,6 +30,7 @@ impl Symbol {
             b'.' => sym(3),
             b'-' | b'|' => sym(4),
             b'%' => sym(5),
+            // doors, whether they're open or closed
             b'+' => sym(6),
             b'^' => sym(7),
             b'!' => sym(8),
@@ -48,24 +56,153 @@ pub fn tile_to_sym(t: u8) -> Option<u8> {
 #[error("Invalid tile: {}, while max is {}", _0, _1)]
 pub struct InvalidTileError(Tile, u8);
 
-pub fn construct_symbol_map<'c>(
+/// Element types of one-hot observations
+pub trait OneHot: Copy {
+    const ZERO: Self;
+    const ONE: Self;
+    /// convert a scalar in [0, 1], for scalar planes of observations
+    fn from_ratio(ratio: f32) -> Self;
+}
+
+impl OneHot for f32 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+    fn from_ratio(ratio: f32) -> Self {
+        ratio
+    }
+}
+
+impl OneHot for f64 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+    fn from_ratio(ratio: f32) -> Self {
+        f64::from(ratio)
+    }
+}
+
+impl OneHot for u8 {
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+    /// scaled to [0, 255]
+    fn from_ratio(ratio: f32) -> Self {
+        (ratio.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+}
+
+/// Memory layout of one-hot observations
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Layout {
+    /// [channel, height, width], as PyTorch
+    Nchw,
+    /// [height, width, channel], as TensorFlow
+    Nhwc,
+}
+
+impl Layout {
+    /// shape of an observation with `channels` channels
+    pub fn shape(self, channels: usize, h: usize, w: usize) -> [usize; 3] {
+        match self {
+            Layout::Nchw => [channels, h, w],
+            Layout::Nhwc => [h, w, channels],
+        }
+    }
+    /// index of `[channel, y, x]` in a flat array
+    pub fn index(self, channels: usize, h: usize, w: usize, [c, y, x]: [usize; 3]) -> usize {
+        match self {
+            Layout::Nchw => (c * h + y) * w + x,
+            Layout::Nhwc => (y * w + x) * channels + c,
+        }
+    }
+}
+
+fn symbol_of(t: u8, symbol_max: u8) -> Result<u8, InvalidTileError> {
+    match tile_to_sym(t) {
+        Some(sym) if sym < symbol_max => Ok(sym),
+        _ => Err(InvalidTileError(t.into(), symbol_max)),
+    }
+}
+
+pub fn construct_symbol_map<'c, T: 'c + OneHot>(
     map: &impl Get2D<Item = u8>,
     h: usize,
     w: usize,
     symbol_max: u8,
-    mut res: impl 'c + FnMut([usize; 3]) -> &'c mut f32,
+    mut res: impl 'c + FnMut([usize; 3]) -> &'c mut T,
 ) -> Result<(), InvalidTileError> {
     for i in 0..usize::from(symbol_max) {
         for y in 0..h {
             for x in 0..w {
-                let t = *map.get_xy(x, y);
-                let sym = tile_to_sym(t).ok_or_else(|| InvalidTileError(t.into(), symbol_max))?;
-                if sym >= symbol_max {
-                    return Err(InvalidTileError(t.into(), symbol_max));
-                }
-                *res([i, y, x]) = if usize::from(sym) == i { 1.0 } else { 0.0 };
+                let sym = symbol_of(*map.get_xy(x, y), symbol_max)?;
+                *res([i, y, x]) = if usize::from(sym) == i {
+                    T::ONE
+                } else {
+                    T::ZERO
+                };
             }
         }
     }
     Ok(())
 }
+
+/// same as `construct_symbol_map`, but writes to a flat array in `layout`,
+/// which has `symbol_max` channels.
+/// Panics if `res` is shorter than `symbol_max * h * w`.
+pub fn construct_symbol_map_with<T: OneHot>(
+    map: &impl Get2D<Item = u8>,
+    h: usize,
+    w: usize,
+    symbol_max: u8,
+    layout: Layout,
+    res: &mut [T],
+) -> Result<(), InvalidTileError> {
+    let channels = usize::from(symbol_max);
+    res[..channels * h * w]
+        .iter_mut()
+        .for_each(|r| *r = T::ZERO);
+    for y in 0..h {
+        for x in 0..w {
+            let sym = symbol_of(*map.get_xy(x, y), symbol_max)?;
+            res[layout.index(channels, h, w, [usize::from(sym), y, x])] = T::ONE;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::GameConfig;
+    #[test]
+    fn symbol_grid() {
+        let runtime = GameConfig::default().build().unwrap();
+        let (w, h) = runtime.screen_size();
+        let mut grid = vec![0; w.0 as usize * h.0 as usize];
+        runtime.symbol_grid(&mut grid).unwrap();
+        let player = tile_to_sym(b'@').unwrap();
+        assert_eq!(grid.iter().filter(|&&s| s == player).count(), 1);
+        let pos = runtime.player_position();
+        assert_eq!(grid[(pos.y.0 * w.0 + pos.x.0) as usize], player);
+        assert!(runtime.symbol_grid(&mut grid[1..]).is_err());
+    }
+    #[test]
+    fn layouts() {
+        let map = vec![b"@.".to_vec(), b"#A".to_vec()];
+        let symbol_max = tile_to_sym(b'A').unwrap() + 1;
+        let channels = usize::from(symbol_max);
+        let mut nchw = vec![0.0f32; channels * 4];
+        construct_symbol_map_with(&map, 2, 2, symbol_max, Layout::Nchw, &mut nchw).unwrap();
+        let mut nhwc = vec![0u8; channels * 4];
+        construct_symbol_map_with(&map, 2, 2, symbol_max, Layout::Nhwc, &mut nhwc).unwrap();
+        for (y, x) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let sym = usize::from(tile_to_sym(map[y][x]).unwrap());
+            for c in 0..channels {
+                let expected = if c == sym { 1 } else { 0 };
+                assert_eq!(nchw[(c * 2 + y) * 2 + x], f32::from(expected));
+                assert_eq!(nhwc[(y * 2 + x) * channels + c], expected);
+            }
+        }
+        assert_eq!(Layout::Nhwc.shape(channels, 2, 3), [2, 3, channels]);
+        let mut small = vec![0u8; channels * 4];
+        assert!(construct_symbol_map_with(&map, 2, 2, 3, Layout::Nhwc, &mut small).is_err());
+    }
+}
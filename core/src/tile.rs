@@ -2,7 +2,9 @@
 use std::fmt;
 
 use derive_more::From;
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, From, Serialize, Deserialize)]
 pub struct Tile(pub u8);
@@ -31,7 +33,76 @@ pub trait Drawable {
     }
 }
 
-/// color representation
-/// currently it's not used at all
+/// color representation, as an ANSI color index(0-15)
+/// it's written as its name like `"bright_white"` in configuration files
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Color(pub u8);
+
+impl Color {
+    pub const BLACK: Color = Color(0);
+    pub const RED: Color = Color(1);
+    pub const GREEN: Color = Color(2);
+    pub const YELLOW: Color = Color(3);
+    pub const BLUE: Color = Color(4);
+    pub const MAGENTA: Color = Color(5);
+    pub const CYAN: Color = Color(6);
+    pub const WHITE: Color = Color(7);
+    pub const BRIGHT_BLACK: Color = Color(8);
+    pub const BRIGHT_RED: Color = Color(9);
+    pub const BRIGHT_GREEN: Color = Color(10);
+    pub const BRIGHT_YELLOW: Color = Color(11);
+    pub const BRIGHT_BLUE: Color = Color(12);
+    pub const BRIGHT_MAGENTA: Color = Color(13);
+    pub const BRIGHT_CYAN: Color = Color(14);
+    pub const BRIGHT_WHITE: Color = Color(15);
+    const NAMES: [&'static str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    pub fn from_name(name: &str) -> Option<Color> {
+        let (bright, name) = match name.strip_prefix("bright_") {
+            Some(name) => (8, name),
+            None => (0, name),
+        };
+        let i = Self::NAMES.iter().position(|&n| n == name)?;
+        Some(Color(i as u8 + bright))
+    }
+    pub fn name(self) -> Option<String> {
+        let name = Self::NAMES.get(usize::from(self.0 % 8))?;
+        match self.0 {
+            0..=7 => Some((*name).to_owned()),
+            8..=15 => Some(format!("bright_{}", name)),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.name() {
+            Some(name) => serializer.serialize_str(&name),
+            None => Err(S::Error::custom(format!("invalid color {}", self.0))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Color::from_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown color name {}", name)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn color_name() {
+        assert_eq!(Color::from_name("yellow"), Some(Color::YELLOW));
+        assert_eq!(Color::from_name("bright_white"), Some(Color::BRIGHT_WHITE));
+        assert_eq!(Color::from_name("purple"), None);
+        assert_eq!(Color::BRIGHT_RED.name().unwrap(), "bright_red");
+        let json = serde_json::to_string(&Color::CYAN).unwrap();
+        assert_eq!(serde_json::from_str::<Color>(&json).unwrap(), Color::CYAN);
+    }
+}
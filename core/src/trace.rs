@@ -0,0 +1,176 @@
+//! compact binary traces of observations, for making datasets from human plays
+//!
+//! A trace file starts with a header:
+//! `b"RGTR"`, format version (u8), width (u16), height (u16), length of status (u8).
+//! Then a record follows for each turn:
+//! length of the input JSON (u16), the input as JSON, status (u32 each), and
+//! symbols of the screen before the input (u8 each, `NO_SYMBOL` for unknown tiles).
+//! All integers are little endian.
+use crate::character::player::Status;
+use crate::error::*;
+use crate::input::InputCode;
+use crate::RunTime;
+use anyhow::{bail, Context};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"RGTR";
+pub const VERSION: u8 = 1;
+/// symbol written for tiles without symbols
+pub const NO_SYMBOL: u8 = 0xff;
+
+/// An observation and the input taken there
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceRecord {
+    pub input: InputCode,
+    /// same as `Status::to_vec`
+    pub status: Vec<u32>,
+    /// symbols of the screen, in row-major order
+    pub symbols: Vec<u8>,
+}
+
+impl TraceRecord {
+    /// take the observation of the current state
+    pub(crate) fn observe(runtime: &RunTime, input: InputCode) -> GameResult<Self> {
+        let (w, h) = runtime.screen_size();
+        let mut symbols = vec![0; w.0 as usize * h.0 as usize];
+        runtime.symbol_grid(&mut symbols)?;
+        Ok(TraceRecord {
+            input,
+            status: runtime.player_status().to_vec(),
+            symbols,
+        })
+    }
+}
+
+/// Writes a trace
+pub struct TraceWriter<W> {
+    writer: W,
+    status_len: usize,
+    screen_len: usize,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut writer: W, width: u16, height: u16) -> GameResult<Self> {
+        let status_len = Status::default().to_vec().len();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        writer.write_all(&[status_len as u8])?;
+        Ok(TraceWriter {
+            writer,
+            status_len,
+            screen_len: usize::from(width) * usize::from(height),
+        })
+    }
+    pub fn write(&mut self, record: &TraceRecord) -> GameResult<()> {
+        if record.status.len() != self.status_len || record.symbols.len() != self.screen_len {
+            bail!(ErrorKind::MaybeBug(
+                "TraceWriter::write invalid record size"
+            ));
+        }
+        let input = serde_json::to_vec(&record.input).context("TraceWriter::write")?;
+        let len = u16::try_from(input.len()).context("TraceWriter::write")?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&input)?;
+        for s in &record.status {
+            self.writer.write_all(&s.to_le_bytes())?;
+        }
+        self.writer.write_all(&record.symbols)?;
+        Ok(())
+    }
+    pub fn flush(&mut self) -> GameResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads a trace, as an iterator of records
+pub struct TraceReader<R> {
+    reader: R,
+    pub width: u16,
+    pub height: u16,
+    status_len: usize,
+}
+
+impl<R: Read> TraceReader<R> {
+    pub fn new(mut reader: R) -> GameResult<Self> {
+        let mut header = [0u8; 10];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            bail!(ErrorKind::InvalidSetting("not a trace file".into()));
+        }
+        if header[4] != VERSION {
+            bail!(ErrorKind::InvalidSetting(
+                format!("unsupported trace version {}", header[4]).into()
+            ));
+        }
+        Ok(TraceReader {
+            reader,
+            width: u16::from_le_bytes([header[5], header[6]]),
+            height: u16::from_le_bytes([header[7], header[8]]),
+            status_len: usize::from(header[9]),
+        })
+    }
+    fn read_record(&mut self) -> GameResult<Option<TraceRecord>> {
+        let mut len = [0u8; 2];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut input = vec![0u8; usize::from(u16::from_le_bytes(len))];
+        self.reader.read_exact(&mut input)?;
+        let input = serde_json::from_slice(&input).context("TraceReader::read_record")?;
+        let mut status = Vec::with_capacity(self.status_len);
+        for _ in 0..self.status_len {
+            let mut buf = [0u8; 4];
+            self.reader.read_exact(&mut buf)?;
+            status.push(u32::from_le_bytes(buf));
+        }
+        let mut symbols = vec![0u8; usize::from(self.width) * usize::from(self.height)];
+        self.reader.read_exact(&mut symbols)?;
+        Ok(Some(TraceRecord {
+            input,
+            status,
+            symbols,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for TraceReader<R> {
+    type Item = GameResult<TraceRecord>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::Action;
+    use crate::symbol::Symbol;
+    use crate::GameConfig;
+    #[test]
+    fn write_and_read() {
+        let mut runtime = GameConfig::default().build().unwrap();
+        let path = std::env::temp_dir().join("rogue-gym-trace-test.bin");
+        let file = std::fs::File::create(&path).unwrap();
+        runtime.start_trace(Box::new(file)).unwrap();
+        let inputs = vec![InputCode::Act(Action::Search); 3];
+        for &input in &inputs {
+            runtime.react_to_input(input).unwrap();
+        }
+        runtime.finish_trace().unwrap();
+        let buf = std::fs::read(&path).unwrap();
+        let reader = TraceReader::new(&buf[..]).unwrap();
+        let (w, h) = (reader.width, reader.height);
+        let records: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records.iter().map(|r| r.input).collect::<Vec<_>>(), inputs);
+        let player = Symbol::from_tile(b'@'.into()).unwrap().to_byte();
+        assert!(records.iter().all(|r| r.symbols.contains(&player)));
+        assert_eq!(records[0].symbols.len(), usize::from(w) * usize::from(h));
+    }
+}
@@ -1,4 +1,6 @@
 use crate::input::System;
+use crate::tile::Color;
+use std::collections::BTreeMap;
 
 /// A representation of Ui transition
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -47,3 +49,36 @@ pub enum MordalMsg {
     Cancel,
     None,
 }
+
+/// Configuration for screens, which doesn't affect the game itself
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ScreenConfig {
+    /// colors of tiles by their characters, like `{"@": "bright_white"}`,
+    /// which override the default ones
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub colors: BTreeMap<char, Color>,
+}
+
+impl ScreenConfig {
+    /// the color of each tile, including the default ones
+    pub fn color_table(&self) -> BTreeMap<char, Color> {
+        let mut table: BTreeMap<_, _> = ('A'..='Z').map(|c| (c, Color::RED)).collect();
+        table.extend(vec![
+            ('@', Color::BRIGHT_WHITE),
+            ('*', Color::YELLOW),
+            ('%', Color::BRIGHT_GREEN),
+            ('+', Color::YELLOW),
+            ('^', Color::MAGENTA),
+            ('!', Color::BRIGHT_MAGENTA),
+            ('?', Color::BRIGHT_CYAN),
+            (':', Color::GREEN),
+            (')', Color::CYAN),
+            (']', Color::CYAN),
+            ('=', Color::BRIGHT_YELLOW),
+            ('/', Color::BRIGHT_BLUE),
+            (',', Color::BRIGHT_YELLOW),
+        ]);
+        table.extend(self.colors.iter().map(|(&c, &color)| (c, color)));
+        table
+    }
+}
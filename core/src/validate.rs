@@ -0,0 +1,208 @@
+//! semantic validation of configurations, which reports all violations with their paths
+use crate::rng::{Parcent, Probability};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+
+/// A violation of a constraint in a configuration
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Violation {
+    /// JSON path of the field, like `item.weapon.weapons[9]`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// All violations found in a configuration
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Violations(pub Vec<Violation>);
+
+impl Violations {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, violation) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects violations while walking a configuration
+#[derive(Default)]
+pub(crate) struct Validator {
+    path: Vec<String>,
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+    fn path(&self) -> String {
+        let mut res = String::new();
+        for seg in &self.path {
+            if !res.is_empty() && !seg.starts_with('[') {
+                res.push('.');
+            }
+            res.push_str(seg);
+        }
+        res
+    }
+    /// validate the field `name` by `f`
+    pub(crate) fn field(&mut self, name: &str, f: impl FnOnce(&mut Self)) {
+        self.path.push(name.to_owned());
+        f(self);
+        self.path.pop();
+    }
+    /// validate the `i`-th element of the current array by `f`
+    pub(crate) fn element(&mut self, i: usize, f: impl FnOnce(&mut Self)) {
+        self.path.push(format!("[{}]", i));
+        f(self);
+        self.path.pop();
+    }
+    pub(crate) fn error(&mut self, message: impl Into<String>) {
+        let path = self.path();
+        self.violations.push(Violation {
+            path,
+            message: message.into(),
+        });
+    }
+    /// report `message` at the field `name` unless `ok`
+    pub(crate) fn check(&mut self, name: &str, ok: bool, message: &str) {
+        if !ok {
+            self.field(name, |v| v.error(message));
+        }
+    }
+    pub(crate) fn parcent(&mut self, name: &str, p: Parcent) {
+        if p.0 > 100 {
+            self.field(name, |v| {
+                v.error(format!("{} is not a percentage between 0 and 100", p.0))
+            });
+        }
+    }
+    pub(crate) fn probability(&mut self, name: &str, p: Probability) {
+        if !p.is_valid() {
+            self.field(name, |v| {
+                v.error(format!(
+                    "{} is not a percentage between 0 and 100",
+                    p.as_percent()
+                ))
+            });
+        }
+    }
+    /// check if `index` is an index of an array of `len` builtin presets
+    pub(crate) fn builtin(&mut self, index: usize, len: usize) {
+        if index >= len {
+            self.error(format!(
+                "builtin index {} is out of range, which should be less than {}",
+                index, len
+            ));
+        }
+    }
+    pub(crate) fn finish(self) -> Violations {
+        Violations(self.violations)
+    }
+}
+
+/// violations which make `value` fail to deserialize as `T`, and `T` deserialized
+/// without the broken fields if it can be.
+/// The path of each error is tracked by `serde_path_to_error`, and then the field is removed
+/// to find the next error.
+pub(crate) fn type_violations<T: DeserializeOwned>(value: &Value) -> (Violations, Option<T>) {
+    let mut value = value.clone();
+    let mut res: Vec<(Vec<Segment>, Violation)> = vec![];
+    loop {
+        let err = match serde_path_to_error::deserialize::<_, T>(&value) {
+            Ok(t) => return (Violations(res.into_iter().map(|r| r.1).collect()), Some(t)),
+            Err(e) => e,
+        };
+        let path = segments(err.path());
+        // a removed field may be required by its parent, which is already reported
+        if !res.iter().any(|(p, _)| p.starts_with(&path)) {
+            res.push((
+                path.clone(),
+                Violation {
+                    path: path_string(&path),
+                    message: err.into_inner().to_string(),
+                },
+            ));
+        }
+        // elements are removed with their array, so that indices in later paths don't change
+        match path.iter().rposition(|s| matches!(s, Segment::Key(_))) {
+            Some(i) => remove(&mut value, &path[..=i]),
+            None => return (Violations(res.into_iter().map(|r| r.1).collect()), None),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// convert a path tracked by `serde_path_to_error` into a path in the JSON value.
+/// Paths are cut at unknown segments, e.g. in flattened fields, so that the nearest known
+/// field is reported.
+fn segments(path: &serde_path_to_error::Path) -> Vec<Segment> {
+    use serde_path_to_error::Segment as S;
+    path.iter()
+        .map_while(|seg| match seg {
+            S::Map { key } => Some(Segment::Key(key.clone())),
+            S::Enum { variant } => Some(Segment::Key(variant.clone())),
+            S::Seq { index } => Some(Segment::Index(*index)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn path_string(path: &[Segment]) -> String {
+    let mut res = String::new();
+    for seg in path {
+        match seg {
+            Segment::Key(key) => {
+                if !res.is_empty() {
+                    res.push('.');
+                }
+                res.push_str(key);
+            }
+            Segment::Index(i) => res.push_str(&format!("[{}]", i)),
+        }
+    }
+    res
+}
+
+/// remove the field at `path`, which ends with a key
+fn remove(value: &mut Value, path: &[Segment]) {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+    let mut parent = value;
+    for seg in parents {
+        parent = match seg {
+            Segment::Key(key) => &mut parent[key.as_str()],
+            Segment::Index(i) => &mut parent[*i],
+        };
+    }
+    if let (Segment::Key(key), Value::Object(fields)) = (last, parent) {
+        fields.remove(key);
+    }
+}
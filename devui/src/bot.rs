@@ -0,0 +1,74 @@
+//! headless runs of baseline agents, which report score statistics
+use anyhow::{bail, Context};
+use rogue_gym_core::agent::{run_agent, Agent, GreedyAgent, RandomAgent, Trajectory};
+use rogue_gym_core::dataset::{record_agent, DatasetWriter};
+use rogue_gym_core::{error::GameResult, rng, GameConfig};
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Kinds of baseline agents
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BotKind {
+    Random,
+    Greedy,
+}
+
+impl BotKind {
+    pub fn from_name(name: &str) -> GameResult<Self> {
+        match name {
+            "random" => Ok(BotKind::Random),
+            "greedy" => Ok(BotKind::Greedy),
+            _ => bail!("Unknown bot kind {}, expected random or greedy", name),
+        }
+    }
+    fn agent(self, seed: u128) -> Box<dyn Agent> {
+        match self {
+            BotKind::Random => Box::new(RandomAgent::new(seed)),
+            BotKind::Greedy => Box::new(GreedyAgent::new()),
+        }
+    }
+}
+
+/// run `episodes` games by the bot, and returns lines of statistics.
+/// If `dataset` is given, all steps are written into it.
+pub fn run_bot(
+    config: &GameConfig,
+    kind: BotKind,
+    episodes: usize,
+    dataset: Option<&str>,
+) -> GameResult<Vec<String>> {
+    if episodes == 0 {
+        bail!("The number of episodes should be positive");
+    }
+    let seed = config.seed.unwrap_or_else(rng::gen_seed);
+    let mut agent = kind.agent(seed);
+    let trajectories = match dataset {
+        Some(path) => {
+            let (w, h) = config.clone().build()?.observation_size();
+            let file = File::create(path).context("Failed to create dataset file")?;
+            let mut writer = DatasetWriter::new(BufWriter::new(file), w as u16, h as u16)?;
+            let trajectories = record_agent(config, &mut *agent, episodes, &mut writer)?;
+            writer.finish()?;
+            trajectories
+        }
+        None => run_agent(config, &mut *agent, episodes)?,
+    };
+    let deaths = trajectories.iter().filter(|t| t.summary.is_dead()).count();
+    let mut res = vec![
+        format!("Episodes: {}", episodes),
+        format!("Deaths: {}", deaths),
+    ];
+    let stat = |name: &str, f: &dyn Fn(&Trajectory) -> f32| {
+        let values: Vec<_> = trajectories.iter().map(f).collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        format!("{}: mean {:.2}, min {}, max {}", name, mean, min, max)
+    };
+    res.push(stat("Reward", &|t| t.total_reward()));
+    res.push(stat("Gold", &|t| t.summary.gold as f32));
+    res.push(stat("Deepest level", &|t| t.summary.deepest_level as f32));
+    res.push(stat("Kills", &|t| t.summary.kills as f32));
+    res.push(stat("Turns", &|t| t.summary.turns as f32));
+    Ok(res)
+}
@@ -0,0 +1,129 @@
+//! command mode started by `:`, to change settings in the middle of a game
+//! (e.g., `:set messages quiet`, `:set screen.colors.@ cyan`)
+//! or to give nicknames to unidentified items (e.g., `:call c healing?`)
+use anyhow::bail;
+use rogue_gym_core::error::GameResult;
+use termion::event::Key;
+
+/// A command line being typed
+#[derive(Clone, Debug, Default)]
+pub struct CommandLine {
+    pub text: String,
+}
+
+pub enum CommandMsg {
+    Continue,
+    Cancel,
+    Confirm(String),
+}
+
+impl CommandLine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn process(&mut self, key: Key) -> CommandMsg {
+        match key {
+            Key::Char('\n') => CommandMsg::Confirm(std::mem::take(&mut self.text)),
+            Key::Esc => CommandMsg::Cancel,
+            Key::Backspace => match self.text.pop() {
+                Some(_) => CommandMsg::Continue,
+                None => CommandMsg::Cancel,
+            },
+            Key::Char(c) => {
+                self.text.push(c);
+                CommandMsg::Continue
+            }
+            _ => CommandMsg::Continue,
+        }
+    }
+}
+
+/// A command typed in the command mode
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// `set <name> <value>`, where name is one of `messages`, `log`, `interval`(only in replays),
+    /// or a path of a mutable setting in the config like `screen.colors.@`
+    Set { name: String, value: String },
+    /// `call <letter> <name>`, to give a nickname to an unidentified item
+    Call { letter: char, name: String },
+}
+
+impl Command {
+    pub fn parse(line: &str) -> GameResult<Self> {
+        let mut words = line.trim().splitn(2, ' ');
+        match words.next() {
+            Some("set") => {
+                let mut args = words.next().unwrap_or("").trim().splitn(2, ' ');
+                let name = args.next().unwrap_or("");
+                let value = args.next().unwrap_or("").trim();
+                if name.is_empty() || value.is_empty() {
+                    bail!("Usage: set <name> <value>");
+                }
+                Ok(Command::Set {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                })
+            }
+            Some("call") => {
+                let mut args = words.next().unwrap_or("").trim().splitn(2, ' ');
+                let mut letter = args.next().unwrap_or("").chars();
+                let name = args.next().unwrap_or("").trim();
+                match (letter.next(), letter.next()) {
+                    (Some(letter), None) => Ok(Command::Call {
+                        letter,
+                        name: name.to_owned(),
+                    }),
+                    _ => bail!("Usage: call <letter> <name>"),
+                }
+            }
+            _ => bail!(
+                "Unknown command {}, only set and call are supported",
+                line.trim()
+            ),
+        }
+    }
+}
+
+/// How many messages are displayed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verbosity {
+    /// all messages are displayed
+    Verbose,
+    /// messages are only kept in the message history
+    Quiet,
+}
+
+impl Verbosity {
+    pub fn from_name(name: &str) -> GameResult<Self> {
+        match name {
+            "verbose" => Ok(Verbosity::Verbose),
+            "quiet" => Ok(Verbosity::Quiet),
+            _ => bail!("Unknown verbosity {}, expected verbose or quiet", name),
+        }
+    }
+}
+
+pub fn convert_log_level(s: &str) -> Option<log::LevelFilter> {
+    use log::LevelFilter::*;
+    let s = s.to_lowercase();
+    match &*s {
+        "off" | "o" => Some(Off),
+        "error" | "e" => Some(Error),
+        "warn" | "w" => Some(Warn),
+        "info" | "i" => Some(Info),
+        "debug" | "d" => Some(Debug),
+        "trace" | "t" => Some(Trace),
+        _ => None,
+    }
+}
+
+/// change the level of logging, which is enabled by `--log`
+pub fn set_log_level(level: &str) -> GameResult<()> {
+    match convert_log_level(level) {
+        Some(level) => {
+            log::set_max_level(level);
+            Ok(())
+        }
+        None => bail!("Unknown log level {}", level),
+    }
+}
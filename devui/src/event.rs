@@ -0,0 +1,139 @@
+//! events from the terminal
+use anyhow::Context;
+use rogue_gym_core::error::GameResult;
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::terminal_size;
+
+/// interval of `Event::Tick`, which is also how often the terminal size is checked
+pub const TICK_MS: u64 = 30;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event {
+    Key(Key),
+    /// the terminal is resized to (width, height)
+    Resize(u16, u16),
+    /// no key is pressed in `TICK_MS`, which is used to run animations between keys
+    Tick,
+}
+
+/// Watches the terminal size.
+/// Since termion doesn't notify us of SIGWINCH, the size is polled.
+#[derive(Clone, Copy, Debug)]
+pub struct SizeWatcher {
+    last: Option<(u16, u16)>,
+}
+
+impl SizeWatcher {
+    pub fn new() -> Self {
+        SizeWatcher {
+            last: terminal_size().ok(),
+        }
+    }
+    /// returns the new size if the terminal is resized
+    pub fn poll(&mut self) -> Option<(u16, u16)> {
+        self.update(terminal_size().ok()?)
+    }
+    /// returns `size` if it differs from the last one
+    fn update(&mut self, size: (u16, u16)) -> Option<(u16, u16)> {
+        if self.last == Some(size) {
+            return None;
+        }
+        self.last = Some(size);
+        Some(size)
+    }
+}
+
+impl Default for SizeWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator of events from the terminal, which never blocks longer than `TICK_MS`
+pub struct Events {
+    keys: mpsc::Receiver<io::Result<Key>>,
+    watcher: SizeWatcher,
+}
+
+impl Events {
+    /// spawn a thread to read keys from stdin
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for key in io::stdin().keys() {
+                if tx.send(key).is_err() {
+                    break;
+                }
+            }
+        });
+        Events {
+            keys: rx,
+            watcher: SizeWatcher::new(),
+        }
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Events {
+    type Item = GameResult<Event>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((w, h)) = self.watcher.poll() {
+            return Some(Ok(Event::Resize(w, h)));
+        }
+        match self.keys.recv_timeout(Duration::from_millis(TICK_MS)) {
+            Ok(key) => Some(key.map(Event::Key).context("in Events::next")),
+            Err(mpsc::RecvTimeoutError::Timeout) => Some(Ok(Event::Tick)),
+            Err(mpsc::RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn size_watcher() {
+        let mut watcher = SizeWatcher {
+            last: Some((80, 24)),
+        };
+        assert_eq!(watcher.update((80, 24)), None);
+        assert_eq!(watcher.update((60, 20)), Some((60, 20)));
+        assert_eq!(watcher.update((60, 20)), None);
+        // the first size is always reported if it's unknown
+        let mut watcher = SizeWatcher { last: None };
+        assert_eq!(watcher.update((80, 24)), Some((80, 24)));
+    }
+
+    #[test]
+    fn events_tick_without_keys() {
+        let (tx, rx) = mpsc::channel();
+        let mut events = Events {
+            keys: rx,
+            watcher: SizeWatcher::new(),
+        };
+        let start = std::time::Instant::now();
+        assert_eq!(events.next().unwrap().unwrap(), Event::Tick);
+        assert!(start.elapsed() >= Duration::from_millis(TICK_MS));
+        tx.send(Ok(Key::Char('h'))).unwrap();
+        tx.send(Ok(Key::Esc)).unwrap();
+        assert_eq!(events.next().unwrap().unwrap(), Event::Key(Key::Char('h')));
+        assert_eq!(events.next().unwrap().unwrap(), Event::Key(Key::Esc));
+        tx.send(Err(io::Error::new(io::ErrorKind::Other, "broken")))
+            .unwrap();
+        assert!(events.next().unwrap().is_err());
+        // stdin is closed
+        drop(tx);
+        assert!(events.next().is_none());
+    }
+}
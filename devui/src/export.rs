@@ -0,0 +1,246 @@
+//! headless rendering of replays, into asciicast files or plain frame dumps
+use anyhow::{bail, Context};
+use rogue_gym_core::{
+    dungeon::{Coord, X, Y},
+    error::GameResult,
+    input::InputCode,
+    tile::{Color, Tile},
+    GameConfig,
+};
+use rogue_gym_uilib::{process_reaction, Cell, CellBuffer, Screen, Transition, REMEMBERED_COLOR};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Output format of `export_replay`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// asciicast v2, which can be played by asciinema
+    Asciicast,
+    /// plain text frames, separated by headers with turn numbers
+    Frames,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> GameResult<Self> {
+        match name {
+            "asciicast" => Ok(ExportFormat::Asciicast),
+            "frames" => Ok(ExportFormat::Frames),
+            _ => bail!(
+                "Unknown export format {}, expected asciicast or frames",
+                name
+            ),
+        }
+    }
+}
+
+/// Screen drawn into a cell grid instead of a terminal
+struct GridScreen {
+    buffer: CellBuffer,
+    colors: BTreeMap<char, Color>,
+}
+
+impl GridScreen {
+    fn new(width: i32, height: i32, colors: BTreeMap<char, Color>) -> Self {
+        GridScreen {
+            buffer: CellBuffer::new(width, height),
+            colors,
+        }
+    }
+    /// rows of the screen, with ANSI color codes if `color` is true
+    fn rows(&self, color: bool) -> Vec<String> {
+        let (w, h) = self.buffer.size();
+        (0..h)
+            .map(|y| {
+                let mut row = String::new();
+                for x in 0..w {
+                    let cell = self.buffer.get(Coord::new(x, y)).unwrap_or(Cell::BLANK);
+                    match cell.color.filter(|_| color) {
+                        Some(Color(ansi)) => {
+                            row.push_str(&format!("\x1b[38;5;{}m{}\x1b[39m", ansi, cell.ch))
+                        }
+                        None => row.push(cell.ch),
+                    }
+                }
+                row.trim_end().to_owned()
+            })
+            .collect()
+    }
+}
+
+impl Screen for GridScreen {
+    fn width(&self) -> X {
+        X(self.buffer.size().0)
+    }
+    fn height(&self) -> Y {
+        Y(self.buffer.size().1)
+    }
+    fn clear_line(&mut self, row: Y) -> GameResult<()> {
+        self.buffer.clear_line(row.0);
+        Ok(())
+    }
+    fn write_char(&mut self, pos: Coord, c: char) -> GameResult<()> {
+        self.buffer.set(pos, Cell::new(c));
+        Ok(())
+    }
+    fn write_tile(&mut self, pos: Coord, t: Tile) -> GameResult<()> {
+        let c = t.to_char();
+        let color = self.colors.get(&c).cloned();
+        self.buffer.set(pos, Cell { ch: c, color });
+        Ok(())
+    }
+    fn write_remembered(&mut self, pos: Coord, t: Tile) -> GameResult<()> {
+        let color = Some(REMEMBERED_COLOR).filter(|_| !self.colors.is_empty());
+        self.buffer.set(
+            pos,
+            Cell {
+                ch: t.to_char(),
+                color,
+            },
+        );
+        Ok(())
+    }
+    fn write_str<S: AsRef<str>>(&mut self, start: Coord, s: S) -> GameResult<()> {
+        self.buffer.clear_from(start);
+        self.buffer.write_str(start, s.as_ref());
+        Ok(())
+    }
+}
+
+/// replay `inputs` without a terminal, and write a frame per input to `out`
+pub fn export_replay(
+    config: GameConfig,
+    inputs: &[InputCode],
+    format: ExportFormat,
+    interval_ms: u64,
+    mut out: impl Write,
+) -> GameResult<()> {
+    let colors = config.screen.color_table();
+    let mut runtime = config.build()?;
+    let (w, h) = runtime.screen_size();
+    let mut screen = GridScreen::new(w.0, h.0, colors);
+    screen.dungeon(&mut runtime)?;
+    screen.status(&runtime.player_status())?;
+    if format == ExportFormat::Asciicast {
+        let header = json!({
+            "version": 2,
+            "width": w.0,
+            "height": h.0,
+            "title": "rogue-gym replay",
+        });
+        writeln!(out, "{}", header).context("Failed to write asciicast header")?;
+    }
+    let mut write_frame = |screen: &GridScreen, turn: usize| -> GameResult<()> {
+        match format {
+            ExportFormat::Asciicast => {
+                let time = (turn as u64 * interval_ms) as f64 / 1000.0;
+                let data = format!("\x1b[H\x1b[2J{}", screen.rows(true).join("\r\n"));
+                writeln!(out, "{}", json!([time, "o", data]))
+            }
+            ExportFormat::Frames => {
+                writeln!(
+                    out,
+                    "--- turn {} ---\n{}",
+                    turn,
+                    screen.rows(false).join("\n")
+                )
+            }
+        }
+        .context("Failed to write exported frame")
+    };
+    write_frame(&screen, 0)?;
+    'outer: for (i, &input) in inputs.iter().enumerate() {
+        let res = runtime.replay_input(input)?;
+        for reaction in res {
+            let result = process_reaction(&mut screen, &mut runtime, reaction)
+                .context("in export_replay")?;
+            if let Transition::Exit = result {
+                write_frame(&screen, i + 1)?;
+                break 'outer;
+            }
+        }
+        write_frame(&screen, i + 1)?;
+    }
+    out.flush().context("Failed to write exported frame")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rogue_gym_core::character::Action;
+    use rogue_gym_core::dungeon::Direction;
+    use serde_json::Value;
+
+    fn config() -> GameConfig {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config
+    }
+
+    fn inputs() -> Vec<InputCode> {
+        [Direction::Left, Direction::Down, Direction::Right]
+            .iter()
+            .map(|&d| InputCode::Act(Action::Move(d)))
+            .collect()
+    }
+
+    #[test]
+    fn asciicast() {
+        let mut out = vec![];
+        export_replay(config(), &inputs(), ExportFormat::Asciicast, 200, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<Value> = out
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let (w, h) = config().build().unwrap().screen_size();
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], w.0);
+        assert_eq!(lines[0]["height"], h.0);
+        // a frame for the start and each input
+        assert_eq!(lines.len(), 1 + 4);
+        for (turn, frame) in lines[1..].iter().enumerate() {
+            assert_eq!(frame[0].as_f64().unwrap(), (turn * 200) as f64 / 1000.0);
+            assert_eq!(frame[1], "o");
+            let data = frame[2].as_str().unwrap();
+            assert!(data.starts_with("\x1b[H\x1b[2J"));
+            assert_eq!(data.matches("\r\n").count() as i32, h.0 - 1);
+            assert!(data.contains('@'));
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let mut out = vec![];
+        export_replay(config(), &inputs(), ExportFormat::Frames, 200, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let (_, h) = config().build().unwrap().screen_size();
+        let headers: Vec<_> = out.lines().filter(|l| l.starts_with("--- turn")).collect();
+        assert_eq!(
+            headers,
+            [
+                "--- turn 0 ---",
+                "--- turn 1 ---",
+                "--- turn 2 ---",
+                "--- turn 3 ---"
+            ]
+        );
+        assert_eq!(out.lines().count() as i32, 4 * (h.0 + 1));
+        // no colors in plain frames
+        assert!(!out.contains('\x1b'));
+        assert_eq!(out.matches('@').count(), 4);
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!(
+            ExportFormat::from_name("asciicast").unwrap(),
+            ExportFormat::Asciicast
+        );
+        assert_eq!(
+            ExportFormat::from_name("frames").unwrap(),
+            ExportFormat::Frames
+        );
+        assert!(ExportFormat::from_name("gif").is_err());
+    }
+}
@@ -1,12 +1,28 @@
 #[macro_use]
 extern crate log;
 
+pub mod bot;
+pub mod command;
+pub mod event;
+pub mod export;
+pub mod scores;
 pub mod screen;
 use anyhow::{bail, Context};
-use rogue_gym_core::{error::GameResult, input::InputCode, GameConfig, RunTime};
-use rogue_gym_uilib::{process_reaction, Screen, Transition};
+use command::{Command, CommandLine, CommandMsg, Verbosity};
+use event::{Event, Events, SizeWatcher};
+use rogue_gym_core::character::Action;
+use rogue_gym_core::dataset::{record_replay, DatasetWriter};
+use rogue_gym_core::input::{InputCode, KeyMap, System};
+use rogue_gym_core::ui::UiState;
+use rogue_gym_core::{
+    error::GameResult, read_file, snapshot::Snapshot, GameConfig, Reaction, RunTime,
+};
+use rogue_gym_uilib::{process_reaction, Animation, Screen, Targeting, TargetingMsg, Transition};
 use screen::{RawTerm, TermScreen};
-use std::io;
+use std::cmp;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
@@ -16,35 +32,330 @@ use termion::input::TermRead;
 fn setup_screen(
     config: GameConfig,
     is_default: bool,
+    resume: Option<&Snapshot>,
+    options: &PlayOptions,
 ) -> GameResult<(TermScreen<RawTerm>, RunTime)> {
     let mut screen = TermScreen::from_raw(config.width, config.height)?;
+    if !options.no_color {
+        screen.set_colors(config.screen.color_table());
+    }
+    if options.direct_draw {
+        screen.set_buffered(false);
+    }
     screen.welcome()?;
     if is_default {
         screen.default_config()?;
     }
-    let mut runtime = config.build()?;
+    let mut runtime = match resume {
+        Some(snapshot) => RunTime::resume(snapshot)?,
+        None => config.build()?,
+    };
     thread::sleep(Duration::from_secs(1));
     screen.dungeon(&mut runtime)?;
     screen.status(&runtime.player_status())?;
     Ok((screen, runtime))
 }
 
+/// Options of `play_game_with`
+#[derive(Clone, Debug, Default)]
+pub struct PlayOptions {
+    /// file to record observations every turn, in the format of `rogue_gym_core::trace`
+    pub trace: Option<String>,
+    /// file to write the play as a dataset for imitation learning, in the format of
+    /// `rogue_gym_core::dataset`, which is written on exit
+    pub dataset: Option<String>,
+    /// snapshot file to save the game, which is also a replay file.
+    /// It's written on exit, by `S` key, and periodically if `autosave` is not 0.
+    pub save: Option<String>,
+    /// if not 0, inputs are flushed to `save` every `autosave` inputs,
+    /// so that a crash doesn't lose the whole play
+    pub autosave: usize,
+    /// snapshot file to resume the game from
+    pub resume: Option<String>,
+    /// draw tiles without colors
+    pub no_color: bool,
+    /// write to the terminal directly, instead of updating only changed cells
+    pub direct_draw: bool,
+}
+
+/// keys used by devui itself, which can't be bound to game inputs
+pub const RESERVED_KEYS: [(Key, &str); 5] = [
+    (Key::Ctrl('p'), "show message history"),
+    (Key::Char('t'), "throw a missile"),
+    (Key::Char('M'), "show the whole map"),
+    (
+        Key::Char(':'),
+        "run a command, like :set messages quiet or :call c healing?",
+    ),
+    (Key::Char('?'), "show this help"),
+];
+
+/// check if `keymap` doesn't conflict with keys used by devui
+pub fn check_keymap(keymap: &KeyMap) -> GameResult<()> {
+    for &(key, usage) in &RESERVED_KEYS {
+        if keymap.get(key.into()).is_some() {
+            bail!("{:?} is reserved to {}, and can't be bound", key, usage);
+        }
+    }
+    Ok(())
+}
+
+const HELP_TITLE: &str = "-- Commands --";
+
+/// symbol of the missile thrown by the player in animations
+const MISSILE: u8 = b')';
+
+/// overlays shown over the dungeon until any key is pressed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Overlay {
+    Help,
+    Map,
+}
+
+impl Overlay {
+    fn draw(self, screen: &mut TermScreen<RawTerm>, runtime: &mut RunTime) -> GameResult<()> {
+        match self {
+            Overlay::Help => screen.overlay(HELP_TITLE, &help_lines(&runtime.keymap)),
+            Overlay::Map => screen.map_overview(runtime),
+        }
+    }
+}
+
+/// lines of the help screen, generated from the keymap
+fn help_lines(keymap: &KeyMap) -> Vec<String> {
+    let mut commands: Vec<_> = keymap
+        .iter()
+        .map(|(key, code)| (code.description(), key.to_string()))
+        .collect();
+    for &(key, usage) in &RESERVED_KEYS {
+        let key = rogue_gym_core::input::Key::from(key);
+        commands.push((usage.to_owned(), key.to_string()));
+    }
+    commands.sort();
+    commands
+        .into_iter()
+        .map(|(desc, key)| format!("{:>9}: {}", key, desc))
+        .collect()
+}
+
+/// write the snapshot via a temporary file, not to break the old one on crash
+fn save_snapshot(runtime: &RunTime, config: &GameConfig, path: &str) -> GameResult<()> {
+    let json = runtime.snapshot(config.clone())?.to_json()?;
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, json.as_bytes()).context("Failed to write snapshot file")?;
+    fs::rename(&tmp, path).context("Failed to write snapshot file")?;
+    Ok(())
+}
+
+/// save a snapshot if `options.autosave` inputs are made since `last_saved`
+fn autosave(
+    runtime: &RunTime,
+    config: &GameConfig,
+    options: &PlayOptions,
+    last_saved: &mut usize,
+) -> GameResult<bool> {
+    let save = match options.save.as_ref() {
+        Some(save) if options.autosave > 0 => save,
+        _ => return Ok(false),
+    };
+    let inputs = runtime.saved_inputs().len();
+    if inputs < *last_saved + options.autosave {
+        return Ok(false);
+    }
+    save_snapshot(runtime, config, save)?;
+    *last_saved = inputs;
+    Ok(true)
+}
+
+/// replay the whole play and record it as a dataset
+fn save_dataset(runtime: &RunTime, config: &GameConfig, path: &str) -> GameResult<()> {
+    let replay = runtime.replay_file(config.clone());
+    let (w, h) = runtime.observation_size();
+    let file = File::create(path).context("Failed to create dataset file")?;
+    let mut writer = DatasetWriter::new(BufWriter::new(file), w as u16, h as u16)?;
+    record_replay(&replay, &mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
+
 pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
+    play_game_with(config, is_default, &PlayOptions::default())
+}
+
+pub fn play_game_with(
+    config: GameConfig,
+    is_default: bool,
+    options: &PlayOptions,
+) -> GameResult<RunTime> {
+    let resume = match options.resume.as_ref() {
+        Some(path) => {
+            let json = read_file(path).context("Failed to read snapshot file")?;
+            Some(Snapshot::from_json(&json)?)
+        }
+        None => None,
+    };
+    // the snapshot has its own configuration
+    let (mut config, is_default) = match resume.as_ref() {
+        Some(snapshot) => (snapshot.replay.config.clone(), false),
+        None => (config, is_default),
+    };
     debug!("devui::play_game config: {:?}", config);
-    let (mut screen, mut runtime) = setup_screen(config, is_default)?;
-    let stdin = io::stdin();
+    check_keymap(&config.keymap)?;
+    let (mut screen, mut runtime) =
+        setup_screen(config.clone(), is_default, resume.as_ref(), options)?;
+    let code = runtime.dungeon_code(&config)?;
+    screen.message(format!("Dungeon code: {}", code))?;
+    if let Some(trace) = options.trace.as_ref() {
+        let file = File::create(trace).context("Failed to create trace file")?;
+        runtime.start_trace(Box::new(BufWriter::new(file)))?;
+    }
     // let's receive keyboard inputs(our main loop)
     let mut pending = false;
-    'outer: for keys in stdin.keys() {
+    let mut last_saved = runtime.saved_inputs().len();
+    // scroll amount of the message history pane, if it's open
+    let mut history: Option<usize> = None;
+    // the direction being chosen in the targeting mode, if the player is throwing something
+    let mut targeting: Option<Targeting> = None;
+    // the overlay shown over the dungeon, which is closed by any key
+    let mut overlay: Option<Overlay> = None;
+    // animations drawn between keys, like flying missiles
+    let mut animation = Animation::new();
+    // the command being typed in the command mode, started by ':'
+    let mut command: Option<CommandLine> = None;
+    'outer: for event in Events::new() {
+        let key = match event.context("in play_game")? {
+            Event::Key(key) => key,
+            Event::Tick => {
+                if let Some(frame) = animation.next_frame() {
+                    screen.animation_frame(&mut runtime, &frame)?;
+                }
+                continue;
+            }
+            Event::Resize(w, h) => {
+                screen.resize(w, h)?;
+                redraw(&mut screen, &mut runtime)?;
+                if let Some(scroll) = history {
+                    history = Some(screen.message_history(scroll)?);
+                } else if let Some(o) = overlay {
+                    o.draw(&mut screen, &mut runtime)?;
+                } else if let Some(t) = targeting {
+                    let path = runtime.missile_path(t.direction);
+                    screen.targeting(&mut runtime, &path)?;
+                }
+                continue;
+            }
+        };
+        // a key skips the animation
+        if !animation.is_empty() {
+            animation.clear();
+            screen.dungeon(&mut runtime)?;
+        }
         screen.clear_notification()?;
-        let key = keys.context("in play_game")?;
+        if overlay.is_some() {
+            overlay = None;
+            screen.clear_dungeon()?;
+            screen.clear_notification()?;
+            redraw(&mut screen, &mut runtime)?;
+            continue;
+        }
+        if let Some(line) = command.as_mut() {
+            match line.process(key) {
+                CommandMsg::Continue => screen.message(format!(":{}", line.text))?,
+                CommandMsg::Cancel => command = None,
+                CommandMsg::Confirm(text) => {
+                    command = None;
+                    let msg = run_command(&text, &mut screen, &mut runtime, &mut config, options)
+                        .unwrap_or_else(|e| format!("{}", e));
+                    screen.message(msg)?;
+                }
+            }
+            continue;
+        }
+        if let Some(scroll) = history {
+            match key {
+                Key::Up | Key::Char('k') => history = Some(screen.message_history(scroll + 1)?),
+                Key::Down | Key::Char('j') => {
+                    history = Some(screen.message_history(scroll.saturating_sub(1))?)
+                }
+                Key::Ctrl('p') | Key::Esc | Key::Char('q') => {
+                    history = None;
+                    screen.clear_dungeon()?;
+                    screen.clear_notification()?;
+                    screen.dungeon(&mut runtime)?;
+                    screen.status(&runtime.player_status())?;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if key == Key::Ctrl('p') && !pending && *runtime.ui() == UiState::Dungeon {
+            history = Some(screen.message_history(0)?);
+            continue;
+        }
+        if key == Key::Char('?') && !pending && *runtime.ui() == UiState::Dungeon {
+            overlay = Some(Overlay::Help);
+            Overlay::Help.draw(&mut screen, &mut runtime)?;
+            continue;
+        }
+        if key == Key::Char('M') && !pending && *runtime.ui() == UiState::Dungeon {
+            overlay = Some(Overlay::Map);
+            Overlay::Map.draw(&mut screen, &mut runtime)?;
+            continue;
+        }
+        if key == Key::Char(':') && !pending && *runtime.ui() == UiState::Dungeon {
+            command = Some(CommandLine::new());
+            screen.message(":")?;
+            continue;
+        }
         if pending {
             if runtime.is_cancel(key.into())? {
                 pending = screen.display_msg()?;
             }
             continue;
         }
-        let res = runtime.react_to_key(key.into());
+        if runtime.keymap.get(key.into()) == Some(InputCode::Sys(System::Save)) {
+            match options.save.as_ref() {
+                Some(save) => {
+                    save_snapshot(&runtime, &config, save)?;
+                    last_saved = runtime.saved_inputs().len();
+                    screen.message(format!("Saved to {}", save))?;
+                }
+                None => screen.message("Use --save option to save the game")?,
+            }
+            continue;
+        }
+        let res = if let Some(t) = targeting.as_mut() {
+            let input = match key {
+                Key::Char('\n') => Some(InputCode::Sys(System::Enter)),
+                _ => runtime.keymap.get(key.into()),
+            };
+            match t.process(input) {
+                TargetingMsg::Continue => {
+                    let path = runtime.missile_path(t.direction);
+                    screen.targeting(&mut runtime, &path)?;
+                    continue;
+                }
+                TargetingMsg::Cancel => {
+                    targeting = None;
+                    screen.clear_notification()?;
+                    screen.dungeon(&mut runtime)?;
+                    continue;
+                }
+                TargetingMsg::Confirm(direction) => {
+                    targeting = None;
+                    screen.clear_notification()?;
+                    screen.dungeon(&mut runtime)?;
+                    animation.missile(&runtime.missile_path(direction), MISSILE.into());
+                    runtime.react_to_input(InputCode::Act(Action::Throw(direction)))
+                }
+            }
+        } else if key == Key::Char('t') && *runtime.ui() == UiState::Dungeon {
+            targeting = Some(Targeting::new());
+            screen.targeting(&mut runtime, &[])?;
+            continue;
+        } else {
+            runtime.react_to_key(key.into())
+        };
         let res = match res {
             Ok(r) => r,
             Err(e) => {
@@ -53,6 +364,7 @@ pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
                 continue;
             }
         };
+        animation.add_reactions(&res);
         for reaction in res {
             let result =
                 process_reaction(&mut screen, &mut runtime, reaction).context("in play_game")?;
@@ -62,11 +374,71 @@ pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
             }
         }
         pending = screen.display_msg()?;
+        autosave(&runtime, &config, options, &mut last_saved)?;
     }
     screen.clear_screen()?;
+    runtime.finish_trace()?;
+    if let Some(save) = options.save.as_ref() {
+        save_snapshot(&runtime, &config, save)?;
+    }
+    if let Some(dataset) = options.dataset.as_ref() {
+        save_dataset(&runtime, &config, dataset)?;
+    }
     Ok(runtime)
 }
 
+/// run a command typed in the command mode, and returns the message to show
+fn run_command(
+    line: &str,
+    screen: &mut TermScreen<RawTerm>,
+    runtime: &mut RunTime,
+    config: &mut GameConfig,
+    options: &PlayOptions,
+) -> GameResult<String> {
+    let (name, value) = match Command::parse(line)? {
+        Command::Set { name, value } => (name, value),
+        Command::Call { letter, name } => {
+            runtime.call_item(letter, &name)?;
+            if name.is_empty() {
+                return Ok(format!("{} is no longer called anything", letter));
+            }
+            return Ok(format!("{} is called {}", letter, name));
+        }
+    };
+    match &*name {
+        "messages" => screen.set_verbosity(Verbosity::from_name(&value)?),
+        "log" => command::set_log_level(&value)?,
+        "interval" => bail!("interval can be set only in replays"),
+        _ => {
+            let mut new_config = config.clone();
+            new_config.set(&name, &value)?;
+            check_keymap(&new_config.keymap)?;
+            runtime.apply_settings(&new_config);
+            if !options.no_color {
+                screen.set_colors(new_config.screen.color_table());
+            }
+            *config = new_config;
+            redraw(screen, runtime)?;
+        }
+    }
+    Ok(format!("{} = {}", name, value))
+}
+
+/// redraw the whole screen, for the current ui state
+fn redraw(screen: &mut TermScreen<RawTerm>, runtime: &mut RunTime) -> GameResult<()> {
+    screen.dungeon(runtime)?;
+    screen.status(&runtime.player_status())?;
+    match runtime.ui().clone() {
+        UiState::Dungeon => Ok(()),
+        UiState::Mordal(kind) => process_reaction(
+            screen,
+            runtime,
+            Reaction::UiTransition(UiState::Mordal(kind)),
+        )
+        .map(|_| ()),
+    }
+}
+
 pub fn show_replay(config: GameConfig, replay: Vec<InputCode>, interval_ms: u64) -> GameResult<()> {
     debug!("devui::show_replay config: {:?}", config);
     let (tx, rx) = mpsc::channel();
@@ -77,19 +449,63 @@ pub fn show_replay(config: GameConfig, replay: Vec<InputCode>, interval_ms: u64)
         }
     });
     let stdin = io::stdin();
+    // the turn number being typed after 'g'
+    let mut goto: Option<usize> = None;
+    // the command being typed after ':'
+    let mut command: Option<CommandLine> = None;
     for key in stdin.keys() {
         let key = key.context("in show_replay")?;
-        let mut end = false;
-        let res = match key {
-            Key::Char('E') | Key::Char('Q') | Key::Char('e') | Key::Char('q') | Key::Esc => {
-                end = true;
-                tx.send(ReplayInst::End)
-            }
-            Key::Char('p') => tx.send(ReplayInst::Pause),
-            Key::Char('s') => tx.send(ReplayInst::Start),
-            _ => continue,
+        if let Some(line) = command.as_mut() {
+            let text = match line.process(key) {
+                CommandMsg::Continue => continue,
+                CommandMsg::Cancel => {
+                    command = None;
+                    continue;
+                }
+                CommandMsg::Confirm(text) => text,
+            };
+            command = None;
+            let inst = match replay_command(&text) {
+                Ok(inst) => inst,
+                Err(e) => ReplayInst::Notify(format!("{}", e)),
+            };
+            if let Err(e) = tx.send(inst) {
+                eprintln!("Error in viewer: {}", e);
+            }
+            continue;
+        }
+        if let Some(turn) = goto {
+            match key {
+                Key::Char(c) if c.is_ascii_digit() => {
+                    let digit = c.to_digit(10).unwrap() as usize;
+                    goto = Some(turn.saturating_mul(10).saturating_add(digit));
+                }
+                Key::Char('\n') => {
+                    goto = None;
+                    if let Err(e) = tx.send(ReplayInst::Jump(turn)) {
+                        eprintln!("Error in viewer: {}", e);
+                    }
+                }
+                _ => goto = None,
+            }
+            continue;
+        }
+        let inst = match key {
+            Key::Char('g') => {
+                goto = Some(0);
+                continue;
+            }
+            Key::Char(':') => {
+                command = Some(CommandLine::new());
+                continue;
+            }
+            key => match replay_key(key) {
+                Some(inst) => inst,
+                None => continue,
+            },
         };
-        if let Err(e) = res {
+        let end = matches!(inst, ReplayInst::End);
+        if let Err(e) = tx.send(inst) {
             eprintln!("Error in viewer: {}", e);
         }
         if end {
@@ -100,60 +516,452 @@ pub fn show_replay(config: GameConfig, replay: Vec<InputCode>, interval_ms: u64)
     Ok(())
 }
 
-#[derive(Clone, Copy, Debug)]
+/// the instruction bound to the key in the replay viewer
+fn replay_key(key: Key) -> Option<ReplayInst> {
+    let inst = match key {
+        Key::Char('E') | Key::Char('Q') | Key::Char('e') | Key::Char('q') | Key::Esc => {
+            ReplayInst::End
+        }
+        Key::Char('p') => ReplayInst::Pause,
+        Key::Char('s') => ReplayInst::Start,
+        Key::Char(' ') => ReplayInst::TogglePause,
+        Key::Char('+') => ReplayInst::Faster,
+        Key::Char('-') => ReplayInst::Slower,
+        Key::Right => ReplayInst::Forward,
+        Key::Left => ReplayInst::Backward,
+        _ => return None,
+    };
+    Some(inst)
+}
+
+/// run a command typed in the replay viewer
+fn replay_command(line: &str) -> GameResult<ReplayInst> {
+    let (name, value) = match Command::parse(line)? {
+        Command::Set { name, value } => (name, value),
+        Command::Call { .. } => bail!("call can't be used in replays"),
+    };
+    match &*name {
+        "interval" => {
+            let interval = value.parse().context("Failed to parse interval")?;
+            Ok(ReplayInst::Interval(interval))
+        }
+        "log" => {
+            command::set_log_level(&value)?;
+            Ok(ReplayInst::Notify(format!("log = {}", value)))
+        }
+        _ => bail!("Only interval and log can be set in replays"),
+    }
+}
+
+#[derive(Clone, Debug)]
 enum ReplayInst {
     Pause,
     Start,
     End,
+    /// go one turn forward, pausing the replay
+    Forward,
+    /// go one turn backward, pausing the replay
+    Backward,
+    /// jump to the turn, pausing the replay
+    Jump(usize),
+    /// halve the interval
+    Faster,
+    /// double the interval
+    Slower,
+    /// set the interval in milliseconds
+    Interval(u64),
+    /// show the message
+    Notify(String),
+    TogglePause,
 }
 
-fn show_replay_(
-    config: GameConfig,
-    mut replay: Vec<InputCode>,
+const MIN_INTERVAL_MS: u64 = 10;
+const MAX_INTERVAL_MS: u64 = 10_000;
+
+/// speed and pause state of the replay viewer
+struct Playback {
     interval_ms: u64,
-    rx: mpsc::Receiver<ReplayInst>,
-) -> GameResult<()> {
-    let (mut screen, mut runtime) = setup_screen(config, false)?;
-    let mut sleeping = false;
-    replay.reverse();
-    loop {
-        match rx.try_recv() {
-            Ok(ReplayInst::Start) => sleeping = false,
-            Ok(ReplayInst::Pause) => sleeping = true,
-            Ok(ReplayInst::End) => break,
-            Err(mpsc::TryRecvError::Disconnected) => bail!("devui::show_replay disconnected!"),
-            Err(mpsc::TryRecvError::Empty) => {}
+    paused: bool,
+}
+
+impl Playback {
+    /// change the speed or pause by `inst`, and returns false if `inst` isn't about them
+    fn control(&mut self, inst: &ReplayInst) -> bool {
+        match *inst {
+            ReplayInst::Start => self.paused = false,
+            ReplayInst::Pause => self.paused = true,
+            ReplayInst::TogglePause => self.paused = !self.paused,
+            ReplayInst::Faster => {
+                self.interval_ms = cmp::max(self.interval_ms / 2, MIN_INTERVAL_MS);
+            }
+            ReplayInst::Slower => {
+                self.interval_ms = cmp::min(self.interval_ms.saturating_mul(2), MAX_INTERVAL_MS);
+            }
+            ReplayInst::Interval(ms) => {
+                self.interval_ms = ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS);
+            }
+            _ => return false,
         }
-        thread::sleep(Duration::from_millis(interval_ms));
-        if sleeping {
-            continue;
+        true
+    }
+}
+
+/// how many turns are between snapshots
+const SNAPSHOT_INTERVAL: usize = 50;
+/// how many snapshots are kept
+const MAX_SNAPSHOTS: usize = 64;
+
+/// Ring buffer of snapshots of the game, keyed by the number of turns played
+struct Snapshots {
+    buf: VecDeque<(usize, RunTime)>,
+}
+
+impl Snapshots {
+    fn new() -> Self {
+        Snapshots {
+            buf: VecDeque::with_capacity(MAX_SNAPSHOTS),
         }
-        let input = match replay.pop() {
-            Some(x) => x,
-            None => continue,
+    }
+    fn record(&mut self, turn: usize, runtime: &RunTime) {
+        if !turn.is_multiple_of(SNAPSHOT_INTERVAL) || self.buf.iter().any(|(t, _)| *t == turn) {
+            return;
+        }
+        if self.buf.len() == MAX_SNAPSHOTS {
+            self.buf.pop_front();
+        }
+        let pos = self.buf.iter().position(|(t, _)| *t > turn);
+        let pos = pos.unwrap_or(self.buf.len());
+        self.buf.insert(pos, (turn, runtime.clone_deterministic()));
+    }
+    /// returns the latest snapshot at or before the turn
+    fn latest(&self, turn: usize) -> Option<(usize, RunTime)> {
+        self.buf
+            .iter()
+            .rev()
+            .find(|(t, _)| *t <= turn)
+            .map(|(t, runtime)| (*t, runtime.clone_deterministic()))
+    }
+}
+
+struct Replayer {
+    config: GameConfig,
+    inputs: Vec<InputCode>,
+    /// the number of inputs already played
+    turn: usize,
+    snapshots: Snapshots,
+}
+
+impl Replayer {
+    /// play the next input and show reactions
+    fn step(
+        &mut self,
+        screen: &mut TermScreen<RawTerm>,
+        runtime: &mut RunTime,
+    ) -> GameResult<Transition> {
+        let input = match self.inputs.get(self.turn) {
+            Some(&x) => x,
+            None => return Ok(Transition::None),
         };
+        self.turn += 1;
         let res = runtime.react_to_input(input);
+        self.snapshots.record(self.turn, runtime);
         let res = match res {
             Ok(r) => r,
             Err(e) => {
                 screen.message(format!("{}", e))?;
-                continue;
+                return Ok(Transition::None);
             }
         };
-        let left_turns = replay.len();
+        self.show_turn(screen, runtime)?;
+        for reaction in res {
+            let result = process_reaction(screen, runtime, reaction).context("in show_replay")?;
+            if let Transition::Exit = result {
+                return Ok(Transition::Exit);
+            }
+        }
+        Ok(Transition::None)
+    }
+    /// jump to the turn without showing reactions
+    fn seek(
+        &mut self,
+        turn: usize,
+        screen: &mut TermScreen<RawTerm>,
+        runtime: &mut RunTime,
+    ) -> GameResult<()> {
+        self.replay_to(turn, runtime)?;
+        screen.clear_screen()?;
+        screen.dungeon(runtime)?;
+        screen.status(&runtime.player_status())?;
+        self.show_turn(screen, runtime)
+    }
+    /// restore the state at the turn, replaying inputs from the latest snapshot before it
+    fn replay_to(&mut self, turn: usize, runtime: &mut RunTime) -> GameResult<()> {
+        let turn = turn.min(self.inputs.len());
+        if turn < self.turn {
+            let (start, snapshot) = match self.snapshots.latest(turn) {
+                Some(snapshot) => snapshot,
+                None => (0, self.config.clone().build()?),
+            };
+            *runtime = snapshot;
+            self.turn = start;
+        }
+        while self.turn < turn {
+            let input = self.inputs[self.turn];
+            self.turn += 1;
+            let _ = runtime.replay_input(input);
+            self.snapshots.record(self.turn, runtime);
+        }
+        Ok(())
+    }
+    fn show_turn(&self, screen: &mut TermScreen<RawTerm>, runtime: &RunTime) -> GameResult<()> {
+        let left_turns = self.inputs.len() - self.turn;
         if left_turns == 0 {
-            screen.message(format!("--Press q or e to exit--"))?;
+            let hash = runtime.state_hash()?;
+            screen.message(format!(
+                "--Press q or e to exit-- (state hash: {:016x})",
+                hash
+            ))
         } else {
-            screen.message(format!("{} turns left", replay.len()))?;
+            screen.message(format!("turn {}: {} turns left", self.turn, left_turns))
         }
-        for reaction in res {
-            let result =
-                process_reaction(&mut screen, &mut runtime, reaction).context("in show_replay")?;
-            match result {
-                Transition::Exit => return Ok(()),
-                Transition::None => {}
+    }
+}
+
+fn show_replay_(
+    config: GameConfig,
+    replay: Vec<InputCode>,
+    interval_ms: u64,
+    rx: mpsc::Receiver<ReplayInst>,
+) -> GameResult<()> {
+    let (mut screen, mut runtime) =
+        setup_screen(config.clone(), false, None, &PlayOptions::default())?;
+    let mut replayer = Replayer {
+        config,
+        inputs: replay,
+        turn: 0,
+        snapshots: Snapshots::new(),
+    };
+    replayer.snapshots.record(0, &runtime);
+    let mut playback = Playback {
+        interval_ms,
+        paused: false,
+    };
+    let mut watcher = SizeWatcher::new();
+    loop {
+        if let Some((w, h)) = watcher.poll() {
+            screen.resize(w, h)?;
+            let turn = replayer.turn;
+            replayer.seek(turn, &mut screen, &mut runtime)?;
+        }
+        let inst = match rx.try_recv() {
+            Ok(inst) => Some(inst),
+            Err(mpsc::TryRecvError::Disconnected) => bail!("devui::show_replay disconnected!"),
+            Err(mpsc::TryRecvError::Empty) => None,
+        };
+        let transition = match inst {
+            Some(inst) if playback.control(&inst) => {
+                if let ReplayInst::Faster | ReplayInst::Slower | ReplayInst::Interval(_) = inst {
+                    screen.message(format!("interval: {}ms", playback.interval_ms))?;
+                }
+                Transition::None
+            }
+            Some(ReplayInst::Notify(msg)) => {
+                screen.message(msg)?;
+                Transition::None
             }
+            Some(ReplayInst::End) => break,
+            Some(ReplayInst::Forward) => {
+                playback.paused = true;
+                replayer.step(&mut screen, &mut runtime)?
+            }
+            Some(ReplayInst::Backward) => {
+                playback.paused = true;
+                let turn = replayer.turn.saturating_sub(1);
+                replayer.seek(turn, &mut screen, &mut runtime)?;
+                Transition::None
+            }
+            Some(ReplayInst::Jump(turn)) => {
+                playback.paused = true;
+                replayer.seek(turn, &mut screen, &mut runtime)?;
+                Transition::None
+            }
+            Some(_) => Transition::None,
+            None => {
+                thread::sleep(Duration::from_millis(playback.interval_ms));
+                if playback.paused {
+                    continue;
+                }
+                replayer.step(&mut screen, &mut runtime)?
+            }
+        };
+        if let Transition::Exit = transition {
+            return Ok(());
         }
     }
     screen.clear_screen()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rogue_gym_core::character::Action;
+    use rogue_gym_core::dungeon::Direction;
+
+    fn config() -> GameConfig {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config
+    }
+
+    /// wander around the first room
+    fn inputs(n: usize) -> Vec<InputCode> {
+        let actions = [
+            Action::Move(Direction::Left),
+            Action::Move(Direction::Down),
+            Action::Search,
+            Action::Move(Direction::Right),
+            Action::Move(Direction::Up),
+        ];
+        actions
+            .iter()
+            .cycle()
+            .take(n)
+            .map(|&a| InputCode::Act(a))
+            .collect()
+    }
+
+    /// state hash after playing the first `turn` inputs from the start
+    fn hash_at(inputs: &[InputCode], turn: usize) -> u64 {
+        let mut runtime = config().build().unwrap();
+        for &input in &inputs[..turn] {
+            let _ = runtime.replay_input(input);
+        }
+        runtime.state_hash().unwrap()
+    }
+
+    #[test]
+    fn playback() {
+        let mut playback = Playback {
+            interval_ms: 100,
+            paused: false,
+        };
+        let keys = [
+            (Key::Char('+'), 50, false),
+            (Key::Char('-'), 100, false),
+            (Key::Char('-'), 200, false),
+            (Key::Char(' '), 200, true),
+            (Key::Char(' '), 200, false),
+            (Key::Char('p'), 200, true),
+            (Key::Char('s'), 200, false),
+        ];
+        for &(key, interval_ms, paused) in &keys {
+            assert!(playback.control(&replay_key(key).unwrap()));
+            assert_eq!(
+                (playback.interval_ms, playback.paused),
+                (interval_ms, paused)
+            );
+        }
+        for _ in 0..20 {
+            playback.control(&ReplayInst::Faster);
+        }
+        assert_eq!(playback.interval_ms, MIN_INTERVAL_MS);
+        for _ in 0..20 {
+            playback.control(&ReplayInst::Slower);
+        }
+        assert_eq!(playback.interval_ms, MAX_INTERVAL_MS);
+        playback.control(&replay_command("set interval 0").unwrap());
+        assert_eq!(playback.interval_ms, MIN_INTERVAL_MS);
+        assert!(!playback.control(&ReplayInst::Forward));
+        assert!(replay_key(Key::Char('x')).is_none());
+    }
+    #[test]
+    fn snapshots() {
+        let runtime = config().build().unwrap();
+        let mut snapshots = Snapshots::new();
+        for turn in 0..(MAX_SNAPSHOTS + 2) * SNAPSHOT_INTERVAL {
+            snapshots.record(turn, &runtime);
+        }
+        assert_eq!(snapshots.buf.len(), MAX_SNAPSHOTS);
+        // the oldest ones are dropped
+        assert!(snapshots.latest(SNAPSHOT_INTERVAL).is_none());
+        let (turn, _) = snapshots.latest(3 * SNAPSHOT_INTERVAL - 1).unwrap();
+        assert_eq!(turn, 2 * SNAPSHOT_INTERVAL);
+        let (turn, _) = snapshots.latest(usize::MAX).unwrap();
+        assert_eq!(turn, (MAX_SNAPSHOTS + 1) * SNAPSHOT_INTERVAL);
+    }
+
+    #[test]
+    fn replay_to() {
+        let inputs = inputs(SNAPSHOT_INTERVAL * 2 + 20);
+        let mut runtime = config().build().unwrap();
+        let mut replayer = Replayer {
+            config: config(),
+            inputs: inputs.clone(),
+            turn: 0,
+            snapshots: Snapshots::new(),
+        };
+        replayer.snapshots.record(0, &runtime);
+        let end = inputs.len();
+        for &turn in &[
+            end,
+            SNAPSHOT_INTERVAL + 10,
+            SNAPSHOT_INTERVAL + 9,
+            3,
+            end + 10,
+        ] {
+            replayer.replay_to(turn, &mut runtime).unwrap();
+            let turn = turn.min(end);
+            assert_eq!(replayer.turn, turn);
+            assert_eq!(runtime.state_hash().unwrap(), hash_at(&inputs, turn));
+        }
+        // snapshots are recorded on the way
+        let (turn, _) = replayer.snapshots.latest(end).unwrap();
+        assert_eq!(turn, SNAPSHOT_INTERVAL * 2);
+    }
+    #[test]
+    fn autosave_every_n_inputs() {
+        let path = std::env::temp_dir().join("rogue-gym-devui-autosave.json");
+        let _ = fs::remove_file(&path);
+        let options = PlayOptions {
+            save: Some(path.to_str().unwrap().to_owned()),
+            autosave: 5,
+            ..Default::default()
+        };
+        let mut runtime = config().build().unwrap();
+        let mut last_saved = 0;
+        let mut saved = vec![];
+        for (i, input) in inputs(12).into_iter().enumerate() {
+            runtime.react_to_input(input).unwrap();
+            if autosave(&runtime, &config(), &options, &mut last_saved).unwrap() {
+                saved.push(i + 1);
+            }
+        }
+        assert_eq!(saved, vec![5, 10]);
+        assert_eq!(last_saved, 10);
+        let json = fs::read_to_string(&path).unwrap();
+        let snapshot = Snapshot::from_json(&json).unwrap();
+        assert_eq!(snapshot.replay.inputs.len(), 10);
+        let resumed = RunTime::resume(&snapshot).unwrap();
+        assert_eq!(resumed.state_hash().unwrap(), hash_at(&inputs(12), 10));
+        // disabled by 0
+        let options = PlayOptions {
+            autosave: 0,
+            ..options
+        };
+        let mut last_saved = 0;
+        assert!(!autosave(&runtime, &config(), &options, &mut last_saved).unwrap());
+    }
+
+    #[test]
+    fn reserved_keys_cant_be_bound() {
+        let keymap = KeyMap::default();
+        check_keymap(&keymap).unwrap();
+        let help = help_lines(&keymap);
+        for &(key, usage) in &RESERVED_KEYS {
+            assert!(help.iter().any(|l| l.ends_with(usage)), "{}", usage);
+            let mut keymap = keymap.clone();
+            keymap.bind(key.into(), InputCode::Act(Action::Search));
+            assert!(check_keymap(&keymap).is_err(), "{:?}", key);
+        }
+    }
+}
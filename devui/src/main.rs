@@ -1,12 +1,27 @@
-use std::fs::{File, OpenOptions};
-use std::io::prelude::*;
+use std::fs::{self, File, OpenOptions};
+use std::io::BufWriter;
+use std::path::Path;
 
 use anyhow::{bail, Context};
 use clap::ArgMatches;
-use rogue_gym_core::{error::GameResult, json_to_inputs, read_file, GameConfig};
-use rogue_gym_devui::{play_game, show_replay};
+use rogue_gym_core::input::KeyMapPatch;
+use rogue_gym_core::ui::{MordalKind, UiState};
+use rogue_gym_core::{
+    error::GameResult, read_file, replay::ReplayFile, share::DungeonCode, GameConfig,
+};
+use rogue_gym_devui::bot::{run_bot, BotKind};
+use rogue_gym_devui::command::convert_log_level;
+use rogue_gym_devui::export::{export_replay, ExportFormat};
+use rogue_gym_devui::scores::{ScoreBoard, ScoreEntry};
+use rogue_gym_devui::{play_game_with, show_replay, PlayOptions};
+use serde_json::json;
 
 const DEFAULT_INTERVAL_MS: u64 = 500;
+const DEFAULT_AUTOSAVE_INTERVAL: usize = 50;
+const DEFAULT_SCORES_FILE: &str = "rogue-gym-scores.json";
+/// how many scores are shown after death
+const DEFAULT_TOP_SCORES: usize = 10;
+const DEFAULT_BOT_EPISODES: usize = 10;
 
 fn main() -> GameResult<()> {
     let args = parse_args();
@@ -14,31 +29,155 @@ fn main() -> GameResult<()> {
     if let Some(seed) = args.value_of("seed") {
         config.seed = Some(seed.parse().context("Failed to parse seed!")?);
     }
+    if let Some(code) = args.value_of("code") {
+        code.parse::<DungeonCode>()?.apply(&mut config)?;
+    }
+    if let Some(keymap) = args.value_of("keymap") {
+        let patch = read_file(keymap).context("Failed to read keymap file!")?;
+        let patch: KeyMapPatch =
+            serde_json::from_str(&patch).context("Failed to parse keymap file!")?;
+        config.keymap.apply(&patch)?;
+    }
     setup_logger(&args)?;
-    if let Some(replay_arg) = args.subcommand_matches("replay") {
+    if args.subcommand_matches("dump-keymap").is_some() {
+        let keymap = serde_json::to_string_pretty(&config.keymap)?;
+        println!("{}", keymap);
+        Ok(())
+    } else if args.subcommand_matches("config-dump").is_some() {
+        println!("{}", config.to_canonical_json()?);
+        Ok(())
+    } else if let Some(replay_arg) = args.subcommand_matches("replay") {
         let fname = replay_arg.value_of("file").unwrap();
         let replay = read_file(fname).context("Failed to read replay file!")?;
-        let replay = json_to_inputs(&replay)?;
+        let replay = ReplayFile::from_json_or_inputs(&replay, &config)?;
         let mut interval = DEFAULT_INTERVAL_MS;
         if let Some(inter) = replay_arg.value_of("interval") {
             interval = inter.parse().context("Failed to parse 'interval' arg!")?;
         }
-        show_replay(config, replay, interval)
+        if let Some(mut export) = replay_arg.values_of("export") {
+            let format = ExportFormat::from_name(export.next().unwrap())?;
+            let file = File::create(export.next().unwrap()).context("Failed to create file!")?;
+            return export_replay(
+                replay.config,
+                &replay.inputs,
+                format,
+                interval,
+                BufWriter::new(file),
+            );
+        }
+        show_replay(replay.config, replay.inputs, interval)
+    } else if let Some(batch_arg) = args.subcommand_matches("batch-replay") {
+        batch_replay(batch_arg.value_of("dir").unwrap(), &config)
+    } else if let Some(scores_arg) = args.subcommand_matches("scores") {
+        let mut num = DEFAULT_TOP_SCORES;
+        if let Some(n) = scores_arg.value_of("num") {
+            num = n.parse().context("Failed to parse 'num' arg!")?;
+        }
+        let board = ScoreBoard::load(args.value_of("scores").unwrap_or(DEFAULT_SCORES_FILE))?;
+        for line in board.lines(num) {
+            println!("{}", line);
+        }
+        Ok(())
+    } else if let Some(bot_arg) = args.subcommand_matches("bot") {
+        let kind = BotKind::from_name(bot_arg.value_of("kind").unwrap_or("greedy"))?;
+        let mut episodes = DEFAULT_BOT_EPISODES;
+        if let Some(n) = bot_arg.value_of("episodes") {
+            episodes = n.parse().context("Failed to parse 'episodes' arg!")?;
+        }
+        if let Some(turns) = bot_arg.value_of("max-turns") {
+            config.episode.max_turns =
+                Some(turns.parse().context("Failed to parse 'max-turns' arg!")?);
+        }
+        for line in run_bot(&config, kind, episodes, bot_arg.value_of("dataset"))? {
+            println!("{}", line);
+        }
+        Ok(())
     } else {
-        let runtime = play_game(config, is_default)?;
-        if let Some(save_file) = args.value_of("save") {
-            let s = runtime.saved_inputs_as_json()?;
-            let mut file = File::create(save_file)?;
-            file.write_all(s.as_bytes())?;
+        let mut autosave = DEFAULT_AUTOSAVE_INTERVAL;
+        if let Some(inter) = args.value_of("autosave") {
+            autosave = inter.parse().context("Failed to parse 'autosave' arg!")?;
+        }
+        let options = PlayOptions {
+            trace: args.value_of("trace").map(str::to_owned),
+            dataset: args.value_of("dataset").map(str::to_owned),
+            save: args.value_of("save").map(str::to_owned),
+            autosave,
+            resume: args.value_of("resume").map(str::to_owned),
+            no_color: args.is_present("no-color"),
+            direct_draw: args.is_present("direct-draw"),
+        };
+        let runtime = play_game_with(config, is_default, &options)?;
+        let summary = runtime.summary();
+        if !summary.is_dead() {
+            return Ok(());
+        }
+        let path = args.value_of("scores").unwrap_or(DEFAULT_SCORES_FILE);
+        let mut board = ScoreBoard::load(path)?;
+        let rank = board.add(ScoreEntry::new(summary));
+        board.save(path)?;
+        for line in board.lines(DEFAULT_TOP_SCORES) {
+            println!("{}", line);
+        }
+        if let Some(rank) = rank.filter(|&r| r > DEFAULT_TOP_SCORES) {
+            println!("You are ranked {}", rank);
         }
         Ok(())
     }
 }
 
+/// replay all json files in `dir` headlessly, and print the results as JSON lines
+fn batch_replay(dir: &str, config: &GameConfig) -> GameResult<()> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).context("Failed to read replay directory!")? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    let mut failed = 0;
+    for path in &files {
+        let file = path.display().to_string();
+        let line = match run_replay(path, config) {
+            Ok(mut line) => {
+                line["file"] = json!(file);
+                line
+            }
+            Err(e) => {
+                failed += 1;
+                json!({"file": file, "ok": false, "error": format!("{:#}", e)})
+            }
+        };
+        println!("{}", line);
+    }
+    if failed > 0 {
+        bail!("{} of {} replays failed", failed, files.len());
+    }
+    Ok(())
+}
+
+fn run_replay(path: &Path, config: &GameConfig) -> GameResult<serde_json::Value> {
+    let json = fs::read_to_string(path).context("Failed to read replay file!")?;
+    let replay = ReplayFile::from_json_or_inputs(&json, config)?;
+    let runtime = replay.run()?;
+    let status = runtime.player_status();
+    Ok(json!({
+        "ok": true,
+        "inputs": replay.inputs.len(),
+        "gold": status.gold,
+        "dungeon_level": status.dungeon_level,
+        "player_level": status.player_level,
+        "dead": matches!(runtime.ui(), UiState::Mordal(MordalKind::Grave(_))),
+        "state_hash": format!("{:016x}", runtime.state_hash()?),
+    }))
+}
+
 fn get_config(args: &ArgMatches) -> GameResult<(GameConfig, bool)> {
-    let file_name = match args.value_of("config") {
-        Some(fname) => fname,
-        None => {
+    let preset = args.value_of("preset");
+    let file_name = match (args.value_of("config"), preset) {
+        (Some(fname), _) => fname,
+        (None, Some(preset)) => return Ok((GameConfig::preset(preset)?, false)),
+        (None, None) => {
             return Ok((GameConfig::default(), true));
         }
     };
@@ -46,7 +185,11 @@ fn get_config(args: &ArgMatches) -> GameResult<(GameConfig, bool)> {
         bail!("Only .json file is allowed as configuration file")
     }
     let f = read_file(file_name).context("in get_config")?;
-    Ok((GameConfig::from_json(&f)?, false))
+    let config = match preset {
+        Some(preset) => GameConfig::from_json_with_preset(preset, &f)?,
+        None => GameConfig::from_json(&f)?,
+    };
+    Ok((config, false))
 }
 
 fn parse_args<'a>() -> ArgMatches<'a> {
@@ -62,6 +205,17 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                 .help("Sets your config json file")
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("preset")
+                .short("p")
+                .long("preset")
+                .value_name("PRESET")
+                .help(
+                    "Use a builtin config(original-rogue, tiny-10x10, no-monsters or item-rich), \
+                     on which the config file is merged",
+                )
+                .takes_value(true),
+        )
         .arg(
             clap::Arg::with_name("log")
                 .short("l")
@@ -86,11 +240,74 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                 .help("Set seed")
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("code")
+                .long("code")
+                .value_name("CODE")
+                .help(
+                    "Play the dungeon of a code shown at the start of a game with the same config",
+                )
+                .conflicts_with("seed")
+                .takes_value(true),
+        )
         .arg(
             clap::Arg::with_name("save")
                 .long("save")
                 .value_name("SAVE")
-                .help("save replay file")
+                .help("save replay file, which can be resumed by --resume")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("keymap")
+                .short("k")
+                .long("keymap")
+                .value_name("KEYMAP")
+                .help("change key bindings by a json file with \"clear\", \"unbind\" and \"bind\"")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("no-color")
+                .long("no-color")
+                .help("draw the dungeon without colors"),
+        )
+        .arg(
+            clap::Arg::with_name("direct-draw")
+                .long("direct-draw")
+                .help("write to the terminal directly, instead of updating only changed cells"),
+        )
+        .arg(
+            clap::Arg::with_name("resume")
+                .long("resume")
+                .value_name("RESUME")
+                .help("resume the game from a file saved by --save")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("autosave")
+                .long("autosave")
+                .value_name("AUTOSAVE")
+                .help("flush inputs to the save file every AUTOSAVE inputs (0 to disable)")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("scores")
+                .long("scores")
+                .value_name("SCORES")
+                .help("score file, where the game is recorded when the player dies")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("trace")
+                .long("trace")
+                .value_name("TRACE")
+                .help("record observations every turn into a binary trace file")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("dataset")
+                .long("dataset")
+                .value_name("DATASET")
+                .help("write the play on exit as a dataset for imitation learning")
                 .takes_value(true),
         )
         .subcommand(
@@ -113,6 +330,82 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                         .value_name("INTERVAL")
                         .help("Interval in replay mode")
                         .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("export")
+                        .long("export")
+                        .number_of_values(2)
+                        .value_names(&["FORMAT", "OUT"])
+                        .help(
+                            "render the replay into OUT without UI (FORMAT: asciicast or frames)",
+                        ),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("scores")
+                .about("Print the top scores")
+                .arg(
+                    clap::Arg::with_name("num")
+                        .short("n")
+                        .long("num")
+                        .value_name("NUM")
+                        .help("how many scores are printed")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("dump-keymap")
+                .about("Print the keymap in use as json, which can be a base of custom keymaps"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("config-dump")
+                .about("Print the config in use as json, with all default settings filled in"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("batch-replay")
+                .about("Run replay files in a directory without UI and print results as JSON lines")
+                .version("0.1")
+                .arg(
+                    clap::Arg::with_name("dir")
+                        .short("d")
+                        .long("dir")
+                        .required(true)
+                        .value_name("DIR")
+                        .help("directory of replay json files")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("bot")
+                .about("Run a baseline agent without UI and print its score statistics")
+                .arg(
+                    clap::Arg::with_name("kind")
+                        .long("kind")
+                        .value_name("KIND")
+                        .help("random or greedy(default: greedy)")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("episodes")
+                        .short("n")
+                        .long("episodes")
+                        .value_name("EPISODES")
+                        .help("how many games the agent plays(default: 10)")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("max-turns")
+                        .long("max-turns")
+                        .value_name("MAX_TURNS")
+                        .help("end each game after MAX_TURNS turns")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("dataset")
+                        .long("dataset")
+                        .value_name("DATASET")
+                        .help("write all steps as a dataset for imitation learning")
+                        .takes_value(true),
                 ),
         )
         .get_matches()
@@ -132,7 +425,8 @@ fn setup_logger(args: &ArgMatches) -> GameResult<()> {
                     message
                 ))
             })
-            .level(level)
+            // filtered only by the max level, which can be changed by `:set log`
+            .level(log::LevelFilter::Trace)
             .chain(
                 OpenOptions::new()
                     .write(true)
@@ -143,20 +437,72 @@ fn setup_logger(args: &ArgMatches) -> GameResult<()> {
             )
             .apply()
             .context("error in setup_log")?;
+        log::set_max_level(level);
     }
     Ok(())
 }
 
-fn convert_log_level(s: &str) -> Option<log::LevelFilter> {
-    use log::LevelFilter::*;
-    let s = s.to_lowercase();
-    match &*s {
-        "off" | "o" => Some(Off),
-        "error" | "e" => Some(Error),
-        "warn" | "w" => Some(Warn),
-        "info" | "i" => Some(Info),
-        "debug" | "d" => Some(Debug),
-        "trace" | "t" => Some(Trace),
-        _ => None,
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rogue_gym_core::character::Action;
+    use rogue_gym_core::dungeon::Direction;
+    use rogue_gym_core::input::InputCode;
+    use std::path::PathBuf;
+
+    fn replay_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rogue-gym-devui-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// play a few turns and returns the replay file with its final state hash
+    fn record(seed: u128) -> (ReplayFile, String) {
+        let mut config = GameConfig::default();
+        config.seed = Some(seed);
+        let mut runtime = config.clone().build().unwrap();
+        let actions = [Direction::Left, Direction::Down, Direction::Right];
+        for &d in actions.iter().cycle().take(20) {
+            let _ = runtime.react_to_input(InputCode::Act(Action::Move(d)));
+        }
+        let mut replay = runtime.replay_file(config);
+        replay.record_hashes(5).unwrap();
+        let hash = format!("{:016x}", runtime.state_hash().unwrap());
+        (replay, hash)
+    }
+
+    #[test]
+    fn run_replay_reports_final_state() {
+        let dir = replay_dir("run-replay");
+        let (replay, hash) = record(1);
+        let path = dir.join("1.json");
+        fs::write(&path, replay.to_json().unwrap()).unwrap();
+        let line = run_replay(&path, &GameConfig::default()).unwrap();
+        assert_eq!(line["ok"], json!(true));
+        assert_eq!(line["inputs"], json!(replay.inputs.len()));
+        assert_eq!(line["state_hash"], json!(hash));
+        assert_eq!(line["dungeon_level"], json!(1));
+    }
+
+    #[test]
+    fn batch_replay_fails_on_a_broken_file() {
+        let dir = replay_dir("batch-replay");
+        for seed in 1..3 {
+            let (replay, _) = record(seed);
+            let path = dir.join(format!("{}.json", seed));
+            fs::write(path, replay.to_json().unwrap()).unwrap();
+        }
+        // not a replay file, so ignored
+        fs::write(dir.join("notes.txt"), "not a replay").unwrap();
+        let dir_name = dir.to_str().unwrap();
+        batch_replay(dir_name, &GameConfig::default()).unwrap();
+        let (mut replay, _) = record(3);
+        for sh in replay.state_hashes.iter_mut() {
+            sh.hash ^= 1;
+        }
+        fs::write(dir.join("3.json"), replay.to_json().unwrap()).unwrap();
+        let err = batch_replay(dir_name, &GameConfig::default()).unwrap_err();
+        assert_eq!(format!("{}", err), "1 of 3 replays failed");
     }
 }
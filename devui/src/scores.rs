@@ -0,0 +1,180 @@
+//! local score list, like the one of the original rogue
+use anyhow::Context;
+use rogue_gym_core::{error::GameResult, summary::GameSummary};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+
+/// how many scores are kept in the file
+pub const MAX_SCORES: usize = 100;
+
+/// A record of a game in the score list
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ScoreEntry {
+    pub name: String,
+    /// when the game ended, in RFC 3339
+    pub date: String,
+    pub summary: GameSummary,
+}
+
+impl ScoreEntry {
+    /// record a game which ended just now, with the name of the current user
+    pub fn new(summary: GameSummary) -> Self {
+        let name = std::env::var("USER").unwrap_or_else(|_| "rogue".to_owned());
+        ScoreEntry {
+            name,
+            date: chrono::Local::now().to_rfc3339(),
+            summary,
+        }
+    }
+    /// how the game ended, like "killed by Bat on level 3"
+    fn description(&self) -> String {
+        let cause = match self.summary.cause_of_death.as_ref() {
+            Some(cause) => {
+                let mut chars = cause.chars();
+                match chars.next() {
+                    Some(c) => c.to_lowercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            }
+            None => "quit".to_owned(),
+        };
+        format!("{} on level {}", cause, self.summary.deepest_level)
+    }
+}
+
+/// Scores sorted from the best, saved as a JSON file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScoreBoard {
+    entries: Vec<ScoreEntry>,
+}
+
+impl ScoreBoard {
+    /// load scores from `path`, or make an empty list if the file doesn't exist yet
+    pub fn load(path: &str) -> GameResult<Self> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).context("Failed to read score file"),
+        };
+        let entries = serde_json::from_str(&json).context("Failed to parse score file")?;
+        let mut board = ScoreBoard { entries };
+        board.sort();
+        Ok(board)
+    }
+    /// write scores via a temporary file, not to break the old one on crash
+    pub fn save(&self, path: &str) -> GameResult<()> {
+        let json = serde_json::to_string_pretty(&self.entries)?;
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, json.as_bytes()).context("Failed to write score file")?;
+        fs::rename(&tmp, path).context("Failed to write score file")?;
+        Ok(())
+    }
+    fn sort(&mut self) {
+        // stable, so older entries come first among the same scores
+        self.entries
+            .sort_by_key(|e| Reverse((e.summary.gold, e.summary.deepest_level, e.summary.kills)));
+        self.entries.truncate(MAX_SCORES);
+    }
+    /// add an entry and returns its rank(1-indexed), or None if it's out of the list
+    pub fn add(&mut self, entry: ScoreEntry) -> Option<usize> {
+        self.entries.push(entry.clone());
+        self.sort();
+        self.entries
+            .iter()
+            .rposition(|e| *e == entry)
+            .map(|rank| rank + 1)
+    }
+    pub fn entries(&self) -> &[ScoreEntry] {
+        &self.entries
+    }
+    /// lines of the top `n` scores, with a header
+    pub fn lines(&self, n: usize) -> Vec<String> {
+        let mut res = vec![format!("{:>4} {:>6}  {}", "Rank", "Score", "Name")];
+        res.extend(self.entries.iter().take(n).enumerate().map(|(i, e)| {
+            format!(
+                "{:>4} {:>6}  {}: {}",
+                i + 1,
+                e.summary.gold,
+                e.name,
+                e.description()
+            )
+        }));
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(name: &str, gold: u32, deepest_level: u32, cause: Option<&str>) -> ScoreEntry {
+        ScoreEntry {
+            name: name.to_owned(),
+            date: "2019-01-01T00:00:00+09:00".to_owned(),
+            summary: GameSummary {
+                cause_of_death: cause.map(str::to_owned),
+                gold,
+                deepest_level,
+                player_level: 1,
+                kills: 0,
+                turns: 100,
+                seed: 1,
+                bosses: vec![],
+                statistics: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn ranking() {
+        let mut board = ScoreBoard::default();
+        assert_eq!(
+            board.add(entry("a", 100, 2, Some("Killed by Bat"))),
+            Some(1)
+        );
+        assert_eq!(board.add(entry("b", 300, 1, None)), Some(1));
+        // the deeper level wins among the same gold
+        assert_eq!(board.add(entry("c", 100, 3, Some("Starved"))), Some(2));
+        // the older one wins among the same score
+        assert_eq!(
+            board.add(entry("d", 100, 2, Some("Killed by Bat"))),
+            Some(4)
+        );
+        let names: Vec<_> = board.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["b", "c", "a", "d"]);
+        let lines = board.lines(2);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "   1    300  b: quit on level 1");
+        assert_eq!(lines[2], "   2    100  c: starved on level 3");
+    }
+
+    #[test]
+    fn only_best_scores_are_kept() {
+        let mut board = ScoreBoard::default();
+        for gold in 0..MAX_SCORES as u32 {
+            board.add(entry("a", gold + 10, 1, None));
+        }
+        assert_eq!(board.add(entry("b", 0, 1, None)), None);
+        assert_eq!(board.entries().len(), MAX_SCORES);
+        assert_eq!(board.add(entry("c", 1000, 1, None)), Some(1));
+        assert_eq!(board.entries().len(), MAX_SCORES);
+        assert_eq!(board.entries().last().unwrap().summary.gold, 11);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join("rogue-gym-devui-scores.json");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        assert_eq!(ScoreBoard::load(path).unwrap(), ScoreBoard::default());
+        let mut board = ScoreBoard::default();
+        board.add(entry("a", 100, 2, Some("Killed by Bat")));
+        board.add(entry("b", 300, 1, None));
+        board.save(path).unwrap();
+        assert_eq!(ScoreBoard::load(path).unwrap(), board);
+        fs::write(path, "not json").unwrap();
+        assert!(ScoreBoard::load(path).is_err());
+    }
+}
@@ -1,17 +1,24 @@
+use crate::command::Verbosity;
 use anyhow::{bail, Context};
 use rogue_gym_core::{
+    character::player::Status,
     dungeon::{Coord, X, Y},
     error::GameResult,
+    tile::{Color, Tile},
 };
-use rogue_gym_uilib::Screen;
-use std::collections::VecDeque;
+use rogue_gym_uilib::{Cell, CellBuffer, Screen, Viewport, REMEMBERED_COLOR};
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
 use std::io::{self, Stdout, Write};
 use termion::raw::{IntoRawMode, RawTerminal};
-use termion::{clear, cursor, terminal_size};
+use termion::{clear, color, cursor, terminal_size};
 use tuple_map::TupleMap2;
 
 pub type RawTerm = RawTerminal<Stdout>;
 
+/// how many messages are kept for the message history pane
+pub const HISTORY_LEN: usize = 200;
+
 /// wrapper of stdout as rogue screen
 pub struct TermScreen<T> {
     /// stdout
@@ -20,6 +27,19 @@ pub struct TermScreen<T> {
     width: u16,
     height: u16,
     pub(crate) pending_messages: VecDeque<String>,
+    /// recent messages, including already displayed ones
+    history: VecDeque<String>,
+    /// colors of tiles, which are drawn without colors if empty
+    colors: BTreeMap<char, Color>,
+    /// the area of the screen shown in the terminal
+    viewport: Viewport,
+    /// if Some, drawing functions write to the buffer and only changed cells are
+    /// written to the terminal on `flush`
+    buffer: Option<CellBuffer>,
+    /// the cursor position in the terminal, for the buffered mode
+    cursor: Option<Coord>,
+    /// messages are only kept in the history if it is `Quiet`
+    verbosity: Verbosity,
 }
 
 impl TermScreen<RawTerm> {
@@ -32,19 +52,21 @@ impl TermScreen<RawTerm> {
         let (width, height) =
             terminal_size().context("[Screen::from_stdout] attempt to get terminal size")?;
         let (w, h) = (w, h).map(|i| i as u16);
-        if width < w {
-            bail!("Screen width must be larger than {} characters", w);
-        }
-        if height < h {
-            bail!("Screen height must be larger than {} characters", h);
-        }
-        Ok(TermScreen {
+        let mut screen = TermScreen {
             term,
             has_notification: false,
             width: w,
             height: h,
             pending_messages: VecDeque::new(),
-        })
+            history: VecDeque::new(),
+            colors: BTreeMap::new(),
+            viewport: Viewport::new(i32::from(w), i32::from(h)),
+            buffer: Some(CellBuffer::new(i32::from(width), i32::from(height))),
+            cursor: None,
+            verbosity: Verbosity::Verbose,
+        };
+        screen.viewport.resize(i32::from(width), i32::from(height));
+        Ok(screen)
     }
 }
 
@@ -67,6 +89,12 @@ impl TermScreen<Stdout> {
             width,
             height,
             pending_messages: VecDeque::new(),
+            history: VecDeque::new(),
+            colors: BTreeMap::new(),
+            viewport: Viewport::new(i32::from(width), i32::from(height)),
+            buffer: None,
+            cursor: None,
+            verbosity: Verbosity::Verbose,
         })
     }
 }
@@ -84,13 +112,24 @@ impl<T: Write> Screen for TermScreen<T> {
         self.write_str(Coord::new(0, 0), msg.as_ref())
     }
     fn clear_line(&mut self, row: Y) -> GameResult<()> {
-        let row = row.0 as u16;
-        write!(self.term, "{}{}", cursor::Goto(1, row), clear::CurrentLine)
+        let pos = match self.viewport.to_view(Coord::new(0, row.0), false) {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.clear_line(pos.y.0);
+            return Ok(());
+        }
+        write!(self.term, "{}{}", pos.into_cursor(), clear::CurrentLine)
             .context("in TermScreen::clear_line")
     }
     fn clear_notification(&mut self) -> GameResult<()> {
         if self.has_notification {
             self.has_notification = false;
+            if let Some(buffer) = self.buffer.as_mut() {
+                buffer.clear_line(0);
+                return Ok(());
+            }
             write!(
                 self.term,
                 "{}{}",
@@ -102,33 +141,184 @@ impl<T: Write> Screen for TermScreen<T> {
         }
         .context("in TermScreen::clear_notification")
     }
+    fn viewport_mut(&mut self) -> Option<&mut Viewport> {
+        Some(&mut self.viewport)
+    }
     fn cursor(&mut self, coord: Coord) -> GameResult<()> {
-        write!(self.term, "{}", coord.into_cursor()).context("in TermScreen::cursor")
+        let pos = match self.viewport.to_view(coord, true) {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        if self.buffer.is_some() {
+            self.cursor = Some(pos);
+            return Ok(());
+        }
+        write!(self.term, "{}", pos.into_cursor()).context("in TermScreen::cursor")
     }
     fn flush(&mut self) -> GameResult<()> {
+        self.render()?;
         self.term.flush().context("in TermScreen::flush")
     }
     fn write_char(&mut self, cd: Coord, c: char) -> GameResult<()> {
-        write!(self.term, "{}{}", cd.into_cursor(), c).context("in TermScreen::write_char")
+        let pos = match self.viewport.to_view(cd, true) {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.set(pos, Cell::new(c));
+            return Ok(());
+        }
+        write!(self.term, "{}{}", pos.into_cursor(), c).context("in TermScreen::write_char")
+    }
+    fn write_tile(&mut self, cd: Coord, t: Tile) -> GameResult<()> {
+        let color = self.colors.get(&t.to_char()).cloned();
+        self.write_colored(cd, t.to_char(), color)
+    }
+    fn write_remembered(&mut self, cd: Coord, t: Tile) -> GameResult<()> {
+        let color = Some(REMEMBERED_COLOR).filter(|_| !self.colors.is_empty());
+        self.write_colored(cd, t.to_char(), color)
     }
     fn write_str<S: AsRef<str>>(&mut self, start: Coord, s: S) -> GameResult<()> {
+        let pos = match self.viewport.to_view(start, false) {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        let len = (self.viewport.size().0 - start.x.0).max(0) as usize;
+        let s: String = s.as_ref().chars().take(len).collect();
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.clear_from(pos);
+            buffer.write_str(pos, &s);
+        } else {
+            write!(
+                self.term,
+                "{}{}{}",
+                pos.into_cursor(),
+                clear::CurrentLine,
+                s
+            )
+            .context("in TermScreen::write_str")?;
+        }
+        self.flush().context("in TermScreen::write_str")
+    }
+    /// draw the status in 2 lines, using the row below the screen for the second line
+    /// if the terminal is large enough
+    fn status(&mut self, status: &Status) -> GameResult<()> {
+        let [first, second] = status.lines();
+        let (w, h) = (self.width, self.height).map(i32::from);
+        if !self.viewport.fits(w, h + 1) {
+            return self.write_str(Coord::new(0, h - 1), format!("{} {}", first, second));
+        }
+        self.write_str(Coord::new(0, h - 1), first)?;
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.clear_line(h);
+            buffer.write_str(Coord::new(0, h), &second);
+            return self.flush();
+        }
         write!(
             self.term,
             "{}{}{}",
-            start.into_cursor(),
+            cursor::Goto(1, self.height + 1),
             clear::CurrentLine,
-            s.as_ref()
+            second
         )
-        .context("in TermScreen::write_str")?;
-        self.flush().context("in TermScreen::write_str")
+        .context("in TermScreen::status")?;
+        self.flush()
     }
     fn pend_message<S: AsRef<str>>(&mut self, msg: S) -> GameResult<()> {
-        self.pending_messages.push_back(msg.as_ref().to_owned());
+        let msg = msg.as_ref().to_owned();
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(msg.clone());
+        self.pending_messages.push_back(msg);
         Ok(())
     }
 }
 
 impl<T: Write> TermScreen<T> {
+    /// handle the resized terminal, after which the whole screen should be redrawn
+    pub fn resize(&mut self, width: u16, height: u16) -> GameResult<()> {
+        self.viewport.resize(i32::from(width), i32::from(height));
+        if let Some(buffer) = self.buffer.as_mut() {
+            let (w, h) = self.viewport.view_size();
+            buffer.resize(w, h);
+            self.cursor = None;
+        }
+        write!(self.term, "{}", clear::All).context("in TermScreen::resize")
+    }
+    /// draw with the double buffer, which updates only changed cells, or write to the
+    /// terminal directly
+    pub fn set_buffered(&mut self, buffered: bool) {
+        self.buffer = if buffered {
+            let (w, h) = self.viewport.view_size();
+            let mut buffer = CellBuffer::new(w, h);
+            buffer.invalidate();
+            Some(buffer)
+        } else {
+            None
+        };
+        self.cursor = None;
+    }
+    /// write changed cells in the buffer to the terminal
+    fn render(&mut self) -> GameResult<()> {
+        let cells = match self.buffer.as_mut() {
+            Some(buffer) => buffer.diff(),
+            None => return Ok(()),
+        };
+        // where the next character is written without moving the cursor
+        let mut next = None;
+        let mut current_color = None;
+        for (cd, cell) in cells {
+            if next != Some(cd) {
+                write!(self.term, "{}", cd.into_cursor()).context("in TermScreen::render")?;
+            }
+            if cell.color != current_color {
+                match cell.color {
+                    Some(Color(ansi)) => write!(self.term, "{}", color::Fg(color::AnsiValue(ansi))),
+                    None => write!(self.term, "{}", color::Fg(color::Reset)),
+                }
+                .context("in TermScreen::render")?;
+                current_color = cell.color;
+            }
+            write!(self.term, "{}", cell.ch).context("in TermScreen::render")?;
+            next = Some(cd + Coord::new(1, 0));
+        }
+        if current_color.is_some() {
+            write!(self.term, "{}", color::Fg(color::Reset)).context("in TermScreen::render")?;
+        }
+        if let Some(cursor) = self.cursor {
+            write!(self.term, "{}", cursor.into_cursor()).context("in TermScreen::render")?;
+        }
+        Ok(())
+    }
+    /// write `c` with `color`, or without colors if it's None
+    fn write_colored(&mut self, cd: Coord, c: char, color: Option<Color>) -> GameResult<()> {
+        let pos = match self.viewport.to_view(cd, true) {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.set(pos, Cell { ch: c, color });
+            return Ok(());
+        }
+        let goto = pos.into_cursor();
+        match color {
+            Some(Color(ansi)) => write!(
+                self.term,
+                "{}{}{}{}",
+                goto,
+                color::Fg(color::AnsiValue(ansi)),
+                c,
+                color::Fg(color::Reset)
+            ),
+            None => write!(self.term, "{}{}", goto, c),
+        }
+        .context("in TermScreen::write_colored")
+    }
+    /// draw tiles with `colors`, or without colors if it's empty
+    pub fn set_colors(&mut self, colors: BTreeMap<char, Color>) {
+        self.colors = colors;
+    }
     pub fn welcome(&mut self) -> GameResult<()> {
         write!(
             self.term,
@@ -138,7 +328,11 @@ impl<T: Write> TermScreen<T> {
             cursor::Goto(1, 2)
         )
         .context("in Screen::welcome")?;
-        self.flush().context("in Screen::welcome")
+        // the text is written directly, so cells in the buffer should be redrawn
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.invalidate();
+        }
+        self.term.flush().context("in Screen::welcome")
     }
     pub fn default_config(&mut self) -> GameResult<()> {
         write!(
@@ -147,9 +341,34 @@ impl<T: Write> TermScreen<T> {
             cursor::Goto(1, 3),
         )
         .context("in Screen::default_config")?;
-        self.flush().context("in Screen::default_config")
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.invalidate();
+        }
+        self.term.flush().context("in Screen::default_config")
+    }
+    /// draw the message history over the dungeon, scrolled `scroll` lines up from the latest
+    /// message, and returns the actual scroll amount, which is limited by the history size
+    pub fn message_history(&mut self, scroll: usize) -> GameResult<usize> {
+        let rows = (self.viewport.size().1 - 2).max(0) as usize;
+        let scroll = cmp::min(scroll, self.history.len().saturating_sub(rows));
+        let end = self.history.len() - scroll;
+        let start = end.saturating_sub(rows);
+        self.clear_dungeon()?;
+        self.message("-- Message history (k/j: scroll, Esc: close) --")?;
+        let lines: Vec<_> = self.history.range(start..end).cloned().collect();
+        for (i, msg) in lines.into_iter().enumerate() {
+            self.write_str(Coord::new(0, i as i32 + 1), msg)?;
+        }
+        Ok(scroll)
+    }
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.verbosity = verbosity;
     }
     pub fn display_msg(&mut self) -> GameResult<bool> {
+        if self.verbosity == Verbosity::Quiet {
+            self.pending_messages.clear();
+            return Ok(false);
+        }
         if let Some(msg) = self.pending_messages.pop_front() {
             if self.pending_messages.is_empty() {
                 self.message(msg)?;
@@ -163,3 +382,93 @@ impl<T: Write> TermScreen<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// a buffered screen of `w` x `h` cells, shown in a `view_w` x `view_h` terminal
+    fn screen(w: u16, h: u16, view_w: u16, view_h: u16) -> TermScreen<Vec<u8>> {
+        let mut viewport = Viewport::new(i32::from(w), i32::from(h));
+        viewport.resize(i32::from(view_w), i32::from(view_h));
+        TermScreen {
+            term: vec![],
+            has_notification: false,
+            width: w,
+            height: h,
+            pending_messages: VecDeque::new(),
+            history: VecDeque::new(),
+            colors: BTreeMap::new(),
+            viewport,
+            buffer: Some(CellBuffer::new(i32::from(view_w), i32::from(view_h))),
+            cursor: None,
+            verbosity: Verbosity::Verbose,
+        }
+    }
+
+    /// text in the `y`th row of the buffer, without trailing spaces
+    fn row(screen: &TermScreen<Vec<u8>>, y: i32) -> String {
+        let buffer = screen.buffer.as_ref().unwrap();
+        let s: String = (0..buffer.size().0)
+            .map(|x| buffer.get(Coord::new(x, y)).unwrap().ch)
+            .collect();
+        s.trim_end().to_owned()
+    }
+
+    #[test]
+    fn message_history() {
+        let mut screen = screen(40, 8, 40, 8);
+        for i in 0..10 {
+            screen.pend_message(format!("msg {}", i)).unwrap();
+        }
+        // 6 rows for messages, and the latest one is at the bottom
+        assert_eq!(screen.message_history(0).unwrap(), 0);
+        assert!(row(&screen, 0).starts_with("-- Message history"));
+        let rows: Vec<_> = (1..7).map(|y| row(&screen, y)).collect();
+        assert_eq!(rows, ["msg 4", "msg 5", "msg 6", "msg 7", "msg 8", "msg 9"]);
+        assert_eq!(screen.message_history(2).unwrap(), 2);
+        assert_eq!(row(&screen, 1), "msg 2");
+        assert_eq!(row(&screen, 6), "msg 7");
+        // scrolling stops at the oldest message
+        assert_eq!(screen.message_history(100).unwrap(), 4);
+        assert_eq!(row(&screen, 1), "msg 0");
+    }
+
+    #[test]
+    fn message_history_is_bounded() {
+        let mut screen = screen(40, 8, 40, 8);
+        for i in 0..HISTORY_LEN + 5 {
+            screen.pend_message(format!("msg {}", i)).unwrap();
+        }
+        assert_eq!(screen.history.len(), HISTORY_LEN);
+        assert_eq!(screen.history.front().unwrap(), "msg 5");
+        assert_eq!(screen.message_history(usize::MAX).unwrap(), HISTORY_LEN - 6);
+        assert_eq!(row(&screen, 1), "msg 5");
+    }
+
+    #[test]
+    fn resize() {
+        let mut screen = screen(80, 24, 80, 24);
+        screen.cursor(Coord::new(70, 20)).unwrap();
+        screen.write_char(Coord::new(70, 20), '@').unwrap();
+        screen.resize(40, 12).unwrap();
+        assert_eq!(screen.buffer.as_ref().unwrap().size(), (40, 12));
+        assert_eq!(screen.cursor, None);
+        assert!(String::from_utf8_lossy(&screen.term).contains(&format!("{}", clear::All)));
+        // the dungeon scrolls to the player in the smaller view
+        let vp = screen.viewport_mut().unwrap();
+        assert_eq!(vp.size(), (40, 12));
+        assert!(vp.follow(Coord::new(70, 20)));
+        let pos = vp.to_view(Coord::new(70, 20), true).unwrap();
+        assert!(pos.x.0 < 40 && pos.y.0 < 11);
+        // the status line stays at the bottom
+        screen.write_str(Coord::new(0, 23), "status").unwrap();
+        assert_eq!(row(&screen, 11), "status");
+        // back to the full size
+        screen.resize(100, 30).unwrap();
+        assert_eq!(screen.buffer.as_ref().unwrap().size(), (100, 30));
+        let vp = screen.viewport_mut().unwrap();
+        assert_eq!(vp.size(), (80, 24));
+        assert!(!vp.is_scrolled());
+    }
+}
@@ -0,0 +1,50 @@
+/* C API of rogue-gym. Link with librogue_gym_ffi. */
+#ifndef ROGUE_GYM_H
+#define ROGUE_GYM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ROGUE_GYM_ABI_VERSION 1
+#define ROGUE_GYM_OK 0
+#define ROGUE_GYM_ERR (-1)
+
+typedef struct RogueGymEnv RogueGymEnv;
+
+uint32_t rogue_gym_abi_version(void);
+
+/* message of the last error in this thread, valid until the next error */
+const char *rogue_gym_last_error(void);
+
+/* make a game from a JSON config (or the default one if NULL), NULL on failure */
+RogueGymEnv *rogue_gym_env_new(const char *config_json);
+
+void rogue_gym_env_free(RogueGymEnv *env);
+
+/* start a new game, with `seed` if it's not NULL */
+int32_t rogue_gym_env_reset(RogueGymEnv *env, const uint64_t *seed);
+
+/* do the `action`-th action; output pointers can be NULL */
+int32_t rogue_gym_env_step(RogueGymEnv *env, uint32_t action, float *reward,
+                           uint8_t *terminated, uint8_t *truncated);
+
+size_t rogue_gym_env_num_actions(const RogueGymEnv *env);
+
+/* write [channels, height, width] into `shape` */
+int32_t rogue_gym_env_observation_shape(const RogueGymEnv *env, size_t shape[3]);
+
+/* the current observation in NCHW layout, valid until the next step, reset or free */
+const float *rogue_gym_env_observation(const RogueGymEnv *env, size_t *len);
+
+/* events in the last step as a JSON array, valid until the next step, reset or free */
+const char *rogue_gym_env_events(const RogueGymEnv *env);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ROGUE_GYM_H */
@@ -0,0 +1,347 @@
+//! C ABI of rogue-gym, for embedding games in programs not written in Rust.
+//! See `include/rogue_gym.h` for the declarations.
+//!
+//! Functions returning `i32` return `ROGUE_GYM_OK` on success, and `ROGUE_GYM_ERR`
+//! on failure, when `rogue_gym_last_error` returns the message.
+use anyhow::{anyhow, Context};
+use rogue_gym_core::error::GameResult;
+use rogue_gym_core::input::{InputCode, KeyMap};
+use rogue_gym_core::symbol::Layout;
+use rogue_gym_core::{GameConfig, RunTime};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// version of this API, which is incremented on incompatible changes
+pub const ROGUE_GYM_ABI_VERSION: u32 = 1;
+pub const ROGUE_GYM_OK: i32 = 0;
+pub const ROGUE_GYM_ERR: i32 = -1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+}
+
+/// run `f` and converts errors and panics into error codes
+fn guard(f: impl FnOnce() -> GameResult<()>) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => ROGUE_GYM_OK,
+        Ok(Err(e)) => {
+            set_error(format!("{:#}", e));
+            ROGUE_GYM_ERR
+        }
+        Err(_) => {
+            set_error("rogue-gym panicked".to_owned());
+            ROGUE_GYM_ERR
+        }
+    }
+}
+
+/// A game with its observation buffer, where actions are indices of `RunTime::action_set`
+pub struct RogueGymEnv {
+    runtime: RunTime,
+    config: GameConfig,
+    actions: Vec<InputCode>,
+    symbols: u8,
+    shape: [usize; 3],
+    observation: Vec<f32>,
+    /// `StepEvent`s of the last step as JSON
+    events: CString,
+    /// the index of the current episode in the seed schedule
+    episode: usize,
+}
+
+impl RogueGymEnv {
+    fn new(config: GameConfig) -> GameResult<Self> {
+        let runtime = Self::build(&config.for_episode(0))?;
+        let symbols = runtime.symbols()?;
+        let shape = runtime.observation_shape()?;
+        let mut env = RogueGymEnv {
+            actions: runtime.action_set(),
+            runtime,
+            config,
+            symbols,
+            shape,
+            observation: vec![0.0; shape.iter().product()],
+            events: CString::new("[]").unwrap_or_default(),
+            episode: 0,
+        };
+        env.observe()?;
+        Ok(env)
+    }
+    fn build(config: &GameConfig) -> GameResult<RunTime> {
+        let mut runtime = config.clone().build()?;
+        runtime.keymap = KeyMap::ai();
+        Ok(runtime)
+    }
+    fn observe(&mut self) -> GameResult<()> {
+        self.runtime
+            .observe(self.symbols, Layout::Nchw, &mut self.observation)
+    }
+}
+
+unsafe fn env_mut<'a>(env: *mut RogueGymEnv) -> GameResult<&'a mut RogueGymEnv> {
+    env.as_mut().context("env is null")
+}
+
+/// Returns `ROGUE_GYM_ABI_VERSION`
+#[no_mangle]
+pub extern "C" fn rogue_gym_abi_version() -> u32 {
+    ROGUE_GYM_ABI_VERSION
+}
+
+/// Returns the message of the last error in this thread, which is valid until the next error
+#[no_mangle]
+pub extern "C" fn rogue_gym_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// Make a game from a JSON config, or the default config if `config_json` is null.
+/// Returns null on failure.
+///
+/// # Safety
+/// `config_json` should be null or a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_new(config_json: *const c_char) -> *mut RogueGymEnv {
+    let mut env = None;
+    let code = guard(|| {
+        let config = if config_json.is_null() {
+            GameConfig::default()
+        } else {
+            let json = CStr::from_ptr(config_json)
+                .to_str()
+                .context("config is not UTF-8")?;
+            GameConfig::from_json(json)?
+        };
+        env = Some(RogueGymEnv::new(config)?);
+        Ok(())
+    });
+    match env {
+        Some(env) if code == ROGUE_GYM_OK => Box::into_raw(Box::new(env)),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Destroy a game made by `rogue_gym_env_new`
+///
+/// # Safety
+/// `env` should be null or a pointer returned by `rogue_gym_env_new` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_free(env: *mut RogueGymEnv) {
+    if !env.is_null() {
+        drop(Box::from_raw(env));
+    }
+}
+
+/// Start a new game. If `seed` is not null, the game and the following ones use the seed.
+///
+/// # Safety
+/// `env` should be a valid pointer, and `seed` should be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_reset(env: *mut RogueGymEnv, seed: *const u64) -> i32 {
+    guard(|| {
+        let env = env_mut(env)?;
+        if let Some(&seed) = seed.as_ref() {
+            env.config.set_seed(u128::from(seed));
+        }
+        env.episode += 1;
+        env.runtime = RogueGymEnv::build(&env.config.for_episode(env.episode))?;
+        env.events = CString::new("[]").unwrap_or_default();
+        env.observe()
+    })
+}
+
+/// Do the `action`-th action, and writes the reward and whether the player died or
+/// reached a goal like `stairs` (`terminated`), or the episode was cut off by `max_turns`
+/// (`truncated`).
+/// Output pointers can be null if not needed.
+///
+/// # Safety
+/// `env` should be a valid pointer, and others should be null or valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_step(
+    env: *mut RogueGymEnv,
+    action: u32,
+    reward: *mut f32,
+    terminated: *mut u8,
+    truncated: *mut u8,
+) -> i32 {
+    guard(|| {
+        let env = env_mut(env)?;
+        let code = env.actions.get(action as usize).cloned().ok_or_else(|| {
+            anyhow!(
+                "Invalid action {}, which should be less than {}",
+                action,
+                env.actions.len()
+            )
+        })?;
+        let (r, info) = env.runtime.advance(code)?;
+        env.observe()?;
+        let events = serde_json::to_string(&info.events).context("rogue_gym_env_step")?;
+        env.events = CString::new(events).context("rogue_gym_env_step")?;
+        if let Some(reward) = reward.as_mut() {
+            *reward = r;
+        }
+        if let Some(terminated) = terminated.as_mut() {
+            *terminated = u8::from(info.terminated());
+        }
+        if let Some(truncated) = truncated.as_mut() {
+            *truncated = u8::from(info.truncated());
+        }
+        Ok(())
+    })
+}
+
+/// The number of actions
+///
+/// # Safety
+/// `env` should be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_num_actions(env: *const RogueGymEnv) -> usize {
+    env.as_ref().map_or(0, |env| env.actions.len())
+}
+
+/// Write `[channels, height, width]` of observations into `shape`
+///
+/// # Safety
+/// `env` should be a valid pointer, and `shape` should point to 3 `size_t`s.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_observation_shape(
+    env: *const RogueGymEnv,
+    shape: *mut usize,
+) -> i32 {
+    guard(|| {
+        let env = env.as_ref().context("env is null")?;
+        if shape.is_null() {
+            return Err(anyhow!("shape is null"));
+        }
+        ptr::copy_nonoverlapping(env.shape.as_ptr(), shape, 3);
+        Ok(())
+    })
+}
+
+/// Pointer to the current observation in NCHW layout, and writes its length into `len`.
+/// It's valid until the next call of `rogue_gym_env_step`, `rogue_gym_env_reset` or
+/// `rogue_gym_env_free`. Returns null if `env` is null.
+///
+/// # Safety
+/// `env` should be null or a valid pointer, and `len` should be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_observation(
+    env: *const RogueGymEnv,
+    len: *mut usize,
+) -> *const f32 {
+    let env = match env.as_ref() {
+        Some(env) => env,
+        None => return ptr::null(),
+    };
+    if let Some(len) = len.as_mut() {
+        *len = env.observation.len();
+    }
+    env.observation.as_ptr()
+}
+
+/// Events in the last step as a JSON array of objects like `{"kind": "killed", "enemy": "Bat"}`.
+/// It's valid until the next call of `rogue_gym_env_step`, `rogue_gym_env_reset` or
+/// `rogue_gym_env_free`. Returns null if `env` is null.
+///
+/// # Safety
+/// `env` should be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_env_events(env: *const RogueGymEnv) -> *const c_char {
+    env.as_ref().map_or(ptr::null(), |env| env.events.as_ptr())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rogue_gym_core::character::Action;
+    #[test]
+    fn step() {
+        let config = CString::new(r#"{"seed": 1, "episode": {"max_turns": 2}}"#).unwrap();
+        unsafe {
+            let env = rogue_gym_env_new(config.as_ptr());
+            assert!(!env.is_null());
+            let mut shape = [0usize; 3];
+            assert_eq!(
+                rogue_gym_env_observation_shape(env, shape.as_mut_ptr()),
+                ROGUE_GYM_OK
+            );
+            let mut len = 0;
+            let obs = rogue_gym_env_observation(env, &mut len);
+            assert!(!obs.is_null());
+            assert_eq!(len, shape.iter().product::<usize>());
+            let n = rogue_gym_env_num_actions(env);
+            let search = (*env)
+                .actions
+                .iter()
+                .position(|a| *a == InputCode::Act(Action::Search))
+                .unwrap() as u32;
+            let (mut reward, mut terminated, mut truncated) = (1.0, 1, 1);
+            for i in 0..2 {
+                let res =
+                    rogue_gym_env_step(env, search, &mut reward, &mut terminated, &mut truncated);
+                assert_eq!(res, ROGUE_GYM_OK);
+                assert_eq!(reward, 0.0);
+                assert_eq!(terminated, 0);
+                assert_eq!(truncated, u8::from(i == 1));
+                let events = CStr::from_ptr(rogue_gym_env_events(env));
+                assert_eq!(events.to_str().unwrap(), "[]");
+            }
+            let res = rogue_gym_env_step(
+                env,
+                n as u32,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            assert_eq!(res, ROGUE_GYM_ERR);
+            let msg = CStr::from_ptr(rogue_gym_last_error()).to_str().unwrap();
+            assert!(msg.starts_with("Invalid action"));
+            let seed = 2u64;
+            assert_eq!(rogue_gym_env_reset(env, &seed), ROGUE_GYM_OK);
+            rogue_gym_env_free(env);
+        }
+        let broken = CString::new("{").unwrap();
+        assert!(unsafe { rogue_gym_env_new(broken.as_ptr()) }.is_null());
+    }
+    #[test]
+    fn terminated_by_hunger() {
+        let config = CString::new(
+            r#"{"seed": 1, "player": {"hunger_time": 100}, "enemies": {"enemies": []},
+                "episode": {"hunger": "Hungry"}}"#,
+        )
+        .unwrap();
+        unsafe {
+            let env = rogue_gym_env_new(config.as_ptr());
+            assert!(!env.is_null());
+            let search = (*env)
+                .actions
+                .iter()
+                .position(|a| *a == InputCode::Act(Action::Search))
+                .unwrap() as u32;
+            let (mut terminated, mut truncated) = (0, 0);
+            for _ in 0..100 {
+                let res = rogue_gym_env_step(
+                    env,
+                    search,
+                    ptr::null_mut(),
+                    &mut terminated,
+                    &mut truncated,
+                );
+                assert_eq!(res, ROGUE_GYM_OK);
+                if terminated == 1 || truncated == 1 {
+                    break;
+                }
+            }
+            assert_eq!((terminated, truncated), (1, 0));
+            rogue_gym_env_free(env);
+        }
+    }
+}
@@ -1,5 +1,14 @@
 from . import envs
 
+try:
+    import gymnasium
+
+    gymnasium.register(
+        id="Rogue-v1", entry_point="rogue_gym.envs.gymnasium_env:RogueGymnasiumEnv"
+    )
+except ImportError:
+    pass
+
 try:
     import rainy
     from . import rainy_impls
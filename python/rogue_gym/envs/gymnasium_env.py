@@ -0,0 +1,58 @@
+"""Provides RogueGymnasiumEnv, a gymnasium environment on the native RogueEnv"""
+import json
+from typing import Any, Dict, Optional, Tuple
+import gymnasium
+from gymnasium import spaces
+import numpy as np
+from numpy import ndarray
+from rogue_gym_python._rogue_gym import RogueEnv as NativeEnv
+
+
+class RogueGymnasiumEnv(gymnasium.Env):
+    """Rogue with the gymnasium API.
+    Episodes are terminated when the player dies or reaches a goal in the `episode`
+    config (`hunger` or `stairs`), and truncated when `max_turns` is reached.
+    """
+
+    metadata = {"render_modes": ["ansi"]}
+
+    def __init__(
+        self,
+        config_dict: Optional[dict] = None,
+        render_mode: Optional[str] = None,
+        **kwargs,
+    ) -> None:
+        super().__init__()
+        config = dict(config_dict or {})
+        config.update(kwargs)
+        self.game = NativeEnv(json.dumps(config))
+        self.render_mode = render_mode
+        self.action_space = spaces.Discrete(len(self.game.action_set()))
+        self.observation_space = spaces.Box(
+            low=0.0,
+            high=1.0,
+            shape=self.game.observation_shape(),
+            dtype=np.float32,
+        )
+
+    def reset(
+        self, *, seed: Optional[int] = None, options: Optional[dict] = None
+    ) -> Tuple[ndarray, Dict[str, Any]]:
+        super().reset(seed=seed)
+        obs = self.game.reset(seed)
+        return obs, {"action_mask": self.action_mask()}
+
+    def step(self, action: int) -> Tuple[ndarray, float, bool, bool, Dict[str, Any]]:
+        obs, reward, _, info = self.game.step(int(action))
+        terminated = info["terminated"]
+        truncated = info["truncated"]
+        info["action_mask"] = self.action_mask()
+        return obs, reward, terminated, truncated, info
+
+    def action_mask(self) -> ndarray:
+        return np.array(self.game.valid_actions(), dtype=np.int8)
+
+    def render(self) -> Optional[str]:
+        if self.render_mode == "ansi":
+            return self.game.render()
+        return None
@@ -57,10 +57,7 @@ class ParallelRogueEnv:
             except Exception:
                 raise ValueError("Invalid action: {}".format(action))
         states = self.game.step(action)
-        rewards = [
-            max(0, after.gold - before.gold)
-            for before, after in zip(self.states, states)
-        ]
+        rewards = [s.reward for s in states]
         done = [s.is_terminal for s in states]
         self.states = states
         return self.states, rewards, done, [{}] * self.num_workers
@@ -244,10 +244,11 @@ class RogueEnv(gym.Env):
             setting = self.image_setting
         return setting.expand(state)
 
-    def __step_str(self, actions: str) -> int:
+    def __step_str(self, actions: str) -> float:
+        reward = 0.0
         for act in actions:
-            self.game.react(ord(act))
-        return len(actions)
+            reward += self.game.react(ord(act))
+        return reward
 
     def step(self, action: Union[int, str]) -> Tuple[PlayerState, float, bool, dict]:
         """
@@ -255,17 +256,15 @@ class RogueEnv(gym.Env):
         @param actions(string):
              key board inputs to rogue(e.g. "hjk" or "hh>")
         """
-        gold_before = self.result.gold
         if isinstance(action, str):
-            self.__step_str(action)
+            reward = self.__step_str(action)
         else:
             try:
                 s = self.ACTIONS[action]
-                self.__step_str(s)
+                reward = self.__step_str(s)
             except Exception as e:
                 raise ValueError("Invalid action: {} causes {}".format(action, e))
         self.__cache()
-        reward = self.result.gold - gold_before
         return self.result, reward, self.result.is_terminal, {}
 
     def seed(self, seed: int) -> None:
@@ -27,7 +27,7 @@ except FileNotFoundError:
 SETUP = ["setuptools-rust>=0.6.0"]
 REQUIRED = ["numpy", "gym"]
 TEST = ["pytest"]
-EXTRA = {"rainy": ["rainy"]}
+EXTRA = {"rainy": ["rainy"], "gymnasium": ["gymnasium"]}
 
 setup(
     name=NAME,
@@ -0,0 +1,235 @@
+//! native gym environment built on `RunTime::step`
+use crate::{pyresult, pyresult_with};
+use numpy::PyArray3;
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use rogue_gym_core::input::{InputCode, KeyMap};
+use rogue_gym_core::step::StepEvent;
+use rogue_gym_core::symbol::Layout;
+use rogue_gym_core::{error::GameResult, GameConfig, RunTime};
+use std::collections::HashMap;
+
+/// `StepEvent` as a dict like `{"kind": "killed", "enemy": "Bat"}`
+fn event_dict<'py>(py: Python<'py>, event: &StepEvent) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    match event {
+        StepEvent::PickedUp { id, item, num } => {
+            dict.set_item("kind", "picked_up")?;
+            dict.set_item("id", id.0)?;
+            dict.set_item("item", item)?;
+            dict.set_item("num", num)?;
+        }
+        StepEvent::Killed { enemy } => {
+            dict.set_item("kind", "killed")?;
+            dict.set_item("enemy", enemy)?;
+        }
+        StepEvent::Damaged { from, amount } => {
+            dict.set_item("kind", "damaged")?;
+            dict.set_item("from", from)?;
+            dict.set_item("amount", amount)?;
+        }
+        StepEvent::Trapped { trap } => {
+            dict.set_item("kind", "trapped")?;
+            dict.set_item("trap", trap)?;
+        }
+        StepEvent::Descended { depth } => {
+            dict.set_item("kind", "descended")?;
+            dict.set_item("depth", depth)?;
+        }
+        StepEvent::Ascended { depth } => {
+            dict.set_item("kind", "ascended")?;
+            dict.set_item("depth", depth)?;
+        }
+        StepEvent::LeveledUp { level } => {
+            dict.set_item("kind", "leveled_up")?;
+            dict.set_item("level", level)?;
+        }
+    }
+    Ok(dict)
+}
+
+/// A game with the gym API, where actions are indices of `action_set()`.
+/// Observations are one-hot arrays of `observation_shape()`, written directly into
+/// new NumPy arrays.
+#[pyclass(unsendable, name = "RogueEnv")]
+pub(crate) struct RogueEnv {
+    runtime: RunTime,
+    config: GameConfig,
+    actions: Vec<InputCode>,
+    symbols: u8,
+    /// the index of the current episode in the seed schedule
+    episode: usize,
+}
+
+impl RogueEnv {
+    fn build(config: &GameConfig) -> GameResult<RunTime> {
+        let mut runtime = config.clone().build()?;
+        runtime.keymap = KeyMap::ai();
+        Ok(runtime)
+    }
+    fn observation<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray3<f32>> {
+        let shape = pyresult(self.runtime.observation_shape())?;
+        let array = PyArray3::zeros(py, shape, false);
+        // the array is newly made and contiguous, so no one else refers to it
+        let out = unsafe { array.as_slice_mut() }?;
+        pyresult(self.runtime.observe(self.symbols, Layout::Nchw, out))?;
+        Ok(array)
+    }
+    /// start the next episode, with `seed` if given
+    fn restart(&mut self, seed: Option<u64>) -> GameResult<()> {
+        if let Some(seed) = seed {
+            self.seed(seed);
+        }
+        self.episode += 1;
+        self.runtime = Self::build(&self.config.for_episode(self.episode))?;
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl RogueEnv {
+    #[new]
+    fn new(config: Option<String>) -> PyResult<Self> {
+        let config = match config {
+            Some(cfg) => pyresult_with(GameConfig::from_json(&cfg), "Failed to parse config")?,
+            None => GameConfig::default(),
+        };
+        let runtime = pyresult(Self::build(&config.for_episode(0)))?;
+        let symbols = pyresult(runtime.symbols())?;
+        Ok(RogueEnv {
+            actions: runtime.action_set(),
+            runtime,
+            config,
+            symbols,
+            episode: 0,
+        })
+    }
+    /// Set the seed used from the next reset
+    fn seed(&mut self, seed: u64) {
+        self.config.set_seed(u128::from(seed));
+    }
+    /// Start a new game and returns the first observation
+    fn reset<'py>(&mut self, py: Python<'py>, seed: Option<u64>) -> PyResult<&'py PyArray3<f32>> {
+        pyresult(self.restart(seed))?;
+        self.observation(py)
+    }
+    /// Do the action and returns (observation, reward, done, info)
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: usize,
+    ) -> PyResult<(&'py PyArray3<f32>, f32, bool, &'py PyDict)> {
+        let code = match self.actions.get(action) {
+            Some(&code) => code,
+            None => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid action {}, which should be less than {}",
+                    action,
+                    self.actions.len()
+                )))
+            }
+        };
+        let (reward, info) = pyresult(self.runtime.advance(code))?;
+        let dict = PyDict::new(py);
+        dict.set_item("dead", info.dead)?;
+        dict.set_item("terminated", info.terminated())?;
+        dict.set_item("truncated", info.truncated())?;
+        dict.set_item(
+            "episode_end",
+            info.episode_end.as_ref().map(ToString::to_string),
+        )?;
+        let events = PyList::empty(py);
+        for event in &info.events {
+            events.append(event_dict(py, event)?)?;
+        }
+        dict.set_item("events", events)?;
+        Ok((self.observation(py)?, reward, info.is_done(), dict))
+    }
+    /// Returns the screen as a string
+    fn render(&self) -> PyResult<String> {
+        pyresult(self.runtime.render_ascii())
+    }
+    /// Descriptions of actions, in the order of action indices
+    fn action_set(&self) -> Vec<String> {
+        self.actions.iter().map(InputCode::description).collect()
+    }
+    /// Mask of actions which do something now
+    fn valid_actions(&self) -> Vec<bool> {
+        self.runtime.valid_actions()
+    }
+    /// (channels, height, width) of observations
+    fn observation_shape(&self) -> PyResult<(usize, usize, usize)> {
+        let [c, h, w] = pyresult(self.runtime.observation_shape())?;
+        Ok((c, h, w))
+    }
+    fn status(&self) -> HashMap<&'static str, u32> {
+        self.runtime
+            .player_status()
+            .to_dict_vec()
+            .into_iter()
+            .collect()
+    }
+    /// Returns config as Json
+    fn dump_config(&self) -> PyResult<String> {
+        pyresult_with(self.config.to_json(), "Error when getting config")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rogue_gym_core::character::Action;
+
+    #[test]
+    fn reset_with_seed() {
+        let mut env = RogueEnv::new(Some(r#"{"seed": 1}"#.to_owned())).unwrap();
+        let first = env.render().unwrap();
+        env.restart(Some(2)).unwrap();
+        let second = env.render().unwrap();
+        assert_ne!(first, second);
+        // the seed is kept for the next reset
+        env.restart(None).unwrap();
+        assert_eq!(env.render().unwrap(), second);
+        env.restart(Some(1)).unwrap();
+        assert_eq!(env.render().unwrap(), first);
+        assert_eq!(env.runtime.keymap, KeyMap::ai());
+    }
+
+    #[test]
+    fn seed_schedule() {
+        let config = r#"{"seed_schedule": {"list": [1, 2]}}"#;
+        let mut env = RogueEnv::new(Some(config.to_owned())).unwrap();
+        let first = env.render().unwrap();
+        env.restart(None).unwrap();
+        let second = env.render().unwrap();
+        assert_ne!(first, second);
+        env.restart(None).unwrap();
+        assert_eq!(env.render().unwrap(), first);
+        // an explicit seed overrides the schedule
+        env.restart(Some(2)).unwrap();
+        env.restart(None).unwrap();
+        assert_eq!(env.render().unwrap(), second);
+        assert_eq!(env.runtime.keymap, KeyMap::ai());
+    }
+
+    #[test]
+    fn step_until_the_episode_ends() {
+        let config = r#"{"seed": 1, "episode": {"max_turns": 5}}"#;
+        let mut env = RogueEnv::new(Some(config.to_owned())).unwrap();
+        let search = InputCode::Act(Action::Search);
+        let index = env.actions.iter().position(|&a| a == search).unwrap();
+        assert_eq!(env.action_set()[index], search.description());
+        assert_eq!(env.valid_actions().len(), env.actions.len());
+        for i in 0..5 {
+            let (reward, info) = env.runtime.advance(env.actions[index]).unwrap();
+            assert_eq!(reward, 0.0);
+            assert_eq!(info.is_done(), i == 4);
+        }
+        assert_eq!(env.status()["turns"], 5);
+        env.restart(None).unwrap();
+        assert_eq!(env.status()["turns"], 0);
+    }
+}
@@ -1,3 +1,4 @@
+mod env;
 mod flags;
 mod state_impls;
 mod thread_impls;
@@ -35,6 +36,8 @@ struct PlayerState {
     symbols: u8,
     message: MessageFlagInner,
     is_terminal: bool,
+    /// reward for the last input
+    reward: f32,
 }
 
 impl PlayerState {
@@ -47,6 +50,7 @@ impl PlayerState {
             symbols,
             message: MessageFlagInner::new(),
             is_terminal: false,
+            reward: 0.0,
         }
     }
     fn reset(&mut self, runtime: &RunTime) -> GameResult<()> {
@@ -54,6 +58,7 @@ impl PlayerState {
         self.draw_map(runtime)?;
         self.message = MessageFlagInner::new();
         self.is_terminal = false;
+        self.reward = 0.0;
         Ok(())
     }
     fn draw_map(&mut self, runtime: &RunTime) -> GameResult<()> {
@@ -155,6 +160,10 @@ impl PlayerState {
     fn is_terminal(&self) -> PyResult<bool> {
         Ok(self.is_terminal)
     }
+    #[getter]
+    fn reward(&self) -> PyResult<f32> {
+        Ok(self.reward)
+    }
     fn status_vec(&self, flag: u32) -> Vec<i32> {
         let flag = StatusFlagInner(flag);
         flag.to_vector(&self.status)
@@ -227,7 +236,7 @@ impl GameState {
         (self.config.height, self.config.width)
     }
     fn set_seed(&mut self, seed: u64) -> PyResult<()> {
-        self.config.seed = Some(seed as u128);
+        self.config.set_seed(seed as u128);
         Ok(())
     }
     /// Reset the game state
@@ -238,7 +247,8 @@ impl GameState {
     fn prev(&self) -> PlayerState {
         self.inner.state()
     }
-    fn react(&mut self, input: u8) -> PyResult<()> {
+    /// React to a key and returns the reward
+    fn react(&mut self, input: u8) -> PyResult<f32> {
         pyresult(self.inner.react(input))
     }
     /// Returns action history as Json
@@ -358,6 +368,7 @@ fn init_mod(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<GameState>()?;
     m.add_class::<PlayerState>()?;
     m.add_class::<ParallelGameState>()?;
+    m.add_class::<env::RogueEnv>()?;
     #[cfg(unix)]
     m.add_wrapped(pyo3::wrap_pyfunction!(replay))?;
     #[cfg(unix)]
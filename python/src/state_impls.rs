@@ -1,7 +1,7 @@
 use crate::PlayerState;
 use anyhow::bail;
 use rogue_gym_core::{
-    error::GameResult,
+    error::{ErrorKind, GameResult},
     input::{Key, KeyMap},
     ui::{MordalKind, UiState},
     GameConfig, Reaction, RunTime,
@@ -12,6 +12,8 @@ pub(crate) struct GameStateImpl {
     state: PlayerState,
     steps: usize,
     max_steps: usize,
+    /// the index of the current episode in the seed schedule
+    episode: usize,
 }
 
 unsafe impl Send for GameStateImpl {}
@@ -23,7 +25,7 @@ impl GameStateImpl {
             .expect("Failed to get symbol max")
             .to_byte()
             + 1;
-        let mut runtime = config.build()?;
+        let mut runtime = config.for_episode(0).build()?;
         runtime.keymap = KeyMap::ai();
         let (w, h) = runtime.screen_size();
         let mut state = PlayerState::new(w, h, symbols);
@@ -33,10 +35,12 @@ impl GameStateImpl {
             state,
             steps: 0,
             max_steps,
+            episode: 0,
         })
     }
     pub(crate) fn reset(&mut self, config: GameConfig) -> GameResult<()> {
-        self.runtime = config.build()?;
+        self.episode += 1;
+        self.runtime = config.for_episode(self.episode).build()?;
         self.runtime.keymap = KeyMap::ai();
         self.state.reset(&mut self.runtime)?;
         self.steps = 0;
@@ -48,13 +52,22 @@ impl GameStateImpl {
     pub(crate) fn symbols(&self) -> usize {
         usize::from(self.state.symbols)
     }
-    pub(crate) fn react(&mut self, input: u8) -> GameResult<()> {
+    /// react to a key and returns the reward for it
+    pub(crate) fn react(&mut self, input: u8) -> GameResult<f32> {
         if self.steps > self.max_steps {
-            return Ok(());
+            self.state.reward = 0.0;
+            return Ok(0.0);
         }
-        let res = self.runtime.react_to_key(Key::Char(input as char))?;
+        let key = Key::Char(input as char);
+        let code = self
+            .runtime
+            .keymap
+            .get(key)
+            .ok_or(ErrorKind::InvalidInput(key))?;
+        let (res, reward) = self.runtime.react_with_reward(code)?;
         self.state.message.reset();
-        let mut dead = false;
+        self.state.reward = reward;
+        let (mut dead, mut ended) = (false, false);
         for reaction in res {
             match reaction {
                 Reaction::Redraw => {
@@ -71,10 +84,11 @@ impl GameStateImpl {
                     ),
                 },
                 Reaction::Notify(msg) => self.state.message.append(&msg),
+                Reaction::EpisodeEnd(_) => ended = true,
             }
         }
         self.steps += 1;
-        self.state.is_terminal = dead || self.steps >= self.max_steps;
-        Ok(())
+        self.state.is_terminal = dead || ended || self.steps >= self.max_steps;
+        Ok(reward)
     }
 }
@@ -123,7 +123,7 @@ impl ThreadWorker {
                     self.sender.send(res)
                 }
                 Instruction::Seed(seed) => {
-                    self.config.seed = Some(seed);
+                    self.config.set_seed(seed);
                     continue;
                 }
                 Instruction::State => self.sender.send(Ok(self.game_state.state())),
@@ -0,0 +1,44 @@
+"""test for RogueGymnasiumEnv"""
+import gymnasium
+import numpy as np
+from rogue_gym.envs.gymnasium_env import RogueGymnasiumEnv
+
+CONFIG = {"seed": 1, "episode": {"max_turns": 3}}
+
+
+def test_make() -> None:
+    env = gymnasium.make("rogue_gym:Rogue-v1", config_dict=CONFIG, render_mode="ansi")
+    obs, info = env.reset(seed=1)
+    assert env.observation_space.contains(obs)
+    assert info["action_mask"].shape == (env.action_space.n,)
+    assert "@" in env.render()
+
+
+def test_truncated() -> None:
+    env = RogueGymnasiumEnv(CONFIG)
+    env.reset()
+    search = env.game.action_set().index("search for secret doors and traps")
+    for i in range(3):
+        obs, reward, terminated, truncated, info = env.step(search)
+        assert obs.dtype == np.float32
+        assert not terminated
+        assert truncated == (i == 2)
+
+
+def test_terminated_by_hunger() -> None:
+    config = {
+        "seed": 1,
+        "player": {"hunger_time": 100},
+        "enemies": {"enemies": []},
+        "episode": {"hunger": "Hungry", "max_turns": 1000},
+    }
+    env = RogueGymnasiumEnv(config)
+    env.reset()
+    search = env.game.action_set().index("search for secret doors and traps")
+    for _ in range(100):
+        _, _, terminated, truncated, info = env.step(search)
+        if terminated or truncated:
+            break
+    assert terminated
+    assert not truncated
+    assert info["episode_end"] is not None
@@ -0,0 +1,35 @@
+"""test for the native RogueEnv"""
+import json
+import numpy as np
+from rogue_gym_python._rogue_gym import RogueEnv
+
+CONFIG = {"seed": 1, "episode": {"max_turns": 5}}
+
+
+def test_reset_and_step() -> None:
+    env = RogueEnv(json.dumps(CONFIG))
+    obs = env.reset()
+    c, h, w = env.observation_shape()
+    assert obs.shape == (c, h, w)
+    assert obs.dtype == np.float32
+    # each cell has exactly one symbol
+    assert np.all(obs.sum(axis=0) == 1.0)
+    actions = env.action_set()
+    assert len(env.valid_actions()) == len(actions)
+    search = actions.index("search for secret doors and traps")
+    for i in range(5):
+        obs, reward, done, info = env.step(search)
+        assert reward == 0.0
+        assert done == (i == 4)
+        assert info["events"] == []
+    assert not info["dead"]
+    assert info["episode_end"] == "5 turns passed"
+
+
+def test_seed() -> None:
+    env = RogueEnv(json.dumps(CONFIG))
+    first = env.render()
+    env.reset(2)
+    assert env.render() != first
+    env.reset(1)
+    assert env.render() == first
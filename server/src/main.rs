@@ -0,0 +1,67 @@
+mod session;
+
+use anyhow::Context;
+use clap::ArgMatches;
+use rogue_gym_core::error::GameResult;
+use session::Sessions;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+
+fn main() -> GameResult<()> {
+    let args = parse_args();
+    let addr = args.value_of("addr").unwrap_or(DEFAULT_ADDR);
+    let listener = TcpListener::bind(addr).context("Failed to bind the address")?;
+    eprintln!("rogue-gym-server is listening on {}", addr);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        // games are not Send, so each connection has its own thread and games
+        thread::spawn(move || {
+            if let Err(e) = serve(stream) {
+                eprintln!("Connection closed by an error: {:#}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// process requests until the client closes the connection
+fn serve(stream: TcpStream) -> GameResult<()> {
+    let reader = BufReader::new(stream.try_clone().context("Failed to clone the stream")?);
+    let mut writer = BufWriter::new(stream);
+    let mut sessions = Sessions::new();
+    for line in reader.lines() {
+        let line = line.context("Failed to read a request")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", sessions.handle_line(&line))
+            .and_then(|_| writer.flush())
+            .context("Failed to write a response")?;
+    }
+    Ok(())
+}
+
+fn parse_args<'a>() -> ArgMatches<'a> {
+    clap::App::new("rogue-gym server")
+        .version("0.1.0")
+        .author("Yuji Kanagawa <yuji.kngw.80s.revive@gmail.com>")
+        .about("host rogue-gym games for remote agents, with JSON lines over TCP")
+        .arg(
+            clap::Arg::with_name("addr")
+                .short("a")
+                .long("addr")
+                .value_name("ADDR")
+                .help("Sets the address to listen on(default: 127.0.0.1:7878)")
+                .takes_value(true),
+        )
+        .get_matches()
+}
@@ -0,0 +1,218 @@
+//! the protocol of the server, where each line of requests and responses is a JSON object
+//!
+//! Requests have `cmd`, which is one of `create`, `reset`, `step`, `observe` and `close`.
+//! Responses have `ok`, and `error` if `ok` is false.
+use anyhow::{anyhow, Context};
+use rogue_gym_core::error::GameResult;
+use rogue_gym_core::input::{InputCode, KeyMap};
+use rogue_gym_core::symbol::Layout;
+use rogue_gym_core::{GameConfig, RunTime};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// A request from a client
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    /// make a game and returns its session id
+    Create {
+        /// kept as JSON, because tagged enums can't buffer the `u128` seed
+        #[serde(default)]
+        config: Option<Value>,
+    },
+    /// start a new game in the session, with `seed` if given
+    Reset {
+        session: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    /// do the `action`-th action and returns the reward and the new observation
+    Step {
+        session: usize,
+        action: usize,
+    },
+    /// returns the current observation
+    Observe {
+        session: usize,
+        #[serde(default)]
+        one_hot: bool,
+    },
+    Close {
+        session: usize,
+    },
+}
+
+/// A game in a session
+struct Session {
+    runtime: RunTime,
+    config: GameConfig,
+    actions: Vec<InputCode>,
+    /// the index of the current episode in the seed schedule
+    episode: usize,
+}
+
+impl Session {
+    fn new(config: GameConfig) -> GameResult<Self> {
+        let runtime = Self::build(&config.for_episode(0))?;
+        Ok(Session {
+            actions: runtime.action_set(),
+            runtime,
+            config,
+            episode: 0,
+        })
+    }
+    fn build(config: &GameConfig) -> GameResult<RunTime> {
+        let mut runtime = config.clone().build()?;
+        runtime.keymap = KeyMap::ai();
+        Ok(runtime)
+    }
+    /// the observation as symbol indices([height, width]) or one-hot([channels, height, width])
+    fn observation(&self, one_hot: bool) -> GameResult<Value> {
+        let (w, h) = self.runtime.observation_size();
+        if one_hot {
+            let shape = self.runtime.observation_shape()?;
+            let mut obs = vec![0.0f32; shape.iter().product()];
+            let symbols = self.runtime.symbols()?;
+            self.runtime.observe(symbols, Layout::Nchw, &mut obs)?;
+            Ok(json!({ "shape": shape, "data": obs }))
+        } else {
+            let mut obs = vec![0u8; w * h];
+            self.runtime.observe_symbols(&mut obs)?;
+            Ok(json!({ "shape": [h, w], "data": obs }))
+        }
+    }
+}
+
+/// Games made by a client, which are dropped when the connection is closed
+#[derive(Default)]
+pub struct Sessions {
+    games: Vec<Option<Session>>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn get_mut(&mut self, id: usize) -> GameResult<&mut Session> {
+        self.games
+            .get_mut(id)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| anyhow!("No session {}", id))
+    }
+    /// process a line of request and returns a line of response
+    pub fn handle_line(&mut self, line: &str) -> String {
+        let res = serde_json::from_str(line)
+            .context("Failed to parse request")
+            .and_then(|req| self.handle(req));
+        match res {
+            Ok(value) => value.to_string(),
+            Err(e) => json!({ "ok": false, "error": format!("{:#}", e) }).to_string(),
+        }
+    }
+    pub fn handle(&mut self, req: Request) -> GameResult<Value> {
+        match req {
+            Request::Create { config } => {
+                let config = match config {
+                    Some(config) => GameConfig::from_json(&config.to_string())?,
+                    None => GameConfig::default(),
+                };
+                let session = Session::new(config)?;
+                let actions: Vec<_> = session.actions.iter().map(|a| a.description()).collect();
+                let shape = session.runtime.observation_shape()?;
+                self.games.push(Some(session));
+                Ok(json!({
+                    "ok": true,
+                    "session": self.games.len() - 1,
+                    "actions": actions,
+                    "observation_shape": shape,
+                }))
+            }
+            Request::Reset { session, seed } => {
+                let session = self.get_mut(session)?;
+                if let Some(seed) = seed {
+                    session.config.set_seed(u128::from(seed));
+                }
+                session.episode += 1;
+                session.runtime = Session::build(&session.config.for_episode(session.episode))?;
+                Ok(json!({ "ok": true, "observation": session.observation(false)? }))
+            }
+            Request::Step { session, action } => {
+                let session = self.get_mut(session)?;
+                let code = *session
+                    .actions
+                    .get(action)
+                    .ok_or_else(|| anyhow!("Invalid action {}", action))?;
+                let (reward, info) = session.runtime.advance(code)?;
+                Ok(json!({
+                    "ok": true,
+                    "reward": reward,
+                    "terminated": info.terminated(),
+                    "truncated": info.truncated(),
+                    "episode_end": info.episode_end.map(|r| r.to_string()),
+                    "events": info.events,
+                    "observation": session.observation(false)?,
+                }))
+            }
+            Request::Observe { session, one_hot } => {
+                let session = self.get_mut(session)?;
+                Ok(json!({ "ok": true, "observation": session.observation(one_hot)? }))
+            }
+            Request::Close { session } => {
+                self.get_mut(session)?;
+                self.games[session] = None;
+                Ok(json!({ "ok": true }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn protocol() {
+        let mut sessions = Sessions::new();
+        let created: Value = serde_json::from_str(&sessions.handle_line(
+            r#"{"cmd": "create", "config": {"seed": 1, "episode": {"max_turns": 1}}}"#,
+        ))
+        .unwrap();
+        assert_eq!(created["ok"], true);
+        assert_eq!(created["session"], 0);
+        let search = created["actions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .position(|a| a == "search for secret doors and traps")
+            .unwrap();
+        let step: Value = serde_json::from_str(&sessions.handle_line(&format!(
+            r#"{{"cmd": "step", "session": 0, "action": {}}}"#,
+            search
+        )))
+        .unwrap();
+        assert_eq!(step["ok"], true);
+        assert_eq!(step["truncated"], true);
+        assert_eq!(step["terminated"], false);
+        assert_eq!(step["observation"]["shape"], json!([24, 80]));
+        assert_eq!(
+            step["observation"]["data"].as_array().unwrap().len(),
+            24 * 80
+        );
+        let observe: Value = serde_json::from_str(
+            &sessions.handle_line(r#"{"cmd": "observe", "session": 0, "one_hot": true}"#),
+        )
+        .unwrap();
+        assert_eq!(
+            observe["observation"]["shape"],
+            created["observation_shape"]
+        );
+        let closed = sessions.handle_line(r#"{"cmd": "close", "session": 0}"#);
+        assert_eq!(closed, r#"{"ok":true}"#);
+        let error: Value =
+            serde_json::from_str(&sessions.handle_line(r#"{"cmd": "reset", "session": 0}"#))
+                .unwrap();
+        assert_eq!(error["ok"], false);
+        assert_eq!(error["error"], "No session 0");
+        let broken: Value = serde_json::from_str(&sessions.handle_line("{")).unwrap();
+        assert_eq!(broken["ok"], false);
+    }
+}
@@ -0,0 +1,186 @@
+//! double-buffered cell grid, to update only changed cells of the display
+use rogue_gym_core::dungeon::Coord;
+use rogue_gym_core::tile::Color;
+use std::cmp;
+
+/// A character with its color
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub color: Option<Color>,
+}
+
+impl Cell {
+    pub const BLANK: Cell = Cell {
+        ch: ' ',
+        color: None,
+    };
+    pub fn new(ch: char) -> Self {
+        Cell { ch, color: None }
+    }
+    pub fn colored(ch: char, color: Color) -> Self {
+        Cell {
+            ch,
+            color: Some(color),
+        }
+    }
+}
+
+/// A grid of cells, holding the next frame(back) and what's on the display(front).
+/// Drawing functions modify only the back buffer, and `diff` returns cells
+/// to update the display.
+#[derive(Clone, Debug)]
+pub struct CellBuffer {
+    width: i32,
+    height: i32,
+    back: Vec<Cell>,
+    /// None for cells we don't know what's displayed
+    front: Vec<Option<Cell>>,
+}
+
+impl CellBuffer {
+    /// make a buffer for a blank display
+    pub fn new(width: i32, height: i32) -> Self {
+        let (width, height) = (cmp::max(width, 0), cmp::max(height, 0));
+        let len = (width * height) as usize;
+        CellBuffer {
+            width,
+            height,
+            back: vec![Cell::BLANK; len],
+            front: vec![Some(Cell::BLANK); len],
+        }
+    }
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+    /// resize the buffer, assuming the display is cleared
+    pub fn resize(&mut self, width: i32, height: i32) {
+        *self = Self::new(width, height);
+    }
+    /// forget what's on the display, so that all cells are drawn in the next frame
+    pub fn invalidate(&mut self) {
+        self.front.iter_mut().for_each(|c| *c = None);
+    }
+    fn index(&self, cd: Coord) -> Option<usize> {
+        let (x, y) = (cd.x.0, cd.y.0);
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        Some((y * self.width + x) as usize)
+    }
+    pub fn get(&self, cd: Coord) -> Option<Cell> {
+        self.index(cd).map(|i| self.back[i])
+    }
+    pub fn set(&mut self, cd: Coord, cell: Cell) {
+        if let Some(i) = self.index(cd) {
+            self.back[i] = cell;
+        }
+    }
+    /// clear cells from `cd` to the end of the row
+    pub fn clear_from(&mut self, cd: Coord) {
+        let (x, y) = (cmp::max(cd.x.0, 0), cd.y.0);
+        for x in x..self.width {
+            self.set(Coord::new(x, y), Cell::BLANK);
+        }
+    }
+    pub fn clear_line(&mut self, y: i32) {
+        self.clear_from(Coord::new(0, y));
+    }
+    pub fn clear(&mut self) {
+        self.back.iter_mut().for_each(|c| *c = Cell::BLANK);
+    }
+    /// write `s` from `start` without colors, truncated at the end of the row
+    pub fn write_str(&mut self, start: Coord, s: &str) {
+        for (i, ch) in s.chars().enumerate() {
+            self.set(start + Coord::new(i as i32, 0), Cell::new(ch));
+        }
+    }
+    /// cells changed since the last call, in row-major order.
+    /// After calling this, the display is assumed to be updated.
+    pub fn diff(&mut self) -> Vec<(Coord, Cell)> {
+        let width = cmp::max(self.width, 1);
+        let mut res = vec![];
+        for (i, (back, front)) in self.back.iter().zip(self.front.iter_mut()).enumerate() {
+            if *front != Some(*back) {
+                let i = i as i32;
+                res.push((Coord::new(i % width, i / width), *back));
+                *front = Some(*back);
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_returns_only_changed_cells() {
+        let mut buffer = CellBuffer::new(4, 3);
+        assert!(buffer.diff().is_empty());
+        buffer.write_str(Coord::new(1, 1), "ab");
+        buffer.set(Coord::new(0, 2), Cell::colored('@', Color::BRIGHT_BLACK));
+        // out of the buffer
+        buffer.set(Coord::new(4, 0), Cell::new('x'));
+        buffer.write_str(Coord::new(3, 0), "yz");
+        assert_eq!(
+            buffer.diff(),
+            vec![
+                (Coord::new(3, 0), Cell::new('y')),
+                (Coord::new(1, 1), Cell::new('a')),
+                (Coord::new(2, 1), Cell::new('b')),
+                (Coord::new(0, 2), Cell::colored('@', Color::BRIGHT_BLACK)),
+            ]
+        );
+        assert!(buffer.diff().is_empty());
+        // redrawing the same cells changes nothing
+        buffer.clear();
+        buffer.write_str(Coord::new(1, 1), "ab");
+        assert_eq!(
+            buffer.diff(),
+            vec![
+                (Coord::new(3, 0), Cell::BLANK),
+                (Coord::new(0, 2), Cell::BLANK)
+            ]
+        );
+    }
+
+    #[test]
+    fn clear_from() {
+        let mut buffer = CellBuffer::new(4, 2);
+        buffer.write_str(Coord::new(0, 0), "abcd");
+        buffer.write_str(Coord::new(0, 1), "efgh");
+        buffer.diff();
+        buffer.clear_from(Coord::new(2, 0));
+        buffer.clear_line(1);
+        let cells: Vec<_> = buffer.diff().into_iter().map(|(cd, _)| cd).collect();
+        assert_eq!(
+            cells,
+            vec![
+                Coord::new(2, 0),
+                Coord::new(3, 0),
+                Coord::new(0, 1),
+                Coord::new(1, 1),
+                Coord::new(2, 1),
+                Coord::new(3, 1),
+            ]
+        );
+        assert_eq!(buffer.get(Coord::new(1, 0)), Some(Cell::new('b')));
+    }
+
+    #[test]
+    fn invalidate_and_resize() {
+        let mut buffer = CellBuffer::new(3, 2);
+        buffer.write_str(Coord::new(0, 0), "ab");
+        buffer.diff();
+        buffer.invalidate();
+        assert_eq!(buffer.diff().len(), 6);
+        buffer.resize(2, 2);
+        assert_eq!(buffer.size(), (2, 2));
+        // the display is cleared on resize
+        assert_eq!(buffer.get(Coord::new(0, 0)), Some(Cell::BLANK));
+        assert!(buffer.diff().is_empty());
+        assert_eq!(buffer.get(Coord::new(2, 0)), None);
+    }
+}
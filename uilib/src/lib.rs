@@ -1,8 +1,23 @@
 //! UI abstraction for rogue-gym
-use rogue_gym_core::dungeon::{Coord, Positioned, X, Y};
+pub mod buffer;
+pub mod viewport;
+pub use buffer::{Cell, CellBuffer};
+use rogue_gym_core::character::{fight::Combatant, player::Status, Action};
+use rogue_gym_core::dungeon::{Coord, Direction, MapLayer, Positioned, X, Y};
 use rogue_gym_core::error::GameResult;
+use rogue_gym_core::input::{InputCode, System};
+use rogue_gym_core::summary::GameSummary;
 use rogue_gym_core::ui::{MordalKind, UiState};
-use rogue_gym_core::{character::player::Status, tile::Tile, GameMsg, Reaction, RunTime};
+use rogue_gym_core::{
+    tile::{Color, Tile},
+    GameMsg, Reaction, RunTime,
+};
+use std::cmp;
+use std::collections::VecDeque;
+pub use viewport::Viewport;
+
+/// color of tiles the player remembers but doesn't see now, if the screen has colors
+pub const REMEMBERED_COLOR: Color = Color::BRIGHT_BLACK;
 
 /// 0-indexed 2d screen for rogue-gym
 pub trait Screen {
@@ -18,6 +33,10 @@ pub trait Screen {
     fn clear_notification(&mut self) -> GameResult<()> {
         self.clear_line(self.height() - 1.into())
     }
+    /// viewport for displays smaller than the screen, if the screen supports scrolling
+    fn viewport_mut(&mut self) -> Option<&mut Viewport> {
+        None
+    }
     fn cursor(&mut self, _cursor: Coord) -> GameResult<()> {
         Ok(())
     }
@@ -28,6 +47,10 @@ pub trait Screen {
     fn write_tile(&mut self, pos: Coord, t: Tile) -> GameResult<()> {
         self.write_char(pos, t.to_char())
     }
+    /// draw a tile the player remembers but doesn't see now, e.g., dimmed
+    fn write_remembered(&mut self, pos: Coord, t: Tile) -> GameResult<()> {
+        self.write_tile(pos, t)
+    }
     fn write_str<S: AsRef<str>>(&mut self, start: Coord, s: S) -> GameResult<()> {
         let mut current = start;
         for c in s.as_ref().chars() {
@@ -50,12 +73,19 @@ pub trait Screen {
         )
     }
     fn dungeon(&mut self, runtime: &mut RunTime) -> GameResult<()> {
+        let player = runtime.player_position();
+        if self.viewport_mut().is_some_and(|vp| vp.follow(player)) {
+            self.clear_dungeon()?;
+        }
         let mut player_pos = None;
-        runtime.draw_screen(|Positioned(cd, tile)| {
+        runtime.draw_screen_layered(|Positioned(cd, tile), layer| {
             if tile.to_byte() == b'@' {
                 player_pos = Some(cd);
             }
-            self.write_tile(cd, tile)
+            match layer {
+                MapLayer::Visible => self.write_tile(cd, tile),
+                MapLayer::Remembered => self.write_remembered(cd, tile),
+            }
         })?;
         if let Some(pos) = player_pos {
             self.cursor(pos)?;
@@ -63,15 +93,85 @@ pub trait Screen {
         self.flush()
     }
     fn inventory(&mut self, runtime: &mut RunTime) -> GameResult<()> {
-        for (i, item) in runtime.itembox().items().enumerate() {
-            let num = (b'a' + i as u8) as char;
-            self.write_str(Coord::new(0, i as i32), format!("{}) {}", num, item))?;
+        for (i, (letter, item)) in runtime.itembox().lettered().enumerate() {
+            self.write_str(
+                Coord::new(0, i as i32),
+                format!("{}) {}", letter, runtime.item_name(item.get())),
+            )?;
         }
         self.write_str(
             Coord::new(0, self.height() - 1.into()),
             "--Press space to continue--",
         )
     }
+    /// draw the projected path of a missile in the targeting mode
+    fn targeting(&mut self, runtime: &mut RunTime, path: &[Coord]) -> GameResult<()> {
+        self.dungeon(runtime)?;
+        self.message("Throw toward which direction? (Enter: throw, Esc: cancel)")?;
+        if let Some((&target, path)) = path.split_last() {
+            for &cd in path {
+                self.write_char(cd, '*')?;
+            }
+            self.cursor(target)?;
+        }
+        self.flush()
+    }
+    /// draw `lines` in columns over the dungeon, with `title` in the message line
+    fn overlay(&mut self, title: &str, lines: &[String]) -> GameResult<()> {
+        let rows = cmp::max(self.height().0 - 2, 1) as usize;
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 2;
+        self.clear_dungeon()?;
+        self.message(title)?;
+        for row in 0..cmp::min(rows, lines.len()) {
+            let s: String = lines
+                .iter()
+                .skip(row)
+                .step_by(rows)
+                .map(|l| format!("{:width$}", l, width = width))
+                .collect();
+            self.write_str(Coord::new(0, row as i32 + 1), s.trim_end())?;
+        }
+        self.write_str(
+            Coord::new(0, self.height() - 1.into()),
+            "--Press any key to continue--",
+        )
+    }
+    /// draw a frame of an animation over the dungeon
+    fn animation_frame(
+        &mut self,
+        runtime: &mut RunTime,
+        frame: &[(Coord, Tile)],
+    ) -> GameResult<()> {
+        self.dungeon(runtime)?;
+        for &(cd, tile) in frame {
+            self.write_tile(cd, tile)?;
+        }
+        self.flush()
+    }
+    /// draw the whole explored map over the dungeon, scaled down to fit the display
+    fn map_overview(&mut self, runtime: &mut RunTime) -> GameResult<()> {
+        let (width, height) = match self.viewport_mut() {
+            Some(vp) => vp.size(),
+            None => (self.width().0, self.height().0),
+        };
+        let lines = overview_lines(runtime, width, cmp::max(height - 2, 1))?;
+        self.clear_dungeon()?;
+        self.message("-- Map (press any key to continue) --")?;
+        for (i, line) in lines.into_iter().enumerate() {
+            self.write_str(Coord::new(0, i as i32 + 1), line)?;
+        }
+        self.flush()
+    }
+    /// draw the tombstone with the cause of death and the score breakdown
+    fn tombstone(&mut self, summary: &GameSummary) -> GameResult<()> {
+        self.clear_dungeon()?;
+        let cause = summary.cause_of_death.as_deref().unwrap_or("");
+        self.dying_msg(cause)?;
+        for (i, line) in summary.lines().into_iter().enumerate() {
+            self.write_str(Coord::new(12, i as i32 + 12), line)?;
+        }
+        self.flush()
+    }
     fn dying_msg(&mut self, sig: &str) -> GameResult<()> {
         const MESSAGES: [&'static str; 9] = [
             r"                __________        ",
@@ -101,6 +201,138 @@ pub trait Screen {
     }
 }
 
+/// State machine of the targeting mode,
+/// in which the player chooses the direction to throw a missile
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Targeting {
+    pub direction: Direction,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TargetingMsg {
+    /// the direction may be changed
+    Continue,
+    Confirm(Direction),
+    Cancel,
+}
+
+impl Targeting {
+    pub fn new() -> Self {
+        Targeting {
+            direction: Direction::Stay,
+        }
+    }
+    pub fn process(&mut self, input: Option<InputCode>) -> TargetingMsg {
+        match input {
+            Some(InputCode::Act(Action::Move(d))) | Some(InputCode::Act(Action::MoveUntil(d))) => {
+                self.direction = d;
+                TargetingMsg::Continue
+            }
+            Some(InputCode::Both {
+                act: Action::Move(d),
+                ..
+            }) => {
+                self.direction = d;
+                TargetingMsg::Continue
+            }
+            Some(InputCode::Sys(System::Enter)) | Some(InputCode::Sys(System::Continue)) => {
+                if self.direction == Direction::Stay {
+                    TargetingMsg::Continue
+                } else {
+                    TargetingMsg::Confirm(self.direction)
+                }
+            }
+            Some(InputCode::Sys(System::Cancel)) => TargetingMsg::Cancel,
+            _ => TargetingMsg::Continue,
+        }
+    }
+}
+
+impl Default for Targeting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lines of the explored map, where each character stands for a block of cells
+/// so that the map fits in `width` x `rows`.
+/// The player, stairs, and traps are preferred to other tiles in a block.
+pub fn overview_lines(runtime: &RunTime, width: i32, rows: i32) -> GameResult<Vec<String>> {
+    fn priority(c: char) -> u8 {
+        match c {
+            '@' => 5,
+            '%' => 4,
+            '^' => 3,
+            '+' => 2,
+            ' ' => 0,
+            _ => 1,
+        }
+    }
+    let (w, h) = runtime.screen_size();
+    // the dungeon is drawn between the message line and the status line
+    let (map_w, map_h) = (w.0, h.0 - 2);
+    let sx = (map_w + width - 1) / cmp::max(width, 1);
+    let sy = (map_h + rows - 1) / cmp::max(rows, 1);
+    let (out_w, out_h) = ((map_w + sx - 1) / sx, (map_h + sy - 1) / sy);
+    let mut grid = vec![vec![' '; out_w as usize]; out_h as usize];
+    runtime.draw_map(|Positioned(cd, tile)| {
+        let (x, y) = (cd.x.0, cd.y.0 - 1);
+        if x < 0 || y < 0 || x >= map_w || y >= map_h {
+            return Ok(());
+        }
+        let cell = &mut grid[(y / sy) as usize][(x / sx) as usize];
+        let c = tile.to_char();
+        if priority(c) > priority(*cell) {
+            *cell = c;
+        }
+        Ok(())
+    })?;
+    Ok(grid
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>().trim_end().to_owned())
+        .collect())
+}
+
+/// Frames of animations, which are drawn one by one between key inputs
+#[derive(Clone, Debug, Default)]
+pub struct Animation {
+    frames: VecDeque<Vec<(Coord, Tile)>>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// a missile flying along `path`.
+    /// The last frame is empty, to erase the missile.
+    pub fn missile(&mut self, path: &[Coord], symbol: Tile) {
+        if path.is_empty() {
+            return;
+        }
+        self.frames
+            .extend(path.iter().map(|&cd| vec![(cd, symbol)]));
+        self.frames.push_back(vec![]);
+    }
+    /// add animations for the reactions, like projectiles from enemies
+    pub fn add_reactions(&mut self, reactions: &[Reaction]) {
+        for reaction in reactions {
+            if let Reaction::Notify(GameMsg::Projectile(p)) = reaction {
+                self.missile(&p.path, p.symbol);
+            }
+        }
+    }
+    pub fn next_frame(&mut self) -> Option<Vec<(Coord, Tile)>> {
+        self.frames.pop_front()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+    /// skip the rest of the animations
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Transition {
     Exit,
@@ -114,32 +346,132 @@ pub fn process_reaction<S: Screen>(
 ) -> GameResult<Transition> {
     match reaction {
         Reaction::Notify(msg) => match msg {
-            GameMsg::CantMove(_) => Ok(()),
-            GameMsg::CantGetItem(kind) => screen.pend_message(format!("You walk onto {:?}", kind)),
+            GameMsg::CantMove(_) | GameMsg::EnemySpawned(_) => Ok(()),
+            GameMsg::CantGetItem(kind) => {
+                let name = runtime.kind_name(&kind, 1);
+                screen.pend_message(format!("You walk onto {}", name))
+            }
             GameMsg::NoDownStair => {
                 screen.pend_message(format!("Hmm... there seems to be no downstair"))
             }
-            GameMsg::GotItem { kind, num } => {
-                screen.pend_message(format!("You got {} {:?}", num, kind))
+            GameMsg::NoWay => screen.pend_message(format!("You don't know the way")),
+            GameMsg::RestInterrupted => screen.pend_message(format!("You stopped resting")),
+            GameMsg::NoUpStair => screen.pend_message(format!("You can't go up from here")),
+            GameMsg::NothingToThrow => {
+                screen.pend_message(format!("You don't have anything to throw"))
+            }
+            GameMsg::NothingToPickUp => {
+                screen.pend_message(format!("There's nothing here to pick up"))
+            }
+            GameMsg::PackIsFull { kind, num } => {
+                let name = runtime.kind_name(&kind, num);
+                screen.pend_message(format!("There's no room in your pack for {}", name))
+            }
+            GameMsg::Dropped { kind, num } => {
+                let name = runtime.kind_name(&kind, num);
+                screen.pend_message(format!("You dropped {}", name))
+            }
+            GameMsg::EnemyDropped { enemy, kind, num } => {
+                let name = runtime.kind_name(&kind, num);
+                screen.pend_message(format!("The {} drops {}", enemy, name))
+            }
+            GameMsg::CantDropHere => {
+                screen.pend_message(format!("There's already something there"))
+            }
+            GameMsg::Returned(kind) => {
+                let name = runtime.kind_name(&kind, 1);
+                screen.pend_message(format!("You caught {} as it came back", name))
+            }
+            GameMsg::Read(kind) => {
+                let name = runtime.kind_name(&kind, 1);
+                screen.pend_message(format!("You read {}", name))
+            }
+            GameMsg::CantRead(kind) => {
+                let name = runtime.kind_name(&kind, 1);
+                screen.pend_message(format!("You can't read {}", name))
+            }
+            GameMsg::Identified(kind) => {
+                let name = runtime.kind_name(&kind, 1);
+                screen.pend_message(format!("Now you know it's {}", name))
+            }
+            GameMsg::NoSuchItem(letter) => {
+                screen.pend_message(format!("You don't have item {}", letter))
+            }
+            GameMsg::ForSale { kind, num, price } => screen.pend_message(format!(
+                "Here is {} for {} gold, pick it up to buy it",
+                runtime.kind_name(&kind, num),
+                price
+            )),
+            GameMsg::Bought {
+                kind, num, price, ..
+            } => screen.pend_message(format!(
+                "You bought {} for {} gold",
+                runtime.kind_name(&kind, num),
+                price
+            )),
+            GameMsg::CantAfford { kind, num, price } => screen.pend_message(format!(
+                "You can't afford {}, which costs {} gold",
+                runtime.kind_name(&kind, num),
+                price
+            )),
+            GameMsg::Sold { kind, num, price } => screen.pend_message(format!(
+                "You sold {} for {} gold",
+                runtime.kind_name(&kind, num),
+                price
+            )),
+            GameMsg::GotItem { kind, num, .. } => {
+                let name = runtime.kind_name(&kind, num);
+                screen.pend_message(format!("You got {}", name))
             }
             GameMsg::SecretDoor => screen.pend_message(format!("You found a secret door")),
+            GameMsg::SecretPassage => screen.pend_message(format!("You found a secret passage")),
+            GameMsg::TrapFound(kind) => screen.pend_message(format!("You found {}", kind)),
+            GameMsg::Trapped(kind) => screen.pend_message(format!("You fell into {}!", kind)),
+            GameMsg::DoorOpened => screen.pend_message(format!("You opened the door")),
+            GameMsg::DoorClosed => screen.pend_message(format!("You closed the door")),
+            GameMsg::NoDoor => screen.pend_message(format!("There's no door you can use there")),
             GameMsg::HitTo(s) => screen.pend_message(format!("You swings and hit {}", s)),
             GameMsg::HitFrom(s) => screen.pend_message(format!("{} swings and hits you", s)),
             GameMsg::MissTo(s) => screen.pend_message(format!("You swing and miss {}", s)),
             GameMsg::MissFrom(s) => screen.pend_message(format!("{} swings and misses you", s)),
+            GameMsg::Combat(event) => match event.attacker {
+                _ if !event.roll.is_critical() => Ok(()),
+                Combatant::Player => screen.pend_message(format!("What a critical hit!")),
+                Combatant::Enemy(s) => screen.pend_message(format!("{} hits you very hard", s)),
+            },
+            GameMsg::Projectile(p) => {
+                screen.pend_message(format!("The {} attacks you from afar", p.from))
+            }
             GameMsg::Killed(s) => screen.pend_message(format!("You defeated the {}", s)),
+            GameMsg::ArmorWeakened => {
+                screen.pend_message(format!("Your armor appears to be weaker now"))
+            }
+            GameMsg::GoldStolen(_) => screen.pend_message(format!("Your purse feels lighter")),
+            GameMsg::ItemStolen { kind, num } => {
+                let name = runtime.kind_name(&kind, num);
+                screen.pend_message(format!("She stole {}!", name))
+            }
+            GameMsg::LevelDrained => screen.pend_message(format!("You suddenly feel weaker")),
+            GameMsg::EffectStarted(e) => screen.pend_message(format!("You are {} now", e)),
+            GameMsg::EffectEnded(e) => screen.pend_message(format!("You are no longer {}", e)),
+            GameMsg::StrengthDrained => {
+                screen.pend_message(format!("You feel a bite in your leg and now feel weaker"))
+            }
             GameMsg::Quit => {
                 screen.pend_message(format!("Thank you for playing!"))?;
                 return Ok(Transition::Exit);
             }
         },
         Reaction::Redraw => screen.dungeon(runtime),
+        Reaction::EpisodeEnd(reason) => {
+            screen.pend_message(format!("The episode ended because {}", reason))
+        }
         Reaction::StatusUpdated => screen.status(&runtime.player_status()),
         Reaction::UiTransition(ui_state) => match ui_state {
             UiState::Mordal(kind) => match kind {
                 MordalKind::Quit => screen.message(format!("You really quit game?(y/n)")),
                 MordalKind::Inventory => screen.inventory(runtime),
-                MordalKind::Grave(msg) => screen.dying_msg(&*msg),
+                MordalKind::Grave(_) => screen.tombstone(&runtime.summary()),
             },
             UiState::Dungeon => {
                 screen.dungeon(runtime)?;
@@ -150,3 +482,186 @@ pub fn process_reaction<S: Screen>(
     }?;
     Ok(Transition::None)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rogue_gym_core::GameConfig;
+
+    /// screen drawn to a grid of characters
+    struct Grid {
+        cells: Vec<Vec<char>>,
+        cursor: Option<Coord>,
+    }
+
+    impl Grid {
+        fn new(runtime: &RunTime) -> Self {
+            let (w, h) = runtime.screen_size();
+            Grid {
+                cells: vec![vec![' '; w.0 as usize]; h.0 as usize],
+                cursor: None,
+            }
+        }
+        fn get(&self, cd: Coord) -> char {
+            self.cells[cd.y.0 as usize][cd.x.0 as usize]
+        }
+    }
+
+    impl Screen for Grid {
+        fn width(&self) -> X {
+            X(self.cells[0].len() as i32)
+        }
+        fn height(&self) -> Y {
+            Y(self.cells.len() as i32)
+        }
+        fn clear_line(&mut self, row: Y) -> GameResult<()> {
+            if let Some(line) = self.cells.get_mut(row.0 as usize) {
+                line.iter_mut().for_each(|c| *c = ' ');
+            }
+            Ok(())
+        }
+        fn cursor(&mut self, cursor: Coord) -> GameResult<()> {
+            self.cursor = Some(cursor);
+            Ok(())
+        }
+        fn write_char(&mut self, pos: Coord, c: char) -> GameResult<()> {
+            let cell = self
+                .cells
+                .get_mut(pos.y.0 as usize)
+                .and_then(|line| line.get_mut(pos.x.0 as usize));
+            if let Some(cell) = cell {
+                *cell = c;
+            }
+            Ok(())
+        }
+    }
+
+    fn runtime() -> RunTime {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.build().unwrap()
+    }
+
+    #[test]
+    fn targeting_chooses_a_direction() {
+        let mut targeting = Targeting::new();
+        let enter = Some(InputCode::Sys(System::Enter));
+        // no direction is chosen yet
+        assert_eq!(targeting.process(enter), TargetingMsg::Continue);
+        let left = InputCode::Act(Action::Move(Direction::Left));
+        assert_eq!(targeting.process(Some(left)), TargetingMsg::Continue);
+        assert_eq!(targeting.direction, Direction::Left);
+        // 'y' is also used to answer yes
+        let y = InputCode::Both {
+            act: Action::Move(Direction::LeftUp),
+            sys: System::Yes,
+        };
+        assert_eq!(targeting.process(Some(y)), TargetingMsg::Continue);
+        // unrelated inputs are ignored
+        assert_eq!(targeting.process(None), TargetingMsg::Continue);
+        let search = InputCode::Act(Action::Search);
+        assert_eq!(targeting.process(Some(search)), TargetingMsg::Continue);
+        assert_eq!(targeting.direction, Direction::LeftUp);
+        assert_eq!(
+            targeting.process(enter),
+            TargetingMsg::Confirm(Direction::LeftUp)
+        );
+        let cancel = Some(InputCode::Sys(System::Cancel));
+        assert_eq!(targeting.process(cancel), TargetingMsg::Cancel);
+    }
+
+    #[test]
+    fn targeting_draws_the_path() {
+        let mut runtime = runtime();
+        let mut screen = Grid::new(&runtime);
+        let directions = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ];
+        let path = directions
+            .iter()
+            .map(|&d| runtime.missile_path(d))
+            .max_by_key(Vec::len)
+            .unwrap();
+        assert!(path.len() >= 2, "path: {:?}", path);
+        screen.targeting(&mut runtime, &path).unwrap();
+        let (&target, path) = path.split_last().unwrap();
+        assert!(path.iter().all(|&cd| screen.get(cd) == '*'));
+        assert_ne!(screen.get(target), '*');
+        assert_eq!(screen.cursor, Some(target));
+        let message: String = screen.cells[0].iter().collect();
+        assert!(message.starts_with("Throw toward which direction?"));
+    }
+
+    #[test]
+    fn overview_scales_the_map() {
+        let mut runtime = runtime();
+        let (w, h) = runtime.screen_size();
+        let player = runtime.player_position();
+        // full size
+        let mut screen = Grid::new(&runtime);
+        screen.dungeon(&mut runtime).unwrap();
+        let lines = overview_lines(&runtime, w.0, h.0 - 2).unwrap();
+        assert_eq!(lines.len() as i32, h.0 - 2);
+        for (y, line) in lines.iter().enumerate() {
+            let row: String = screen.cells[y + 1].iter().collect();
+            assert_eq!(line, row.trim_end());
+        }
+        // half size, where the player is preferred to other tiles in the block
+        let lines = overview_lines(&runtime, w.0 / 2, (h.0 - 2) / 2).unwrap();
+        assert_eq!(lines.len() as i32, (h.0 - 2) / 2);
+        assert!(lines.iter().all(|l| l.chars().count() as i32 <= w.0 / 2));
+        let (x, y) = (player.x.0 / 2, (player.y.0 - 1) / 2);
+        assert_eq!(lines[y as usize].chars().nth(x as usize), Some('@'));
+        assert_eq!(lines.concat().matches('@').count(), 1);
+    }
+
+    #[test]
+    fn map_overview_fits_the_screen() {
+        let mut runtime = runtime();
+        let mut screen = Grid::new(&runtime);
+        screen.map_overview(&mut runtime).unwrap();
+        let message: String = screen.cells[0].iter().collect();
+        assert!(message.starts_with("-- Map"));
+        let player = runtime.player_position();
+        assert_eq!(screen.get(player), '@');
+    }
+
+    #[test]
+    fn missile_animation() {
+        let mut animation = Animation::new();
+        animation.missile(&[], Tile(b')'));
+        assert!(animation.is_empty());
+        let path = [Coord::new(3, 2), Coord::new(4, 2)];
+        animation.missile(&path, Tile(b')'));
+        let frames: Vec<_> = std::iter::from_fn(|| animation.next_frame()).collect();
+        assert_eq!(
+            frames,
+            vec![
+                vec![(path[0], Tile(b')'))],
+                vec![(path[1], Tile(b')'))],
+                vec![],
+            ]
+        );
+        animation.missile(&path, Tile(b')'));
+        animation.clear();
+        assert!(animation.next_frame().is_none());
+    }
+
+    #[test]
+    fn animation_frame_is_drawn_over_the_dungeon() {
+        let mut runtime = runtime();
+        let mut screen = Grid::new(&runtime);
+        let player = runtime.player_position();
+        let cd = player + Coord::new(1, 0);
+        screen
+            .animation_frame(&mut runtime, &[(cd, Tile(b')'))])
+            .unwrap();
+        assert_eq!(screen.get(player), '@');
+        assert_eq!(screen.get(cd), ')');
+        screen.animation_frame(&mut runtime, &[]).unwrap();
+        assert_ne!(screen.get(cd), ')');
+    }
+}
@@ -0,0 +1,190 @@
+//! viewport to show a screen larger than the display
+use rogue_gym_core::dungeon::Coord;
+use std::cmp;
+
+/// how close the player can get to the edge of the view before scrolling
+const SCROLL_MARGIN: i32 = 4;
+
+/// A window of the screen, for displays smaller than the screen.
+/// Only the dungeon is scrolled, and the message line(the first row)
+/// and the status line(the last row) always stay in the view.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Viewport {
+    screen_width: i32,
+    screen_height: i32,
+    view_width: i32,
+    view_height: i32,
+    /// the top-left cell of the dungeon shown in the view
+    scroll: (i32, i32),
+}
+
+impl Viewport {
+    pub fn new(screen_width: i32, screen_height: i32) -> Self {
+        Viewport {
+            screen_width,
+            screen_height,
+            view_width: screen_width,
+            view_height: screen_height,
+            scroll: (0, 0),
+        }
+    }
+    /// set the size of the display
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.view_width = cmp::max(width, 1);
+        self.view_height = cmp::max(height, 3);
+        self.scroll = (
+            clamp(self.scroll.0, self.screen_width, self.view_width),
+            clamp(self.scroll.1, self.dungeon_rows(), self.view_rows()),
+        );
+    }
+    /// the size of the area actually used in the display
+    pub fn size(&self) -> (i32, i32) {
+        (
+            cmp::min(self.screen_width, self.view_width),
+            cmp::min(self.screen_height, self.view_height),
+        )
+    }
+    /// the size of the display
+    pub fn view_size(&self) -> (i32, i32) {
+        (self.view_width, self.view_height)
+    }
+    /// whether the display has room for `width` x `height` cells
+    pub fn fits(&self, width: i32, height: i32) -> bool {
+        self.view_width >= width && self.view_height >= height
+    }
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll != (0, 0)
+    }
+    fn dungeon_rows(&self) -> i32 {
+        self.screen_height - 2
+    }
+    fn view_rows(&self) -> i32 {
+        self.size().1 - 2
+    }
+    /// scroll the view when the player is near its edge, and returns if it's scrolled
+    pub fn follow(&mut self, player: Coord) -> bool {
+        let scroll = (
+            follow_axis(
+                player.x.0,
+                self.scroll.0,
+                self.screen_width,
+                self.view_width,
+            ),
+            follow_axis(
+                player.y.0 - 1,
+                self.scroll.1,
+                self.dungeon_rows(),
+                self.view_rows(),
+            ),
+        );
+        let changed = scroll != self.scroll;
+        self.scroll = scroll;
+        changed
+    }
+    /// position of `cd` in the view, or None if it's out of the view.
+    /// Tiles of the dungeon are scrolled, while texts aren't.
+    pub fn to_view(&self, cd: Coord, is_tile: bool) -> Option<Coord> {
+        let (width, height) = self.size();
+        let (mut x, mut y) = (cd.x.0, cd.y.0);
+        if y == self.screen_height - 1 {
+            y = height - 1;
+        } else {
+            if is_tile {
+                x -= self.scroll.0;
+                y -= self.scroll.1;
+            }
+            if y >= height - 1 || (is_tile && y < 1) {
+                return None;
+            }
+        }
+        if x < 0 || y < 0 || x >= width {
+            return None;
+        }
+        Some(Coord::new(x, y))
+    }
+}
+
+fn clamp(scroll: i32, len: i32, view: i32) -> i32 {
+    if len <= view {
+        0
+    } else {
+        cmp::min(cmp::max(scroll, 0), len - view)
+    }
+}
+
+/// edge scrolling along an axis
+fn follow_axis(pos: i32, scroll: i32, len: i32, view: i32) -> i32 {
+    let margin = cmp::min(SCROLL_MARGIN, (view - 1) / 2);
+    let scroll = if pos - scroll < margin {
+        pos - margin
+    } else if pos - scroll >= view - margin {
+        pos - view + margin + 1
+    } else {
+        scroll
+    };
+    clamp(scroll, len, view)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_scroll_in_a_large_display() {
+        let mut vp = Viewport::new(80, 24);
+        vp.resize(100, 30);
+        assert_eq!(vp.size(), (80, 24));
+        assert!(!vp.follow(Coord::new(79, 22)));
+        assert!(!vp.is_scrolled());
+        assert_eq!(
+            vp.to_view(Coord::new(79, 22), true),
+            Some(Coord::new(79, 22))
+        );
+        assert_eq!(vp.to_view(Coord::new(80, 22), true), None);
+    }
+
+    #[test]
+    fn follow_the_player() {
+        let mut vp = Viewport::new(80, 24);
+        vp.resize(40, 12);
+        // 10 rows for the dungeon
+        assert!(!vp.follow(Coord::new(10, 5)));
+        assert!(vp.follow(Coord::new(38, 5)));
+        assert_eq!(vp.to_view(Coord::new(38, 5), true), Some(Coord::new(35, 5)));
+        assert!(vp.follow(Coord::new(38, 12)));
+        let pos = vp.to_view(Coord::new(38, 12), true).unwrap();
+        assert_eq!(pos, Coord::new(35, 10 - SCROLL_MARGIN));
+        // doesn't scroll out of the screen
+        assert!(vp.follow(Coord::new(79, 22)));
+        assert_eq!(
+            vp.to_view(Coord::new(79, 22), true),
+            Some(Coord::new(39, 10))
+        );
+        assert!(!vp.follow(Coord::new(79, 22)));
+        // tiles scrolled out are hidden, while texts aren't scrolled
+        assert_eq!(vp.to_view(Coord::new(10, 5), true), None);
+        assert_eq!(vp.to_view(Coord::new(0, 0), false), Some(Coord::new(0, 0)));
+        assert_eq!(
+            vp.to_view(Coord::new(0, 23), false),
+            Some(Coord::new(0, 11))
+        );
+    }
+
+    #[test]
+    fn resize_keeps_the_scroll_in_the_screen() {
+        let mut vp = Viewport::new(80, 24);
+        vp.resize(40, 12);
+        vp.follow(Coord::new(79, 22));
+        assert!(vp.is_scrolled());
+        vp.resize(60, 20);
+        assert_eq!(
+            vp.to_view(Coord::new(79, 22), true),
+            Some(Coord::new(59, 18))
+        );
+        vp.resize(80, 24);
+        assert!(!vp.is_scrolled());
+        // too small displays still have a row for the dungeon
+        vp.resize(0, 0);
+        assert_eq!(vp.size(), (1, 3));
+    }
+}
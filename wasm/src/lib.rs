@@ -0,0 +1,229 @@
+//! JavaScript bindings of rogue-gym via wasm-bindgen, for playing and watching replays
+//! in browsers
+use rogue_gym_core::dungeon::Positioned;
+use rogue_gym_core::error::GameResult;
+use rogue_gym_core::input::{InputCode, Key};
+use rogue_gym_core::replay::ReplayFile;
+use rogue_gym_core::step::StepEvent;
+use rogue_gym_core::{GameConfig, RunTime};
+use wasm_bindgen::prelude::*;
+
+fn js_result<T>(result: GameResult<T>) -> Result<T, JsValue> {
+    result.map_err(|e| JsValue::from_str(&format!("{:#}", e)))
+}
+
+/// the screen and the status lines, without any escape sequences
+fn render(runtime: &RunTime) -> GameResult<String> {
+    let (w, h) = runtime.screen_size();
+    let (w, h) = (w.0 as usize, h.0 as usize);
+    let mut screen = vec![vec![' '; w]; h];
+    runtime.draw_screen(|Positioned(cd, tile)| {
+        let (x, y) = (cd.x.0 as usize, cd.y.0 as usize);
+        if x < w && y < h {
+            screen[y][x] = tile.to_char();
+        }
+        Ok(())
+    })?;
+    let mut res: Vec<String> = screen
+        .into_iter()
+        .map(|r| r.into_iter().collect())
+        .collect();
+    res.extend(runtime.player_status().lines().iter().cloned());
+    Ok(res.join("\n"))
+}
+
+/// A game, which can be played by keys like the terminal UI, or by action indices
+#[wasm_bindgen]
+pub struct Game {
+    runtime: RunTime,
+    config: GameConfig,
+    actions: Vec<InputCode>,
+    done: bool,
+    events: Vec<StepEvent>,
+    /// the index of the current episode in the seed schedule
+    episode: usize,
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// make a game from a JSON config, or the default config if it's undefined
+    #[wasm_bindgen(constructor)]
+    pub fn new(config_json: Option<String>) -> Result<Game, JsValue> {
+        let config = match config_json {
+            Some(json) => js_result(GameConfig::from_json(&json))?,
+            None => GameConfig::default(),
+        };
+        let runtime = js_result(config.for_episode(0).build())?;
+        Ok(Game {
+            actions: runtime.action_set(),
+            runtime,
+            config,
+            done: false,
+            events: vec![],
+            episode: 0,
+        })
+    }
+    /// start a new game, with `seed` if it's given
+    pub fn reset(&mut self, seed: Option<u32>) -> Result<(), JsValue> {
+        if let Some(seed) = seed {
+            self.config.set_seed(u128::from(seed));
+        }
+        self.episode += 1;
+        self.runtime = js_result(self.config.for_episode(self.episode).build())?;
+        self.done = false;
+        self.events.clear();
+        Ok(())
+    }
+    /// react to a key name like "h" or "Esc", which returns false for unbound keys
+    pub fn press(&mut self, key: &str) -> Result<bool, JsValue> {
+        let code = match Key::from_name(key).and_then(|key| self.runtime.keymap.get(key)) {
+            Some(code) => code,
+            None => return Ok(false),
+        };
+        self.step_input(code)?;
+        Ok(true)
+    }
+    /// do the `action`-th action of `action_names` and returns the reward
+    pub fn step(&mut self, action: usize) -> Result<f32, JsValue> {
+        let code = match self.actions.get(action) {
+            Some(&code) => code,
+            None => return Err(JsValue::from_str("Invalid action")),
+        };
+        self.step_input(code)
+    }
+    fn step_input(&mut self, code: InputCode) -> Result<f32, JsValue> {
+        let (reward, info) = js_result(self.runtime.advance(code))?;
+        self.done |= info.is_done();
+        self.events = info.events;
+        Ok(reward)
+    }
+    /// true if the player died or the episode ended by a limit
+    #[wasm_bindgen(getter)]
+    pub fn done(&self) -> bool {
+        self.done
+    }
+    /// what happened in the last `step` or `press`, as a JSON array of objects
+    /// like `{"kind": "killed", "enemy": "Bat"}`
+    pub fn events_json(&self) -> String {
+        serde_json::to_string(&self.events).unwrap_or_default()
+    }
+    /// descriptions of actions for `step`, as a JSON array
+    pub fn action_names(&self) -> String {
+        let names: Vec<_> = self.actions.iter().map(InputCode::description).collect();
+        serde_json::to_string(&names).unwrap_or_default()
+    }
+    pub fn render(&self) -> Result<String, JsValue> {
+        js_result(render(&self.runtime))
+    }
+    /// structured state for scripts, same as `RunTime::state_as_json`
+    pub fn state_json(&self) -> Result<String, JsValue> {
+        js_result(self.runtime.state_as_json())
+    }
+    /// inputs so far as a replay file, which `Replay` can play
+    pub fn replay_json(&self) -> Result<String, JsValue> {
+        js_result(self.runtime.replay_file(self.config.clone()).to_json())
+    }
+}
+
+/// A replay viewer, which advances the game one input at a time
+#[wasm_bindgen]
+pub struct Replay {
+    runtime: RunTime,
+    inputs: Vec<InputCode>,
+    next: usize,
+}
+
+#[wasm_bindgen]
+impl Replay {
+    /// load a replay file made by the terminal UI or `Game::replay_json`
+    #[wasm_bindgen(constructor)]
+    pub fn new(replay_json: &str) -> Result<Replay, JsValue> {
+        let replay = js_result(ReplayFile::from_json(replay_json))?;
+        let runtime = js_result(replay.config.clone().build())?;
+        Ok(Replay {
+            runtime,
+            inputs: replay.inputs,
+            next: 0,
+        })
+    }
+    /// apply the next input, which returns false at the end of the replay
+    pub fn advance(&mut self) -> Result<bool, JsValue> {
+        let input = match self.inputs.get(self.next) {
+            Some(&input) => input,
+            None => return Ok(false),
+        };
+        js_result(self.runtime.replay_input(input))?;
+        self.next += 1;
+        Ok(true)
+    }
+    #[wasm_bindgen(getter)]
+    pub fn position(&self) -> usize {
+        self.next
+    }
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+    pub fn render(&self) -> Result<String, JsValue> {
+        js_result(render(&self.runtime))
+    }
+}
+
+// `JsValue` only works on wasm targets, so only successful calls are tested natively
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"{"seed": 1, "episode": {"max_turns": 6}}"#;
+
+    #[test]
+    fn play_and_replay() {
+        let mut game = Game::new(Some(CONFIG.to_owned())).unwrap();
+        let (w, h) = game.runtime.screen_size();
+        let screen = game.render().unwrap();
+        // the screen and 2 status lines
+        assert_eq!(screen.lines().count() as i32, h.0 + 2);
+        assert!(screen.lines().all(|l| l.chars().count() as i32 <= w.0));
+        assert!(screen.contains('@'));
+        assert!(!game.press("F12").unwrap());
+        for key in ["h", "j", "l"] {
+            assert!(game.press(key).unwrap());
+        }
+        let names: Vec<String> = serde_json::from_str(&game.action_names()).unwrap();
+        let search = InputCode::Act(rogue_gym_core::character::Action::Search);
+        let index = names
+            .iter()
+            .position(|n| *n == search.description())
+            .unwrap();
+        for _ in 0..2 {
+            assert_eq!(game.step(index).unwrap(), 0.0);
+            assert!(!game.done());
+        }
+        assert_eq!(game.events_json(), "[]");
+        game.step(index).unwrap();
+        assert!(game.done());
+        let mut replay = Replay::new(&game.replay_json().unwrap()).unwrap();
+        assert_eq!(replay.len(), 6);
+        while replay.advance().unwrap() {}
+        assert_eq!(replay.position(), 6);
+        assert_eq!(replay.render().unwrap(), game.render().unwrap());
+    }
+
+    #[test]
+    fn reset() {
+        let mut game = Game::new(Some(CONFIG.to_owned())).unwrap();
+        let first = game.render().unwrap();
+        for _ in 0..6 {
+            game.press("s").unwrap();
+        }
+        assert!(game.done());
+        game.reset(None).unwrap();
+        assert!(!game.done());
+        assert_eq!(game.render().unwrap(), first);
+        game.reset(Some(2)).unwrap();
+        assert_ne!(game.render().unwrap(), first);
+    }
+}